@@ -0,0 +1,90 @@
+use crate::sysfs;
+use std::fs;
+use std::path::Path;
+
+/// An external power source (AC adapter or USB-C charger) as reported by sysfs.
+#[derive(Debug, Clone)]
+pub struct Adapter {
+    /// `Mains`, `USB`, `USB_C` or `USB_PD`
+    pub kind: String,
+    pub online: bool,
+    /// Power the adapter is delivering, in watts, when the kernel reports it
+    pub watts: Option<f64>,
+    /// Most the adapter advertises it can deliver, in watts
+    pub rated_watts: Option<f64>,
+    pub manufacturer: Option<String>,
+    pub model: Option<String>,
+}
+
+impl Adapter {
+    /// Returns the online adapter, falling back to the first one found.
+    pub fn detect() -> Option<Adapter> {
+        let mut adapters: Vec<Adapter> = fs::read_dir(sysfs::power_supply_dir())
+            .ok()?
+            .flatten()
+            .filter_map(|entry| Self::read(&entry.path()))
+            .collect();
+
+        adapters.sort_by_key(|adapter| !adapter.online);
+        adapters.into_iter().next()
+    }
+
+    fn read(path: &Path) -> Option<Adapter> {
+        let kind = read_string(&path.join("type"))?;
+        if !matches!(kind.as_str(), "Mains" | "USB" | "USB_C" | "USB_PD") {
+            return None;
+        }
+
+        let online = read_number(&path.join("online")).is_some_and(|v| v > 0.0);
+        let rated_watts = read_number(&path.join("voltage_max"))
+            .zip(read_number(&path.join("current_max")))
+            .map(|(uv, ua)| uv / 1e6 * ua / 1e6)
+            .filter(|watts| *watts > 0.0);
+
+        // Values are exposed in micro-units (µW, µV, µA). Only present
+        // readings count; the maximums are the rating, not the draw.
+        let watts = read_number(&path.join("power_now"))
+            .map(|uw| uw / 1e6)
+            .or_else(|| {
+                let volts = read_number(&path.join("voltage_now"))?;
+                let amps = read_number(&path.join("current_now"))?;
+                Some(volts / 1e6 * amps / 1e6)
+            })
+            .filter(|watts| *watts > 0.0);
+
+        Some(Adapter {
+            kind,
+            online,
+            watts,
+            rated_watts,
+            manufacturer: read_string(&path.join("manufacturer")).filter(|s| !s.is_empty()),
+            model: read_string(&path.join("model_name")).filter(|s| !s.is_empty()),
+        })
+    }
+
+    /// Tells chargers apart, e.g. "Apple 96W USB-C Power Adapter (96 W)"
+    /// or "USB_PD 45 W". `None` when the kernel reports nothing that
+    /// identifies the charger.
+    pub fn identity(&self) -> Option<String> {
+        let rated = self.rated_watts.map(|watts| format!("{:.0} W", watts));
+        let name = [self.manufacturer.as_deref(), self.model.as_deref()]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(" ");
+        match (name.is_empty(), rated) {
+            (false, Some(rated)) => Some(format!("{} ({})", name, rated)),
+            (false, None) => Some(name),
+            (true, Some(rated)) => Some(format!("{} {}", self.kind, rated)),
+            (true, None) => None,
+        }
+    }
+}
+
+fn read_string(path: &Path) -> Option<String> {
+    fs::read_to_string(path).ok().map(|s| s.trim().to_string())
+}
+
+fn read_number(path: &Path) -> Option<f64> {
+    read_string(path)?.parse().ok()
+}
@@ -0,0 +1,337 @@
+use crate::daemon::BatterySnapshot;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Degrees below the limit the temperature has to fall before the alert re-arms.
+const TEMPERATURE_HYSTERESIS: f32 = 2.0;
+/// How long an alert command may run before it is killed.
+const ACTION_TIMEOUT: Duration = Duration::from_secs(60);
+/// Percentage points above a threshold the charge has to climb before its
+/// notification re-arms.
+const CHARGE_HYSTERESIS: f32 = 2.0;
+
+/// Fires once when the battery gets hotter than `limit` while charging.
+#[derive(Debug)]
+pub struct TemperatureAlert {
+    limit: f32,
+    triggered: bool,
+}
+
+impl TemperatureAlert {
+    pub fn new(limit: f32) -> Self {
+        Self {
+            limit,
+            triggered: false,
+        }
+    }
+
+    /// Returns an alert message the first time the limit is exceeded.
+    pub fn update(&mut self, snapshot: &BatterySnapshot) -> Option<String> {
+        let temperature = snapshot.temperature?;
+        let charging = snapshot.status == "charging";
+
+        if !charging || temperature < self.limit - TEMPERATURE_HYSTERESIS {
+            self.triggered = false;
+            return None;
+        }
+        if self.triggered || temperature <= self.limit {
+            return None;
+        }
+
+        self.triggered = true;
+        Some(format!(
+            "Battery is at {:.1}°C while charging (limit {:.1}°C)",
+            temperature, self.limit
+        ))
+    }
+}
+
+/// Which low and full battery notifications the daemon sends.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChargeNotifications {
+    /// Percentage at which to warn, `None` to never warn
+    pub warn: Option<f32>,
+    /// Percentage at which to send a critical notification
+    pub critical: Option<f32>,
+    /// Whether to suggest unplugging once the battery is full
+    pub full: bool,
+    /// How long to wait before repeating a notification while the battery
+    /// stays below its threshold
+    pub cooldown: Duration,
+}
+
+impl Default for ChargeNotifications {
+    fn default() -> Self {
+        Self {
+            warn: Some(20.0),
+            critical: Some(10.0),
+            full: true,
+            cooldown: Duration::from_secs(30 * 60),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Urgency {
+    Normal,
+    Critical,
+}
+
+impl Urgency {
+    fn name(self) -> &'static str {
+        match self {
+            Urgency::Normal => "normal",
+            Urgency::Critical => "critical",
+        }
+    }
+}
+
+/// A notification `ChargeAlert` decided to send.
+#[derive(Debug, Clone)]
+pub struct ChargeNotice {
+    /// Recorded as a daemon event
+    pub event: &'static str,
+    pub summary: &'static str,
+    pub body: String,
+    pub urgency: Urgency,
+}
+
+/// Tracks the charge against the configured thresholds so each one fires
+/// once per crossing, repeating no more than once per cooldown.
+#[derive(Debug)]
+pub struct ChargeAlert {
+    config: ChargeNotifications,
+    warned: Option<Instant>,
+    critical: Option<Instant>,
+    full: bool,
+}
+
+impl ChargeAlert {
+    pub fn new(config: ChargeNotifications) -> Self {
+        Self {
+            config,
+            warned: None,
+            critical: None,
+            full: false,
+        }
+    }
+
+    pub fn update(&mut self, snapshot: &BatterySnapshot) -> Option<ChargeNotice> {
+        let percent = snapshot.percent;
+        match snapshot.status.as_str() {
+            "discharging" => {
+                self.full = false;
+                self.rearm(percent);
+                self.check_low(percent)
+            }
+            "full" | "charging" => {
+                self.warned = None;
+                self.critical = None;
+                let full = snapshot.status == "full" || percent >= 100.0;
+                if !self.config.full || !full || self.full {
+                    return None;
+                }
+                self.full = true;
+                Some(ChargeNotice {
+                    event: "full",
+                    summary: "Battery fully charged",
+                    body: "You can unplug the charger".to_string(),
+                    urgency: Urgency::Normal,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Forgets thresholds the charge has climbed back above.
+    fn rearm(&mut self, percent: f32) {
+        if self
+            .config
+            .warn
+            .is_some_and(|warn| percent > warn + CHARGE_HYSTERESIS)
+        {
+            self.warned = None;
+        }
+        if self
+            .config
+            .critical
+            .is_some_and(|critical| percent > critical + CHARGE_HYSTERESIS)
+        {
+            self.critical = None;
+        }
+    }
+
+    fn check_low(&mut self, percent: f32) -> Option<ChargeNotice> {
+        let due =
+            |sent: Option<Instant>| sent.is_none_or(|sent| sent.elapsed() >= self.config.cooldown);
+
+        if let Some(critical) = self.config.critical
+            && percent <= critical
+        {
+            if !due(self.critical) {
+                return None;
+            }
+            let now = Instant::now();
+            self.critical = Some(now);
+            // A critical notification covers the warning too.
+            self.warned = Some(now);
+            return Some(ChargeNotice {
+                event: "critical",
+                summary: "Battery critically low",
+                body: format!("{:.0}% left, plug in now", percent),
+                urgency: Urgency::Critical,
+            });
+        }
+        if let Some(warn) = self.config.warn
+            && percent <= warn
+            && due(self.warned)
+        {
+            self.warned = Some(Instant::now());
+            return Some(ChargeNotice {
+                event: "low",
+                summary: "Battery low",
+                body: format!("{:.0}% left", percent),
+                urgency: Urgency::Normal,
+            });
+        }
+        None
+    }
+}
+
+/// Which way the charge has to cross an action's threshold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Crossing {
+    Below,
+    Above,
+}
+
+/// A shell command run when the charge crosses a threshold.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlertAction {
+    pub crossing: Crossing,
+    pub threshold: f32,
+    pub command: String,
+}
+
+impl AlertAction {
+    /// Whether going from `previous` to `percent` crosses the threshold in
+    /// this action's direction.
+    fn crossed(&self, previous: f32, percent: f32) -> bool {
+        match self.crossing {
+            Crossing::Below => previous > self.threshold && percent <= self.threshold,
+            Crossing::Above => previous < self.threshold && percent >= self.threshold,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        let direction = match self.crossing {
+            Crossing::Below => "below",
+            Crossing::Above => "above",
+        };
+        format!("{} {}%: {}", direction, self.threshold, self.command)
+    }
+
+    /// Runs the command through `sh -c` in the background, with the
+    /// reading in `AMPTOP_PERCENT` and `AMPTOP_STATUS`. Its output goes to
+    /// the daemon's log; commands still running after `ACTION_TIMEOUT` are
+    /// killed.
+    fn run(&self, snapshot: &BatterySnapshot) {
+        eprintln!("Running alert action ({})", self.describe());
+        let child = Command::new("sh")
+            .args(["-c", &self.command])
+            .env("AMPTOP_PERCENT", format!("{:.0}", snapshot.percent))
+            .env("AMPTOP_STATUS", &snapshot.status)
+            .env("AMPTOP_THRESHOLD", self.threshold.to_string())
+            .stdin(Stdio::null())
+            .spawn();
+        let mut child = match child {
+            Ok(child) => child,
+            Err(e) => {
+                eprintln!("Failed to run alert action `{}`: {}", self.command, e);
+                return;
+            }
+        };
+
+        let command = self.command.clone();
+        thread::spawn(move || {
+            let started = Instant::now();
+            loop {
+                match child.try_wait() {
+                    Ok(Some(status)) if status.success() => return,
+                    Ok(Some(status)) => {
+                        eprintln!("Alert action `{}` exited with {}", command, status);
+                        return;
+                    }
+                    Ok(None) if started.elapsed() >= ACTION_TIMEOUT => {
+                        eprintln!("Alert action `{}` timed out, killing it", command);
+                        child.kill().ok();
+                        child.wait().ok();
+                        return;
+                    }
+                    Ok(None) => thread::sleep(Duration::from_millis(200)),
+                    Err(e) => {
+                        eprintln!("Failed to wait for alert action `{}`: {}", command, e);
+                        return;
+                    }
+                }
+            }
+        });
+    }
+}
+
+/// Runs each action's command when a new reading crosses its threshold.
+/// The first reading only sets the starting point, so starting the daemon
+/// already below a threshold doesn't fire it.
+#[derive(Debug)]
+pub struct ActionTrigger {
+    actions: Vec<AlertAction>,
+    previous: Option<f32>,
+}
+
+impl ActionTrigger {
+    pub fn new(actions: Vec<AlertAction>) -> Self {
+        Self {
+            actions,
+            previous: None,
+        }
+    }
+
+    /// Runs the actions crossed since the previous reading and returns them.
+    pub fn update(&mut self, snapshot: &BatterySnapshot) -> Vec<&AlertAction> {
+        let previous = self.previous.replace(snapshot.percent);
+        let Some(previous) = previous else {
+            return Vec::new();
+        };
+        let crossed: Vec<&AlertAction> = self
+            .actions
+            .iter()
+            .filter(|action| action.crossed(previous, snapshot.percent))
+            .collect();
+        for action in &crossed {
+            action.run(snapshot);
+        }
+        crossed
+    }
+}
+
+/// Logs an alert and raises a desktop notification when `notify-send` is
+/// available.
+pub fn notify(summary: &str, body: &str) {
+    notify_with(Urgency::Normal, summary, body);
+}
+
+pub fn notify_with(urgency: Urgency, summary: &str, body: &str) {
+    eprintln!("{}: {}", summary, body);
+    if let Err(e) = Command::new("notify-send")
+        .args([
+            "--app-name=amptop",
+            &format!("--urgency={}", urgency.name()),
+            summary,
+            body,
+        ])
+        .status()
+    {
+        eprintln!("Failed to send notification: {}", e);
+    }
+}
@@ -0,0 +1,115 @@
+//! Annotations written by other tools into a file or named pipe.
+//!
+//! Each line becomes an annotation stamped with the time it was read, so a
+//! build script can `echo "build started" >> ~/annotations` and the mark
+//! shows up on the history graph.
+
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::os::unix::fs::FileTypeExt;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+
+/// How often a regular file is checked for new lines.
+const POLL: Duration = Duration::from_secs(1);
+/// Longest annotation kept; the rest of the line is dropped.
+const MAX_LEN: usize = 200;
+
+#[derive(Debug, Clone)]
+pub struct Annotation {
+    pub timestamp: i64,
+    pub text: String,
+}
+
+/// Follows `path` on a background thread and returns the receiving end of
+/// its channel. A named pipe is reopened each time its writer closes it; a
+/// regular file is followed from its current end, like `tail -f`.
+pub fn watch(path: PathBuf) -> Receiver<Annotation> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        loop {
+            let is_fifo = fs::metadata(&path).is_ok_and(|meta| meta.file_type().is_fifo());
+            let result = if is_fifo {
+                follow_pipe(&path, &tx)
+            } else {
+                follow_file(&path, &tx)
+            };
+            if result.is_err() {
+                // The daemon has stopped listening.
+                break;
+            }
+            thread::sleep(POLL);
+        }
+    });
+    rx
+}
+
+/// Reads one writer's worth of lines from a named pipe. Opening blocks
+/// until something opens the pipe for writing.
+fn follow_pipe(path: &Path, tx: &Sender<Annotation>) -> Result<(), ()> {
+    let Ok(file) = File::open(path) else {
+        return Ok(());
+    };
+    for line in BufReader::new(file).lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        send(&line, tx)?;
+    }
+    Ok(())
+}
+
+/// Follows a regular file until it disappears or is replaced.
+fn follow_file(path: &Path, tx: &Sender<Annotation>) -> Result<(), ()> {
+    let Ok(mut file) = File::open(path) else {
+        return Ok(());
+    };
+    let Ok(mut position) = file.seek(SeekFrom::End(0)) else {
+        return Ok(());
+    };
+    let mut reader = BufReader::new(file);
+    let mut line = String::new();
+
+    loop {
+        match reader.read_line(&mut line) {
+            Ok(0) => {
+                let Ok(meta) = fs::metadata(path) else {
+                    return Ok(());
+                };
+                if meta.len() < position {
+                    // Truncated: start again from the top.
+                    if reader.seek(SeekFrom::Start(0)).is_err() {
+                        return Ok(());
+                    }
+                    position = 0;
+                }
+                thread::sleep(POLL);
+            }
+            Ok(read) => {
+                position += read as u64;
+                // Wait for the rest of a partially written line.
+                if !line.ends_with('\n') {
+                    thread::sleep(POLL);
+                    continue;
+                }
+                send(&line, tx)?;
+                line.clear();
+            }
+            Err(_) => return Ok(()),
+        }
+    }
+}
+
+fn send(line: &str, tx: &Sender<Annotation>) -> Result<(), ()> {
+    let text: String = line.trim().chars().take(MAX_LEN).collect();
+    if text.is_empty() {
+        return Ok(());
+    }
+    tx.send(Annotation {
+        timestamp: chrono::Utc::now().timestamp(),
+        text,
+    })
+    .map_err(|_| ())
+}
@@ -1,49 +1,926 @@
-use crate::config::Config;
+use crate::annotations::Annotation;
+use crate::collector::Collector;
+use crate::config::{ChartAxis, Collector as CollectorSetting, Config, TemperatureUnit, Unit};
+use crate::daemon::{
+    BatteryDaemon, BatterySnapshot, ChargeBands, HealthSample, History, HostSummary,
+};
+use crate::desktop::DesktopPower;
 use crate::errors::{Error, Result};
+use crate::forecast::{self, Load, SmoothedDrain, UsageModel};
 use crate::info::BatteryInfo;
+use crate::keymap::Action;
+use crate::live::Subscription;
+use crate::loader::HistoryLoader;
+use crate::overhead::{Overhead, ProcessUsage};
+use crate::sessions::Session;
+use crate::theme::Theme;
+use crate::uevent::{self, PowerEvent};
 use crate::ui;
-use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+use crate::workload::{self, Preset};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::{execute, terminal::SetTitle};
 use ratatui::{
     DefaultTerminal, Frame,
-    layout::{Constraint, Direction, Layout},
+    layout::{Constraint, Direction, Layout, Rect},
 };
+use std::collections::VecDeque;
+use std::io::Write;
+use std::sync::mpsc::Receiver;
+use std::time::{Duration, Instant};
+
+/// How often to check for power supply events while waiting for input.
+const POWER_EVENT_POLL: Duration = Duration::from_millis(100);
+/// How long a toast notification stays on screen.
+const TOAST_DURATION: Duration = Duration::from_secs(4);
+/// Portion of `TOAST_DURATION` during which the toast is drawn dimmed.
+const TOAST_FADE: Duration = Duration::from_secs(1);
+/// Roughly how many columns the Overview history graph has; the history is
+/// reloaded once per column's worth of time.
+const HISTORY_POINTS: u32 = 500;
+/// Shortest refresh interval in low-power mode.
+const LOW_POWER_DELAY: Duration = Duration::from_secs(10);
+/// How long the Overview history is reused in low-power mode before the
+/// database is queried again.
+const LOW_POWER_HISTORY_REFRESH: Duration = Duration::from_secs(5 * 60);
+/// How often the history is queried again while the daemon streams new
+/// samples, to pick up annotations and downsampled rows.
+const LIVE_HISTORY_REFRESH: Duration = Duration::from_secs(5 * 60);
+/// xterm sequences that save and restore the window title, so `--title`
+/// leaves the terminal as it found it.
+const PUSH_TITLE: &str = "\x1b[22;0t";
+const POP_TITLE: &str = "\x1b[23;0t";
+/// How often details that rarely change, such as the charge limit, are
+/// read again.
+const DETAILS_REFRESH: Duration = Duration::from_secs(60);
+/// How often the workload presets are learned again from history.
+const WORKLOAD_REFRESH: Duration = Duration::from_secs(10 * 60);
+/// How often the Sessions, Health and Fleet tabs check for new history.
+const TAB_DATA_REFRESH: Duration = Duration::from_secs(30);
+/// Days of history the Sessions tab lists.
+const SESSION_DAYS: i64 = 30;
+/// Samples drawn for the selected Fleet host.
+const FLEET_HOST_SAMPLES: usize = 500;
+/// Refreshes kept for the sparkline under the gauge, an hour at the default
+/// delay.
+const TREND_SAMPLES: usize = 3600;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tab {
+    Overview,
+    History,
+    Sessions,
+    Health,
+    Fleet,
+    Settings,
+}
+
+impl Tab {
+    pub const ALL: [Tab; 6] = [
+        Tab::Overview,
+        Tab::History,
+        Tab::Sessions,
+        Tab::Health,
+        Tab::Fleet,
+        Tab::Settings,
+    ];
+
+    pub fn title(self) -> &'static str {
+        match self {
+            Tab::Overview => "Overview",
+            Tab::History => "History",
+            Tab::Sessions => "Sessions",
+            Tab::Health => "Health",
+            Tab::Fleet => "Fleet",
+            Tab::Settings => "Settings",
+        }
+    }
+
+    fn index(self) -> usize {
+        Self::ALL.iter().position(|&tab| tab == self).unwrap_or(0)
+    }
+
+    fn next(self) -> Tab {
+        Self::ALL[(self.index() + 1) % Self::ALL.len()]
+    }
+
+    fn previous(self) -> Tab {
+        Self::ALL[(self.index() + Self::ALL.len() - 1) % Self::ALL.len()]
+    }
+
+    /// Whether the tab shows the history graph, so the graph keys apply.
+    fn shows_history(self) -> bool {
+        matches!(self, Tab::Overview | Tab::History)
+    }
+}
+
+/// Spans selectable with `+`/`-`, in hours.
+const RANGE_PRESETS: [u64; 5] = [1, 6, 24, 7 * 24, 30 * 24];
+/// Narrowest and widest spans reachable by zooming.
+const MIN_SPAN: Duration = Duration::from_secs(10 * 60);
+const MAX_SPAN: Duration = Duration::from_secs(365 * 24 * 60 * 60);
+
+/// Time window shown in the Overview history graph.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HistoryWindow {
+    pub span: Duration,
+    /// Right edge of the window, or `None` to follow the latest samples
+    pub end: Option<i64>,
+}
+
+impl Default for HistoryWindow {
+    fn default() -> Self {
+        Self {
+            span: Duration::from_secs(24 * 60 * 60),
+            end: None,
+        }
+    }
+}
+
+impl HistoryWindow {
+    /// Compact span such as `90m`, `6h` or `7d`.
+    pub fn span_label(&self) -> String {
+        let minutes = self.span.as_secs() / 60;
+        match minutes {
+            m if m % (24 * 60) == 0 => format!("{}d", m / (24 * 60)),
+            m if m % 60 == 0 && m >= 60 => format!("{}h", m / 60),
+            m => format!("{}m", m),
+        }
+    }
+
+    fn secs(&self) -> i64 {
+        self.span.as_secs() as i64
+    }
+
+    /// The next preset span wider or narrower than the current one.
+    fn preset(self, wider: bool) -> Self {
+        let presets = RANGE_PRESETS.map(|hours| Duration::from_secs(hours * 60 * 60));
+        let span = if wider {
+            presets.into_iter().find(|&span| span > self.span)
+        } else {
+            presets.into_iter().rev().find(|&span| span < self.span)
+        };
+        self.with_span(span.unwrap_or(self.span))
+    }
+
+    /// Halves (`zoom_in`) or doubles the span, keeping the window's center
+    /// in place unless it follows the latest samples.
+    fn zoom(self, zoom_in: bool) -> Self {
+        let span = if zoom_in {
+            self.span / 2
+        } else {
+            self.span * 2
+        };
+        self.with_span(span.clamp(MIN_SPAN, MAX_SPAN))
+    }
+
+    fn with_span(self, span: Duration) -> Self {
+        let end = self.end.map(|end| {
+            let center = end - self.secs() / 2;
+            center + span.as_secs() as i64 / 2
+        });
+        Self { span, end }.clamped()
+    }
+
+    /// Moves the window by a quarter of its span; negative steps go back in
+    /// time. Panning past the present follows the latest samples again.
+    fn pan(self, steps: i64) -> Self {
+        let now = chrono::Utc::now().timestamp();
+        let end = self.end.unwrap_or(now) + steps * self.secs() / 4;
+        Self {
+            end: Some(end),
+            ..self
+        }
+        .clamped()
+    }
+
+    /// The window of the same span with `timestamp` in its middle.
+    fn centered_on(self, timestamp: i64) -> Self {
+        Self {
+            end: Some(timestamp + self.secs() / 2),
+            ..self
+        }
+        .clamped()
+    }
+
+    fn clamped(self) -> Self {
+        let now = chrono::Utc::now().timestamp();
+        Self {
+            end: self.end.filter(|&end| end < now),
+            ..self
+        }
+    }
+
+    /// Start and end of the window, as Unix timestamps.
+    pub fn bounds(&self) -> (i64, i64) {
+        let end = self.end.unwrap_or_else(|| chrono::Utc::now().timestamp());
+        (end - self.secs(), end)
+    }
+
+    /// Annotations in the window, oldest first. Missing annotations only
+    /// leave the graph unmarked, so errors are ignored.
+    fn annotations(&self, db: &History) -> Vec<Annotation> {
+        let (since, until) = self.bounds();
+        db.annotations_between(since, until).unwrap_or_default()
+    }
+}
+
+/// What the Sessions, Health and Fleet tabs show. Only the current tab's
+/// data is queried, when the tab is opened or the history changes, rather
+/// than on every draw.
+#[derive(Debug)]
+pub struct TabData {
+    pub sessions: Result<Vec<Session>>,
+    pub charge_bands: Result<ChargeBands>,
+    pub wear: Result<Vec<HealthSample>>,
+    pub hosts: Result<Vec<HostSummary>>,
+    /// Recent samples of the selected host, newest first
+    pub host_logs: Result<Vec<BatterySnapshot>>,
+}
+
+impl Default for TabData {
+    fn default() -> Self {
+        Self {
+            sessions: Ok(Vec::new()),
+            charge_bands: Ok(ChargeBands::default()),
+            wear: Ok(Vec::new()),
+            hosts: Ok(Vec::new()),
+            host_logs: Ok(Vec::new()),
+        }
+    }
+}
+
+/// Why `Application::wait_for_input` returned.
+enum Wake {
+    Input,
+    Refresh,
+    HistoryLoaded,
+}
+
+#[derive(Debug)]
+struct Toast {
+    message: String,
+    shown_at: Instant,
+}
+
+/// The "can I finish this?" dialog.
+#[derive(Debug)]
+pub struct FinishPrompt {
+    pub input: String,
+    pub load: Load,
+    pub answer: Option<String>,
+}
+
+impl FinishPrompt {
+    fn answer(&mut self, battery: &BatteryInfo) {
+        let answer = match humantime::parse_duration(self.input.trim()) {
+            Err(_) => "Enter a duration like 45m or 2h30m".to_string(),
+            Ok(task) => {
+                let model = UsageModel::load().unwrap_or(None);
+                match forecast::can_finish(battery, model, task, self.load) {
+                    Some(verdict) => verdict.summary(),
+                    None => "Not enough data to estimate".to_string(),
+                }
+            }
+        };
+        self.answer = Some(answer);
+    }
+}
+
+/// The "go to date" prompt, moving the history graph to a point in time.
+#[derive(Debug, Default)]
+pub struct GotoPrompt {
+    pub input: String,
+    /// Why the last input couldn't be used
+    pub error: Option<String>,
+}
+
+/// The panel showing amptop's own resource use.
+#[derive(Debug)]
+pub struct OverheadPanel {
+    /// This process when the panel was opened
+    baseline: Option<ProcessUsage>,
+    /// This process's use since the panel was opened
+    pub tui: Option<Overhead>,
+    /// The daemon's PID and use since it started, when it runs
+    pub daemon: Option<(i32, ProcessUsage)>,
+}
+
+impl OverheadPanel {
+    fn open() -> Self {
+        let mut panel = Self {
+            baseline: ProcessUsage::read(None),
+            tui: None,
+            daemon: None,
+        };
+        panel.update();
+        panel
+    }
+
+    fn update(&mut self) {
+        self.tui = ProcessUsage::read(None)
+            .zip(self.baseline)
+            .and_then(|(now, baseline)| now.since(&baseline));
+        self.daemon = BatteryDaemon::running_pid()
+            .and_then(|pid| Some((pid, ProcessUsage::read(Some(pid))?)));
+    }
+}
 
 #[derive(Debug)]
 pub struct Application {
     battery_info: BatteryInfo,
+    /// Shown instead of the battery details on machines without one
+    desktop: DesktopPower,
     config: Config,
+    power_events: Option<Receiver<PowerEvent>>,
+    last_state: Option<battery::State>,
+    /// Drain of the ongoing discharge, smoothed over the last few minutes
+    smoothed_drain: SmoothedDrain,
+    /// Charge percentage at each refresh since amptop started, oldest first
+    trend: VecDeque<f64>,
+    toast: Option<Toast>,
+    tab: Tab,
+    theme: Theme,
+    /// Y axis of the history graphs, switched with `a`
+    chart_axis: ChartAxis,
+    /// Units of the Energy and Environments panels, switched with `u`
+    unit: Unit,
+    temperature_unit: TemperatureUnit,
+    fleet_selected: usize,
+    finish_prompt: Option<FinishPrompt>,
+    goto_prompt: Option<GotoPrompt>,
+    show_help: bool,
+    show_peripherals: bool,
+    overhead: Option<OverheadPanel>,
+    /// Connection every history query goes through, opened on first use
+    db: Option<History>,
+    /// Span of time the Overview history graph shows
+    history_window: HistoryWindow,
+    /// Overview history, newest first, and when it was loaded
+    history: Result<Vec<BatterySnapshot>>,
+    history_loaded: Instant,
+    /// Fills `history` in the background; the previous samples stay on
+    /// screen until their slice of the window arrives
+    history_loader: Option<HistoryLoader>,
+    /// `History::version` when the history was loaded
+    history_version: Option<i64>,
+    /// New samples streamed by the running daemon; without it the history
+    /// is polled from the database
+    live: Option<Subscription>,
+    /// Records history while no daemon is running, when the user agreed
+    collector: Option<Collector>,
+    /// Asking whether to start the collector
+    collector_prompt: bool,
+    /// Annotations within the history window
+    annotations: Vec<Annotation>,
+    /// Full cycles estimated from the history, when the battery doesn't
+    /// count them
+    estimated_cycles: Option<f64>,
+    tab_data: TabData,
+    /// Tab and `History::version` the tab data was loaded for, if current
+    tab_data_key: Option<(Tab, Option<i64>)>,
+    tab_data_loaded: Instant,
+    workloads: Vec<Preset>,
+    workloads_loaded: Instant,
+    details_loaded: Instant,
+    refreshed_at: Instant,
+    /// Last terminal title written with `--title`
+    title: Option<String>,
+    /// Why the battery could not be read, and since when; the last
+    /// readings stay on screen, greyed out, until a refresh succeeds
+    refresh_error: Option<(String, Instant)>,
+    needs_redraw: bool,
     exit: bool,
 }
 
 impl Application {
     pub fn init(config: Config) -> Result<Self> {
-        let battery_info = BatteryInfo::new()?;
+        let mut battery_info = BatteryInfo::new()?;
+        battery_info.restore_unplugged_energy();
+        let last_state = battery_info.battery_state();
+        let mut desktop = DesktopPower::new(config.ups().map(str::to_string), config.upower());
+        if !battery_info.has_battery() {
+            desktop.refresh();
+        }
+        // Subscribe first, so no sample falls between the load and the feed.
+        let live = Subscription::connect(&BatteryDaemon::socket_path()).ok();
+        let history_window = HistoryWindow::default();
+        let (since, until) = history_window.bounds();
+        let (db, history, annotations) = match History::open() {
+            Ok(db) => {
+                let annotations = history_window.annotations(&db);
+                (Some(db), Ok(Vec::new()), annotations)
+            }
+            Err(e) => (None, Err(e), Vec::new()),
+        };
+        let history_version = db.as_ref().and_then(|db| db.version().ok());
+        let needs_cycles = battery_info.cycle_count().is_none();
+        let no_daemon =
+            battery_info.has_battery() && !config.demo() && !BatteryDaemon::is_running();
+        let collector = (no_daemon && config.collector() == CollectorSetting::Always)
+            .then(|| Collector::start(config.daemon_interval()));
+        let collector_prompt = no_daemon && config.collector() == CollectorSetting::Ask;
         Ok(Self {
             battery_info,
+            desktop,
+            power_events: uevent::subscribe().ok(),
+            last_state,
+            smoothed_drain: SmoothedDrain::load(),
+            trend: VecDeque::with_capacity(TREND_SAMPLES),
+            toast: None,
+            tab: Tab::Overview,
+            theme: config.theme(),
+            chart_axis: config.chart_axis(),
+            unit: config.unit(),
+            temperature_unit: config.temp_unit().unwrap_or(config.unit().temperature()),
             config,
+            fleet_selected: 0,
+            finish_prompt: None,
+            goto_prompt: None,
+            show_help: false,
+            show_peripherals: false,
+            overhead: None,
+            db,
+            history_window,
+            history,
+            history_loaded: Instant::now(),
+            history_loader: Some(HistoryLoader::start(since, until, needs_cycles)),
+            history_version,
+            live,
+            collector,
+            collector_prompt,
+            annotations,
+            estimated_cycles: None,
+            tab_data: TabData::default(),
+            tab_data_key: None,
+            tab_data_loaded: Instant::now(),
+            workloads: workload::load().unwrap_or_default(),
+            workloads_loaded: Instant::now(),
+            details_loaded: Instant::now(),
+            refreshed_at: Instant::now(),
+            title: None,
+            refresh_error: None,
+            needs_redraw: true,
             exit: false,
         })
     }
 
+    pub fn show_toast(&mut self, message: &str) {
+        self.toast = Some(Toast {
+            message: message.to_string(),
+            shown_at: Instant::now(),
+        });
+        self.needs_redraw = true;
+    }
+
     fn exit(&mut self) {
         self.exit = true;
     }
 
     pub fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        if self.config.title() {
+            write!(std::io::stdout(), "{}", PUSH_TITLE)?;
+        }
+        let result = self.run_loop(terminal);
+        if self.config.title() {
+            write!(std::io::stdout(), "{}", POP_TITLE)?;
+            std::io::stdout().flush()?;
+        }
+        result
+    }
+
+    fn run_loop(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
         while !self.exit {
-            terminal.draw(|frame| self.draw(frame))?;
+            // Redraw while a toast is shown so it fades on time.
+            if self.needs_redraw || self.toast.is_some() {
+                self.load_tab_data();
+                terminal.draw(|frame| self.draw(frame))?;
+                self.needs_redraw = false;
+                if self.config.title() {
+                    self.update_title()?;
+                }
+            }
 
-            if event::poll(*self.config.delay())
-                .map_err(|e| Error::Crossterm(format!("Event poll error: {}", e)))?
+            match self.wait_for_input()? {
+                Wake::Input => {
+                    self.handle_events()?;
+                    self.needs_redraw = true;
+                }
+                Wake::Refresh => self.refresh(),
+                Wake::HistoryLoaded => {}
+            }
+
+            if self
+                .toast
+                .as_ref()
+                .is_some_and(|toast| toast.shown_at.elapsed() >= TOAST_DURATION)
             {
-                self.handle_events()?;
-            } else {
-                self.battery_info.refresh()?;
+                self.toast = None;
+                self.needs_redraw = true;
             }
         }
         Ok(())
     }
 
+    /// Writes e.g. "amptop — 78% ⚡ 2h 41m" to the terminal title when it
+    /// changed since the last time.
+    fn update_title(&mut self) -> Result<()> {
+        let title = self.window_title();
+        if self.title.as_ref() != Some(&title) {
+            execute!(std::io::stdout(), SetTitle(&title))?;
+            self.title = Some(title);
+        }
+        Ok(())
+    }
+
+    fn window_title(&self) -> String {
+        let Some((_, percent)) = self.battery_info.state_of_charge() else {
+            return "amptop".to_string();
+        };
+        let mut title = format!("amptop — {:.0}%", percent);
+        let status = match self.battery_info.battery_state() {
+            Some(battery::State::Charging) => Some(("⚡", self.battery_info.time_to_full())),
+            Some(battery::State::Discharging) => Some(("🔋", self.battery_info.time_to_empty())),
+            _ => None,
+        };
+        if let Some((icon, remaining)) = status {
+            title.push(' ');
+            title.push_str(icon);
+            if let Some(remaining) = remaining {
+                title.push(' ');
+                title.push_str(&remaining);
+            }
+        }
+        title
+    }
+
+    /// Low-power mode only kicks in on battery, where amptop's own drain
+    /// matters.
+    fn low_power_active(&self) -> bool {
+        self.config.low_power()
+            && self.battery_info.battery_state() == Some(battery::State::Discharging)
+    }
+
+    /// Reads the battery again and reloads whatever history is due. A
+    /// failed battery read (a removed battery, an ACPI hiccup) keeps the
+    /// previous readings and is shown as a badge instead of ending amptop.
+    fn refresh(&mut self) {
+        let previous = self.battery_info.reading().cloned();
+        match self.battery_info.refresh() {
+            Ok(()) => {
+                if self.refresh_error.take().is_some() {
+                    self.show_toast("Battery readings restored");
+                }
+            }
+            Err(err) => {
+                let since = self.refresh_error.take().map(|(_, since)| since);
+                self.refresh_error = Some((err.to_string(), since.unwrap_or_else(Instant::now)));
+                self.needs_redraw = true;
+            }
+        }
+        if self.battery_info.reading() != previous.as_ref() {
+            self.needs_redraw = true;
+        }
+        self.update_smoothed_drain();
+        if self.refresh_error.is_none()
+            && let Some((_, percent)) = self.battery_info.state_of_charge()
+        {
+            // The sparkline only changes when a different value enters or
+            // leaves it; a steady charge doesn't need a redraw.
+            let changed = self.trend.back() != Some(&percent);
+            let dropped = if self.trend.len() == TREND_SAMPLES {
+                self.trend.pop_front()
+            } else {
+                None
+            };
+            self.trend.push_back(percent);
+            self.needs_redraw |= changed || dropped.is_some_and(|old| old != percent);
+        }
+
+        if !self.battery_info.has_battery() {
+            self.desktop.refresh();
+            self.needs_redraw = true;
+        } else if self.show_peripherals {
+            self.desktop.refresh_peripherals();
+            self.needs_redraw = true;
+        }
+        if let Some(ref mut panel) = self.overhead {
+            panel.update();
+            self.needs_redraw = true;
+        }
+
+        if self.details_loaded.elapsed() >= DETAILS_REFRESH {
+            let limit = self.battery_info.charge_limit();
+            self.battery_info.refresh_details();
+            self.details_loaded = Instant::now();
+            self.needs_redraw |= self.battery_info.charge_limit() != limit;
+        }
+
+        // The graphs span the loaded samples, so without new rows from the
+        // daemon a reload would draw the same thing.
+        let mut history_refresh = self.history_window.span / HISTORY_POINTS;
+        if self.low_power_active() {
+            history_refresh = history_refresh.max(LOW_POWER_HISTORY_REFRESH);
+        }
+        if self.live.is_some() && self.history_window.end.is_none() {
+            history_refresh = history_refresh.max(LIVE_HISTORY_REFRESH);
+        } else if self.live.is_none()
+            && let Ok(live) = Subscription::connect(&BatteryDaemon::socket_path())
+        {
+            // A daemon just started; catch up on what it wrote before.
+            self.live = Some(live);
+            self.reload_history();
+        }
+        if self.history_loaded.elapsed() >= history_refresh {
+            let version = self.history_db_version();
+            if version.is_none() || version != self.history_version {
+                self.reload_history();
+            } else {
+                self.history_loaded = Instant::now();
+            }
+        }
+        if self.tab_data_key.is_some() && self.tab_data_loaded.elapsed() >= TAB_DATA_REFRESH {
+            let version = self.history_db_version();
+            if version.is_none() || Some((self.tab, version)) != self.tab_data_key {
+                self.tab_data_key = None;
+                self.needs_redraw = true;
+            }
+            self.tab_data_loaded = Instant::now();
+        }
+        if self.workloads_loaded.elapsed() >= WORKLOAD_REFRESH {
+            self.workloads = workload::load().unwrap_or_default();
+            self.workloads_loaded = Instant::now();
+        }
+
+        if let Some(finished) = self.collector.as_mut().and_then(Collector::finished) {
+            // A daemon that took over needs no mention; the live feed
+            // picks it up.
+            self.collector = None;
+            if let Err(e) = finished {
+                self.show_toast(&format!("Stopped recording history: {}", e));
+            }
+        }
+
+        self.check_state_change();
+        self.refreshed_at = Instant::now();
+    }
+
+    /// Folds the latest reading into the smoothed drain, which only covers
+    /// the ongoing discharge.
+    fn update_smoothed_drain(&mut self) {
+        let shown = self
+            .smoothed_time_to_empty()
+            .map(|time| time.as_secs() / 60);
+        match self.battery_info.total() {
+            Some(reading) if reading.state == battery::State::Discharging => {
+                let watts = reading.energy_rate.get::<battery::units::power::watt>();
+                self.smoothed_drain
+                    .update(chrono::Utc::now().timestamp(), f64::from(watts));
+            }
+            _ => self.smoothed_drain.reset(),
+        }
+        // The estimate keeps moving while the reading holds steady.
+        if self
+            .smoothed_time_to_empty()
+            .map(|time| time.as_secs() / 60)
+            != shown
+        {
+            self.needs_redraw = true;
+        }
+    }
+
+    /// Time to empty at the smoothed drain, while discharging.
+    fn smoothed_time_to_empty(&self) -> Option<Duration> {
+        let reading = self
+            .battery_info
+            .total()
+            .filter(|reading| reading.state == battery::State::Discharging)?;
+        let energy = reading.energy.get::<battery::units::energy::watt_hour>();
+        self.smoothed_drain.time_to_empty(f64::from(energy))
+    }
+
+    /// The cached connection, opened again if it failed before.
+    fn db(&mut self) -> Result<&History> {
+        if self.db.is_none() {
+            self.db = Some(History::open()?);
+        }
+        Ok(self.db.as_ref().expect("opened above"))
+    }
+
+    fn history_db_version(&mut self) -> Option<i64> {
+        self.db().and_then(History::version).ok()
+    }
+
+    /// Reloads the history window in the background, replacing whatever
+    /// load is still running.
+    fn reload_history(&mut self) {
+        let window = self.history_window;
+        let loaded = self
+            .db()
+            .map(|db| (db.version().ok(), window.annotations(db)));
+        match loaded {
+            Ok((version, annotations)) => {
+                self.history_version = version;
+                self.annotations = annotations;
+                let (since, until) = window.bounds();
+                let needs_cycles = self.battery_info.cycle_count().is_none();
+                self.history_loader = Some(HistoryLoader::start(since, until, needs_cycles));
+            }
+            Err(e) => {
+                self.history_version = None;
+                self.history = Err(e);
+                self.annotations = Vec::new();
+                self.history_loader = None;
+            }
+        }
+        self.history_loaded = Instant::now();
+        self.needs_redraw = true;
+    }
+
+    /// Merges the slices the loader has read so far into the history.
+    /// Returns whether anything arrived.
+    fn receive_history(&mut self) -> bool {
+        let Some(ref loader) = self.history_loader else {
+            return false;
+        };
+        let (slices, finished) = loader.poll();
+        let (since, until) = (loader.since, loader.until);
+        let cycles = loader.cycles();
+        if finished {
+            self.history_loader = None;
+        }
+        // Samples streamed in while the loader ran are newer than what it
+        // reads; keep them while the window follows the latest samples.
+        let following = self.history_window.end.is_none();
+        let in_window = |timestamp: i64| {
+            (since..=until).contains(&timestamp) || (following && timestamp > until)
+        };
+        if let Some(cycles) = cycles {
+            self.estimated_cycles = cycles;
+        }
+        if slices.is_empty() && !finished && cycles.is_none() {
+            return false;
+        }
+
+        for slice in slices {
+            let logs = match slice.logs {
+                Ok(logs) => logs,
+                Err(e) => {
+                    self.history = Err(e);
+                    continue;
+                }
+            };
+            let mut history: Vec<BatterySnapshot> =
+                std::mem::replace(&mut self.history, Ok(Vec::new())).unwrap_or_default();
+            // Drop what this slice replaces and what fell out of the window.
+            history.retain(|log| {
+                in_window(log.timestamp) && !(slice.since..=slice.until).contains(&log.timestamp)
+            });
+            history.extend(logs);
+            history.sort_by_key(|log| std::cmp::Reverse(log.timestamp));
+            self.history = Ok(history);
+        }
+        if finished && let Ok(ref mut history) = self.history {
+            history.retain(|log| in_window(log.timestamp));
+        }
+        self.needs_redraw = true;
+        true
+    }
+
+    /// Adds the samples the daemon streamed since the last call to the
+    /// history, when the window follows the latest samples. Returns whether
+    /// anything arrived.
+    fn receive_live(&mut self) -> bool {
+        let Some(ref live) = self.live else {
+            return false;
+        };
+        let (snapshots, disconnected) = live.poll();
+        if disconnected {
+            self.live = None;
+        }
+        if snapshots.is_empty() || self.history_window.end.is_some() {
+            return false;
+        }
+
+        let (since, _) = self.history_window.bounds();
+        if let Ok(ref mut history) = self.history {
+            history.extend(snapshots);
+            history.sort_by_key(|log| std::cmp::Reverse(log.timestamp));
+            history.dedup_by_key(|log| log.timestamp);
+            history.retain(|log| log.timestamp >= since);
+        }
+        self.needs_redraw = true;
+        true
+    }
+
+    /// Queries what the current tab shows, unless it's already loaded for
+    /// this tab and history version.
+    fn load_tab_data(&mut self) {
+        if !matches!(self.tab, Tab::Sessions | Tab::Health | Tab::Fleet) {
+            return;
+        }
+        let (tab, selected) = (self.tab, self.fleet_selected);
+        let version = self.history_db_version();
+        if version.is_some() && self.tab_data_key == Some((tab, version)) {
+            return;
+        }
+
+        match tab {
+            Tab::Sessions => {
+                let since = chrono::Utc::now().timestamp() - SESSION_DAYS * 24 * 60 * 60;
+                self.tab_data.sessions = self.db().and_then(|db| db.sessions(since));
+            }
+            Tab::Health => {
+                self.tab_data.charge_bands = self.db().and_then(History::charge_bands);
+                self.tab_data.wear = self.db().and_then(History::health);
+            }
+            _ => {
+                self.tab_data.hosts = self.db().and_then(History::fleet);
+                let host = match self.tab_data.hosts {
+                    Ok(ref hosts) if !hosts.is_empty() => {
+                        Some(hosts[selected.min(hosts.len() - 1)].host.clone())
+                    }
+                    _ => None,
+                };
+                self.tab_data.host_logs = match host {
+                    Some(host) => self
+                        .db()
+                        .and_then(|db| db.host_logs(&host, Some(FLEET_HOST_SAMPLES))),
+                    None => Ok(Vec::new()),
+                };
+            }
+        }
+        self.tab_data_key = Some((tab, version));
+        self.tab_data_loaded = Instant::now();
+    }
+
+    fn set_history_window(&mut self, window: HistoryWindow) {
+        if window != self.history_window {
+            self.history_window = window;
+            self.reload_history();
+        }
+    }
+
+    fn check_state_change(&mut self) {
+        let state = self.battery_info.battery_state();
+        if state == self.last_state {
+            return;
+        }
+
+        let message = match state {
+            Some(battery::State::Charging) => Some("Charging"),
+            Some(battery::State::Discharging) => Some("Discharging"),
+            Some(battery::State::Full) => Some("Fully charged"),
+            _ => None,
+        };
+        if let Some(message) = message {
+            self.show_toast(message);
+        }
+        self.last_state = state;
+    }
+
+    /// Waits for terminal input until the next refresh is due, returning
+    /// early when a power supply event, a slice of history or a streamed
+    /// sample arrives.
+    fn wait_for_input(&mut self) -> Result<Wake> {
+        let delay = if self.low_power_active() {
+            (*self.config.delay()).max(LOW_POWER_DELAY)
+        } else {
+            *self.config.delay()
+        };
+        let deadline = self.refreshed_at + delay;
+
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let timeout = if self.power_events.is_some()
+                || self.history_loader.is_some()
+                || self.live.is_some()
+            {
+                remaining.min(POWER_EVENT_POLL)
+            } else {
+                remaining
+            };
+
+            if event::poll(timeout)
+                .map_err(|e| Error::Crossterm(format!("Event poll error: {}", e)))?
+            {
+                return Ok(Wake::Input);
+            }
+
+            if self.receive_history() || self.receive_live() {
+                return Ok(Wake::HistoryLoaded);
+            }
+
+            let power_changed = self
+                .power_events
+                .as_ref()
+                .is_some_and(|events| events.try_iter().count() > 0);
+
+            if power_changed || Instant::now() >= deadline {
+                return Ok(Wake::Refresh);
+            }
+        }
+    }
+
     fn handle_events(&mut self) -> Result<()> {
         match event::read().map_err(|e| Error::Crossterm(format!("Event read error: {}", e)))? {
             Event::Key(key_event) if key_event.kind == KeyEventKind::Press => {
@@ -55,51 +932,349 @@ impl Application {
     }
 
     fn handle_key_event(&mut self, key_event: KeyEvent) {
-        match key_event.code {
-            KeyCode::Char('q') | KeyCode::Esc => self.exit(),
-            KeyCode::Char('c')
-                if key_event
-                    .modifiers
-                    .contains(crossterm::event::KeyModifiers::CONTROL) =>
-            {
-                self.exit()
+        if self.collector_prompt {
+            match key_event.code {
+                KeyCode::Char('y') | KeyCode::Enter => {
+                    self.collector_prompt = false;
+                    self.collector = Some(Collector::start(self.config.daemon_interval()));
+                    self.show_toast("Recording history while amptop is open");
+                }
+                KeyCode::Char('n') | KeyCode::Esc => self.collector_prompt = false,
+                _ if self.config.keymap().action(&key_event) == Some(Action::Quit) => self.exit(),
+                _ => {}
+            }
+            return;
+        }
+
+        if let Some(ref mut prompt) = self.finish_prompt {
+            match key_event.code {
+                KeyCode::Esc => self.finish_prompt = None,
+                KeyCode::Enter => prompt.answer(&self.battery_info),
+                KeyCode::Tab => prompt.load = prompt.load.next(),
+                KeyCode::Backspace => {
+                    prompt.input.pop();
+                }
+                KeyCode::Char(c) if c.is_ascii_alphanumeric() || c == ' ' => prompt.input.push(c),
+                _ => {}
+            }
+            return;
+        }
+
+        if let Some(ref mut prompt) = self.goto_prompt {
+            match key_event.code {
+                KeyCode::Esc => self.goto_prompt = None,
+                KeyCode::Enter => match Config::parse_time(prompt.input.trim()) {
+                    Ok(timestamp) => {
+                        self.goto_prompt = None;
+                        if !self.tab.shows_history() {
+                            self.tab = Tab::History;
+                        }
+                        self.set_history_window(self.history_window.centered_on(timestamp));
+                    }
+                    Err(e) => prompt.error = Some(e),
+                },
+                KeyCode::Backspace => {
+                    prompt.input.pop();
+                }
+                KeyCode::Char(c) => prompt.input.push(c),
+                _ => {}
+            }
+            return;
+        }
+
+        let action = self.config.keymap().action(&key_event);
+        if key_event.code == KeyCode::Char('c')
+            && key_event.modifiers.contains(KeyModifiers::CONTROL)
+        {
+            self.exit();
+            return;
+        }
+
+        if self.show_help {
+            match action {
+                Some(Action::Help) => self.show_help = false,
+                _ if key_event.code == KeyCode::Esc => self.show_help = false,
+                Some(Action::Quit) => self.exit(),
+                _ => {}
+            }
+            return;
+        }
+
+        if self.show_peripherals {
+            match action {
+                Some(Action::Peripherals) => self.show_peripherals = false,
+                _ if key_event.code == KeyCode::Esc => self.show_peripherals = false,
+                Some(Action::Quit) => self.exit(),
+                _ => {}
+            }
+            return;
+        }
+
+        if self.overhead.is_some() {
+            match action {
+                Some(Action::Overhead) => self.overhead = None,
+                _ if key_event.code == KeyCode::Esc => self.overhead = None,
+                Some(Action::Quit) => self.exit(),
+                _ => {}
+            }
+            return;
+        }
+
+        // The digits always go to a tab; the keymap doesn't let them be
+        // bound to anything else.
+        if let KeyCode::Char(c @ '1'..='9') = key_event.code
+            && key_event.modifiers.is_empty()
+        {
+            if let Some(&tab) = Tab::ALL.get(c as usize - '1' as usize) {
+                self.tab = tab;
+            }
+            return;
+        }
+
+        let Some(action) = action else {
+            return;
+        };
+        match action {
+            Action::Quit => self.exit(),
+            Action::Help => self.show_help = true,
+            Action::Overhead => self.overhead = Some(OverheadPanel::open()),
+            Action::NextTab => self.tab = self.tab.next(),
+            Action::PreviousTab => self.tab = self.tab.previous(),
+            Action::ZoomIn if self.tab == Tab::Fleet => {
+                self.fleet_selected = self.fleet_selected.saturating_sub(1);
+                self.tab_data_key = None;
+            }
+            Action::ZoomOut if self.tab == Tab::Fleet => {
+                let hosts = self.tab_data.hosts.as_ref().map_or(0, Vec::len);
+                self.fleet_selected = (self.fleet_selected + 1).min(hosts.saturating_sub(1));
+                self.tab_data_key = None;
+            }
+            Action::SwitchBattery => self.battery_info.cycle_battery(),
+            Action::ChartAxis => {
+                self.chart_axis = self.chart_axis.next();
+                let label = match self.chart_axis {
+                    ChartAxis::Percent => "percent",
+                    ChartAxis::WattHours => "watt-hours",
+                    ChartAxis::Joules => "kilojoules",
+                };
+                self.show_toast(&format!("History graphs in {}", label));
+            }
+            Action::ToggleUnit => self.toggle_unit(),
+            Action::Peripherals if self.battery_info.has_battery() => {
+                self.desktop.refresh_peripherals();
+                self.show_peripherals = true;
+            }
+            Action::WidenRange => self.set_history_window(self.history_window.preset(true)),
+            Action::NarrowRange => self.set_history_window(self.history_window.preset(false)),
+            Action::PanLeft if self.tab.shows_history() => {
+                self.set_history_window(self.history_window.pan(-1))
+            }
+            Action::PanRight if self.tab.shows_history() => {
+                self.set_history_window(self.history_window.pan(1))
+            }
+            Action::ZoomIn if self.tab.shows_history() => {
+                self.set_history_window(self.history_window.zoom(true))
+            }
+            Action::ZoomOut if self.tab.shows_history() => {
+                self.set_history_window(self.history_window.zoom(false))
+            }
+            Action::Latest => self.set_history_window(HistoryWindow {
+                end: None,
+                ..self.history_window
+            }),
+            Action::GoTo => self.goto_prompt = Some(GotoPrompt::default()),
+            Action::Finish => {
+                self.finish_prompt = Some(FinishPrompt {
+                    input: String::new(),
+                    load: Load::Normal,
+                    answer: None,
+                })
             }
             _ => {}
         }
     }
 
+    /// Cycles human units in Celsius, human units in Fahrenheit, SI units
+    /// in Kelvin and mAh. A temperature unit set in the config stays put
+    /// and only the energy units switch.
+    fn toggle_unit(&mut self) {
+        if self.config.temp_unit().is_some() {
+            self.unit = self.unit.next();
+        } else {
+            (self.unit, self.temperature_unit) = match (self.unit, self.temperature_unit) {
+                (Unit::Human, TemperatureUnit::Celsius) => {
+                    (Unit::Human, TemperatureUnit::Fahrenheit)
+                }
+                (unit, _) => (unit.next(), unit.next().temperature()),
+            };
+        }
+        let unit = match self.unit {
+            Unit::Human => "human units",
+            Unit::Si => "SI units",
+            Unit::MilliampHours => "mAh",
+        };
+        self.show_toast(&format!(
+            "Showing {}, {}",
+            unit,
+            self.temperature_unit.symbol()
+        ));
+    }
+
     fn draw(&self, frame: &mut Frame) {
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(1), Constraint::Min(0)])
+            .split(frame.area());
+
+        ui::draw_tabs(self.tab, frame, rows[0], &self.theme);
+        if let Some((ref message, since)) = self.refresh_error {
+            ui::draw_error_badge(message, since.elapsed(), frame, rows[0], &self.theme);
+        }
+        match self.tab {
+            Tab::Overview => self.draw_overview(frame, rows[1]),
+            Tab::History => {
+                ui::draw_history(
+                    &self.history,
+                    &self.annotations,
+                    (&self.history_window, &self.config.keymap().graph_hint()),
+                    (
+                        self.chart_axis,
+                        self.temperature_unit,
+                        self.config.max_charge_temp(),
+                    ),
+                    frame,
+                    rows[1],
+                    &self.theme,
+                );
+                if self.history_loader.is_some() {
+                    ui::draw_loading(frame, rows[1], &self.theme);
+                }
+            }
+            Tab::Sessions => {
+                ui::draw_sessions(&self.tab_data.sessions, frame, rows[1], &self.theme)
+            }
+            Tab::Health => ui::draw_health(
+                &self.battery_info,
+                &self.tab_data,
+                frame,
+                rows[1],
+                &self.theme,
+            ),
+            Tab::Fleet => ui::draw_fleet(
+                &self.tab_data,
+                self.config.keymap(),
+                self.chart_axis,
+                frame,
+                rows[1],
+                self.fleet_selected,
+                &self.theme,
+            ),
+            Tab::Settings => ui::draw_settings(&self.config, frame, rows[1], &self.theme),
+        }
+
+        if let Some(ref prompt) = self.finish_prompt {
+            ui::draw_finish_prompt(prompt, frame, &self.theme);
+        }
+        if let Some(ref prompt) = self.goto_prompt {
+            ui::draw_goto_prompt(prompt, frame, &self.theme);
+        }
+        if self.collector_prompt {
+            ui::draw_collector_prompt(frame, &self.theme);
+        }
+        if self.show_peripherals {
+            ui::draw_peripherals(&self.desktop, self.config.keymap(), frame, &self.theme);
+        }
+        if let Some(ref panel) = self.overhead {
+            ui::draw_overhead(panel, self.config.keymap(), frame, &self.theme);
+        }
+        if self.show_help {
+            ui::draw_help(self.config.keymap(), frame);
+        }
+
+        if let Some(ref toast) = self.toast {
+            let fading = toast.shown_at.elapsed() + TOAST_FADE >= TOAST_DURATION;
+            ui::draw_toast(&toast.message, fading, frame, &self.theme);
+        }
+    }
+
+    fn draw_overview(&self, frame: &mut Frame, area: Rect) {
+        if !self.battery_info.has_battery() {
+            ui::draw_desktop_power(&self.desktop, frame, area, &self.theme);
+            return;
+        }
+
         let main_columns = Layout::default()
             .direction(Direction::Horizontal)
             .constraints([Constraint::Length(40), Constraint::Min(20)])
-            .split(frame.area());
+            .split(area);
 
         let left_column = Layout::default()
             .direction(Direction::Vertical)
             .constraints([
                 Constraint::Length(3),
+                Constraint::Length(3),
+                Constraint::Length(11),
                 Constraint::Length(10),
-                Constraint::Length(9),
                 Constraint::Length(5),
                 Constraint::Min(4),
             ])
             .split(main_columns[0]);
 
-        ui::draw_state_of_charge_bar(&self.battery_info, frame, left_column[0]);
-        ui::draw_common_info(&self.battery_info, frame, left_column[1]);
-        ui::draw_energy_info(
+        ui::draw_state_of_charge_bar(
             &self.battery_info,
+            self.config.scale_to_limit(),
+            self.config.gauge(),
+            frame,
+            left_column[0],
+            &self.theme,
+        );
+        ui::draw_trend(&self.trend, frame, left_column[1], &self.theme);
+        ui::draw_common_info(
+            &self.battery_info,
+            self.estimated_cycles,
             frame,
             left_column[2],
-            self.config.unit(),
         );
-        ui::draw_timing_info(&self.battery_info, frame, left_column[3]);
-        ui::draw_environment_info(
+        ui::draw_energy_info(&self.battery_info, frame, left_column[3], self.unit);
+        ui::draw_timing_info(
             &self.battery_info,
+            self.smoothed_time_to_empty(),
             frame,
             left_column[4],
-            self.config.unit(),
         );
-        ui::draw_drain_graph(frame, main_columns[1]);
+        ui::draw_environment_info(
+            &self.battery_info,
+            frame,
+            left_column[5],
+            self.temperature_unit,
+            self.config.max_charge_temp(),
+            &self.theme,
+        );
+        if self.refresh_error.is_some() {
+            ui::dim(frame, main_columns[0], &self.theme);
+        }
+        let right_column = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(10), Constraint::Length(6)])
+            .split(main_columns[1]);
+        ui::draw_drain_graph(
+            frame,
+            right_column[0],
+            &self.history,
+            &self.annotations,
+            Some((&self.history_window, &self.config.keymap().graph_hint())),
+            self.chart_axis,
+            &self.theme,
+        );
+        if self.history_loader.is_some() {
+            ui::draw_loading(frame, right_column[0], &self.theme);
+        }
+        ui::draw_workloads(
+            &self.battery_info,
+            &self.workloads,
+            frame,
+            right_column[1],
+            &self.theme,
+        );
     }
 }
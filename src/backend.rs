@@ -0,0 +1,104 @@
+//! Where battery readings come from. Some hardware misreports through one
+//! source but not another, so `--backend` picks one at runtime.
+
+use crate::errors::Result;
+use crate::info::Reading;
+use crate::sysfs;
+use crate::upower;
+use std::fmt;
+use std::sync::OnceLock;
+
+static SELECTED: OnceLock<Backend> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Backend {
+    /// `Sysfs` when a custom sysfs root is set, `Native` otherwise
+    #[default]
+    Auto,
+    Native,
+    Sysfs,
+    UPower,
+}
+
+impl Backend {
+    pub fn name(self) -> &'static str {
+        match self {
+            Backend::Auto => "auto",
+            Backend::Native => "native",
+            Backend::Sysfs => "sysfs",
+            Backend::UPower => "upower",
+        }
+    }
+
+    /// Selects the backend for every later `open`. Only the first call has
+    /// an effect.
+    pub fn select(self) {
+        SELECTED.set(self).ok();
+    }
+
+    pub fn selected() -> Backend {
+        SELECTED.get().copied().unwrap_or_default()
+    }
+
+    /// Opens the selected backend.
+    pub fn open() -> Result<Box<dyn BatteryBackend>> {
+        Ok(match Self::selected() {
+            Backend::Auto if sysfs::custom_root().is_some() => Box::new(SysfsBackend),
+            Backend::Auto | Backend::Native => Box::new(NativeBackend::new()?),
+            Backend::Sysfs => Box::new(SysfsBackend),
+            Backend::UPower => Box::new(UPowerBackend),
+        })
+    }
+}
+
+/// A source of battery readings.
+pub trait BatteryBackend: fmt::Debug {
+    /// Reads every system battery again.
+    fn read(&mut self) -> Result<Vec<Reading>>;
+}
+
+/// The `battery` crate, reading from the platform's native API.
+#[derive(Debug)]
+pub struct NativeBackend {
+    manager: battery::Manager,
+    batteries: Vec<battery::Battery>,
+}
+
+impl NativeBackend {
+    fn new() -> Result<Self> {
+        let manager = battery::Manager::new()?;
+        let batteries = manager.batteries()?.flatten().collect();
+        Ok(Self { manager, batteries })
+    }
+}
+
+impl BatteryBackend for NativeBackend {
+    fn read(&mut self) -> Result<Vec<Reading>> {
+        for battery in self.batteries.iter_mut() {
+            self.manager.refresh(battery)?;
+        }
+        let mut readings: Vec<Reading> = self.batteries.iter().map(Reading::from).collect();
+        sysfs::add_details(&mut readings);
+        Ok(readings)
+    }
+}
+
+/// Direct reads from `/sys/class/power_supply`, or a relocated copy.
+#[derive(Debug)]
+pub struct SysfsBackend;
+
+impl BatteryBackend for SysfsBackend {
+    fn read(&mut self) -> Result<Vec<Reading>> {
+        Ok(sysfs::read_batteries())
+    }
+}
+
+/// The batteries UPower reports, through `upower --dump`.
+#[derive(Debug)]
+pub struct UPowerBackend;
+
+impl BatteryBackend for UPowerBackend {
+    fn read(&mut self) -> Result<Vec<Reading>> {
+        upower::batteries()
+    }
+}
@@ -0,0 +1,279 @@
+//! `amptop bugreport`: one JSON file with what a maintainer needs to look
+//! into a platform-specific problem. Hostname, user name, home directory,
+//! serial numbers and hardware addresses are redacted automatically, and the
+//! user can drop sections or redact more before anything is written.
+
+use crate::backend::Backend;
+use crate::daemon::BatteryDaemon;
+use crate::hibernate;
+use crate::migrations;
+use crate::sysfs;
+use crate::upower;
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, BufRead, Write};
+
+/// History included, in seconds.
+const RECENT_LOGS_SECS: i64 = 24 * 60 * 60;
+
+/// Shorter values are left alone, since replacing them would garble
+/// unrelated text.
+const MIN_REDACTED_LEN: usize = 3;
+
+/// One top-level key of the report.
+#[derive(Debug)]
+pub struct Section {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub value: Value,
+}
+
+/// Gathers every section. `config` is the effective configuration.
+pub fn collect(config: Value, backend: Backend) -> Vec<Section> {
+    let mut sections = vec![
+        Section {
+            name: "versions",
+            description: "amptop, OS, kernel and database versions",
+            value: versions(backend),
+        },
+        Section {
+            name: "doctor",
+            description: "output of `amptop doctor`",
+            value: doctor(),
+        },
+        Section {
+            name: "config",
+            description: "effective settings",
+            value: config,
+        },
+        Section {
+            name: "power_supply",
+            description: "raw attributes of every /sys/class/power_supply device",
+            value: power_supply(),
+        },
+    ];
+    if let Ok(dump) = upower::dump() {
+        sections.push(Section {
+            name: "upower",
+            description: "raw `upower --dump` output",
+            value: Value::String(dump),
+        });
+    }
+    sections.push(Section {
+        name: "recent_logs",
+        description: "snapshots recorded in the last 24 hours",
+        value: recent_logs(),
+    });
+    sections
+}
+
+fn versions(backend: Backend) -> Value {
+    let os = fs::read_to_string("/etc/os-release")
+        .ok()
+        .and_then(|release| {
+            release.lines().find_map(|line| {
+                Some(
+                    line.strip_prefix("PRETTY_NAME=")?
+                        .trim_matches('"')
+                        .to_string(),
+                )
+            })
+        });
+    let schema = BatteryDaemon::database_exists()
+        .then(|| BatteryDaemon::open_database().ok())
+        .flatten()
+        .and_then(|conn| migrations::current_version(&conn).ok());
+    json!({
+        "amptop": env!("CARGO_PKG_VERSION"),
+        "os": os,
+        "kernel": read_trimmed("/proc/sys/kernel/osrelease"),
+        "arch": std::env::consts::ARCH,
+        "backend": backend.name(),
+        "sysfs_root": sysfs::root(),
+        "schema_version": schema,
+        "daemon_running": BatteryDaemon::is_running(),
+    })
+}
+
+fn doctor() -> Value {
+    hibernate::readiness()
+        .into_iter()
+        .map(|check| {
+            json!({
+                "name": check.name,
+                "detail": check.detail,
+                "problem": check.problem,
+            })
+        })
+        .collect()
+}
+
+/// Every readable attribute of each device, keyed by device and file
+/// name. This covers the `uevent` properties and the attributes it leaves
+/// out, such as charge thresholds.
+fn power_supply() -> Value {
+    let mut devices = BTreeMap::new();
+    for device in fs::read_dir(sysfs::power_supply_dir())
+        .into_iter()
+        .flatten()
+        .flatten()
+    {
+        let mut attributes = BTreeMap::new();
+        for file in fs::read_dir(device.path()).into_iter().flatten().flatten() {
+            // Skips subdirectories and links such as `device` and `subsystem`.
+            if !file.file_type().is_ok_and(|kind| kind.is_file()) {
+                continue;
+            }
+            if let Ok(value) = fs::read_to_string(file.path()) {
+                attributes.insert(
+                    file.file_name().to_string_lossy().into_owned(),
+                    value.trim_end().to_string(),
+                );
+            }
+        }
+        devices.insert(
+            device.file_name().to_string_lossy().into_owned(),
+            attributes,
+        );
+    }
+    json!(devices)
+}
+
+fn recent_logs() -> Value {
+    let since = chrono::Utc::now().timestamp() - RECENT_LOGS_SECS;
+    match BatteryDaemon::get_logs_since(since) {
+        Ok(logs) => json!(logs),
+        Err(e) => json!({ "error": e.to_string() }),
+    }
+}
+
+fn read_trimmed(path: &str) -> Option<String> {
+    fs::read_to_string(path).ok().map(|s| s.trim().to_string())
+}
+
+/// Values redacted from every report: the hostname, user name, home
+/// directory and the serial numbers of all power supplies, peripherals
+/// included.
+pub fn identifying_values() -> Vec<(String, &'static str)> {
+    let mut values = Vec::new();
+    if let Ok(home) = std::env::var("HOME") {
+        values.push((home, "~"));
+    }
+    if let Some(hostname) = read_trimmed("/proc/sys/kernel/hostname") {
+        values.push((hostname, "<hostname>"));
+    }
+    if let Ok(user) = std::env::var("USER") {
+        values.push((user, "<user>"));
+    }
+    for device in fs::read_dir(sysfs::power_supply_dir())
+        .into_iter()
+        .flatten()
+        .flatten()
+    {
+        if let Some(serial) = sysfs::read_string(&device.path().join("serial_number")) {
+            values.push((serial, "<serial>"));
+        }
+    }
+    values
+}
+
+/// Asks on the terminal whether to keep each section, then for anything
+/// else to redact. Returns the sections kept and the extra values.
+pub fn review(sections: Vec<Section>) -> io::Result<(Vec<Section>, Vec<String>)> {
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    let mut ask = |prompt: &str| -> io::Result<String> {
+        print!("{}", prompt);
+        io::stdout().flush()?;
+        Ok(lines.next().transpose()?.unwrap_or_default())
+    };
+
+    println!(
+        "The report redacts your hostname, user name, home directory, serial numbers and hardware addresses."
+    );
+    let mut kept = Vec::new();
+    for section in sections {
+        let size = section.value.to_string().len();
+        let answer = ask(&format!(
+            "Include {} ({}, {:.1} kB)? [Y/n] ",
+            section.name,
+            section.description,
+            size as f64 / 1e3
+        ))?;
+        if !answer.trim().eq_ignore_ascii_case("n") {
+            kept.push(section);
+        }
+    }
+
+    let extra = ask(
+        "Anything else to redact, e.g. a charger or network name (comma-separated, Enter for none)? ",
+    )?;
+    let extra = extra
+        .split(',')
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+        .collect();
+    Ok((kept, extra))
+}
+
+/// The report as pretty-printed JSON, with `redactions` replaced.
+pub fn render(sections: &[Section], redactions: &[(String, &str)]) -> String {
+    let report: serde_json::Map<String, Value> = sections
+        .iter()
+        .map(|section| (section.name.to_string(), section.value.clone()))
+        .collect();
+    let mut text = serde_json::to_string_pretty(&report).expect("report is serializable");
+    // Longest first, so a home directory goes before the user name in it.
+    let mut redactions: Vec<_> = redactions.iter().collect();
+    redactions.sort_by_key(|(value, _)| std::cmp::Reverse(value.len()));
+    for (value, replacement) in redactions {
+        if value.len() < MIN_REDACTED_LEN {
+            continue;
+        }
+        // Match the value as it appears inside JSON strings.
+        let escaped = serde_json::to_string(value).expect("strings are serializable");
+        text = text.replace(&escaped[1..escaped.len() - 1], replacement);
+    }
+    redact_hardware_addresses(&text)
+}
+
+/// Replaces anything shaped like a MAC address, such as the Bluetooth
+/// address in a peripheral's device name (`hid-aa:bb:cc:dd:ee:ff-battery`)
+/// or UPower object path (`mouse_dev_AA_BB_CC_DD_EE_FF`).
+fn redact_hardware_addresses(text: &str) -> String {
+    const LEN: usize = 17;
+    let bytes = text.as_bytes();
+    let is_address = |start: usize| {
+        let candidate = &bytes[start..start + LEN];
+        let separator = candidate[2];
+        matches!(separator, b':' | b'_' | b'-')
+            && candidate.iter().enumerate().all(|(i, &b)| {
+                if i % 3 == 2 {
+                    b == separator
+                } else {
+                    b.is_ascii_hexdigit()
+                }
+            })
+            && (start == 0 || !bytes[start - 1].is_ascii_alphanumeric())
+            && bytes
+                .get(start + LEN)
+                .is_none_or(|b| !b.is_ascii_alphanumeric())
+    };
+
+    let mut redacted = String::with_capacity(text.len());
+    let mut copied = 0;
+    let mut i = 0;
+    while i + LEN <= bytes.len() {
+        if is_address(i) {
+            redacted.push_str(&text[copied..i]);
+            redacted.push_str("<address>");
+            i += LEN;
+            copied = i;
+        } else {
+            i += 1;
+        }
+    }
+    redacted.push_str(&text[copied..]);
+    redacted
+}
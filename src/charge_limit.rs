@@ -0,0 +1,191 @@
+//! Firmware charge thresholds: the charge at which the battery stops
+//! charging, and on some laptops the one below which it starts again.
+//!
+//! Most drivers expose `charge_control_start_threshold` and
+//! `charge_control_end_threshold` on the battery, but each vendor accepts a
+//! different subset of values, so `set` checks them before writing.
+
+use crate::errors::{Error, Result};
+use crate::sysfs;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Attribute names, current ones first. Older ThinkPad drivers use the
+/// `charge_*_threshold` spellings.
+const END_FILES: [&str; 2] = ["charge_control_end_threshold", "charge_stop_threshold"];
+const START_FILES: [&str; 2] = ["charge_control_start_threshold", "charge_start_threshold"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Vendor {
+    /// `thinkpad_acpi`: start and end, start below end
+    ThinkPad,
+    /// `asus-wmi`: end only
+    Asus,
+    /// `dell-laptop`: start 50-95%, end 55-100%, at least 5 points apart,
+    /// and only used in the "Custom" charge mode
+    Dell,
+    Other,
+}
+
+impl Vendor {
+    pub fn detect() -> Vendor {
+        let dmi = sysfs::root().join("class/dmi/id");
+        let vendor = sysfs::read_string(&dmi.join("sys_vendor")).unwrap_or_default();
+        let product = sysfs::read_string(&dmi.join("product_version")).unwrap_or_default();
+        match vendor.to_ascii_lowercase() {
+            v if v.starts_with("lenovo") && product.contains("ThinkPad") => Vendor::ThinkPad,
+            v if v.starts_with("asus") => Vendor::Asus,
+            v if v.starts_with("dell") => Vendor::Dell,
+            _ => Vendor::Other,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Vendor::ThinkPad => "ThinkPad",
+            Vendor::Asus => "ASUS",
+            Vendor::Dell => "Dell",
+            Vendor::Other => "generic",
+        }
+    }
+
+    fn check(self, start: Option<u8>, end: u8) -> Result<()> {
+        let invalid = |message: String| Err(Error::ChargeLimit(message));
+        if let Some(start) = start
+            && start >= end
+        {
+            return invalid(format!(
+                "start threshold {}% must be below the end threshold {}%",
+                start, end
+            ));
+        }
+        match (self, start) {
+            (Vendor::Asus, Some(_)) => {
+                invalid("ASUS laptops only support an end threshold".to_string())
+            }
+            (Vendor::Dell, _) if end < 55 => {
+                invalid("Dell laptops need an end threshold of at least 55%".to_string())
+            }
+            (Vendor::Dell, Some(start)) if !(50..=95).contains(&start) => {
+                invalid("Dell laptops need a start threshold between 50% and 95%".to_string())
+            }
+            (Vendor::Dell, Some(start)) if end - start < 5 => {
+                invalid("Dell laptops need the thresholds at least 5 points apart".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// One battery's configured thresholds, in percent.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Thresholds {
+    /// Battery directory name, e.g. `BAT0`
+    pub battery: String,
+    pub start: Option<f64>,
+    pub end: Option<f64>,
+}
+
+impl Thresholds {
+    /// Percentage at which charging stops, when below 100%.
+    pub fn limit(&self) -> Option<f64> {
+        self.end.filter(|end| *end > 0.0 && *end < 100.0)
+    }
+
+    /// e.g. "75-80%", "80%" or "off".
+    pub fn describe(&self) -> String {
+        match (self.start.filter(|start| *start > 0.0), self.limit()) {
+            (Some(start), Some(end)) => format!("{:.0}-{:.0}%", start, end),
+            (None, Some(end)) => format!("{:.0}%", end),
+            _ => "off".to_string(),
+        }
+    }
+}
+
+/// Thresholds of every battery whose driver supports an end threshold.
+pub fn read() -> Vec<Thresholds> {
+    sysfs::battery_dirs()
+        .iter()
+        .filter_map(|dir| {
+            let end = find(dir, &END_FILES)?;
+            Some(Thresholds {
+                battery: dir.file_name()?.to_string_lossy().into_owned(),
+                start: find(dir, &START_FILES).and_then(|path| sysfs::read_number(&path)),
+                end: sysfs::read_number(&end),
+            })
+        })
+        .collect()
+}
+
+/// Sets the thresholds of every supporting battery, returning the values
+/// read back. Needs root.
+pub fn set(start: Option<u8>, end: u8) -> Result<Vec<Thresholds>> {
+    let vendor = Vendor::detect();
+    vendor.check(start, end)?;
+
+    let dirs: Vec<PathBuf> = sysfs::battery_dirs()
+        .into_iter()
+        .filter(|dir| find(dir, &END_FILES).is_some())
+        .collect();
+    if dirs.is_empty() {
+        return Err(Error::ChargeLimit(
+            "no battery exposes charge_control_end_threshold; this laptop's driver doesn't support charge limits".to_string(),
+        ));
+    }
+
+    for dir in &dirs {
+        let Some(end_file) = find(dir, &END_FILES) else {
+            continue;
+        };
+        let start_file = match start {
+            Some(start) => match find(dir, &START_FILES) {
+                Some(path) => Some((path, start)),
+                None => {
+                    return Err(Error::ChargeLimit(format!(
+                        "{} only supports an end threshold",
+                        dir.display()
+                    )));
+                }
+            },
+            None => None,
+        };
+
+        if vendor == Vendor::Dell && dir.join("charge_types").exists() {
+            write(&dir.join("charge_types"), "Custom")?;
+        }
+        // Drivers reject a start at or above the current end (and the
+        // reverse), so write whichever keeps them ordered in between.
+        let current_end = sysfs::read_number(&end_file).unwrap_or(100.0);
+        match start_file {
+            Some((path, start)) if f64::from(start) >= current_end => {
+                write(&end_file, &end.to_string())?;
+                write(&path, &start.to_string())?;
+            }
+            Some((path, start)) => {
+                write(&path, &start.to_string())?;
+                write(&end_file, &end.to_string())?;
+            }
+            None => write(&end_file, &end.to_string())?,
+        }
+    }
+    Ok(read())
+}
+
+fn find(dir: &Path, names: &[&str]) -> Option<PathBuf> {
+    names
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|path| path.exists())
+}
+
+fn write(path: &Path, value: &str) -> Result<()> {
+    fs::write(path, value).map_err(|e| {
+        Error::ChargeLimit(match e.kind() {
+            io::ErrorKind::PermissionDenied => {
+                format!("permission denied writing {}; run as root", path.display())
+            }
+            _ => format!("writing {} to {}: {}", value, path.display(), e),
+        })
+    })
+}
@@ -0,0 +1,81 @@
+//! Care advice and notification defaults that depend on what the battery
+//! is made of. A lithium iron phosphate pack is happy sitting at 100%,
+//! whereas the same habit slowly wears out a Li-ion one.
+
+use crate::alerts::ChargeNotifications;
+use battery::Technology;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Chemistry {
+    LithiumIon,
+    LithiumPolymer,
+    LithiumIronPhosphate,
+    NickelMetalHydride,
+    NickelCadmium,
+}
+
+impl Chemistry {
+    /// `None` for unknown and unusual chemistries, which keep the generic
+    /// defaults and get no advice.
+    pub fn from_technology(technology: Technology) -> Option<Chemistry> {
+        match technology {
+            Technology::LithiumIon => Some(Chemistry::LithiumIon),
+            Technology::LithiumPolymer => Some(Chemistry::LithiumPolymer),
+            Technology::LithiumIronPhosphate => Some(Chemistry::LithiumIronPhosphate),
+            Technology::NickelMetalHydride => Some(Chemistry::NickelMetalHydride),
+            Technology::NickelCadmium => Some(Chemistry::NickelCadmium),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Chemistry::LithiumIon => "Li-ion",
+            Chemistry::LithiumPolymer => "Li-polymer",
+            Chemistry::LithiumIronPhosphate => "LiFePO4",
+            Chemistry::NickelMetalHydride => "NiMH",
+            Chemistry::NickelCadmium => "NiCd",
+        }
+    }
+
+    /// One line of care advice for the Health tab.
+    pub fn tip(self) -> &'static str {
+        match self {
+            Chemistry::LithiumIon => {
+                "lasts longest kept between 20% and 80%; avoid leaving it at 100% or running it flat, especially when warm"
+            }
+            Chemistry::LithiumPolymer => {
+                "like Li-ion, prefers 20-80% and dislikes heat; have it replaced if the case starts to bulge"
+            }
+            Chemistry::LithiumIronPhosphate => {
+                "tolerates being stored at 100% and deep discharges well, so there is no need to unplug when full"
+            }
+            Chemistry::NickelMetalHydride => {
+                "benefits from a full discharge and charge now and then, and self-discharges quickly when stored"
+            }
+            Chemistry::NickelCadmium => {
+                "run it down fully before recharging now and then to avoid the memory effect"
+            }
+        }
+    }
+
+    /// Daemon notification thresholds for settings the config file leaves
+    /// unset. Only lithium-ion style packs are worth unplugging when full,
+    /// and nickel-based ones are meant to be run down further.
+    pub fn notifications(self) -> ChargeNotifications {
+        let defaults = ChargeNotifications::default();
+        match self {
+            Chemistry::LithiumIon | Chemistry::LithiumPolymer => defaults,
+            Chemistry::LithiumIronPhosphate => ChargeNotifications {
+                full: false,
+                ..defaults
+            },
+            Chemistry::NickelMetalHydride | Chemistry::NickelCadmium => ChargeNotifications {
+                warn: Some(10.0),
+                critical: Some(5.0),
+                full: false,
+                ..defaults
+            },
+        }
+    }
+}
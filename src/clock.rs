@@ -0,0 +1,73 @@
+//! Sample timestamps that stay ordered across wall-clock jumps.
+//!
+//! Timestamps are stored as Unix time, so time zone changes never affect
+//! them, but NTP corrections and manual clock edits can move the wall clock
+//! backwards. Pairing each sample with `CLOCK_BOOTTIME` (which keeps counting
+//! during suspend and never jumps) lets the daemon detect such jumps and keep
+//! intervals between samples positive.
+//!
+//! The corrected timeline is only trusted while it stays within `MAX_AHEAD`
+//! of the wall clock. A row stamped in the future, or the clock set back
+//! further than that, would otherwise push every later sample ahead too.
+
+use chrono::Utc;
+
+/// How far a timestamp may run ahead of the wall clock, in seconds.
+const MAX_AHEAD: i64 = 10 * 60;
+
+#[derive(Debug, Default)]
+pub struct MonotonicClock {
+    /// Timestamp handed out last, and the boot time it was taken at
+    last: Option<(i64, i64)>,
+}
+
+impl MonotonicClock {
+    /// Continues from the latest timestamp already stored, so a restart after
+    /// the clock was set back doesn't produce out-of-order rows either. A
+    /// stored timestamp more than `MAX_AHEAD` in the future is ignored.
+    pub fn resume_after(latest: Option<i64>) -> Self {
+        let now = Utc::now().timestamp();
+        Self {
+            last: latest
+                .filter(|&timestamp| timestamp <= now + MAX_AHEAD)
+                .map(|timestamp| (timestamp, boot_time() - 1)),
+        }
+    }
+
+    /// Corrects a wall-clock reading taken just now.
+    pub fn stamp(&mut self, wall: i64) -> i64 {
+        let boot = boot_time();
+
+        let timestamp = match self.last {
+            // The wall clock went backwards (or stalled): advance by the time
+            // that actually elapsed instead, unless that runs too far ahead.
+            Some((last, last_boot)) if wall <= last => {
+                let advanced = last + (boot - last_boot).max(1);
+                if advanced <= wall + MAX_AHEAD {
+                    advanced
+                } else {
+                    wall
+                }
+            }
+            _ => wall,
+        };
+
+        self.last = Some((timestamp, boot));
+        timestamp
+    }
+}
+
+/// Seconds since boot, including time spent suspended.
+fn boot_time() -> i64 {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    #[cfg(target_os = "linux")]
+    let clock = libc::CLOCK_BOOTTIME;
+    #[cfg(not(target_os = "linux"))]
+    let clock = libc::CLOCK_MONOTONIC;
+
+    unsafe { libc::clock_gettime(clock, &mut ts) };
+    ts.tv_sec
+}
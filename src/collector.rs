@@ -0,0 +1,53 @@
+//! Records history from the TUI while no daemon is running, so history
+//! accumulates for users who never start one, for as long as amptop is
+//! open.
+//!
+//! Sampling runs on a background thread with its own connection and stops
+//! once a daemon starts, leaving the recording to it.
+
+use crate::daemon::BatteryDaemon;
+use crate::errors::Result;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+
+#[derive(Debug)]
+pub struct Collector {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<Result<()>>>,
+}
+
+impl Collector {
+    /// Starts sampling every `interval_secs`. Dropping the collector stops
+    /// it and waits for a write in progress.
+    pub fn start(interval_secs: u64) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = thread::spawn({
+            let stop = Arc::clone(&stop);
+            move || BatteryDaemon::new(interval_secs).collect_until(&stop)
+        });
+        Self {
+            stop,
+            thread: Some(thread),
+        }
+    }
+
+    /// Whether sampling stopped on its own, because a daemon started or
+    /// the database failed. Returns the failure, if any, once.
+    pub fn finished(&mut self) -> Option<Result<()>> {
+        if !self.thread.as_ref()?.is_finished() {
+            return None;
+        }
+        let thread = self.thread.take()?;
+        Some(thread.join().unwrap_or(Ok(())))
+    }
+}
+
+impl Drop for Collector {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
@@ -0,0 +1,135 @@
+//! A/B comparison of battery drain between two periods, for judging whether
+//! a kernel, firmware or TLP change made a difference.
+
+use crate::daemon::{BatterySnapshot, BoundaryEvent};
+use crate::downtime::{self, DowntimeKind};
+use crate::stats;
+
+/// z-score for a two-sided 95% confidence interval.
+const Z_95: f64 = 1.96;
+
+/// A mean with its 95% confidence interval, from `samples` measurements.
+#[derive(Debug, Clone, Copy)]
+pub struct Estimate {
+    pub mean: f64,
+    /// Half the width of the confidence interval
+    pub margin: f64,
+    pub samples: usize,
+}
+
+impl Estimate {
+    /// Needs at least two values to say anything about the spread.
+    fn of(values: &[f64]) -> Option<Estimate> {
+        let n = values.len();
+        if n < 2 {
+            return None;
+        }
+        let mean = values.iter().sum::<f64>() / n as f64;
+        let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1) as f64;
+        Some(Estimate {
+            mean,
+            margin: Z_95 * (variance / n as f64).sqrt(),
+            samples: n,
+        })
+    }
+
+    fn standard_error(&self) -> f64 {
+        self.margin / Z_95
+    }
+}
+
+/// Change from period A to period B.
+#[derive(Debug, Clone, Copy)]
+pub struct Difference {
+    pub change: f64,
+    pub margin: f64,
+}
+
+impl Difference {
+    fn between(a: Estimate, b: Estimate) -> Difference {
+        Difference {
+            change: b.mean - a.mean,
+            margin: Z_95 * (a.standard_error().powi(2) + b.standard_error().powi(2)).sqrt(),
+        }
+    }
+
+    /// Whether the confidence interval of the change excludes zero.
+    pub fn significant(&self) -> bool {
+        self.change.abs() > self.margin
+    }
+}
+
+/// Drain measured over one period.
+#[derive(Debug, Clone, Copy)]
+pub struct PeriodDrain {
+    /// W, per interval between two discharging samples
+    pub active: Option<Estimate>,
+    pub idle: Option<Estimate>,
+    /// %/h, per suspend
+    pub suspend: Option<Estimate>,
+}
+
+impl PeriodDrain {
+    /// `logs` is the period's history, oldest first.
+    pub fn measure(logs: &[BatterySnapshot], events: &[BoundaryEvent]) -> PeriodDrain {
+        let mut active = Vec::new();
+        let mut idle = Vec::new();
+        for interval in stats::discharge_intervals(logs) {
+            match interval.start.idle {
+                Some(true) => idle.push(interval.watts()),
+                Some(false) => active.push(interval.watts()),
+                None => {}
+            }
+        }
+
+        let suspend: Vec<f64> = downtime::detect(logs, events)
+            .iter()
+            .filter(|d| d.kind == DowntimeKind::Suspended && d.lost() >= 0.0)
+            .map(|d| d.drain_rate())
+            .collect();
+
+        PeriodDrain {
+            active: Estimate::of(&active),
+            idle: Estimate::of(&idle),
+            suspend: Estimate::of(&suspend),
+        }
+    }
+}
+
+/// One row of the comparison: a metric in both periods and the change.
+#[derive(Debug, Clone, Copy)]
+pub struct Row {
+    pub name: &'static str,
+    pub unit: &'static str,
+    pub a: Option<Estimate>,
+    pub b: Option<Estimate>,
+}
+
+impl Row {
+    pub fn difference(&self) -> Option<Difference> {
+        Some(Difference::between(self.a?, self.b?))
+    }
+}
+
+pub fn compare(a: &PeriodDrain, b: &PeriodDrain) -> [Row; 3] {
+    [
+        Row {
+            name: "Active drain",
+            unit: "W",
+            a: a.active,
+            b: b.active,
+        },
+        Row {
+            name: "Idle drain",
+            unit: "W",
+            a: a.idle,
+            b: b.idle,
+        },
+        Row {
+            name: "Suspend drain",
+            unit: "%/h",
+            a: a.suspend,
+            b: b.suspend,
+        },
+    ]
+}
@@ -1,14 +1,252 @@
-use crate::daemon::BatteryDaemon;
+use crate::alerts::{AlertAction, ChargeNotifications, Crossing};
+use crate::backend::Backend;
+use crate::bugreport;
+use crate::charge_limit::{self, Vendor};
+use crate::chemistry::Chemistry;
+use crate::compare::{self, Estimate, PeriodDrain};
+use crate::daemon::{self, BatteryDaemon, DaemonSettings, DaemonStatus, Downsample};
+use crate::db;
+use crate::downtime::{self, DowntimeKind};
+use crate::durations::{self, DurationStyle};
+use crate::errors::Error;
 use crate::errors::Result;
-use clap::{Parser, Subcommand};
+use crate::export::{self, ExportFormat};
+use crate::forecast::{self, Load, UsageModel};
+use crate::graph::{self, ImageFormat};
+use crate::hibernate;
+use crate::info::BatteryInfo;
+use crate::keymap::Keymap;
+use crate::migrations;
+use crate::mqtt::MqttConfig;
+use crate::overhead::{self, ProcessUsage};
+use crate::power_saver::PowerSaverConfig;
+use crate::report::{self, ReportFormat};
+use crate::service;
+use crate::sessions::{self, Session, SessionKind};
+use crate::stats;
+use crate::status_bar::{self, BarFormat, BarStyle};
+use crate::tags::{Tag, TagUsage};
+use crate::theme::{Theme, ThemeName};
+use crate::xdg;
+use chrono::NaiveTime;
+use clap::parser::ValueSource;
+use clap::{ArgMatches, Args, CommandFactory, FromArgMatches, Parser, Subcommand};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::fs;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
 pub enum Unit {
     Human,
     Si,
+    /// Charge as battery spec sheets quote it, with energies in Wh elsewhere
+    MilliampHours,
 }
 
+impl Unit {
+    fn name(self) -> &'static str {
+        match self {
+            Unit::Human => "human",
+            Unit::Si => "si",
+            Unit::MilliampHours => "mah",
+        }
+    }
+
+    /// Cycles human units, SI units and mAh.
+    pub fn next(self) -> Self {
+        match self {
+            Unit::Human => Unit::Si,
+            Unit::Si => Unit::MilliampHours,
+            Unit::MilliampHours => Unit::Human,
+        }
+    }
+
+    /// The temperature scale shown with these units.
+    pub fn temperature(self) -> TemperatureUnit {
+        match self {
+            Unit::Human | Unit::MilliampHours => TemperatureUnit::Celsius,
+            Unit::Si => TemperatureUnit::Kelvin,
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum TemperatureUnit {
+    Celsius,
+    Fahrenheit,
+    Kelvin,
+}
+
+impl TemperatureUnit {
+    fn name(self) -> &'static str {
+        match self {
+            TemperatureUnit::Celsius => "C",
+            TemperatureUnit::Fahrenheit => "F",
+            TemperatureUnit::Kelvin => "K",
+        }
+    }
+
+    /// The unit as shown after a value, e.g. `°C`.
+    pub fn symbol(self) -> &'static str {
+        match self {
+            TemperatureUnit::Celsius => "°C",
+            TemperatureUnit::Fahrenheit => "°F",
+            TemperatureUnit::Kelvin => "K",
+        }
+    }
+
+    /// `celsius` in this unit.
+    pub fn convert(self, celsius: f64) -> f64 {
+        match self {
+            TemperatureUnit::Celsius => celsius,
+            TemperatureUnit::Fahrenheit => celsius * 9.0 / 5.0 + 32.0,
+            TemperatureUnit::Kelvin => celsius + 273.15,
+        }
+    }
+}
+
+/// What the history graph's Y axis shows. Percent hides capacity fade:
+/// 80% today is fewer Wh than 80% a year ago.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum ChartAxis {
+    Percent,
+    WattHours,
+    Joules,
+}
+
+impl ChartAxis {
+    fn name(self) -> &'static str {
+        match self {
+            ChartAxis::Percent => "percent",
+            ChartAxis::WattHours => "wh",
+            ChartAxis::Joules => "joule",
+        }
+    }
+
+    /// Cycles percent, Wh and joules.
+    pub fn next(self) -> Self {
+        match self {
+            ChartAxis::Percent => ChartAxis::WattHours,
+            ChartAxis::WattHours => ChartAxis::Joules,
+            ChartAxis::Joules => ChartAxis::Percent,
+        }
+    }
+}
+
+/// Whether the TUI records history itself while no daemon is running.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum Collector {
+    /// Asks each time the TUI starts without a daemon
+    Ask,
+    Always,
+    Never,
+}
+
+impl Collector {
+    fn name(self) -> &'static str {
+        match self {
+            Collector::Ask => "ask",
+            Collector::Always => "always",
+            Collector::Never => "never",
+        }
+    }
+}
+
+/// Charge levels, in percent, below which the state-of-charge gauge
+/// turns the low and critical colors.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GaugeThresholds {
+    pub low: f32,
+    pub critical: f32,
+}
+
+impl Default for GaugeThresholds {
+    fn default() -> Self {
+        Self {
+            low: 30.0,
+            critical: 15.0,
+        }
+    }
+}
+
+/// Where the effective value of a setting came from.
+#[derive(Debug, Eq, PartialEq, Copy, Clone, Default)]
+pub enum SettingSource {
+    #[default]
+    Default,
+    File,
+    CommandLine,
+}
+
+impl SettingSource {
+    pub fn label(self) -> &'static str {
+        match self {
+            SettingSource::Default => "default",
+            SettingSource::File => "config file",
+            SettingSource::CommandLine => "command line",
+        }
+    }
+}
+
+/// Sources of the settings that can be kept in the config file.
+#[derive(Debug, Default, Clone, Copy)]
+struct Sources {
+    delay: SettingSource,
+    unit: SettingSource,
+    temp_unit: SettingSource,
+    theme: SettingSource,
+    colors: SettingSource,
+    keys: SettingSource,
+    chart_axis: SettingSource,
+    collector: SettingSource,
+    durations: SettingSource,
+    database: SettingSource,
+    daemon_interval: SettingSource,
+    daemon_retention: SettingSource,
+    daemon_per_minute: SettingSource,
+    daemon_per_hour: SettingSource,
+    gauge_low: SettingSource,
+    gauge_critical: SettingSource,
+    bar_icons: SettingSource,
+    bar_charging_icon: SettingSource,
+    bar_warning_color: SettingSource,
+    bar_critical_color: SettingSource,
+    mqtt: SettingSource,
+    notify_warn: SettingSource,
+    notify_critical: SettingSource,
+    notify_full: SettingSource,
+    notify_cooldown: SettingSource,
+    notify_daily_summary: SettingSource,
+    notify_max_charge_temp: SettingSource,
+    alerts: SettingSource,
+}
+
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum Format {
+    Plain,
+    Json,
+}
+
+/// Most recent downtimes listed by `stats`.
+const DOWNTIMES_SHOWN: usize = 5;
+/// Most recent weeks of suspend drain listed by `stats`.
+const SUSPEND_WEEKS_SHOWN: usize = 8;
+/// Most recent daily summaries listed by `stats`.
+const DAILY_SUMMARIES_SHOWN: usize = 7;
+
+/// Seconds between daemon readings when nothing else is configured.
+const DEFAULT_DAEMON_INTERVAL: u64 = 60;
+/// Longest interval between daemon readings, a day.
+const MAX_DAEMON_INTERVAL: u64 = 24 * 60 * 60;
+/// Battery temperature, in °C, considered too hot while charging.
+const DEFAULT_MAX_CHARGE_TEMP: f32 = 45.0;
+/// Charging temperature limits that make sense for a battery, in °C.
+const MAX_CHARGE_TEMPS: RangeInclusive<f32> = 0.0..=100.0;
+
 #[derive(Parser, Debug)]
 #[command(name = "amptop")]
 #[command(about = "Interactive battery statistics", long_about = None)]
@@ -20,10 +258,11 @@ pub struct Config {
     #[arg(
         short,
         long,
+        global = true,
         default_value = "1",
         value_parser = Config::parse_duration
     )]
-    /// Delay between updates, in seconds (TUI mode only)
+    /// Delay between updates, in seconds (TUI mode and watch)
     delay: Duration,
 
     #[arg(
@@ -32,8 +271,130 @@ pub struct Config {
         default_value = "human",
         value_parser = Config::parse_unit
     )]
-    /// Measurement units displayed, possible values (human, si) (TUI mode only)
+    /// Measurement units displayed, possible values (human, si, mah) (TUI mode only)
     unit: Unit,
+
+    #[arg(long, value_parser = Config::parse_temperature_unit)]
+    /// Temperature unit, possible values (C, F, K); follows --unit when not given (TUI mode only)
+    temp_unit: Option<TemperatureUnit>,
+
+    #[arg(long, default_value = "default", value_parser = Config::parse_theme)]
+    /// Color theme, possible values (default, solarized, gruvbox, mono, high-contrast) (TUI mode only)
+    theme: ThemeName,
+
+    /// Colors from the config file replacing the theme's, by role
+    #[arg(skip)]
+    colors: BTreeMap<String, String>,
+
+    /// Keys from the config file, with the actions they aren't given for
+    /// left on their defaults
+    #[arg(skip)]
+    keymap: Keymap,
+
+    #[arg(long, default_value = "percent", value_parser = Config::parse_chart_axis)]
+    /// History graph Y axis, possible values (percent, wh, joule) (TUI mode only)
+    chart_axis: ChartAxis,
+
+    #[arg(long, default_value = "ask", value_parser = Config::parse_collector)]
+    /// Record history from the TUI while no daemon is running, possible values (ask, always, never) (TUI mode only)
+    collector: Collector,
+
+    #[arg(long, global = true, default_value = "compact", value_parser = Config::parse_duration_style)]
+    /// How durations are written, possible values (compact, long)
+    durations: DurationStyle,
+
+    #[arg(long, global = true, value_name = "FILE")]
+    /// Read defaults from this file instead of ~/.config/amptop/config.toml
+    config: Option<PathBuf>,
+
+    #[arg(long, global = true, value_name = "FILE")]
+    /// History database to use instead of $XDG_DATA_HOME/amptop/battery.db
+    db_path: Option<PathBuf>,
+
+    /// Daemon interval from the config file, used when `daemon start` isn't given one
+    #[arg(skip = DEFAULT_DAEMON_INTERVAL)]
+    daemon_interval: u64,
+
+    /// Daemon history retention from the config file, in days
+    #[arg(skip)]
+    daemon_retention: Option<u64>,
+
+    /// Daemon downsampling from the config file
+    #[arg(skip)]
+    daemon_downsample: Downsample,
+
+    /// Where the gauge changes color, from the config file
+    #[arg(skip)]
+    gauge: GaugeThresholds,
+
+    /// Icons and colors of `status-bar`, from the config file
+    #[arg(skip)]
+    status_bar: BarStyle,
+
+    /// Low and full battery notifications sent by the daemon, from the config file
+    #[arg(skip)]
+    notifications: ChargeNotifications,
+
+    /// Local time the daemon sends the day's summary at, from the config file
+    #[arg(skip)]
+    daily_summary: Option<NaiveTime>,
+
+    /// Commands the daemon runs at charge thresholds, from the config file
+    #[arg(skip)]
+    alert_actions: Vec<AlertAction>,
+
+    /// Broker the daemon publishes snapshots to, from the config file
+    #[arg(skip)]
+    mqtt: Option<MqttConfig>,
+
+    #[arg(skip)]
+    sources: Sources,
+
+    #[arg(long)]
+    /// While discharging, redraw less often and only when something changed (TUI mode only)
+    low_power: bool,
+
+    #[arg(long)]
+    /// Treat the firmware charge limit as 100% in the charge gauge (TUI mode only)
+    scale_to_limit: bool,
+
+    #[arg(long)]
+    /// Keep the terminal window title updated with the charge and time left (TUI mode only)
+    title: bool,
+
+    #[arg(
+        long,
+        global = true,
+        default_value_t = DEFAULT_MAX_CHARGE_TEMP,
+        value_name = "CELSIUS",
+        value_parser = Config::parse_celsius
+    )]
+    /// Battery temperature considered too hot while charging
+    max_charge_temp: f32,
+
+    #[arg(long, global = true)]
+    /// Print errors from subcommands as JSON objects on stderr, and watch readings as JSON Lines, for scripts
+    json: bool,
+
+    #[arg(long, global = true, value_name = "PATH")]
+    /// Read battery data from this sysfs mount instead of /sys (e.g. /run/host/sys in containers)
+    sysfs_root: Option<PathBuf>,
+
+    #[arg(long, global = true, default_value = "auto", value_parser = Config::parse_backend)]
+    /// Where battery readings come from, possible values (auto, native, sysfs, upower)
+    backend: Backend,
+
+    #[arg(long, conflicts_with_all = ["db_path", "sysfs_root", "backend"])]
+    /// Show a simulated battery with a few weeks of generated history (TUI mode only)
+    demo: bool,
+
+    #[arg(long, value_name = "NAME")]
+    /// Network UPS Tools device to show when there is no battery, e.g. myups@localhost (TUI mode only)
+    ups: Option<String>,
+
+    #[arg(long)]
+    /// Also list Bluetooth peripherals known to UPower, e.g. mice and earbuds (TUI mode only)
+    upower: bool,
 }
 
 #[derive(Subcommand, Debug)]
@@ -41,76 +402,2767 @@ pub enum Command {
     /// Manage the battery monitoring daemon
     #[command(name = "daemon")]
     Daemon {
+        #[arg(long, global = true, value_parser = Config::parse_profile_name)]
+        /// Run a separate collector with its own PID file and history, e.g. `ups`
+        name: Option<String>,
+
         #[command(subcommand)]
         action: DaemonAction,
     },
+    /// Inspect and maintain the history database
+    #[command(name = "db")]
+    Db {
+        #[command(subcommand)]
+        action: DbAction,
+    },
+    /// Work with the recorded history
+    #[command(name = "logs")]
+    Logs {
+        #[command(subcommand)]
+        action: LogsAction,
+    },
+    /// Draw the recorded history to an image
+    #[command(name = "graph")]
+    Graph {
+        #[command(subcommand)]
+        action: GraphAction,
+    },
+    /// Show, export or import the configuration
+    #[command(name = "config")]
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    /// List the charge and discharge sessions found in the recorded history
+    #[command(name = "sessions")]
+    Sessions {
+        #[command(subcommand)]
+        action: SessionsAction,
+    },
+    /// Mark named activities and report what they cost
+    #[command(name = "tag")]
+    Tag {
+        #[command(subcommand)]
+        action: TagAction,
+    },
+    /// Read or set the firmware charge thresholds (needs root to set)
+    #[command(name = "charge-limit")]
+    ChargeLimit {
+        #[command(subcommand)]
+        action: ChargeLimitAction,
+    },
+    /// Check whether the system can carry out amptop's battery actions
+    Doctor,
+    /// Collect versions, doctor output, config, raw battery data and recent history for a bug report
+    #[command(name = "bugreport")]
+    Bugreport {
+        #[arg(short, long, value_name = "FILE")]
+        /// Where to write the report (default: amptop-bugreport-<date>.json in the current directory)
+        output: Option<PathBuf>,
+
+        #[arg(short, long)]
+        /// Include every section without asking
+        yes: bool,
+    },
+    /// Write a battery health report with capacity history, sessions and charts
+    #[command(name = "report")]
+    Report {
+        #[arg(short, long, value_name = "FILE")]
+        /// Where to write the report (default: amptop-report-<date>.html in the current directory)
+        output: Option<PathBuf>,
+
+        #[arg(short, long, value_parser = Config::parse_report_format)]
+        /// Report format, possible values (html, markdown); by default Markdown for .md files and HTML otherwise
+        format: Option<ReportFormat>,
+    },
+    /// Print the current battery state and exit
+    #[command(name = "stat")]
+    Stat {
+        #[arg(short, long, default_value = "plain", value_parser = Config::parse_format)]
+        /// Output format, possible values (plain, json)
+        format: Format,
+    },
+    /// Print the battery state at every --delay until stopped, as JSON Lines with --json
+    #[command(name = "watch")]
+    Watch {
+        #[arg(short = 'n', long, value_name = "N")]
+        /// Stop after this many readings
+        count: Option<u64>,
+    },
+    /// Print one status line for Waybar, Polybar or i3blocks and exit
+    #[command(name = "status-bar")]
+    StatusBar {
+        #[arg(short, long, default_value = "waybar", value_parser = Config::parse_bar_format)]
+        /// Output format, possible values (waybar, polybar, i3blocks)
+        format: BarFormat,
+    },
+    /// Print statistics computed from the recorded history
+    #[command(name = "stats")]
+    Stats {
+        #[command(subcommand)]
+        action: Option<StatsAction>,
+    },
+    /// Compare idle, active and suspend drain between two periods
+    #[command(name = "compare")]
+    Compare {
+        #[arg(long, value_name = "START..END", value_parser = Config::parse_period)]
+        /// Period before the change, e.g. 2024-05-01..2024-05-08
+        a: (i64, i64),
+
+        #[arg(long, value_name = "START..END", value_parser = Config::parse_period)]
+        /// Period after the change; END may be left out to mean now
+        b: (i64, i64),
+    },
+    /// Check whether the current charge will last for a task
+    #[command(name = "can")]
+    Can {
+        #[arg(long, value_parser = Config::parse_task_duration)]
+        /// How long the task takes, e.g. 45m or 2h30m
+        duration: Duration,
+
+        #[arg(long, default_value = "normal", value_parser = Config::parse_load)]
+        /// Expected load compared to everyday use, possible values (light, normal, heavy)
+        load: Load,
+    },
+    /// Record a single battery snapshot and exit (for cron or systemd timers)
+    #[command(name = "log-once")]
+    LogOnce,
 }
 
 #[derive(Subcommand, Debug)]
 pub enum DaemonAction {
     /// Start the daemon to collect battery statistics in the background
-    Start {
-        #[arg(short, long, default_value = "60")]
-        /// Interval in seconds between battery readings (recommended: 60-300)
-        interval: u64,
-    },
+    #[command(visible_alias = "run")]
+    Start(StartArgs),
+    /// Stop the running daemon, then start it again with these options
+    Restart(StartArgs),
+    /// Make the running daemon re-read its interval, retention and downsampling from the config file
+    Reload,
     /// Stop the running daemon
     Stop,
     /// Check if daemon is currently running
     Status,
+    /// Install, enable and start a systemd user service running the daemon with these options
+    Install(StartArgs),
+    /// Stop, disable and remove the systemd user service
+    Uninstall,
 }
 
-impl Config {
-    pub fn delay(&self) -> &Duration {
-        &self.delay
+/// Options of `daemon start`, `daemon restart` and `daemon install`.
+#[derive(Args, Debug)]
+pub struct StartArgs {
+    #[arg(
+        short,
+        long,
+        default_value = "60",
+        value_parser = clap::value_parser!(u64).range(1..=MAX_DAEMON_INTERVAL)
+    )]
+    /// Interval in seconds between battery readings (recommended: 60-300, at most a day)
+    interval: u64,
+
+    #[arg(long, value_name = "PERCENT")]
+    /// Switch to the power-saver profile when discharging at or below this percentage
+    power_saver_threshold: Option<f32>,
+
+    #[arg(long, value_name = "PERCENT", requires = "power_saver_threshold")]
+    /// Backlight level to dim to while power saving, as a percentage of maximum
+    power_saver_backlight: Option<u8>,
+
+    #[arg(long, value_name = "DURATION", value_parser = Config::parse_task_duration)]
+    /// Hibernate when the estimated time left on battery drops below this, e.g. 15m
+    hibernate_below: Option<Duration>,
+
+    #[arg(long, value_name = "FILE")]
+    /// Store each line written to this file or named pipe as an annotation on the history graph
+    annotations: Option<PathBuf>,
+
+    #[arg(long, value_name = "DAYS", value_parser = clap::value_parser!(u64).range(1..))]
+    /// Delete history older than this many days (checked hourly)
+    retention_days: Option<u64>,
+
+    #[arg(long, value_name = "DAYS", value_parser = clap::value_parser!(u64).range(1..))]
+    /// Keep one averaged sample per minute for history older than this
+    per_minute_after: Option<u64>,
+
+    #[arg(long, value_name = "DAYS", value_parser = clap::value_parser!(u64).range(1..))]
+    /// Keep one averaged sample per hour for history older than this
+    per_hour_after: Option<u64>,
+
+    #[arg(long)]
+    /// Stay in the foreground and print each snapshot, for systemd, runit or containers
+    foreground: bool,
+
+    #[arg(long)]
+    /// Stop (or kill) any daemon that seems to be running and remove its PID file first
+    force: bool,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum DbAction {
+    /// Check the database for corruption and impossible rows
+    Check {
+        #[arg(long)]
+        /// Delete invalid rows and rebuild the database file
+        repair: bool,
+    },
+    /// Delete history older than a number of days
+    Prune {
+        #[arg(long, value_name = "DAYS")]
+        /// Keep this many days of history
+        older_than: u64,
+
+        #[arg(long)]
+        /// Only report how many rows would be deleted
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum GraphAction {
+    /// Write the charge and power history as an SVG or PNG image
+    Export {
+        #[arg(short, long, value_name = "FILE")]
+        /// Image to write, as SVG or PNG by its extension
+        output: PathBuf,
+
+        #[arg(short, long, default_value = "7d", value_parser = Config::parse_task_duration)]
+        /// How much history to draw, e.g. 6h, 7d or 30d
+        range: Duration,
+
+        #[arg(long, value_parser = Config::parse_time)]
+        /// End of the range instead of now, e.g. 2024-05-01, "2024-05-01 08:00" or 7d (ago)
+        until: Option<i64>,
+
+        #[arg(long, value_parser = Config::parse_chart_axis)]
+        /// Y axis, possible values (percent, wh, joule); defaults to the chart_axis setting
+        axis: Option<ChartAxis>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigAction {
+    /// Print the effective settings and where each one comes from
+    Show,
+    /// Print the effective settings as a config file
+    Export {
+        #[arg(short, long, value_name = "FILE")]
+        /// Write to this file instead of stdout
+        output: Option<PathBuf>,
+    },
+    /// Replace the config file with FILE, keeping a backup of the old one
+    Import {
+        /// Config file to import
+        file: PathBuf,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SessionsAction {
+    /// Print each session's start, duration, charge change and average power
+    List {
+        #[arg(long, default_value = "30")]
+        /// Only list sessions from the last DAYS days
+        days: u64,
+
+        #[arg(long, value_parser = Config::parse_session_kind)]
+        /// Only list one kind of session, possible values (charge, discharge)
+        kind: Option<SessionKind>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum StatsAction {
+    /// Print the voltage on battery and how far it sags at heavy draw
+    Voltage {
+        #[arg(long)]
+        /// Only use history from the last DAYS days
+        days: Option<u64>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum TagAction {
+    /// Start tagging time as NAME, stopping the running tag
+    Start {
+        /// What you're doing, e.g. "video call"
+        name: String,
+    },
+    /// Stop the running tag
+    Stop,
+    /// Print the battery used per tag
+    Report,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ChargeLimitAction {
+    /// Print each battery's charge thresholds
+    Get,
+    /// Stop charging at END percent
+    Set {
+        #[arg(value_parser = clap::value_parser!(u8).range(1..=100))]
+        /// Charge at which charging stops, e.g. 80
+        end: u8,
+
+        #[arg(long, value_parser = clap::value_parser!(u8).range(0..=99))]
+        /// Charge below which charging starts again, where supported
+        start: Option<u8>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum LogsAction {
+    /// Print recorded snapshots, oldest first
+    Export {
+        #[arg(short, long, default_value = "json", value_parser = Config::parse_export_format)]
+        /// Output format, possible values (json, csv)
+        format: ExportFormat,
+
+        #[arg(short, long, value_name = "FILE")]
+        /// Write to this file instead of stdout
+        output: Option<PathBuf>,
+
+        #[arg(long, value_parser = Config::parse_time)]
+        /// Only include snapshots from this time on, e.g. 2024-05-01, "2024-05-01 08:00" or 7d (ago)
+        since: Option<i64>,
+
+        #[arg(long, value_parser = Config::parse_time)]
+        /// Only include snapshots up to this time, in the same formats as --since
+        until: Option<i64>,
+    },
+}
+
+/// Defaults read from the config file. Command-line flags take precedence.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileConfig {
+    /// Seconds between TUI updates
+    #[serde(skip_serializing_if = "Option::is_none")]
+    delay: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    unit: Option<String>,
+    /// C, F or K, independent of `unit`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temp_unit: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    theme: Option<String>,
+    /// Theme colors to replace, by role
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    colors: BTreeMap<String, String>,
+    /// Keys to bind, by action
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    keys: BTreeMap<String, KeySetting>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    chart_axis: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    collector: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    durations: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    database: Option<PathBuf>,
+    daemon: DaemonFileConfig,
+    gauge: GaugeFileConfig,
+    status_bar: StatusBarFileConfig,
+    notifications: NotificationsFileConfig,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    alerts: Vec<AlertFileConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mqtt: Option<MqttFileConfig>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+struct DaemonFileConfig {
+    /// Seconds between battery readings
+    #[serde(skip_serializing_if = "Option::is_none")]
+    interval: Option<u64>,
+    /// Days of history to keep
+    #[serde(skip_serializing_if = "Option::is_none")]
+    retention_days: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    per_minute_after: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    per_hour_after: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+struct GaugeFileConfig {
+    /// Percent below which the gauge turns the low color, and the daemon
+    /// warns unless `notifications.warn` says otherwise
+    #[serde(skip_serializing_if = "Option::is_none")]
+    low: Option<f32>,
+    /// Same for the critical color and notification
+    #[serde(skip_serializing_if = "Option::is_none")]
+    critical: Option<f32>,
+}
+
+/// The keys of one action: a single key or a list of them.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+enum KeySetting {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl KeySetting {
+    fn into_specs(self) -> Vec<String> {
+        match self {
+            KeySetting::One(spec) => vec![spec],
+            KeySetting::Many(specs) => specs,
+        }
     }
+}
 
-    pub fn unit(&self) -> Unit {
-        self.unit
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+struct StatusBarFileConfig {
+    /// Icons from empty to full
+    #[serde(skip_serializing_if = "Option::is_none")]
+    icons: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    charging_icon: Option<String>,
+    /// `#rrggbb` colors below `gauge.low` and `gauge.critical` while discharging
+    #[serde(skip_serializing_if = "Option::is_none")]
+    warning_color: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    critical_color: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct MqttFileConfig {
+    /// `mqtt://host:port`
+    broker: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    password: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    topic_prefix: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    discovery_prefix: Option<String>,
+}
+
+/// A notification threshold: a percentage, or `false` to turn it off.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(untagged)]
+enum ThresholdSetting {
+    Percent(f32),
+    Enabled(bool),
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+struct NotificationsFileConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    warn: Option<ThresholdSetting>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    critical: Option<ThresholdSetting>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    full: Option<bool>,
+    /// How long to wait before repeating a notification, e.g. "30m"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cooldown: Option<String>,
+    /// Local time to send a summary of the day's use at, e.g. "21:00"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    daily_summary: Option<String>,
+    /// Battery temperature, in °C, to warn above while charging
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_charge_temp: Option<f32>,
+}
+
+/// One `[[alerts]]` entry: a command and exactly one of `below`/`above`.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct AlertFileConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    below: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    above: Option<f32>,
+    command: String,
+}
+
+impl AlertFileConfig {
+    fn parse(&self) -> std::result::Result<AlertAction, String> {
+        let (crossing, threshold) = match (self.below, self.above) {
+            (Some(below), None) => (Crossing::Below, below),
+            (None, Some(above)) => (Crossing::Above, above),
+            _ => {
+                return Err(format!(
+                    "alert `{}` needs exactly one of below or above",
+                    self.command
+                ));
+            }
+        };
+        if !(0.0..=100.0).contains(&threshold) {
+            return Err(format!("{} isn't a valid percentage", threshold));
+        }
+        if self.command.trim().is_empty() {
+            return Err("alert command is empty".to_string());
+        }
+        Ok(AlertAction {
+            crossing,
+            threshold,
+            command: self.command.clone(),
+        })
     }
+}
 
-    fn parse_duration(s: &str) -> std::result::Result<Duration, String> {
-        match s.parse::<u64>() {
-            Ok(seconds) if seconds > 0 => Ok(Duration::from_secs(seconds)),
-            _ => Err(format!("{} isn't a positive number", s)),
+impl FileConfig {
+    /// Reads the config file at `path`. A missing file is only an error
+    /// when it was asked for explicitly.
+    fn read(path: &Path, explicit: bool) -> Result<FileConfig> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound && !explicit => {
+                return Ok(FileConfig::default());
+            }
+            Err(e) => return Err(Error::Config(format!("{}: {}", path.display(), e))),
+        };
+        toml::from_str(&text).map_err(|e| Error::Config(format!("{}: {}", path.display(), e)))
+    }
+}
+
+impl ConfigAction {
+    /// What a failure of this action is reported as.
+    fn failure(&self) -> &'static str {
+        match self {
+            ConfigAction::Show => "Failed to show the configuration",
+            ConfigAction::Export { .. } => "Failed to export the configuration",
+            ConfigAction::Import { .. } => "Failed to import the configuration",
         }
     }
+}
 
-    fn parse_unit(s: &str) -> std::result::Result<Unit, String> {
-        match s {
-            _ if s.eq_ignore_ascii_case("human") => Ok(Unit::Human),
-            _ if s.eq_ignore_ascii_case("si") => Ok(Unit::Si),
-            _ => Err(format!("{} isn't a valid unit", s)),
+impl Config {
+    /// Parses the command line and fills in anything not given there from
+    /// the config file.
+    pub fn load() -> Result<Config> {
+        Self::from_matches(&Config::command().get_matches())
+    }
+
+    /// `load`, exiting with the error's status if that fails, after printing
+    /// the error, as JSON with `--json`.
+    pub fn load_or_exit() -> Config {
+        let matches = Config::command().get_matches();
+        match Self::from_matches(&matches) {
+            Ok(config) => config,
+            Err(e) => {
+                if matches.get_flag("json") {
+                    eprintln!("{}", json_error("Failed to load the configuration", &e));
+                } else {
+                    eprintln!("{}", e);
+                }
+                std::process::exit(e.exit_code());
+            }
         }
     }
 
-    // FIX: This section might need some more careful attention.
-    pub fn handle_command(&self) -> Result<bool> {
-        if let Some(ref command) = self.command {
-            match command {
-                Command::Daemon { action } => match action {
-                    DaemonAction::Start { interval } => {
-                        let daemon = BatteryDaemon::new(*interval);
-                        match daemon.start_daemon() {
-                            Ok(_) => println!("Daemon started successfully"),
-                            Err(e) => eprintln!("Failed to start daemon: {}", e),
-                        }
-                    }
-                    DaemonAction::Stop => match BatteryDaemon::stop_daemon() {
-                        Ok(_) => println!("Daemon stopped successfully"),
-                        Err(e) => eprintln!("Failed to stop daemon: {}", e),
-                    },
-                    DaemonAction::Status => {
-                        if BatteryDaemon::is_running() {
-                            println!("Daemon is running");
-                        } else {
-                            println!("Daemon is not running");
-                        }
-                    }
-                },
+    fn from_matches(matches: &ArgMatches) -> Result<Config> {
+        let mut config = Config::from_arg_matches(matches).unwrap_or_else(|e| e.exit());
+
+        let path = config.config_path();
+        let file = FileConfig::read(&path, config.config.is_some())?;
+        let invalid = |msg: String| Error::Config(format!("{}: {}", path.display(), msg));
+        let source = |id: &str, in_file: bool| match matches.value_source(id) {
+            Some(ValueSource::CommandLine) => SettingSource::CommandLine,
+            _ if in_file => SettingSource::File,
+            _ => SettingSource::Default,
+        };
+
+        config.sources.delay = source("delay", file.delay.is_some());
+        if let Some(delay) = file
+            .delay
+            .filter(|_| config.sources.delay == SettingSource::File)
+        {
+            config.delay = Self::parse_duration(&delay.to_string()).map_err(invalid)?;
+        }
+        config.sources.unit = source("unit", file.unit.is_some());
+        if let Some(ref unit) = file
+            .unit
+            .filter(|_| config.sources.unit == SettingSource::File)
+        {
+            config.unit = Self::parse_unit(unit).map_err(invalid)?;
+        }
+        config.sources.temp_unit = source("temp_unit", file.temp_unit.is_some());
+        if let Some(ref unit) = file
+            .temp_unit
+            .filter(|_| config.sources.temp_unit == SettingSource::File)
+        {
+            config.temp_unit = Some(Self::parse_temperature_unit(unit).map_err(invalid)?);
+        }
+        config.sources.theme = source("theme", file.theme.is_some());
+        if let Some(ref theme) = file
+            .theme
+            .filter(|_| config.sources.theme == SettingSource::File)
+        {
+            config.theme = Self::parse_theme(theme).map_err(invalid)?;
+        }
+        let mut theme = config.theme();
+        for (role, color) in &file.colors {
+            theme
+                .set(role, color)
+                .map_err(|e| invalid(format!("colors: {}", e)))?;
+        }
+        if !file.colors.is_empty() {
+            config.colors = file.colors.clone();
+            config.sources.colors = SettingSource::File;
+        }
+        if !file.keys.is_empty() {
+            let keys = file
+                .keys
+                .iter()
+                .map(|(action, keys)| (action.clone(), keys.clone().into_specs()))
+                .collect();
+            config.keymap =
+                Keymap::with_overrides(&keys).map_err(|e| invalid(format!("keys: {}", e)))?;
+            config.sources.keys = SettingSource::File;
+        }
+        config.sources.chart_axis = source("chart_axis", file.chart_axis.is_some());
+        if let Some(ref axis) = file
+            .chart_axis
+            .filter(|_| config.sources.chart_axis == SettingSource::File)
+        {
+            config.chart_axis = Self::parse_chart_axis(axis).map_err(invalid)?;
+        }
+        config.sources.collector = source("collector", file.collector.is_some());
+        if let Some(ref collector) = file
+            .collector
+            .filter(|_| config.sources.collector == SettingSource::File)
+        {
+            config.collector = Self::parse_collector(collector).map_err(invalid)?;
+        }
+        config.sources.durations = source("durations", file.durations.is_some());
+        if let Some(ref style) = file
+            .durations
+            .filter(|_| config.sources.durations == SettingSource::File)
+        {
+            config.durations = Self::parse_duration_style(style).map_err(invalid)?;
+        }
+        durations::set_style(config.durations);
+        config.sources.database = source("db_path", file.database.is_some());
+        // The demo brings its own database
+        if config.db_path.is_none() && !config.demo {
+            config.db_path = file.database.as_deref().map(expand_home);
+        }
+        if let Some(ref path) = config.db_path {
+            BatteryDaemon::set_database_path(path.clone());
+        }
+        if let Some(Command::Daemon {
+            name: Some(ref name),
+            ..
+        }) = config.command
+        {
+            BatteryDaemon::set_profile(name.clone());
+        }
+
+        if let Some(interval) = file.daemon.interval {
+            config.daemon_interval = Self::parse_daemon_interval(interval).map_err(invalid)?;
+            config.sources.daemon_interval = SettingSource::File;
+        }
+        Self::check_daemon_days(&file.daemon).map_err(invalid)?;
+        if let Some(days) = file.daemon.retention_days {
+            config.daemon_retention = Some(days);
+            config.sources.daemon_retention = SettingSource::File;
+        }
+        if let Some(days) = file.daemon.per_minute_after {
+            config.daemon_downsample.per_minute_after = Some(days);
+            config.sources.daemon_per_minute = SettingSource::File;
+        }
+        if let Some(days) = file.daemon.per_hour_after {
+            config.daemon_downsample.per_hour_after = Some(days);
+            config.sources.daemon_per_hour = SettingSource::File;
+        }
+        let percent = |value: f32| {
+            if (0.0..=100.0).contains(&value) {
+                Ok(value)
+            } else {
+                Err(invalid(format!("{} isn't a valid percentage", value)))
             }
-            return Ok(true);
+        };
+        if let Some(low) = file.gauge.low {
+            config.gauge.low = percent(low)?;
+            config.sources.gauge_low = SettingSource::File;
+        }
+        if let Some(critical) = file.gauge.critical {
+            config.gauge.critical = percent(critical)?;
+            config.sources.gauge_critical = SettingSource::File;
         }
-        Ok(false)
+        if config.gauge.critical > config.gauge.low {
+            return Err(invalid(format!(
+                "gauge.critical ({}%) is above gauge.low ({}%)",
+                config.gauge.critical, config.gauge.low
+            )));
+        }
+        let bar = &file.status_bar;
+        if let Some(ref icons) = bar.icons {
+            if icons.is_empty() {
+                return Err(invalid(
+                    "status_bar.icons needs at least one icon".to_string(),
+                ));
+            }
+            config.status_bar.icons = icons.clone();
+            config.sources.bar_icons = SettingSource::File;
+        }
+        if let Some(ref icon) = bar.charging_icon {
+            config.status_bar.charging_icon = icon.clone();
+            config.sources.bar_charging_icon = SettingSource::File;
+        }
+        if let Some(ref color) = bar.warning_color {
+            config.status_bar.warning_color = Self::parse_hex_color(color)
+                .map_err(|e| invalid(format!("status_bar.warning_color: {}", e)))?;
+            config.sources.bar_warning_color = SettingSource::File;
+        }
+        if let Some(ref color) = bar.critical_color {
+            config.status_bar.critical_color = Self::parse_hex_color(color)
+                .map_err(|e| invalid(format!("status_bar.critical_color: {}", e)))?;
+            config.sources.bar_critical_color = SettingSource::File;
+        }
+        // The gauge's levels are the notification thresholds too, unless
+        // those are set themselves.
+        if config.sources.gauge_low == SettingSource::File {
+            config.notifications.warn = Some(config.gauge.low);
+            config.sources.notify_warn = SettingSource::File;
+        }
+        if config.sources.gauge_critical == SettingSource::File {
+            config.notifications.critical = Some(config.gauge.critical);
+            config.sources.notify_critical = SettingSource::File;
+        }
+        let defaults = ChargeNotifications::default();
+        let notifications = &file.notifications;
+        if let Some(warn) = notifications.warn {
+            config.notifications.warn =
+                Self::parse_threshold(warn, defaults.warn).map_err(invalid)?;
+            config.sources.notify_warn = SettingSource::File;
+        }
+        if let Some(critical) = notifications.critical {
+            config.notifications.critical =
+                Self::parse_threshold(critical, defaults.critical).map_err(invalid)?;
+            config.sources.notify_critical = SettingSource::File;
+        }
+        if let Some(full) = notifications.full {
+            config.notifications.full = full;
+            config.sources.notify_full = SettingSource::File;
+        }
+        if let Some(ref cooldown) = notifications.cooldown {
+            config.notifications.cooldown = Self::parse_task_duration(cooldown).map_err(invalid)?;
+            config.sources.notify_cooldown = SettingSource::File;
+        }
+        if let Some(ref at) = notifications.daily_summary {
+            config.daily_summary = Some(Self::parse_clock_time(at).map_err(invalid)?);
+            config.sources.notify_daily_summary = SettingSource::File;
+        }
+        config.sources.notify_max_charge_temp =
+            source("max_charge_temp", notifications.max_charge_temp.is_some());
+        if config.sources.notify_max_charge_temp == SettingSource::File
+            && let Some(limit) = notifications.max_charge_temp
+        {
+            config.max_charge_temp = Self::parse_max_charge_temp(limit).map_err(invalid)?;
+        }
+
+        config.alert_actions = file
+            .alerts
+            .iter()
+            .map(AlertFileConfig::parse)
+            .collect::<std::result::Result<_, _>>()
+            .map_err(invalid)?;
+        if !config.alert_actions.is_empty() {
+            config.sources.alerts = SettingSource::File;
+        }
+        if let Some(ref mqtt) = file.mqtt {
+            let prefix = |name: &str, value: &Option<String>, default: &str| {
+                let prefix = value.as_deref().unwrap_or(default).trim_matches('/');
+                if prefix.is_empty() || prefix.contains(['+', '#']) {
+                    Err(invalid(format!(
+                        "mqtt.{}: {} isn't a valid topic",
+                        name, prefix
+                    )))
+                } else {
+                    Ok(prefix.to_string())
+                }
+            };
+            config.mqtt = Some(MqttConfig {
+                broker: MqttConfig::parse_broker(&mqtt.broker)
+                    .map_err(|e| invalid(format!("mqtt.broker: {}", e)))?,
+                username: mqtt.username.clone(),
+                password: mqtt.password.clone(),
+                topic_prefix: prefix(
+                    "topic_prefix",
+                    &mqtt.topic_prefix,
+                    MqttConfig::DEFAULT_TOPIC_PREFIX,
+                )?,
+                discovery_prefix: prefix(
+                    "discovery_prefix",
+                    &mqtt.discovery_prefix,
+                    MqttConfig::DEFAULT_DISCOVERY_PREFIX,
+                )?,
+            });
+            config.sources.mqtt = SettingSource::File;
+        }
+
+        let start = matches.subcommand_matches("daemon").and_then(|daemon| {
+            daemon
+                .subcommand_matches("start")
+                .or_else(|| daemon.subcommand_matches("restart"))
+                .or_else(|| daemon.subcommand_matches("install"))
+        });
+        if let Some(Command::Daemon {
+            action:
+                DaemonAction::Start(args) | DaemonAction::Restart(args) | DaemonAction::Install(args),
+            ..
+        }) = config.command.as_mut()
+        {
+            if start.and_then(|start| start.value_source("interval"))
+                == Some(ValueSource::CommandLine)
+            {
+                config.daemon_interval = args.interval;
+                config.sources.daemon_interval = SettingSource::CommandLine;
+            } else {
+                args.interval = config.daemon_interval;
+            }
+            let from_command_line =
+                |value: &mut Option<u64>, setting: &mut Option<u64>, source: &mut SettingSource| {
+                    if value.is_some() {
+                        *setting = *value;
+                        *source = SettingSource::CommandLine;
+                    } else {
+                        *value = *setting;
+                    }
+                };
+            from_command_line(
+                &mut args.retention_days,
+                &mut config.daemon_retention,
+                &mut config.sources.daemon_retention,
+            );
+            from_command_line(
+                &mut args.per_minute_after,
+                &mut config.daemon_downsample.per_minute_after,
+                &mut config.sources.daemon_per_minute,
+            );
+            from_command_line(
+                &mut args.per_hour_after,
+                &mut config.daemon_downsample.per_hour_after,
+                &mut config.sources.daemon_per_hour,
+            );
+        }
+
+        Ok(config)
+    }
+
+    /// The config file in use, whether or not it exists.
+    pub fn config_path(&self) -> PathBuf {
+        self.config
+            .clone()
+            .unwrap_or_else(Self::default_config_path)
+    }
+
+    /// The effective settings in config file form, with the broker
+    /// credentials replaced, for sharing in a bug report.
+    fn redacted_file(&self) -> FileConfig {
+        let mut file = self.effective_file();
+        if let Some(ref mut mqtt) = file.mqtt {
+            for secret in [&mut mqtt.username, &mut mqtt.password] {
+                if secret.is_some() {
+                    *secret = Some("<redacted>".to_string());
+                }
+            }
+        }
+        file
+    }
+
+    /// The effective settings in config file form.
+    fn effective_file(&self) -> FileConfig {
+        FileConfig {
+            delay: Some(self.delay.as_secs()),
+            unit: Some(self.unit.name().to_string()),
+            temp_unit: self.temp_unit.map(|unit| unit.name().to_string()),
+            theme: Some(self.theme.name().to_string()),
+            colors: self.colors.clone(),
+            keys: self
+                .keymap
+                .overrides()
+                .into_iter()
+                .map(|(action, mut keys)| {
+                    let setting = match keys.len() {
+                        1 => KeySetting::One(keys.remove(0)),
+                        _ => KeySetting::Many(keys),
+                    };
+                    (action, setting)
+                })
+                .collect(),
+            chart_axis: Some(self.chart_axis.name().to_string()),
+            collector: Some(self.collector.name().to_string()),
+            durations: Some(self.durations.name().to_string()),
+            database: self.db_path.clone(),
+            daemon: DaemonFileConfig {
+                interval: Some(self.daemon_interval),
+                retention_days: self.daemon_retention,
+                per_minute_after: self.daemon_downsample.per_minute_after,
+                per_hour_after: self.daemon_downsample.per_hour_after,
+            },
+            gauge: GaugeFileConfig {
+                low: Some(self.gauge.low),
+                critical: Some(self.gauge.critical),
+            },
+            status_bar: StatusBarFileConfig {
+                icons: Some(self.status_bar.icons.clone()),
+                charging_icon: Some(self.status_bar.charging_icon.clone()),
+                warning_color: Some(self.status_bar.warning_color.clone()),
+                critical_color: Some(self.status_bar.critical_color.clone()),
+            },
+            notifications: NotificationsFileConfig {
+                warn: Some(Self::threshold_setting(self.notifications.warn)),
+                critical: Some(Self::threshold_setting(self.notifications.critical)),
+                full: Some(self.notifications.full),
+                cooldown: Some(humantime::format_duration(self.notifications.cooldown).to_string()),
+                daily_summary: self.daily_summary.map(|at| at.format("%H:%M").to_string()),
+                max_charge_temp: Some(self.max_charge_temp),
+            },
+            alerts: self
+                .alert_actions
+                .iter()
+                .map(|action| AlertFileConfig {
+                    below: (action.crossing == Crossing::Below).then_some(action.threshold),
+                    above: (action.crossing == Crossing::Above).then_some(action.threshold),
+                    command: action.command.clone(),
+                })
+                .collect(),
+            mqtt: self.mqtt.as_ref().map(|mqtt| MqttFileConfig {
+                broker: format!("mqtt://{}", mqtt.broker),
+                username: mqtt.username.clone(),
+                password: mqtt.password.clone(),
+                topic_prefix: Some(mqtt.topic_prefix.clone()),
+                discovery_prefix: Some(mqtt.discovery_prefix.clone()),
+            }),
+        }
+    }
+
+    fn threshold_setting(threshold: Option<f32>) -> ThresholdSetting {
+        match threshold {
+            Some(percent) => ThresholdSetting::Percent(percent),
+            None => ThresholdSetting::Enabled(false),
+        }
+    }
+
+    /// Reads a threshold from the config file; `true` keeps the default.
+    fn parse_threshold(
+        setting: ThresholdSetting,
+        default: Option<f32>,
+    ) -> std::result::Result<Option<f32>, String> {
+        match setting {
+            ThresholdSetting::Enabled(false) => Ok(None),
+            ThresholdSetting::Enabled(true) => Ok(default),
+            ThresholdSetting::Percent(percent) if (0.0..=100.0).contains(&percent) => {
+                Ok(Some(percent))
+            }
+            ThresholdSetting::Percent(percent) => {
+                Err(format!("{} isn't a valid percentage", percent))
+            }
+        }
+    }
+
+    fn parse_daemon_interval(secs: u64) -> std::result::Result<u64, String> {
+        if secs == 0 {
+            Err("daemon.interval: must be at least 1 second".to_string())
+        } else if secs > MAX_DAEMON_INTERVAL {
+            Err(format!(
+                "daemon.interval: must be at most {} seconds (a day)",
+                MAX_DAEMON_INTERVAL
+            ))
+        } else {
+            Ok(secs)
+        }
+    }
+
+    /// Checks that the retention and downsampling ages of `[daemon]` are at
+    /// least a day, since 0 would delete or average all history every hour.
+    fn check_daemon_days(daemon: &DaemonFileConfig) -> std::result::Result<(), String> {
+        let settings = [
+            ("retention_days", daemon.retention_days),
+            ("per_minute_after", daemon.per_minute_after),
+            ("per_hour_after", daemon.per_hour_after),
+        ];
+        match settings.iter().find(|(_, days)| *days == Some(0)) {
+            Some((name, _)) => Err(format!("daemon.{}: must be at least 1 day", name)),
+            None => Ok(()),
+        }
+    }
+
+    fn parse_max_charge_temp(celsius: f32) -> std::result::Result<f32, String> {
+        if MAX_CHARGE_TEMPS.contains(&celsius) {
+            Ok(celsius)
+        } else {
+            Err(format!(
+                "notifications.max_charge_temp: {} isn't a battery temperature in °C",
+                celsius
+            ))
+        }
+    }
+
+    fn parse_celsius(s: &str) -> std::result::Result<f32, String> {
+        match s.parse::<f32>() {
+            Ok(celsius) if MAX_CHARGE_TEMPS.contains(&celsius) => Ok(celsius),
+            _ => Err(format!(
+                "{} isn't a battery temperature in °C, expected {} to {}",
+                s,
+                MAX_CHARGE_TEMPS.start(),
+                MAX_CHARGE_TEMPS.end()
+            )),
+        }
+    }
+
+    fn default_config_path() -> PathBuf {
+        xdg::config_home().join("amptop/config.toml")
+    }
+
+    pub fn delay(&self) -> &Duration {
+        &self.delay
+    }
+
+    pub fn unit(&self) -> Unit {
+        self.unit
+    }
+
+    /// The temperature unit given with `--temp-unit` or `temp_unit`, if any.
+    pub fn temp_unit(&self) -> Option<TemperatureUnit> {
+        self.temp_unit
+    }
+
+    /// The selected theme with the config file's colors applied.
+    pub fn theme(&self) -> Theme {
+        let mut theme = Theme::new(self.theme);
+        for (role, color) in &self.colors {
+            // Checked when the config was loaded.
+            theme.set(role, color).ok();
+        }
+        theme
+    }
+
+    pub fn keymap(&self) -> &Keymap {
+        &self.keymap
+    }
+
+    pub fn chart_axis(&self) -> ChartAxis {
+        self.chart_axis
+    }
+
+    pub fn collector(&self) -> Collector {
+        self.collector
+    }
+
+    pub fn daemon_interval(&self) -> u64 {
+        self.daemon_interval
+    }
+
+    pub fn gauge(&self) -> GaugeThresholds {
+        self.gauge
+    }
+
+    pub fn low_power(&self) -> bool {
+        self.low_power
+    }
+
+    pub fn scale_to_limit(&self) -> bool {
+        self.scale_to_limit
+    }
+
+    pub fn demo(&self) -> bool {
+        self.demo
+    }
+
+    pub fn title(&self) -> bool {
+        self.title
+    }
+
+    pub fn max_charge_temp(&self) -> f32 {
+        self.max_charge_temp
+    }
+
+    pub fn db_path(&self) -> Option<&Path> {
+        self.db_path.as_deref()
+    }
+
+    pub fn sysfs_root(&self) -> Option<&Path> {
+        self.sysfs_root.as_deref()
+    }
+
+    pub fn backend(&self) -> Backend {
+        self.backend
+    }
+
+    pub fn ups(&self) -> Option<&str> {
+        self.ups.as_deref()
+    }
+
+    pub fn upower(&self) -> bool {
+        self.upower
+    }
+
+    fn parse_duration(s: &str) -> std::result::Result<Duration, String> {
+        match s.parse::<u64>() {
+            Ok(seconds) if seconds > 0 => Ok(Duration::from_secs(seconds)),
+            _ => Err(format!("{} isn't a positive number", s)),
+        }
+    }
+
+    fn parse_task_duration(s: &str) -> std::result::Result<Duration, String> {
+        humantime::parse_duration(s).map_err(|e| format!("{} isn't a valid duration: {}", s, e))
+    }
+
+    /// Parses a local time of day such as `21:00`.
+    fn parse_clock_time(s: &str) -> std::result::Result<NaiveTime, String> {
+        NaiveTime::parse_from_str(s.trim(), "%H:%M")
+            .map_err(|_| format!("{} isn't a valid time, expected HH:MM", s))
+    }
+
+    fn parse_load(s: &str) -> std::result::Result<Load, String> {
+        Load::ALL
+            .into_iter()
+            .find(|load| s.eq_ignore_ascii_case(load.label()))
+            .ok_or_else(|| format!("{} isn't a valid load", s))
+    }
+
+    fn parse_session_kind(s: &str) -> std::result::Result<SessionKind, String> {
+        SessionKind::ALL
+            .into_iter()
+            .find(|kind| s.eq_ignore_ascii_case(kind.label()))
+            .ok_or_else(|| format!("{} isn't a valid session kind", s))
+    }
+
+    fn parse_format(s: &str) -> std::result::Result<Format, String> {
+        match s {
+            _ if s.eq_ignore_ascii_case("plain") => Ok(Format::Plain),
+            _ if s.eq_ignore_ascii_case("json") => Ok(Format::Json),
+            _ => Err(format!("{} isn't a valid format", s)),
+        }
+    }
+
+    fn parse_bar_format(s: &str) -> std::result::Result<BarFormat, String> {
+        match s {
+            _ if s.eq_ignore_ascii_case("waybar") => Ok(BarFormat::Waybar),
+            _ if s.eq_ignore_ascii_case("polybar") => Ok(BarFormat::Polybar),
+            _ if s.eq_ignore_ascii_case("i3blocks") => Ok(BarFormat::I3blocks),
+            _ => Err(format!("{} isn't a valid status bar format", s)),
+        }
+    }
+
+    /// A `#rrggbb` color, the form Polybar and i3blocks both understand.
+    fn parse_hex_color(s: &str) -> std::result::Result<String, String> {
+        match s.strip_prefix('#') {
+            Some(hex) if hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()) => {
+                Ok(s.to_string())
+            }
+            _ => Err(format!("{} isn't a #rrggbb color", s)),
+        }
+    }
+
+    fn parse_report_format(s: &str) -> std::result::Result<ReportFormat, String> {
+        match s {
+            _ if s.eq_ignore_ascii_case("html") => Ok(ReportFormat::Html),
+            _ if s.eq_ignore_ascii_case("markdown") || s.eq_ignore_ascii_case("md") => {
+                Ok(ReportFormat::Markdown)
+            }
+            _ => Err(format!("{} isn't a valid report format", s)),
+        }
+    }
+
+    fn parse_export_format(s: &str) -> std::result::Result<ExportFormat, String> {
+        match s {
+            _ if s.eq_ignore_ascii_case("json") => Ok(ExportFormat::Json),
+            _ if s.eq_ignore_ascii_case("csv") => Ok(ExportFormat::Csv),
+            _ => Err(format!("{} isn't a valid export format", s)),
+        }
+    }
+
+    /// Parses a point in time as a Unix timestamp. Accepts RFC 3339, local
+    /// dates and times, or a duration meaning that long ago (`3d`, or
+    /// `3 days ago`).
+    pub fn parse_time(s: &str) -> std::result::Result<i64, String> {
+        use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone};
+
+        if let Ok(time) = DateTime::parse_from_rfc3339(s) {
+            return Ok(time.timestamp());
+        }
+        let local = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M:%S"]
+            .iter()
+            .find_map(|format| NaiveDateTime::parse_from_str(s, format).ok())
+            .or_else(|| {
+                NaiveDate::parse_from_str(s, "%Y-%m-%d")
+                    .ok()
+                    .and_then(|date| date.and_hms_opt(0, 0, 0))
+            });
+        if let Some(time) = local {
+            return Local
+                .from_local_datetime(&time)
+                .earliest()
+                .map(|time| time.timestamp())
+                .ok_or_else(|| format!("{} doesn't exist in the local time zone", s));
+        }
+        let ago = s.strip_suffix("ago").map_or(s, str::trim_end);
+        if let Ok(ago) = humantime::parse_duration(ago) {
+            return i64::try_from(ago.as_secs())
+                .ok()
+                .and_then(|secs| chrono::Utc::now().timestamp().checked_sub(secs))
+                .ok_or_else(|| format!("{} is too far back", s));
+        }
+        Err(format!("{} isn't a valid time", s))
+    }
+
+    /// Parses `START..END`, each side accepted by `parse_time`. A missing
+    /// end means now.
+    fn parse_period(s: &str) -> std::result::Result<(i64, i64), String> {
+        let (start, end) = s
+            .split_once("..")
+            .ok_or_else(|| format!("{} isn't a period, use START..END", s))?;
+        let start = Self::parse_time(start.trim())?;
+        let end = match end.trim() {
+            "" => chrono::Utc::now().timestamp(),
+            end => Self::parse_time(end)?,
+        };
+        if end <= start {
+            return Err(format!("{} ends before it starts", s));
+        }
+        Ok((start, end))
+    }
+
+    fn parse_duration_style(s: &str) -> std::result::Result<DurationStyle, String> {
+        match s {
+            _ if s.eq_ignore_ascii_case("compact") => Ok(DurationStyle::Compact),
+            _ if s.eq_ignore_ascii_case("long") => Ok(DurationStyle::Long),
+            _ => Err(format!("{} isn't a valid duration style", s)),
+        }
+    }
+
+    fn parse_chart_axis(s: &str) -> std::result::Result<ChartAxis, String> {
+        match s {
+            _ if s.eq_ignore_ascii_case("percent") => Ok(ChartAxis::Percent),
+            _ if s.eq_ignore_ascii_case("wh") => Ok(ChartAxis::WattHours),
+            _ if s.eq_ignore_ascii_case("joule") => Ok(ChartAxis::Joules),
+            _ => Err(format!("{} isn't a valid chart axis", s)),
+        }
+    }
+
+    fn parse_collector(s: &str) -> std::result::Result<Collector, String> {
+        match s {
+            _ if s.eq_ignore_ascii_case("ask") => Ok(Collector::Ask),
+            _ if s.eq_ignore_ascii_case("always") => Ok(Collector::Always),
+            _ if s.eq_ignore_ascii_case("never") => Ok(Collector::Never),
+            _ => Err(format!("{} isn't a valid collector setting", s)),
+        }
+    }
+
+    fn parse_theme(s: &str) -> std::result::Result<ThemeName, String> {
+        ThemeName::from_name(s).ok_or_else(|| format!("{} isn't a valid theme", s))
+    }
+
+    fn parse_profile_name(s: &str) -> std::result::Result<String, String> {
+        let valid = !s.is_empty()
+            && s.chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if valid {
+            Ok(s.to_string())
+        } else {
+            Err("use only letters, digits, '-' and '_'".to_string())
+        }
+    }
+
+    fn parse_backend(s: &str) -> std::result::Result<Backend, String> {
+        [
+            Backend::Auto,
+            Backend::Native,
+            Backend::Sysfs,
+            Backend::UPower,
+        ]
+        .into_iter()
+        .find(|backend| s.eq_ignore_ascii_case(backend.name()))
+        .ok_or_else(|| format!("{} isn't a valid backend", s))
+    }
+
+    fn parse_unit(s: &str) -> std::result::Result<Unit, String> {
+        match s {
+            _ if s.eq_ignore_ascii_case("human") => Ok(Unit::Human),
+            _ if s.eq_ignore_ascii_case("si") => Ok(Unit::Si),
+            _ if s.eq_ignore_ascii_case("mah") => Ok(Unit::MilliampHours),
+            _ => Err(format!("{} isn't a valid unit", s)),
+        }
+    }
+
+    /// Accepts `C`, `F` and `K` as well as the scales' names.
+    fn parse_temperature_unit(s: &str) -> std::result::Result<TemperatureUnit, String> {
+        match s.to_ascii_lowercase().as_str() {
+            "c" | "celsius" => Ok(TemperatureUnit::Celsius),
+            "f" | "fahrenheit" => Ok(TemperatureUnit::Fahrenheit),
+            "k" | "kelvin" => Ok(TemperatureUnit::Kelvin),
+            _ => Err(format!("{} isn't a valid temperature unit", s)),
+        }
+    }
+
+    /// Notification settings, with the ones the config file leaves unset
+    /// suited to the battery's chemistry.
+    fn notifications_for(&self, chemistry: Option<Chemistry>) -> ChargeNotifications {
+        let Some(chemistry) = chemistry else {
+            return self.notifications;
+        };
+        let defaults = chemistry.notifications();
+        let unset = |source| source == SettingSource::Default;
+        ChargeNotifications {
+            warn: if unset(self.sources.notify_warn) {
+                defaults.warn
+            } else {
+                self.notifications.warn
+            },
+            critical: if unset(self.sources.notify_critical) {
+                defaults.critical
+            } else {
+                self.notifications.critical
+            },
+            full: if unset(self.sources.notify_full) {
+                defaults.full
+            } else {
+                self.notifications.full
+            },
+            cooldown: self.notifications.cooldown,
+        }
+    }
+
+    fn start_daemon(&self, args: &StartArgs) -> Result<()> {
+        if args.force {
+            BatteryDaemon::force_stop()?;
+        }
+        let mut daemon = BatteryDaemon::new(args.interval)
+            .with_max_charge_temp(self.max_charge_temp)
+            .with_notifications(
+                self.notifications_for(BatteryInfo::new().ok().and_then(|info| info.chemistry())),
+            )
+            .with_alert_actions(self.alert_actions.clone());
+        if let Some(ref mqtt) = self.mqtt {
+            daemon = daemon.with_mqtt(mqtt.clone());
+        }
+        if let Some(at) = self.daily_summary {
+            daemon = daemon.with_daily_summary(at);
+        }
+        if let Some(threshold) = args.power_saver_threshold {
+            daemon = daemon.with_power_saver(PowerSaverConfig {
+                threshold,
+                backlight: args.power_saver_backlight,
+            });
+        }
+        if let Some(threshold) = args.hibernate_below {
+            if hibernate::readiness()
+                .iter()
+                .any(|check| check.problem.is_some())
+            {
+                eprintln!(
+                    "Warning: this system may not be able to hibernate; run `amptop doctor` for details"
+                );
+            }
+            daemon = daemon.with_hibernate_below(threshold);
+        }
+        if let Some(ref path) = args.annotations {
+            // The daemon runs from its data directory
+            daemon = daemon.with_annotations(std::path::absolute(path)?);
+        }
+        if let Some(days) = args.retention_days {
+            daemon = daemon.with_retention(days);
+        }
+        daemon = daemon.with_downsample(Downsample {
+            per_minute_after: args.per_minute_after,
+            per_hour_after: args.per_hour_after,
+        });
+        let mut daemon = daemon.with_reload(self.daemon_settings_reader());
+        if args.foreground {
+            daemon.run_foreground()
+        } else {
+            daemon.start_daemon()
+        }
+    }
+
+    /// Writes and starts a systemd user service running the daemon in the
+    /// foreground. A daemon started with `daemon start` is stopped first,
+    /// since the service would otherwise find it running.
+    fn install_service(&self, profile: Option<&str>, args: &StartArgs) -> Result<()> {
+        let command = self.foreground_command(profile, args)?;
+        if BatteryDaemon::is_running() {
+            BatteryDaemon::stop_daemon()?;
+            println!("Stopped the running daemon; systemd starts it from now on");
+        }
+        let path = service::install(&service::unit_name(profile), &command)?;
+        println!("Installed {} and started it", path.display());
+        Ok(())
+    }
+
+    /// The command line the systemd service runs. Only options given on
+    /// this command line are passed on; the rest are left to the config
+    /// file, so editing it and running `daemon reload` keeps working.
+    fn foreground_command(&self, profile: Option<&str>, args: &StartArgs) -> Result<Vec<String>> {
+        let absolute = |path: &Path| -> Result<String> {
+            Ok(std::path::absolute(path)?.display().to_string())
+        };
+        let given = |source: SettingSource| source == SettingSource::CommandLine;
+
+        let mut command = vec![std::env::current_exe()?.display().to_string()];
+        if let Some(ref path) = self.config {
+            command.extend(["--config".to_string(), absolute(path)?]);
+        }
+        if let Some(path) = self
+            .db_path
+            .as_ref()
+            .filter(|_| given(self.sources.database))
+        {
+            command.extend(["--db-path".to_string(), absolute(path)?]);
+        }
+        if let Some(ref root) = self.sysfs_root {
+            command.extend(["--sysfs-root".to_string(), absolute(root)?]);
+        }
+        if self.backend != Backend::Auto {
+            command.extend(["--backend".to_string(), self.backend.name().to_string()]);
+        }
+        if self.max_charge_temp != DEFAULT_MAX_CHARGE_TEMP {
+            command.extend([
+                "--max-charge-temp".to_string(),
+                self.max_charge_temp.to_string(),
+            ]);
+        }
+
+        command.extend(["daemon", "run", "--foreground"].map(String::from));
+        if let Some(profile) = profile {
+            command.extend(["--name".to_string(), profile.to_string()]);
+        }
+        let mut option = |name: &str, value: Option<String>| {
+            if let Some(value) = value {
+                command.extend([format!("--{}", name), value]);
+            }
+        };
+        option(
+            "interval",
+            given(self.sources.daemon_interval).then(|| args.interval.to_string()),
+        );
+        option(
+            "power-saver-threshold",
+            args.power_saver_threshold
+                .map(|percent| percent.to_string()),
+        );
+        option(
+            "power-saver-backlight",
+            args.power_saver_backlight
+                .map(|percent| percent.to_string()),
+        );
+        option(
+            "hibernate-below",
+            args.hibernate_below
+                .map(|below| humantime::format_duration(below).to_string()),
+        );
+        option(
+            "annotations",
+            args.annotations.as_deref().map(absolute).transpose()?,
+        );
+        option(
+            "retention-days",
+            args.retention_days
+                .filter(|_| given(self.sources.daemon_retention))
+                .map(|days| days.to_string()),
+        );
+        option(
+            "per-minute-after",
+            args.per_minute_after
+                .filter(|_| given(self.sources.daemon_per_minute))
+                .map(|days| days.to_string()),
+        );
+        option(
+            "per-hour-after",
+            args.per_hour_after
+                .filter(|_| given(self.sources.daemon_per_hour))
+                .map(|days| days.to_string()),
+        );
+        Ok(command)
+    }
+
+    /// Reads the daemon settings a reload may change. Settings given on the
+    /// command line stay as they are; the rest come from the config file,
+    /// falling back to the defaults when removed from it.
+    fn daemon_settings_reader(&self) -> impl Fn() -> Result<DaemonSettings> + 'static {
+        let path = std::path::absolute(self.config_path()).unwrap_or_else(|_| self.config_path());
+        let explicit = self.config.is_some();
+        let fixed = |source: SettingSource, value: Option<u64>| {
+            value.filter(|_| source == SettingSource::CommandLine)
+        };
+        let interval = fixed(self.sources.daemon_interval, Some(self.daemon_interval));
+        let retention = fixed(self.sources.daemon_retention, self.daemon_retention);
+        let per_minute = fixed(
+            self.sources.daemon_per_minute,
+            self.daemon_downsample.per_minute_after,
+        );
+        let per_hour = fixed(
+            self.sources.daemon_per_hour,
+            self.daemon_downsample.per_hour_after,
+        );
+
+        move || {
+            let file = FileConfig::read(&path, explicit)?.daemon;
+            let invalid = |e: String| Error::Config(format!("{}: {}", path.display(), e));
+            let from_file = file
+                .interval
+                .map(Self::parse_daemon_interval)
+                .transpose()
+                .map_err(invalid)?;
+            Self::check_daemon_days(&file).map_err(invalid)?;
+            Ok(DaemonSettings {
+                interval_secs: interval.or(from_file).unwrap_or(DEFAULT_DAEMON_INTERVAL),
+                retention_days: retention.or(file.retention_days),
+                downsample: Downsample {
+                    per_minute_after: per_minute.or(file.per_minute_after),
+                    per_hour_after: per_hour.or(file.per_hour_after),
+                },
+            })
+        }
+    }
+
+    // FIX: This section might need some more careful attention.
+    /// Runs the subcommand, if one was given, and returns the exit status
+    /// the process should end with. Failures are reported on stderr, as JSON
+    /// with `--json`.
+    pub fn handle_command(&self) -> Option<i32> {
+        let command = self.command.as_ref()?;
+        let failed = |context: &'static str| move |e: Error| (context, e);
+        let result = match command {
+            Command::Daemon { action, name } => match action {
+                DaemonAction::Start(args) => self
+                    .start_daemon(args)
+                    .map(|_| {
+                        if args.foreground {
+                            println!("Daemon stopped")
+                        } else {
+                            println!("Daemon started successfully")
+                        }
+                    })
+                    .map_err(failed("Failed to start daemon")),
+                DaemonAction::Restart(args) => BatteryDaemon::force_stop()
+                    .and_then(|_| self.start_daemon(args))
+                    .map(|_| {
+                        if args.foreground {
+                            println!("Daemon stopped")
+                        } else {
+                            println!("Daemon restarted successfully")
+                        }
+                    })
+                    .map_err(failed("Failed to restart daemon")),
+                DaemonAction::Install(args) => self
+                    .install_service(name.as_deref(), args)
+                    .map_err(failed("Failed to install the service")),
+                DaemonAction::Uninstall => service::uninstall(&service::unit_name(name.as_deref()))
+                    .map(|path| println!("Removed {}", path.display()))
+                    .map_err(|e| ("Failed to uninstall the service", e.into())),
+                DaemonAction::Reload => BatteryDaemon::reload_daemon()
+                    .map(|_| println!("Daemon is reloading its settings"))
+                    .map_err(failed("Failed to reload daemon")),
+                DaemonAction::Stop => BatteryDaemon::stop_daemon()
+                    .map(|_| println!("Daemon stopped successfully"))
+                    .map_err(failed("Failed to stop daemon")),
+                DaemonAction::Status => {
+                    let running = match BatteryDaemon::status() {
+                        Ok(status) => {
+                            Self::print_daemon_status(&status);
+                            status.pid.is_some()
+                        }
+                        Err(e) => {
+                            self.report_error("Failed to read the database", &e);
+                            let running = BatteryDaemon::is_running();
+                            if running {
+                                println!("Daemon is running");
+                            } else {
+                                println!("Daemon is not running");
+                            }
+                            running
+                        }
+                    };
+                    if let Ok(coverage) = BatteryDaemon::history_coverage(30) {
+                        println!(
+                            "History coverage: {:.0}% of the last 30 days (daemon off: {:.0}%, asleep: {:.0}%)",
+                            coverage.percent(),
+                            coverage.daemon_off() as f64 / coverage.window as f64 * 100.0,
+                            coverage.asleep() as f64 / coverage.window as f64 * 100.0,
+                        );
+                    }
+                    // Like an init script's status, "not running" has its own
+                    // exit status rather than an error message.
+                    if !running {
+                        return Some(Error::DaemonNotRunning.exit_code());
+                    }
+                    Ok(())
+                }
+            },
+            Command::Db { action } => match action {
+                DbAction::Check { repair } => {
+                    Self::check_database(*repair).map_err(failed("Failed to check database"))
+                }
+                DbAction::Prune {
+                    older_than,
+                    dry_run,
+                } => Self::prune_database(*older_than, *dry_run)
+                    .map_err(failed("Failed to prune database")),
+            },
+            Command::Can { duration, load } => {
+                Self::answer_can(*duration, *load).map_err(failed("Failed to estimate runtime"))
+            }
+            Command::Config { action } => match action {
+                ConfigAction::Show => {
+                    self.show_config();
+                    Ok(())
+                }
+                ConfigAction::Export { output } => self.export_config(output.as_deref()),
+                ConfigAction::Import { file } => self.import_config(file),
+            }
+            .map_err(failed(action.failure())),
+            Command::Logs { action } => match action {
+                LogsAction::Export {
+                    format,
+                    output,
+                    since,
+                    until,
+                } => Self::export_logs(*format, output.as_deref(), *since, *until)
+                    .map_err(failed("Failed to export logs")),
+            },
+            Command::Graph { action } => match action {
+                GraphAction::Export {
+                    output,
+                    range,
+                    until,
+                    axis,
+                } => self
+                    .export_graph(output, *range, *until, *axis)
+                    .map_err(failed("Failed to export the graph")),
+            },
+            Command::Stat { format } => {
+                Self::print_stat(*format).map_err(failed("Failed to read battery"))
+            }
+            Command::Sessions { action } => match action {
+                SessionsAction::List { days, kind } => {
+                    Self::list_sessions(*days, *kind).map_err(failed("Failed to list sessions"))
+                }
+            },
+            Command::Tag { action } => {
+                Self::handle_tag(action).map_err(failed("Failed to update tags"))
+            }
+            Command::Doctor => {
+                // Scripts can check readiness by the exit status alone.
+                if !Self::doctor() {
+                    return Some(1);
+                }
+                Ok(())
+            }
+            Command::Bugreport { output, yes } => self
+                .write_bugreport(output.as_deref(), *yes)
+                .map_err(failed("Failed to write the bug report")),
+            Command::Report { output, format } => Self::write_report(output.as_deref(), *format)
+                .map_err(failed("Failed to write the report")),
+            Command::ChargeLimit { action } => {
+                Self::handle_charge_limit(action).map_err(failed("Failed to set the charge limit"))
+            }
+            Command::Compare { a, b } => {
+                Self::print_comparison(*a, *b).map_err(failed("Failed to compare periods"))
+            }
+            Command::Watch { count } => {
+                self.watch(*count).map_err(failed("Failed to read battery"))
+            }
+            Command::StatusBar { format } => {
+                match self.print_status_bar(*format) {
+                    // i3blocks marks the block urgent on exit code 33.
+                    Ok(status_bar::Urgency::Critical) if *format == BarFormat::I3blocks => {
+                        return Some(33);
+                    }
+                    result => result.map(|_| ()).map_err(failed("Failed to read battery")),
+                }
+            }
+            Command::Stats { action } => match action {
+                None => Self::print_stats(),
+                Some(StatsAction::Voltage { days }) => Self::print_voltage_stats(*days),
+            }
+            .map_err(failed("Failed to compute statistics")),
+            Command::LogOnce => match BatteryDaemon::new(0).log_once() {
+                Ok(true) => {
+                    println!("Snapshot recorded");
+                    Ok(())
+                }
+                Ok(false) => Err(Error::NoBattery),
+                Err(e) => Err(e),
+            }
+            .map_err(failed("Failed to record snapshot")),
+        };
+
+        match result {
+            Ok(()) => Some(0),
+            Err((context, e)) => {
+                self.report_error(context, &e);
+                Some(e.exit_code())
+            }
+        }
+    }
+
+    /// Prints a failed command's error on stderr, as a JSON object with
+    /// `--json`.
+    fn report_error(&self, context: &str, e: &Error) {
+        if self.json {
+            eprintln!("{}", json_error(context, e));
+        } else {
+            eprintln!("{}: {}", context, e);
+        }
+    }
+
+    fn check_database(repair: bool) -> Result<()> {
+        let conn = BatteryDaemon::open_database()?;
+        let report = db::check(&conn)?;
+
+        println!(
+            "Schema version:        {}",
+            migrations::current_version(&conn)?
+        );
+        if report.corruption.is_empty() {
+            println!("SQLite integrity check: ok");
+        } else {
+            println!("SQLite integrity check reported problems:");
+            for message in &report.corruption {
+                println!("  {}", message);
+            }
+        }
+        println!("Out-of-range rows:     {}", report.out_of_range);
+        println!("Non-monotonic rows:    {}", report.non_monotonic);
+        println!("Repeated timestamps:   {}", report.repeated_timestamps);
+        println!("Duplicate rows:        {}", report.duplicates);
+
+        if report.is_clean() {
+            println!("No problems found");
+        } else if repair {
+            let (removed, corrected) = db::repair(&conn)?;
+            println!(
+                "Removed {} invalid rows, corrected {} timestamps and rebuilt the database",
+                removed, corrected
+            );
+        } else {
+            println!("Run `amptop db check --repair` to clean up");
+        }
+        Ok(())
+    }
+
+    fn prune_database(days: u64, dry_run: bool) -> Result<()> {
+        let conn = BatteryDaemon::open_database()?;
+        let report = db::prune(&conn, daemon::days_ago(days)?, dry_run)?;
+
+        let verb = if dry_run { "Would delete" } else { "Deleted" };
+        println!(
+            "{} {} samples, {} daemon events and {} daemon runs older than {} days",
+            verb, report.logs, report.events, report.runs, days
+        );
+        if !dry_run && report.total() > 0 {
+            // Give the freed pages back to the file system.
+            conn.execute_batch("VACUUM")?;
+        }
+        Ok(())
+    }
+
+    fn answer_can(duration: Duration, load: Load) -> Result<()> {
+        let battery = BatteryInfo::new()?;
+        let model = UsageModel::load().unwrap_or(None);
+
+        match forecast::can_finish(&battery, model, duration, load) {
+            Some(verdict) => {
+                println!("{}", verdict.summary());
+                match model {
+                    Some(model) => println!(
+                        "Based on {:.1} W average drain over {:.0}h of recorded discharge, {} load",
+                        model.draw_watts,
+                        model.hours_observed,
+                        load.label()
+                    ),
+                    None => println!(
+                        "No discharge history yet, based on the current rate, {} load",
+                        load.label()
+                    ),
+                }
+            }
+            None if !battery.has_battery() => return Err(Error::NoBattery),
+            None => eprintln!(
+                "No discharge history or live discharge rate to estimate from, start the daemon to collect data"
+            ),
+        }
+        Ok(())
+    }
+
+    /// Effective values of the settings the config file can hold, with
+    /// where each one came from.
+    pub fn settings(&self) -> Vec<(&'static str, String, SettingSource)> {
+        let database = self
+            .db_path
+            .clone()
+            .unwrap_or_else(BatteryDaemon::default_database_path);
+        let threshold = |percent: Option<f32>| match percent {
+            Some(percent) => format!("{}%", percent),
+            None => "off".to_string(),
+        };
+        let days = |days: Option<u64>| match days {
+            Some(days) => format!("{}d", days),
+            None => "off".to_string(),
+        };
+        vec![
+            (
+                "delay",
+                format!("{}s", self.delay.as_secs()),
+                self.sources.delay,
+            ),
+            ("unit", self.unit.name().to_string(), self.sources.unit),
+            (
+                "temp_unit",
+                self.temp_unit
+                    .unwrap_or(self.unit.temperature())
+                    .name()
+                    .to_string(),
+                self.sources.temp_unit,
+            ),
+            ("theme", self.theme.name().to_string(), self.sources.theme),
+            (
+                "colors",
+                match self.colors.len() {
+                    0 => "theme".to_string(),
+                    1 => self
+                        .colors
+                        .iter()
+                        .map(|(role, color)| format!("{} = {}", role, color))
+                        .collect(),
+                    count => format!("{} replaced", count),
+                },
+                self.sources.colors,
+            ),
+            (
+                "keys",
+                {
+                    let overrides = self.keymap.overrides();
+                    match overrides.len() {
+                        0 => "default".to_string(),
+                        1 => overrides
+                            .iter()
+                            .map(|(action, keys)| format!("{} = {}", action, keys.join(", ")))
+                            .collect(),
+                        count => format!("{} remapped", count),
+                    }
+                },
+                self.sources.keys,
+            ),
+            (
+                "chart_axis",
+                self.chart_axis.name().to_string(),
+                self.sources.chart_axis,
+            ),
+            (
+                "collector",
+                self.collector.name().to_string(),
+                self.sources.collector,
+            ),
+            (
+                "durations",
+                self.durations.name().to_string(),
+                self.sources.durations,
+            ),
+            (
+                "database",
+                database.display().to_string(),
+                self.sources.database,
+            ),
+            (
+                "daemon.interval",
+                format!("{}s", self.daemon_interval),
+                self.sources.daemon_interval,
+            ),
+            (
+                "daemon.retention_days",
+                days(self.daemon_retention),
+                self.sources.daemon_retention,
+            ),
+            (
+                "daemon.per_minute_after",
+                days(self.daemon_downsample.per_minute_after),
+                self.sources.daemon_per_minute,
+            ),
+            (
+                "daemon.per_hour_after",
+                days(self.daemon_downsample.per_hour_after),
+                self.sources.daemon_per_hour,
+            ),
+            (
+                "gauge.low",
+                format!("{}%", self.gauge.low),
+                self.sources.gauge_low,
+            ),
+            (
+                "gauge.critical",
+                format!("{}%", self.gauge.critical),
+                self.sources.gauge_critical,
+            ),
+            (
+                "status_bar.icons",
+                self.status_bar.icons.join(" "),
+                self.sources.bar_icons,
+            ),
+            (
+                "status_bar.charging_icon",
+                self.status_bar.charging_icon.clone(),
+                self.sources.bar_charging_icon,
+            ),
+            (
+                "status_bar.warning_color",
+                self.status_bar.warning_color.clone(),
+                self.sources.bar_warning_color,
+            ),
+            (
+                "status_bar.critical_color",
+                self.status_bar.critical_color.clone(),
+                self.sources.bar_critical_color,
+            ),
+            (
+                "notifications.warn",
+                threshold(self.notifications.warn),
+                self.sources.notify_warn,
+            ),
+            (
+                "notifications.critical",
+                threshold(self.notifications.critical),
+                self.sources.notify_critical,
+            ),
+            (
+                "notifications.full",
+                if self.notifications.full { "on" } else { "off" }.to_string(),
+                self.sources.notify_full,
+            ),
+            (
+                "notifications.cooldown",
+                durations::format(self.notifications.cooldown),
+                self.sources.notify_cooldown,
+            ),
+            (
+                "notifications.daily_summary",
+                self.daily_summary
+                    .map(|at| at.format("%H:%M").to_string())
+                    .unwrap_or_else(|| "off".to_string()),
+                self.sources.notify_daily_summary,
+            ),
+            (
+                "notifications.max_charge_temp",
+                format!("{} °C", self.max_charge_temp),
+                self.sources.notify_max_charge_temp,
+            ),
+            (
+                "alerts",
+                match self.alert_actions.len() {
+                    0 => "none".to_string(),
+                    1 => self.alert_actions[0].describe(),
+                    count => format!("{} actions", count),
+                },
+                self.sources.alerts,
+            ),
+            (
+                "mqtt",
+                match self.mqtt {
+                    Some(ref mqtt) => format!("{} under {}", mqtt.broker, mqtt.topic_prefix),
+                    None => "off".to_string(),
+                },
+                self.sources.mqtt,
+            ),
+        ]
+    }
+
+    fn show_config(&self) {
+        let path = self.config_path();
+        println!(
+            "Config file: {}{}",
+            path.display(),
+            if path.exists() { "" } else { " (not found)" }
+        );
+        for (name, value, source) in self.settings() {
+            println!("  {:<27} {:<40} ({})", name, value, source.label());
+        }
+    }
+
+    fn export_config(&self, output: Option<&Path>) -> Result<()> {
+        let text = toml::to_string(&self.effective_file())
+            .map_err(|e| Error::Config(format!("Couldn't serialize settings: {}", e)))?;
+        match output {
+            Some(path) => {
+                fs::write(path, text)?;
+                println!("Exported settings to {}", path.display());
+            }
+            None => print!("{}", text),
+        }
+        Ok(())
+    }
+
+    fn import_config(&self, file: &Path) -> Result<()> {
+        // Reject files this version can't read or load before touching
+        // anything, checked as if they were passed with --config.
+        let args = [
+            OsStr::new("amptop"),
+            OsStr::new("--config"),
+            file.as_os_str(),
+        ];
+        let matches = Config::command()
+            .try_get_matches_from(args)
+            .map_err(|e| Error::Config(e.to_string()))?;
+        Self::from_matches(&matches)?;
+
+        let path = self.config_path();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        if path.exists() {
+            let backup = path.with_extension("toml.bak");
+            fs::copy(&path, &backup)?;
+            println!("Backed up the previous config to {}", backup.display());
+        }
+        fs::copy(file, &path)?;
+        println!("Imported {} into {}", file.display(), path.display());
+        Ok(())
+    }
+
+    fn export_logs(
+        format: ExportFormat,
+        output: Option<&Path>,
+        since: Option<i64>,
+        until: Option<i64>,
+    ) -> Result<()> {
+        if let (Some(since), Some(until)) = (since, until)
+            && since > until
+        {
+            return Err(Error::Config(
+                "--since is later than --until, nothing to export".to_string(),
+            ));
+        }
+        let logs = BatteryDaemon::get_logs_between(since.unwrap_or(0), until.unwrap_or(i64::MAX))?;
+        match output {
+            Some(path) => {
+                let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+                export::write_logs(&logs, format, &mut file)?;
+                std::io::Write::flush(&mut file)?;
+                println!("Exported {} snapshots to {}", logs.len(), path.display());
+                Ok(())
+            }
+            None => export::write_logs(&logs, format, &mut std::io::stdout().lock()),
+        }
+    }
+
+    fn export_graph(
+        &self,
+        output: &Path,
+        range: Duration,
+        until: Option<i64>,
+        axis: Option<ChartAxis>,
+    ) -> Result<()> {
+        use chrono::{Local, TimeZone};
+
+        let Some(format) = ImageFormat::for_path(output) else {
+            return Err(Error::Config(format!(
+                "{} should end in .svg or .png",
+                output.display()
+            )));
+        };
+        let until = until.unwrap_or_else(|| chrono::Utc::now().timestamp());
+        let since = i64::try_from(range.as_secs())
+            .ok()
+            .and_then(|secs| until.checked_sub(secs))
+            .ok_or_else(|| {
+                Error::Config(format!("{} is too long a range", durations::format(range)))
+            })?;
+        let logs = BatteryDaemon::get_logs_between(since, until)?;
+        if logs.is_empty() {
+            println!(
+                "No history in the {} before {}, start the daemon to collect data",
+                durations::format(range),
+                Local
+                    .timestamp_opt(until, 0)
+                    .single()
+                    .map(|dt| dt.format("%Y-%m-%d %H:%M").to_string())
+                    .unwrap_or_default()
+            );
+            return Ok(());
+        }
+        graph::export(
+            &logs,
+            axis.unwrap_or(self.chart_axis),
+            format,
+            output,
+            graph::DEFAULT_SIZE,
+        )
+        .map_err(|e| Error::Io(std::io::Error::other(e)))?;
+        println!("Drew {} snapshots to {}", logs.len(), output.display());
+        Ok(())
+    }
+
+    fn print_stat(format: Format) -> Result<()> {
+        let battery = BatteryInfo::new()?;
+        let Some(summary) = battery.summary() else {
+            return Err(Error::NoBattery);
+        };
+
+        match format {
+            Format::Json => println!(
+                "{}",
+                serde_json::to_string_pretty(&summary).expect("summary is serializable")
+            ),
+            Format::Plain => {
+                let format_time = |secs: u64| durations::format(Duration::from_secs(secs));
+                println!("Percent:       {:.1}%", summary.percent);
+                println!("State:         {}", summary.state);
+                println!("Energy rate:   {:.2} W", summary.energy_rate_watts);
+                println!(
+                    "Energy:        {:.2} / {:.2} Wh",
+                    summary.energy_wh, summary.energy_full_wh
+                );
+                println!("Health:        {:.1}%", summary.health_percent);
+                if let Some(secs) = summary.time_to_full_secs {
+                    println!("Time to full:  {}", format_time(secs));
+                }
+                if let Some(secs) = summary.time_to_empty_secs {
+                    println!("Time to empty: {}", format_time(secs));
+                }
+                if let Some(celsius) = summary.temperature_celsius {
+                    println!("Temperature:   {:.1} °C", celsius);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Prints a reading every `delay`, `count` times or until stdout is
+    /// closed: one JSON object per line with `--json`, a line of text
+    /// otherwise.
+    fn watch(&self, count: Option<u64>) -> Result<()> {
+        use chrono::Local;
+        use std::io::Write;
+
+        let mut battery = BatteryInfo::new()?;
+        let mut out = std::io::stdout().lock();
+        let mut printed = 0;
+        while count.is_none_or(|count| printed < count) {
+            if printed > 0 {
+                std::thread::sleep(self.delay);
+                battery.refresh()?;
+            }
+            let Some(summary) = battery.summary() else {
+                return Err(Error::NoBattery);
+            };
+            let now = Local::now();
+            let line = if self.json {
+                let mut object = serde_json::to_value(&summary).expect("summary is serializable");
+                object["timestamp"] = now.timestamp().into();
+                object.to_string()
+            } else {
+                let estimate = summary
+                    .time_to_empty_secs
+                    .or(summary.time_to_full_secs)
+                    .map(|secs| durations::format(Duration::from_secs(secs)))
+                    .unwrap_or_default();
+                format!(
+                    "{}  {:5.1}%  {:<11}  {:6.2} W  {}",
+                    now.format("%Y-%m-%d %H:%M:%S"),
+                    summary.percent,
+                    summary.state,
+                    summary.energy_rate_watts,
+                    estimate
+                )
+            };
+            // A closed pipe, e.g. `| head`, ends the watch.
+            if writeln!(out, "{}", line).and_then(|_| out.flush()).is_err() {
+                break;
+            }
+            printed += 1;
+        }
+        Ok(())
+    }
+
+    fn print_status_bar(&self, format: BarFormat) -> Result<status_bar::Urgency> {
+        let battery = BatteryInfo::new()?;
+        let Some(summary) = battery.summary() else {
+            return Err(Error::NoBattery);
+        };
+        let (line, urgency) = status_bar::render(&summary, &self.status_bar, self.gauge, format);
+        println!("{}", line);
+        Ok(urgency)
+    }
+
+    fn list_sessions(days: u64, kind: Option<SessionKind>) -> Result<()> {
+        use chrono::{Local, TimeZone};
+
+        let mut sessions: Vec<Session> = BatteryDaemon::sessions(daemon::days_ago(days)?)?;
+        if let Some(kind) = kind {
+            sessions.retain(|session| session.kind == kind);
+        }
+        if sessions.is_empty() {
+            println!("No sessions in the last {} days", days);
+            return Ok(());
+        }
+
+        println!(
+            "{:<16}  {:<9}  {:>10}  {:>13}  {:>8}  {:>8}  Charger",
+            "Started", "Type", "Duration", "Change", "Energy", "Average"
+        );
+        for session in &sessions {
+            println!(
+                "{:<16}  {:<9}  {:>10}  {:>13}  {:>8}  {:>8}  {}",
+                Local
+                    .timestamp_opt(session.start, 0)
+                    .single()
+                    .map(|dt| dt.format("%Y-%m-%d %H:%M").to_string())
+                    .unwrap_or_default(),
+                session.kind.label(),
+                durations::format(Duration::from_secs(session.duration() as u64)),
+                format!(
+                    "{:.0}% → {:.0}%",
+                    session.start_percent, session.end_percent
+                ),
+                session
+                    .energy_delta()
+                    .map(|wh| format!("{:.1} Wh", wh))
+                    .unwrap_or_else(|| "-".to_string()),
+                session
+                    .average_watts()
+                    .map(|watts| format!("{:.1} W", watts))
+                    .unwrap_or_else(|| "-".to_string()),
+                session.adapter.as_deref().unwrap_or("")
+            );
+        }
+        Ok(())
+    }
+
+    fn handle_tag(action: &TagAction) -> Result<()> {
+        let now = chrono::Utc::now().timestamp();
+        let describe = |tag: &Tag| {
+            format!(
+                "\"{}\" after {}",
+                tag.name,
+                durations::format(Duration::from_secs((tag.end(now) - tag.started_at) as u64))
+            )
+        };
+        match action {
+            TagAction::Start { name } => {
+                if let Some(stopped) = BatteryDaemon::start_tag(name)? {
+                    println!("Stopped {}", describe(&stopped));
+                }
+                println!("Tagging \"{}\"", name);
+            }
+            TagAction::Stop => match BatteryDaemon::stop_tag()? {
+                Some(stopped) => println!("Stopped {}", describe(&stopped)),
+                None => println!("No tag is running"),
+            },
+            TagAction::Report => Self::print_tag_report(now)?,
+        }
+        Ok(())
+    }
+
+    fn print_daemon_status(status: &DaemonStatus) {
+        use chrono::{Local, TimeZone};
+
+        let now = chrono::Utc::now().timestamp();
+        let ago = |timestamp: i64| {
+            durations::format(Duration::from_secs((now - timestamp).max(0) as u64))
+        };
+        let date = |timestamp: i64| {
+            Local
+                .timestamp_opt(timestamp, 0)
+                .single()
+                .map(|dt| dt.format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_default()
+        };
+
+        match status.pid {
+            Some(pid) => println!("Daemon is running (PID {})", pid),
+            None => println!("Daemon is not running"),
+        }
+        if let Some(started_at) = status.started_at {
+            println!(
+                "Uptime:         {} (since {})",
+                ago(started_at),
+                date(started_at)
+            );
+        }
+        if let Some(interval) = status.interval_secs {
+            println!(
+                "Interval:       {}",
+                durations::format(Duration::from_secs(interval))
+            );
+        }
+        println!(
+            "Database:       {} ({:.1} MB)",
+            status.database.display(),
+            status.database_bytes as f64 / 1e6
+        );
+        println!("Snapshots:      {}", status.snapshots);
+        match status.last_snapshot {
+            Some(last) => println!("Last snapshot:  {} ({} ago)", date(last), ago(last)),
+            None => println!("Last snapshot:  never"),
+        }
+    }
+
+    /// Prints the readiness checks; returns whether all of them passed.
+    fn doctor() -> bool {
+        println!("Hibernate (daemon start --hibernate-below):");
+        let checks = hibernate::readiness();
+        for check in &checks {
+            let status = if check.problem.is_some() {
+                "FAIL"
+            } else {
+                "ok"
+            };
+            println!("  {:<4}  {:<16} {}", status, check.name, check.detail);
+            if let Some(ref problem) = check.problem {
+                println!("        {:<16} {}", "", problem);
+            }
+        }
+
+        let passed = checks.iter().all(|check| check.problem.is_none());
+        if passed {
+            println!("No problems found");
+        } else {
+            println!(
+                "Hibernating may fail; fix the problems above before relying on --hibernate-below"
+            );
+        }
+        passed
+    }
+
+    fn write_bugreport(&self, output: Option<&Path>, yes: bool) -> Result<()> {
+        use std::io::IsTerminal;
+
+        let config = serde_json::to_value(self.redacted_file())
+            .map_err(|e| Error::Config(format!("Couldn't serialize settings: {}", e)))?;
+        let sections = bugreport::collect(config, self.backend);
+        let mut redactions = bugreport::identifying_values();
+        let sections = if yes || !std::io::stdin().is_terminal() {
+            sections
+        } else {
+            let (kept, extra) = bugreport::review(sections)?;
+            redactions.extend(extra.into_iter().map(|value| (value, "<redacted>")));
+            kept
+        };
+
+        let path = match output {
+            Some(path) => path.to_path_buf(),
+            None => PathBuf::from(format!(
+                "amptop-bugreport-{}.json",
+                chrono::Local::now().format("%Y%m%d-%H%M%S")
+            )),
+        };
+        fs::write(&path, bugreport::render(&sections, &redactions))?;
+        println!("Wrote the bug report to {}", path.display());
+        println!(
+            "Check it for anything private, then attach it to an issue at https://github.com/harshvsri/amptop/issues"
+        );
+        Ok(())
+    }
+
+    fn write_report(output: Option<&Path>, format: Option<ReportFormat>) -> Result<()> {
+        let path = match output {
+            Some(path) => path.to_path_buf(),
+            None => PathBuf::from(format!(
+                "amptop-report-{}.{}",
+                chrono::Local::now().format("%Y%m%d-%H%M%S"),
+                match format {
+                    Some(ReportFormat::Markdown) => "md",
+                    _ => "html",
+                }
+            )),
+        };
+        let format = format.unwrap_or_else(|| ReportFormat::for_path(&path));
+        let battery = BatteryInfo::new()?;
+        fs::write(&path, report::render(&battery, format)?)?;
+        println!("Wrote the report to {}", path.display());
+        Ok(())
+    }
+
+    fn handle_charge_limit(action: &ChargeLimitAction) -> Result<()> {
+        let thresholds = match *action {
+            ChargeLimitAction::Get => charge_limit::read(),
+            ChargeLimitAction::Set { end, start } => charge_limit::set(start, end)?,
+        };
+        if thresholds.is_empty() {
+            println!("No battery supports charge thresholds on this machine");
+            return Ok(());
+        }
+        let vendor = Vendor::detect();
+        if vendor != Vendor::Other {
+            println!("{} laptop", vendor.name());
+        }
+        for battery in &thresholds {
+            println!("{}: {}", battery.battery, battery.describe());
+        }
+        Ok(())
+    }
+
+    fn print_tag_report(now: i64) -> Result<()> {
+        let tags = BatteryDaemon::tags()?;
+        if tags.is_empty() {
+            println!("No tags yet, start one with `amptop tag start NAME`");
+            return Ok(());
+        }
+
+        let mut usage: Vec<TagUsage> = Vec::new();
+        for tag in &tags {
+            let end = tag.end(now);
+            let logs = BatteryDaemon::get_logs_between(tag.started_at, end)?;
+            let entry = match usage.iter_mut().find(|usage| usage.name == tag.name) {
+                Some(entry) => entry,
+                None => {
+                    usage.push(TagUsage {
+                        name: tag.name.clone(),
+                        ..TagUsage::default()
+                    });
+                    usage.last_mut().unwrap()
+                }
+            };
+            entry.add(end - tag.started_at, &logs);
+        }
+        usage.sort_by(|a, b| b.energy.total_cmp(&a.energy));
+
+        println!(
+            "{:<20} {:>5} {:>10} {:>8} {:>9} {:>8}",
+            "Tag", "Times", "Time", "Used", "Energy", "Average"
+        );
+        for entry in &usage {
+            println!(
+                "{:<20} {:>5} {:>10} {:>7.1}% {:>6.1} Wh {:>8}",
+                entry.name,
+                entry.intervals,
+                durations::format(Duration::from_secs(entry.seconds as u64)),
+                entry.percent,
+                entry.energy,
+                entry
+                    .average_watts()
+                    .map(|watts| format!("{:.1} W", watts))
+                    .unwrap_or_else(|| "-".to_string())
+            );
+        }
+        if let Some(running) = tags.iter().find(|tag| tag.stopped_at.is_none()) {
+            println!();
+            println!("\"{}\" is still running", running.name);
+        }
+        println!();
+        println!("Only time on battery counts; energy needs the daemon running while tagged");
+        Ok(())
+    }
+
+    fn print_comparison(a: (i64, i64), b: (i64, i64)) -> Result<()> {
+        let measure = |(since, until): (i64, i64)| -> Result<PeriodDrain> {
+            let logs = BatteryDaemon::get_logs_between(since, until)?;
+            let events = BatteryDaemon::boundary_events(since)?;
+            Ok(PeriodDrain::measure(&logs, &events))
+        };
+        let rows = compare::compare(&measure(a)?, &measure(b)?);
+
+        let estimate = |estimate: Option<Estimate>, unit: &str| match estimate {
+            Some(e) => format!("{:.2} ± {:.2} {} (n={})", e.mean, e.margin, unit, e.samples),
+            None => "not enough data".to_string(),
+        };
+        println!("{:<15} {:<28} {:<28} Change", "", "A", "B");
+        for row in &rows {
+            let change = match row.difference() {
+                Some(d) => format!(
+                    "{:+.2} ± {:.2} {}{}",
+                    d.change,
+                    d.margin,
+                    row.unit,
+                    if d.significant() {
+                        ""
+                    } else {
+                        " (not significant)"
+                    }
+                ),
+                None => "-".to_string(),
+            };
+            println!(
+                "{:<15} {:<28} {:<28} {}",
+                row.name,
+                estimate(row.a, row.unit),
+                estimate(row.b, row.unit),
+                change
+            );
+        }
+        println!();
+        println!("Ranges are 95% confidence intervals of the mean");
+        Ok(())
+    }
+
+    fn print_stats() -> Result<()> {
+        use chrono::{Local, TimeZone};
+
+        let logs = BatteryDaemon::get_logs_since(0)?;
+        let Some(intervals) = stats::interval_stats(&logs) else {
+            println!("Not enough history yet, start the daemon to collect data");
+            return Ok(());
+        };
+
+        let format = |secs: i64| durations::format(Duration::from_secs(secs as u64));
+        let gap_at = Local
+            .timestamp_opt(intervals.largest_gap_at, 0)
+            .single()
+            .map(|dt| dt.format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_default();
+
+        println!("Sampling");
+        println!("  Samples:          {}", intervals.samples);
+        println!("  Median interval:  {}", format(intervals.median));
+        println!("  95th percentile:  {}", format(intervals.p95));
+        println!(
+            "  Largest gap:      {} (after {})",
+            format(intervals.largest_gap),
+            gap_at
+        );
+
+        if let Some(split) = stats::usage_split(&logs) {
+            let watts = |watts: Option<f64>| {
+                watts
+                    .map(|w| format!("{:.2} W", w))
+                    .unwrap_or_else(|| "N/A".to_string())
+            };
+            println!();
+            println!("Discharge");
+            println!(
+                "  Active use:       {} over {:.1}h",
+                watts(split.active_watts),
+                split.active_hours
+            );
+            println!(
+                "  Idle:             {} over {:.1}h",
+                watts(split.idle_watts),
+                split.idle_hours
+            );
+            if let Some(life) = split.active_battery_life() {
+                println!(
+                    "  Active-use life:  {} on a full charge",
+                    format(life.as_secs() as i64)
+                );
+            }
+        }
+
+        let sessions = BatteryDaemon::sessions(0)?;
+        let life = stats::battery_life(&logs, &sessions);
+        println!();
+        println!("Battery life");
+        match life.full_discharge {
+            Some(runtime) => println!(
+                "  Full discharge:   {} from 100% to 0% (over {} discharge{})",
+                format(runtime.as_secs() as i64),
+                life.discharges,
+                if life.discharges == 1 { "" } else { "s" }
+            ),
+            None => println!("  Full discharge:   no discharge long enough yet"),
+        }
+        if let Some(drain) = life.active_drain {
+            println!("  Drain in use:     {:.1} %/h with the screen on", drain);
+        }
+        match life.charge_20_80 {
+            Some(time) => println!(
+                "  Charge 20→80%:    {} (over {} charge{})",
+                format(time.as_secs() as i64),
+                life.charges,
+                if life.charges == 1 { "" } else { "s" }
+            ),
+            None => println!("  Charge 20→80%:    no charge from 20% to 80% recorded yet"),
+        }
+        if let Some(cycles) = life.cycles_per_week {
+            println!("  Cycles per week:  {:.1}", cycles);
+        }
+
+        let chargers = sessions::charger_stats(&sessions);
+        if !chargers.is_empty() {
+            println!();
+            println!("Chargers");
+            for charger in &chargers {
+                println!(
+                    "  {:<32} {:>5.1} %/h  {:>8}  over {} session{}",
+                    charger.adapter,
+                    charger.percent_per_hour().unwrap_or(0.0),
+                    charger
+                        .watts()
+                        .map(|watts| format!("{:.1} W", watts))
+                        .unwrap_or_else(|| "N/A".to_string()),
+                    charger.sessions,
+                    if charger.sessions == 1 { "" } else { "s" }
+                );
+            }
+        }
+
+        let date = |timestamp: i64| {
+            Local
+                .timestamp_opt(timestamp, 0)
+                .single()
+                .map(|dt| dt.format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_default()
+        };
+        let events = BatteryDaemon::boundary_events(0)?;
+        let downtimes = downtime::detect(&logs, &events);
+        if !downtimes.is_empty() {
+            println!();
+            println!("Powered off and suspended");
+            for downtime in downtimes.iter().rev().take(DOWNTIMES_SHOWN) {
+                let change = if downtime.lost() >= 0.0 {
+                    format!("lost {:.0}%", downtime.lost())
+                } else {
+                    format!("gained {:.0}%", -downtime.lost())
+                };
+                println!(
+                    "  {}  {} over {} while {}{}",
+                    date(downtime.start),
+                    change,
+                    format(downtime.duration()),
+                    downtime.kind.label(),
+                    match downtime.kind {
+                        DowntimeKind::Suspended if downtime.lost() >= 0.0 => {
+                            format!(" ({:.2} %/h)", downtime.drain_rate())
+                        }
+                        _ => String::new(),
+                    }
+                );
+            }
+        }
+
+        let weeks = downtime::weekly_suspend_drain(&downtimes);
+        if !weeks.is_empty() {
+            println!();
+            println!("Suspend drain");
+            for week in weeks.iter().rev().take(SUSPEND_WEEKS_SHOWN) {
+                println!(
+                    "  Week of {}  {:.2} %/h over {:.1}h asleep ({} suspend{})",
+                    date(week.week).get(..10).unwrap_or_default(),
+                    week.rate,
+                    week.hours,
+                    week.suspends,
+                    if week.suspends == 1 { "" } else { "s" }
+                );
+            }
+            let mode = downtime::mem_sleep_mode();
+            if let Some(ref mode) = mode {
+                println!("  Sleep mode:       {}", mode);
+            }
+            if weeks
+                .last()
+                .is_some_and(|week| week.rate > downtime::SHALLOW_SLEEP_DRAIN)
+            {
+                println!(
+                    "  Over {:.0} %/h usually means s2idle instead of deep sleep{}",
+                    downtime::SHALLOW_SLEEP_DRAIN,
+                    if mode.as_deref() == Some("s2idle") {
+                        "; see /sys/power/mem_sleep"
+                    } else {
+                        " or a device waking the machine"
+                    }
+                );
+            }
+        }
+
+        let summaries = BatteryDaemon::daily_summaries(DAILY_SUMMARIES_SHOWN)?;
+        if !summaries.is_empty() {
+            println!();
+            println!("Daily summaries");
+            for (date, summary) in &summaries {
+                println!("  {}  {}", date, summary.describe());
+            }
+        }
+
+        println!();
+        println!("amptop overhead");
+        match BatteryDaemon::running_pid()
+            .and_then(|pid| Some((pid, ProcessUsage::read(Some(pid))?)))
+        {
+            Some((pid, usage)) => {
+                println!(
+                    "  Daemon (PID {}):  {}",
+                    pid,
+                    usage.lifetime().map(|o| o.describe()).unwrap_or_default()
+                );
+                println!(
+                    "  CPU time:         {} over {} running",
+                    overhead::format_cpu_time(usage.cpu),
+                    durations::format(usage.uptime)
+                );
+            }
+            None => println!("  Daemon not running"),
+        }
+        Ok(())
+    }
+
+    fn print_voltage_stats(days: Option<u64>) -> Result<()> {
+        let since = days.map_or(Ok(0), daemon::days_ago)?;
+        let logs = BatteryDaemon::get_logs_since(since)?;
+        let Some(voltage) = stats::voltage_stats(&logs) else {
+            println!("No voltage readings on battery yet, the daemon records them from now on");
+            return Ok(());
+        };
+
+        let reading = |reading: &stats::VoltageReading| {
+            format!(
+                "{:.2} V at {:.0}%{}",
+                reading.volts,
+                reading.percent,
+                reading
+                    .watts
+                    .map(|watts| format!(", {:.1} W", watts))
+                    .unwrap_or_default()
+            )
+        };
+        println!("Voltage on battery");
+        println!("  Samples:          {}", voltage.samples);
+        println!("  Minimum:          {}", reading(&voltage.min));
+        println!("  Maximum:          {}", reading(&voltage.max));
+        println!("  Average:          {:.2} V", voltage.avg);
+
+        println!();
+        let Some(heavy_from) = voltage.heavy_from else {
+            println!("No power readings to compare light and heavy draw with");
+            return Ok(());
+        };
+        if voltage.bands.is_empty() {
+            println!("Not enough light and heavy draw at the same charge to compare yet");
+            return Ok(());
+        }
+        println!("Sag at heavy draw (from {:.1} W)", heavy_from);
+        println!(
+            "  {:<10} {:>16} {:>16} {:>8} {:>8}",
+            "Charge", "Light", "Heavy", "Sag", "mΩ"
+        );
+        for band in &voltage.bands {
+            println!(
+                "  {:<10} {:>16} {:>16} {:>8} {:>8}",
+                format!("{:.0}-{:.0}%", band.from, band.from + stats::VOLTAGE_BAND),
+                format!("{:.2} V {:>4.1} W", band.light_volts, band.light_watts),
+                format!("{:.2} V {:>4.1} W", band.heavy_volts, band.heavy_watts),
+                format!("{:.2} V", band.sag()),
+                band.resistance()
+                    .map(|milliohms| format!("{:.0}", milliohms))
+                    .unwrap_or_else(|| "N/A".to_string())
+            );
+        }
+        if let Some(sag) = voltage.average_sag() {
+            println!("  Average sag:      {:.2} V", sag);
+        }
+        if let Some(milliohms) = voltage.resistance() {
+            println!(
+                "  Resistance:       about {:.0} mΩ; a value that keeps rising points to aging cells",
+                milliohms
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Resolves a leading `~/` to the home directory.
+/// A failed command's error as printed with `--json`.
+fn json_error(context: &str, e: &Error) -> serde_json::Value {
+    serde_json::json!({
+        "error": e.kind(),
+        "message": e.to_string(),
+        "context": context,
+        "exit_code": e.exit_code(),
+    })
+}
+
+fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), std::env::var_os("HOME")) {
+        (Ok(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => path.to_path_buf(),
     }
 }
@@ -1,22 +1,338 @@
+use crate::adapter::Adapter;
+use crate::alerts::{
+    self, ActionTrigger, AlertAction, ChargeAlert, ChargeNotifications, TemperatureAlert,
+};
+use crate::annotations::{self, Annotation};
+use crate::clock::MonotonicClock;
+use crate::db;
+use crate::durations;
 use crate::errors::{Error, Result};
-use battery::{Manager, State};
-use chrono::Utc;
+use crate::hibernate::{self, SmartHibernate};
+use crate::idle;
+use crate::info::BatteryInfo;
+use crate::live::Publisher;
+use crate::migrations;
+use crate::mqtt::{MqttConfig, MqttPublisher};
+use crate::power_saver::{PowerSaver, PowerSaverConfig};
+use crate::sessions::{self, Session, SessionKind};
+use crate::stats::{self, DaySummary};
+use crate::tags::Tag;
+use crate::uevent;
+use crate::xdg;
+use battery::State;
+use chrono::{Local, NaiveTime, TimeZone, Utc};
 use daemonize::Daemonize;
-use rusqlite::Connection;
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
 use std::fs;
+use std::io::Write;
+use std::os::fd::AsRawFd;
 use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::RecvTimeoutError;
+use std::time::Instant;
 use std::{thread, time::Duration};
 
-#[derive(Debug, Clone)]
+/// How often the monitoring loop checks for a pending shutdown request.
+const SHUTDOWN_POLL: Duration = Duration::from_millis(250);
+/// Longest interval between two samples that still counts as continuous history.
+pub const MAX_GAP: Duration = Duration::from_secs(60 * 60);
+/// How long `stop_daemon` waits for the daemon to exit on its own.
+const STOP_TIMEOUT: Duration = Duration::from_secs(5);
+/// How often the battery's capacity is written to `health_logs`.
+const HEALTH_EVERY: Duration = Duration::from_secs(24 * 60 * 60);
+/// How often the monitoring loop prunes and downsamples old history.
+const MAINTAIN_EVERY: Duration = Duration::from_secs(60 * 60);
+/// How long a connection waits for another one's write to finish.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Database location set from the configuration, if any.
+static DATABASE_PATH: OnceLock<PathBuf> = OnceLock::new();
+/// Named daemon profile selected with `daemon --name`, if any.
+static PROFILE: OnceLock<String> = OnceLock::new();
+
+static SHUTDOWN: AtomicBool = AtomicBool::new(false);
+/// Set by SIGHUP: re-read the reloadable settings.
+static RELOAD: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn request_shutdown(_signal: libc::c_int) {
+    SHUTDOWN.store(true, Ordering::SeqCst);
+}
+
+extern "C" fn request_reload(_signal: libc::c_int) {
+    RELOAD.store(true, Ordering::SeqCst);
+}
+
+fn install_signal_handlers() {
+    let handler = request_shutdown as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    let reload = request_reload as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    unsafe {
+        libc::signal(libc::SIGTERM, handler);
+        libc::signal(libc::SIGINT, handler);
+        libc::signal(libc::SIGHUP, reload);
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BatterySnapshot {
     pub percent: f32,
     pub timestamp: i64,
     pub status: String,
+    /// Energy stored in the battery, in Wh
+    pub energy: Option<f32>,
+    /// Power the connected adapter reports delivering, in W
+    pub adapter_watts: Option<f32>,
+    /// Battery temperature, in °C
+    pub temperature: Option<f32>,
+    /// Full-charge capacity relative to design capacity, in percent
+    pub health: Option<f32>,
+    /// Whether the user was idle when the sample was taken
+    pub idle: Option<bool>,
+    /// Power flowing into or out of the battery, in W
+    pub power_watts: Option<f32>,
+    /// Charger connected, as given by `Adapter::identity`
+    pub adapter: Option<String>,
+    /// Battery voltage, in V
+    pub voltage: Option<f32>,
+}
+
+impl BatterySnapshot {
+    fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            percent: row.get(0)?,
+            timestamp: row.get(1)?,
+            status: row.get(2)?,
+            energy: row.get(3)?,
+            adapter_watts: row.get(4)?,
+            temperature: row.get(5)?,
+            health: row.get(6)?,
+            idle: row.get(7)?,
+            power_watts: row.get(8)?,
+            adapter: row.get(9)?,
+            voltage: row.get(10)?,
+        })
+    }
+}
+
+/// Columns selected for `BatterySnapshot::from_row`, in order.
+const SNAPSHOT_COLUMNS: &str = "percent, timestamp, status, energy, adapter_watts, temperature, health, idle, power_watts, adapter, voltage";
+
+/// Latest known state of one machine writing into the database.
+#[derive(Debug, Clone)]
+pub struct HostSummary {
+    pub host: String,
+    pub latest: BatterySnapshot,
+}
+
+impl HostSummary {
+    /// Letter grade for the battery's state of health.
+    pub fn health_grade(&self) -> Option<char> {
+        self.latest.health.map(|health| match health {
+            h if h >= 90.0 => 'A',
+            h if h >= 80.0 => 'B',
+            h if h >= 70.0 => 'C',
+            h if h >= 60.0 => 'D',
+            _ => 'F',
+        })
+    }
+}
+
+/// Name this machine records its samples under. A named profile records
+/// under `<hostname>/<profile>`, keeping its history apart from the default
+/// collector's.
+pub fn local_hostname() -> String {
+    let mut buf = [0u8; 256];
+    let ok = unsafe { libc::gethostname(buf.as_mut_ptr().cast(), buf.len()) } == 0;
+    let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    let hostname = if ok && len > 0 {
+        String::from_utf8_lossy(&buf[..len]).into_owned()
+    } else {
+        "localhost".to_string()
+    };
+
+    match PROFILE.get() {
+        Some(profile) => format!("{}/{}", hostname, profile),
+        None => hostname,
+    }
+}
+
+/// Unix timestamp `days` days before now, or a config error if that is
+/// further back than a timestamp can go.
+pub fn days_ago(days: u64) -> Result<i64> {
+    i64::try_from(days)
+        .ok()
+        .and_then(|days| days.checked_mul(24 * 60 * 60))
+        .and_then(|secs| Utc::now().timestamp().checked_sub(secs))
+        .ok_or_else(|| Error::Config(format!("{} days is too far back", days)))
+}
+
+/// Identifies the current boot; changes every time the machine starts.
+fn boot_id() -> Option<String> {
+    fs::read_to_string("/proc/sys/kernel/random/boot_id")
+        .ok()
+        .map(|id| id.trim().to_string())
+        .filter(|id| !id.is_empty())
+}
+
+/// When the machine booted, as a Unix timestamp.
+fn boot_time() -> Option<i64> {
+    fs::read_to_string("/proc/stat")
+        .ok()?
+        .lines()
+        .find_map(|line| line.strip_prefix("btime "))?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Whether systemd is taking the system down, as opposed to the daemon
+/// being stopped on its own.
+fn system_shutting_down() -> bool {
+    std::process::Command::new("systemctl")
+        .arg("is-system-running")
+        .output()
+        .is_ok_and(|output| String::from_utf8_lossy(&output.stdout).trim() == "stopping")
+}
+
+/// A `boot`, `shutdown` or `stopped` event with the charge at that moment,
+/// when known.
+#[derive(Debug, Clone)]
+pub struct BoundaryEvent {
+    pub timestamp: i64,
+    pub event: String,
+    pub percent: Option<f32>,
+}
+
+/// The battery's capacity at one point in time, from `health_logs`.
+#[derive(Debug, Clone, Copy)]
+pub struct HealthSample {
+    pub timestamp: i64,
+    /// Wh
+    pub energy_full: f32,
+    pub energy_full_design: f32,
+    pub cycle_count: Option<u32>,
+}
+
+impl HealthSample {
+    /// Capacity lost compared to the design capacity, in percent.
+    pub fn wear(&self) -> f32 {
+        100.0 - self.energy_full / self.energy_full_design * 100.0
+    }
+}
+
+/// Seconds spent in each charge band, aggregated from the history.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ChargeBands {
+    /// Pinned at 100%
+    pub full: i64,
+    /// 80% up to (but not including) 100%
+    pub high: i64,
+    /// 20% up to 80%
+    pub mid: i64,
+    /// Below 20%
+    pub low: i64,
+}
+
+impl ChargeBands {
+    pub fn total(&self) -> i64 {
+        self.full + self.high + self.mid + self.low
+    }
+}
+
+/// How much of a time window is covered by recorded history, in seconds.
+#[derive(Debug, Clone, Copy)]
+pub struct Coverage {
+    pub window: i64,
+    /// Time covered by consecutive samples
+    pub sampled: i64,
+    /// Time the daemon was running, whether or not it managed to sample
+    pub daemon_running: i64,
+}
+
+impl Coverage {
+    pub fn percent(&self) -> f64 {
+        self.sampled as f64 / self.window as f64 * 100.0
+    }
+
+    /// Uncovered time during which the daemon wasn't running at all.
+    pub fn daemon_off(&self) -> i64 {
+        (self.window - self.daemon_running).clamp(0, self.window - self.sampled)
+    }
+
+    /// Uncovered time while the daemon was running, i.e. the machine was
+    /// suspended or powered off without stopping the daemon.
+    pub fn asleep(&self) -> i64 {
+        (self.window - self.sampled - self.daemon_off()).max(0)
+    }
+}
+
+/// What `amptop daemon status` reports.
+#[derive(Debug, Clone)]
+pub struct DaemonStatus {
+    pub pid: Option<i32>,
+    /// When the running daemon started
+    pub started_at: Option<i64>,
+    /// Sampling interval of the running daemon, in seconds
+    pub interval_secs: Option<u64>,
+    pub database: PathBuf,
+    /// Size of the database file, including its write-ahead log
+    pub database_bytes: u64,
+    /// Snapshots stored, from every host
+    pub snapshots: i64,
+    /// When this machine last recorded a snapshot
+    pub last_snapshot: Option<i64>,
+}
+
+/// Settings `daemon reload` changes without restarting the daemon.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DaemonSettings {
+    pub interval_secs: u64,
+    pub retention_days: Option<u64>,
+    pub downsample: Downsample,
 }
 
 pub struct BatteryDaemon {
     db_path: PathBuf,
     interval_secs: u64,
+    power_saver: Option<PowerSaverConfig>,
+    hibernate_below: Option<Duration>,
+    max_charge_temp: Option<f32>,
+    retention_days: Option<u64>,
+    downsample: Downsample,
+    annotations_path: Option<PathBuf>,
+    notifications: ChargeNotifications,
+    alert_actions: Vec<AlertAction>,
+    mqtt: Option<MqttConfig>,
+    /// Local time after which the day's summary is sent
+    daily_summary: Option<NaiveTime>,
+    /// Running attached to a terminal or service manager; snapshots are
+    /// printed to stdout
+    foreground: bool,
+    /// Reads the settings again on SIGHUP
+    reload: Option<Box<dyn Fn() -> Result<DaemonSettings>>>,
+}
+
+/// Ages, in days, past which history is reduced to fewer samples.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Downsample {
+    pub per_minute_after: Option<u64>,
+    pub per_hour_after: Option<u64>,
+}
+
+/// Mutable state carried across iterations of the monitoring loop.
+struct MonitorState {
+    power_saver: Option<PowerSaver>,
+    hibernate: Option<SmartHibernate>,
+    temperature_alert: Option<TemperatureAlert>,
+    charge_alert: ChargeAlert,
+    actions: ActionTrigger,
+    last_status: Option<String>,
+    clock: MonotonicClock,
+    last_maintenance: Option<Instant>,
+    /// Socket new snapshots are streamed to, for TUIs watching live
+    live: Option<Publisher>,
+    mqtt: Option<MqttPublisher>,
 }
 
 impl BatteryDaemon {
@@ -24,93 +340,781 @@ impl BatteryDaemon {
         Self {
             db_path: Self::init_or_get_path(),
             interval_secs,
+            power_saver: None,
+            hibernate_below: None,
+            max_charge_temp: None,
+            retention_days: None,
+            downsample: Downsample::default(),
+            annotations_path: None,
+            notifications: ChargeNotifications::default(),
+            alert_actions: Vec::new(),
+            mqtt: None,
+            daily_summary: None,
+            foreground: false,
+            reload: None,
+        }
+    }
+
+    /// Calls `reload` for new settings when the daemon receives SIGHUP.
+    pub fn with_reload(mut self, reload: impl Fn() -> Result<DaemonSettings> + 'static) -> Self {
+        self.reload = Some(Box::new(reload));
+        self
+    }
+
+    pub fn with_max_charge_temp(mut self, limit: f32) -> Self {
+        self.max_charge_temp = Some(limit);
+        self
+    }
+
+    pub fn with_power_saver(mut self, config: PowerSaverConfig) -> Self {
+        self.power_saver = Some(config);
+        self
+    }
+
+    /// Hibernates when the estimated runtime left drops below `threshold`.
+    pub fn with_hibernate_below(mut self, threshold: Duration) -> Self {
+        self.hibernate_below = Some(threshold);
+        self
+    }
+
+    /// Deletes history older than `days` while monitoring.
+    pub fn with_retention(mut self, days: u64) -> Self {
+        self.retention_days = Some(days);
+        self
+    }
+
+    /// Replaces old samples with per-minute and per-hour averages while
+    /// monitoring.
+    pub fn with_downsample(mut self, downsample: Downsample) -> Self {
+        self.downsample = downsample;
+        self
+    }
+
+    pub fn with_notifications(mut self, notifications: ChargeNotifications) -> Self {
+        self.notifications = notifications;
+        self
+    }
+
+    /// Runs each action's command when the charge crosses its threshold.
+    pub fn with_alert_actions(mut self, actions: Vec<AlertAction>) -> Self {
+        self.alert_actions = actions;
+        self
+    }
+
+    /// Publishes each snapshot to an MQTT broker for Home Assistant.
+    pub fn with_mqtt(mut self, config: MqttConfig) -> Self {
+        self.mqtt = Some(config);
+        self
+    }
+
+    /// Sends and stores a summary of the day's use once it's past `at`,
+    /// local time.
+    pub fn with_daily_summary(mut self, at: NaiveTime) -> Self {
+        self.daily_summary = Some(at);
+        self
+    }
+
+    /// Stores each line written to `path`, a file or named pipe, as an
+    /// annotation.
+    pub fn with_annotations(mut self, path: PathBuf) -> Self {
+        self.annotations_path = Some(path);
+        self
+    }
+
+    /// `$XDG_DATA_HOME/amptop`, falling back to `~/.local/share/amptop`.
+    fn data_dir() -> PathBuf {
+        xdg::data_home().join("amptop")
+    }
+
+    /// `daemon.pid`, or `daemon-<profile>.pid` for a named profile.
+    fn pid_file_path() -> PathBuf {
+        Self::data_dir().join(format!("{}.pid", Self::file_stem()))
+    }
+
+    /// `daemon.sock`, or `daemon-<profile>.sock`, where the running daemon
+    /// streams new snapshots.
+    pub fn socket_path() -> PathBuf {
+        Self::data_dir().join(format!("{}.sock", Self::file_stem()))
+    }
+
+    fn file_stem() -> String {
+        match PROFILE.get() {
+            Some(profile) => format!("daemon-{}", profile),
+            None => "daemon".to_string(),
         }
     }
 
+    /// Selects a named daemon profile for the rest of the process.
+    pub fn set_profile(name: String) {
+        PROFILE.set(name).ok();
+    }
+
+    /// Where the database lives unless configured otherwise.
+    pub fn default_database_path() -> PathBuf {
+        Self::data_dir().join("battery.db")
+    }
+
     fn init_or_get_path() -> PathBuf {
-        let home = std::env::var("HOME").unwrap_or_else(|_| "/tmp".to_string());
-        let data_dir = PathBuf::from(home).join(".local/share/amptop");
-        fs::create_dir_all(&data_dir).ok();
-        data_dir.join("battery.db")
+        if let Some(path) = DATABASE_PATH.get() {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).ok();
+            }
+            return path.clone();
+        }
+        fs::create_dir_all(Self::data_dir()).ok();
+        Self::default_database_path()
+    }
+
+    /// Stores history in `path` instead of the data directory. Only the
+    /// first call has an effect.
+    pub fn set_database_path(path: PathBuf) {
+        DATABASE_PATH.set(path).ok();
+    }
+
+    pub fn database_exists() -> bool {
+        Self::init_or_get_path().exists()
+    }
+
+    /// Opens the history database, creating its tables if needed.
+    pub fn open_database() -> Result<Connection> {
+        Self::create_schema(Connection::open(Self::init_or_get_path())?)
     }
 
     fn init_database(&self) -> Result<Connection> {
-        let conn = Connection::open(&self.db_path)?;
+        Self::create_schema(Connection::open(&self.db_path)?)
+    }
+
+    /// Upgrades the schema and creates this connection's views.
+    ///
+    /// The database is kept in WAL mode, so the TUI and other commands read
+    /// while the daemon writes instead of failing with "database is locked".
+    /// Writers that do collide wait up to `BUSY_TIMEOUT` for each other.
+    fn create_schema(conn: Connection) -> Result<Connection> {
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        // Returns the mode in effect, which stays the old one on file
+        // systems without shared memory support, such as some network mounts.
+        let _mode: String = conn.query_row("PRAGMA journal_mode = WAL", [], |row| row.get(0))?;
+        // Durable at every checkpoint and safe against corruption in WAL
+        // mode, with one fsync per checkpoint instead of one per sample.
+        conn.pragma_update(None, "synchronous", "NORMAL")?;
+        migrations::migrate(&conn)?;
+        // Raw and downsampled rows read as one history.
         conn.execute(
-            "CREATE TABLE IF NOT EXISTS battery_logs (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                percent REAL NOT NULL,
-                timestamp INTEGER NOT NULL,
-                status TEXT NOT NULL
-            )",
+            &format!(
+                "CREATE TEMP VIEW IF NOT EXISTS history AS
+                SELECT {0}, host FROM battery_logs
+                UNION ALL
+                SELECT {0}, host FROM battery_aggregates",
+                SNAPSHOT_COLUMNS
+            ),
             [],
         )?;
+        // Rows from before hosts were recorded are attributed to this machine.
         conn.execute(
-            "CREATE INDEX IF NOT EXISTS idx_timestamp ON battery_logs(timestamp)",
+            &format!(
+                "CREATE TEMP VIEW IF NOT EXISTS local_logs AS
+                SELECT * FROM history WHERE COALESCE(host, '{0}') = '{0}'",
+                local_hostname().replace('\'', "''")
+            ),
             [],
         )?;
         Ok(conn)
     }
 
     fn collect_snapshot(&self) -> Result<Option<BatterySnapshot>> {
-        if let Some(battery) = Manager::new()?.batteries()?.next() {
-            let battery = battery?;
+        let info = BatteryInfo::new()?;
+        let Some(battery) = info.reading() else {
+            return Ok(None);
+        };
 
-            let percent = battery
-                .state_of_charge()
-                .get::<battery::units::ratio::percent>();
+        let percent = battery
+            .state_of_charge
+            .get::<battery::units::ratio::percent>();
 
-            let timestamp = Utc::now().timestamp();
+        let timestamp = Utc::now().timestamp();
 
-            let status = match battery.state() {
-                State::Charging => "charging",
-                State::Discharging => "discharging",
-                State::Full => "full",
-                State::Empty => "empty",
-                _ => "unknown",
-            };
+        let status = match battery.state {
+            State::Charging => "charging",
+            State::Discharging => "discharging",
+            State::Full => "full",
+            State::Empty => "empty",
+            _ => "unknown",
+        };
 
-            return Ok(Some(BatterySnapshot {
-                percent,
-                timestamp,
-                status: status.to_string(),
-            }));
-        }
-        Ok(None)
+        let energy = battery.energy.get::<battery::units::energy::watt_hour>();
+        let temperature = battery
+            .temperature
+            .map(|temp| temp.get::<battery::units::thermodynamic_temperature::degree_celsius>());
+        let adapter = Adapter::detect().filter(|adapter| adapter.online);
+        let adapter_watts = adapter
+            .as_ref()
+            .and_then(|adapter| adapter.watts)
+            .map(|watts| watts as f32);
+
+        Ok(Some(BatterySnapshot {
+            percent,
+            timestamp,
+            status: status.to_string(),
+            energy: Some(energy),
+            adapter_watts,
+            temperature,
+            health: Some(
+                battery
+                    .state_of_health
+                    .get::<battery::units::ratio::percent>(),
+            ),
+            idle: idle::is_idle(),
+            power_watts: Some(battery.energy_rate.get::<battery::units::power::watt>()),
+            adapter: adapter.and_then(|adapter| adapter.identity()),
+            voltage: Some(
+                battery
+                    .voltage
+                    .get::<battery::units::electric_potential::volt>(),
+            )
+            .filter(|volts| *volts > 0.0),
+        }))
+    }
+
+    fn store_annotation(conn: &Connection, annotation: &Annotation) -> Result<()> {
+        conn.execute(
+            "INSERT INTO annotations (timestamp, host, text) VALUES (?1, ?2, ?3)",
+            (annotation.timestamp, local_hostname(), &annotation.text),
+        )?;
+        Ok(())
     }
 
-    fn store_snapshot(&self, conn: &Connection, snapshot: &BatterySnapshot) -> Result<()> {
+    fn store_snapshot(conn: &Connection, snapshot: &BatterySnapshot) -> Result<()> {
         conn.execute(
-            "INSERT INTO battery_logs (percent, timestamp, status) VALUES (?1, ?2, ?3)",
-            (&snapshot.percent, &snapshot.timestamp, &snapshot.status),
+            "INSERT INTO battery_logs
+                (percent, timestamp, status, energy, adapter_watts, temperature, health, host, idle,
+                 power_watts, adapter, voltage)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            (
+                &snapshot.percent,
+                &snapshot.timestamp,
+                &snapshot.status,
+                &snapshot.energy,
+                &snapshot.adapter_watts,
+                &snapshot.temperature,
+                &snapshot.health,
+                &local_hostname(),
+                &snapshot.idle,
+                &snapshot.power_watts,
+                &snapshot.adapter,
+                &snapshot.voltage,
+            ),
         )?;
         Ok(())
     }
 
-    fn monitor(&self) -> Result<()> {
+    /// Appends snapshots recorded elsewhere, such as imported history, in a
+    /// single transaction.
+    pub fn import_snapshots(snapshots: &[BatterySnapshot]) -> Result<usize> {
+        let mut conn = Self::open_database()?;
+        let tx = conn.transaction()?;
+        for snapshot in snapshots {
+            Self::store_snapshot(&tx, snapshot)?;
+        }
+        tx.commit()?;
+        Ok(snapshots.len())
+    }
+
+    /// Appends capacity readings recorded elsewhere in a single transaction.
+    pub fn import_health(samples: &[HealthSample]) -> Result<usize> {
+        let mut conn = Self::open_database()?;
+        let tx = conn.transaction()?;
+        for sample in samples {
+            tx.execute(
+                "INSERT INTO health_logs (timestamp, host, energy_full, energy_full_design, cycle_count)
+                VALUES (?1, ?2, ?3, ?4, ?5)",
+                (
+                    sample.timestamp,
+                    local_hostname(),
+                    sample.energy_full,
+                    sample.energy_full_design,
+                    sample.cycle_count,
+                ),
+            )?;
+        }
+        tx.commit()?;
+        Ok(samples.len())
+    }
+
+    /// Takes a single snapshot and appends it to the database.
+    /// Returns `false` when no battery was found.
+    pub fn log_once(&self) -> Result<bool> {
+        let conn = self.init_database()?;
+        match self.collect_snapshot()? {
+            Some(mut snapshot) => {
+                let mut clock = MonotonicClock::resume_after(Self::latest_timestamp(&conn)?);
+                snapshot.timestamp = clock.stamp(snapshot.timestamp);
+                Self::store_snapshot(&conn, &snapshot)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Samples until SIGTERM or SIGINT. The handlers only set a flag that is
+    /// checked between writes, so a signal never cuts an insert short, and
+    /// the run is closed and the database checkpointed even when sampling
+    /// stops on an error.
+    fn monitor(&mut self) -> Result<()> {
+        install_signal_handlers();
+
         let conn = self.init_database()?;
+        self.record_boot(&conn)?;
+        self.store_event(&conn, "started", None)?;
+        let run_id = self.begin_run(&conn)?;
+        let result = self.sample_until_shutdown(&conn, run_id);
+        let closed = self.close(conn, run_id);
+        fs::remove_file(Self::pid_file_path()).ok();
+        result.and(closed)
+    }
+
+    fn sample_until_shutdown(&mut self, conn: &Connection, run_id: i64) -> Result<()> {
+        let mut state = MonitorState {
+            power_saver: self.power_saver.map(PowerSaver::new),
+            hibernate: self.hibernate_below.map(SmartHibernate::new),
+            temperature_alert: self.max_charge_temp.map(TemperatureAlert::new),
+            charge_alert: ChargeAlert::new(self.notifications),
+            actions: ActionTrigger::new(self.alert_actions.clone()),
+            last_status: None,
+            clock: MonotonicClock::resume_after(Self::latest_timestamp(conn)?),
+            last_maintenance: None,
+            live: Publisher::bind(Self::socket_path())
+                .inspect_err(|e| eprintln!("Live feed unavailable: {}", e))
+                .ok(),
+            mqtt: self
+                .mqtt
+                .clone()
+                .map(|config| MqttPublisher::new(config, self.interval_secs)),
+        };
+        // Power supply events let us record plug/unplug transitions the moment
+        // they happen; without them we fall back to plain interval sampling.
+        let power_events = uevent::subscribe().ok();
+        let annotations = self.annotations_path.clone().map(annotations::watch);
 
-        loop {
+        while !SHUTDOWN.load(Ordering::SeqCst) {
             if let Some(snapshot) = self.collect_snapshot()? {
-                self.store_snapshot(&conn, &snapshot)?;
+                self.record(conn, snapshot, &mut state)?;
+            }
+            self.heartbeat(conn, run_id)?;
+            self.record_health_if_due(conn)?;
+            self.maintain_if_due(conn, &mut state)?;
+            self.summarize_day_if_due(conn)?;
+
+            let sampled_at = Instant::now();
+            while !SHUTDOWN.load(Ordering::SeqCst) {
+                if RELOAD.swap(false, Ordering::SeqCst) {
+                    self.reload(conn, run_id, &mut state)?;
+                }
+                let remaining = sampled_at
+                    .checked_add(Duration::from_secs(self.interval_secs))
+                    .map_or(Duration::MAX, |deadline| {
+                        deadline.saturating_duration_since(Instant::now())
+                    });
+                if remaining.is_zero() {
+                    break;
+                }
+                let step = remaining.min(SHUTDOWN_POLL);
+
+                if let Some(ref annotations) = annotations {
+                    for annotation in annotations.try_iter() {
+                        Self::store_annotation(conn, &annotation)?;
+                    }
+                }
+
+                let Some(ref events) = power_events else {
+                    thread::sleep(step);
+                    continue;
+                };
+
+                match events.recv_timeout(step) {
+                    Ok(event) => {
+                        if let Some(mut snapshot) = self.collect_snapshot()?
+                            && state.last_status.as_ref() != Some(&snapshot.status)
+                        {
+                            snapshot.timestamp = event.timestamp;
+                            self.record(conn, snapshot, &mut state)?;
+                        }
+                    }
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => thread::sleep(step),
+                }
             }
-            thread::sleep(Duration::from_secs(self.interval_secs));
         }
+        Ok(())
     }
 
-    pub fn start_daemon(&self) -> Result<()> {
-        let home = std::env::var("HOME").unwrap_or_else(|_| "/tmp".to_string());
-        let pid_dir = PathBuf::from(home).join(".local/share/amptop");
-        let pid_file = pid_dir.join("daemon.pid");
-        fs::create_dir_all(&pid_dir)?;
+    /// Samples from within another process, such as the TUI, until `stop`
+    /// is set or a daemon starts. Unlike the daemon it sends no alerts,
+    /// holds no PID file and leaves pruning and downsampling to the daemon,
+    /// but its run counts towards history coverage all the same.
+    pub fn collect_until(&self, stop: &AtomicBool) -> Result<()> {
+        let conn = self.init_database()?;
+        let run_id = self.begin_run(&conn)?;
+        let result = self.collect_while_no_daemon(&conn, run_id, stop);
+        let ended = self.end_run(&conn, run_id);
+        result.and(ended)
+    }
 
-        if pid_file.exists() && BatteryDaemon::is_running() {
-            return Err(Error::DaemonAlreadyRunning);
+    fn collect_while_no_daemon(
+        &self,
+        conn: &Connection,
+        run_id: i64,
+        stop: &AtomicBool,
+    ) -> Result<()> {
+        let mut clock = MonotonicClock::resume_after(Self::latest_timestamp(conn)?);
+        let mut last_status: Option<String> = None;
+        while !stop.load(Ordering::SeqCst) && !Self::is_running() {
+            if let Some(mut snapshot) = self.collect_snapshot()? {
+                snapshot.timestamp = clock.stamp(snapshot.timestamp);
+                Self::store_snapshot(conn, &snapshot)?;
+                if last_status
+                    .as_ref()
+                    .is_some_and(|last| *last != snapshot.status)
+                {
+                    sync_sessions(conn)?;
+                }
+                last_status = Some(snapshot.status);
+            }
+            self.heartbeat(conn, run_id)?;
+
+            let deadline = Instant::now().checked_add(Duration::from_secs(self.interval_secs));
+            while !stop.load(Ordering::SeqCst) {
+                let remaining = deadline.map_or(Duration::MAX, |deadline| {
+                    deadline.saturating_duration_since(Instant::now())
+                });
+                if remaining.is_zero() {
+                    break;
+                }
+                thread::sleep(remaining.min(SHUTDOWN_POLL));
+            }
+        }
+        Ok(())
+    }
+
+    /// Ends the run, records why the daemon stopped and closes the
+    /// database.
+    fn close(&self, conn: Connection, run_id: i64) -> Result<()> {
+        self.end_run(&conn, run_id)?;
+        self.store_event(&conn, "stopped", None)?;
+        if system_shutting_down() {
+            let percent = self.collect_snapshot()?.map(|snapshot| snapshot.percent);
+            self.store_event(&conn, "shutdown", percent)?;
+        }
+        // Fold the write-ahead log back into the database, so a clean stop
+        // leaves a single self-contained file.
+        conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))?;
+        conn.close().map_err(|(_, e)| e)?;
+        Ok(())
+    }
+
+    /// Applies the settings the reload callback returns. A config file that
+    /// fails to parse keeps the current settings running.
+    fn reload(&mut self, conn: &Connection, run_id: i64, state: &mut MonitorState) -> Result<()> {
+        let Some(ref reload) = self.reload else {
+            return Ok(());
+        };
+        let settings = match reload() {
+            Ok(settings) => settings,
+            Err(e) => {
+                eprintln!("Reload failed, keeping the current settings: {}", e);
+                return Ok(());
+            }
+        };
+
+        self.interval_secs = settings.interval_secs;
+        self.retention_days = settings.retention_days;
+        self.downsample = settings.downsample;
+        conn.execute(
+            "UPDATE daemon_runs SET interval_secs = ?1 WHERE id = ?2",
+            (self.interval_secs as i64, run_id),
+        )?;
+        // Apply new retention and downsampling right away.
+        state.last_maintenance = None;
+        self.store_event(conn, "reloaded", None)?;
+        Ok(())
+    }
+
+    fn latest_timestamp(conn: &Connection) -> Result<Option<i64>> {
+        Ok(
+            conn.query_row("SELECT MAX(timestamp) FROM local_logs", [], |row| {
+                row.get(0)
+            })?,
+        )
+    }
+
+    /// Opens a run span for this daemon process and returns its row id.
+    fn begin_run(&self, conn: &Connection) -> Result<i64> {
+        let now = Utc::now().timestamp();
+        conn.execute(
+            "INSERT INTO daemon_runs (started_at, last_seen, interval_secs) VALUES (?1, ?1, ?2)",
+            (now, self.interval_secs as i64),
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// Extends the current run span. If the daemon crashes, `last_seen` still
+    /// bounds how long it was actually running.
+    fn heartbeat(&self, conn: &Connection, run_id: i64) -> Result<()> {
+        conn.execute(
+            "UPDATE daemon_runs SET last_seen = ?1 WHERE id = ?2",
+            (Utc::now().timestamp(), run_id),
+        )?;
+        Ok(())
+    }
+
+    fn end_run(&self, conn: &Connection, run_id: i64) -> Result<()> {
+        let now = Utc::now().timestamp();
+        conn.execute(
+            "UPDATE daemon_runs SET last_seen = ?1, stopped_at = ?1 WHERE id = ?2",
+            (now, run_id),
+        )?;
+        Ok(())
+    }
+
+    /// Writes the battery's full and design capacity to `health_logs` when
+    /// the last entry for this machine is older than `HEALTH_EVERY`.
+    fn record_health_if_due(&self, conn: &Connection) -> Result<()> {
+        let now = Utc::now().timestamp();
+        let last: Option<i64> = conn.query_row(
+            "SELECT MAX(timestamp) FROM health_logs WHERE host = ?1",
+            [local_hostname()],
+            |row| row.get(0),
+        )?;
+        if last.is_some_and(|last| now - last < HEALTH_EVERY.as_secs() as i64) {
+            return Ok(());
+        }
+
+        let info = BatteryInfo::new()?;
+        let Some(battery) = info.total() else {
+            return Ok(());
+        };
+        let design = battery
+            .energy_full_design
+            .get::<battery::units::energy::watt_hour>();
+        if design <= 0.0 {
+            return Ok(());
+        }
+        conn.execute(
+            "INSERT INTO health_logs (timestamp, host, energy_full, energy_full_design, cycle_count)
+            VALUES (?1, ?2, ?3, ?4, ?5)",
+            (
+                now,
+                local_hostname(),
+                battery.energy_full.get::<battery::units::energy::watt_hour>(),
+                design,
+                battery.cycle_count,
+            ),
+        )?;
+        Ok(())
+    }
+
+    /// Applies the retention and downsampling policies at startup and then
+    /// every `MAINTAIN_EVERY`.
+    fn maintain_if_due(&self, conn: &Connection, state: &mut MonitorState) -> Result<()> {
+        if state
+            .last_maintenance
+            .is_some_and(|last| last.elapsed() < MAINTAIN_EVERY)
+        {
+            return Ok(());
         }
 
-        let stdout = fs::File::create(pid_dir.join("daemon.out"))?;
-        let stderr = fs::File::create(pid_dir.join("daemon.err"))?;
+        if let Some(days) = self.retention_days {
+            db::prune(conn, days_ago(days)?, false)?;
+        }
+        if let Some(days) = self.downsample.per_minute_after {
+            db::downsample(conn, days_ago(days)?, 60)?;
+        }
+        if let Some(days) = self.downsample.per_hour_after {
+            db::downsample(conn, days_ago(days)?, 60 * 60)?;
+        }
+        sync_sessions(conn)?;
+        state.last_maintenance = Some(Instant::now());
+        Ok(())
+    }
+
+    /// Sends today's summary once it's past the configured time, unless
+    /// one is already stored for today.
+    fn summarize_day_if_due(&self, conn: &Connection) -> Result<()> {
+        let Some(at) = self.daily_summary else {
+            return Ok(());
+        };
+        let now = Local::now();
+        if now.time() < at {
+            return Ok(());
+        }
+        let date = now.date_naive();
+        let sent = conn
+            .query_row(
+                "SELECT 1 FROM daily_summaries WHERE host = ?1 AND date = ?2",
+                (local_hostname(), date.to_string()),
+                |_| Ok(()),
+            )
+            .optional()?
+            .is_some();
+        if sent {
+            return Ok(());
+        }
+
+        let midnight = Local
+            .from_local_datetime(&date.and_time(NaiveTime::MIN))
+            .earliest()
+            .map_or(now.timestamp(), |midnight| midnight.timestamp());
+        let logs = conn
+            .prepare_cached(&format!(
+                "SELECT {} FROM local_logs WHERE timestamp >= ?1 ORDER BY timestamp ASC",
+                SNAPSHOT_COLUMNS
+            ))?
+            .query_map([midnight], BatterySnapshot::from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        let Some(summary) = stats::day_summary(&logs) else {
+            return Ok(());
+        };
+
+        conn.execute(
+            "INSERT INTO daily_summaries (host, date, battery_hours, avg_watts, cycles, health)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            (
+                local_hostname(),
+                date.to_string(),
+                summary.battery_hours,
+                summary.avg_watts,
+                summary.cycles,
+                summary.health,
+            ),
+        )?;
+        alerts::notify("Daily summary", &format!("Today: {}", summary.describe()));
+        Ok(())
+    }
+
+    /// Records a `boot` event with the current charge when this is the
+    /// first run since the machine booted.
+    fn record_boot(&self, conn: &Connection) -> Result<()> {
+        let Some(boot_id) = boot_id() else {
+            return Ok(());
+        };
+        let previous: Option<String> = conn
+            .query_row(
+                "SELECT boot_id FROM daemon_events
+                WHERE boot_id IS NOT NULL ORDER BY id DESC LIMIT 1",
+                [],
+                |row| row.get(0),
+            )
+            .optional()?;
+        // Without an earlier boot there is nothing to compare against.
+        if previous.is_none() || previous.as_ref() == Some(&boot_id) {
+            return Ok(());
+        }
+
+        let percent = self.collect_snapshot()?.map(|snapshot| snapshot.percent);
+        conn.execute(
+            "INSERT INTO daemon_events (timestamp, event, percent, boot_id)
+            VALUES (?1, 'boot', ?2, ?3)",
+            (
+                boot_time().unwrap_or_else(|| Utc::now().timestamp()),
+                percent,
+                &boot_id,
+            ),
+        )?;
+        Ok(())
+    }
+
+    fn store_event(&self, conn: &Connection, event: &str, percent: Option<f32>) -> Result<()> {
+        conn.execute(
+            "INSERT INTO daemon_events (timestamp, event, percent, boot_id) VALUES (?1, ?2, ?3, ?4)",
+            (Utc::now().timestamp(), event, percent, boot_id()),
+        )?;
+        Ok(())
+    }
+
+    fn record(
+        &self,
+        conn: &Connection,
+        mut snapshot: BatterySnapshot,
+        state: &mut MonitorState,
+    ) -> Result<()> {
+        snapshot.timestamp = state.clock.stamp(snapshot.timestamp);
+        Self::store_snapshot(conn, &snapshot)?;
+        if let Some(ref mut live) = state.live {
+            live.publish(&snapshot);
+        }
+        if let Some(ref mut mqtt) = state.mqtt {
+            mqtt.publish(&snapshot);
+        }
+        if self.foreground {
+            println!(
+                "{}  {:5.1}%  {:<11}  {}",
+                Local
+                    .timestamp_opt(snapshot.timestamp, 0)
+                    .single()
+                    .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string())
+                    .unwrap_or_default(),
+                snapshot.percent,
+                snapshot.status,
+                snapshot
+                    .power_watts
+                    .map(|watts| format!("{:.2} W", watts))
+                    .unwrap_or_default()
+            );
+        }
+        if let Some(ref mut power_saver) = state.power_saver {
+            power_saver.update(&snapshot);
+        }
+        if let Some(ref mut smart_hibernate) = state.hibernate
+            && let Some(remaining) = smart_hibernate.update(&snapshot)
+        {
+            self.store_event(conn, "hibernate", Some(snapshot.percent))?;
+            alerts::notify(
+                "Hibernating",
+                &format!(
+                    "About {} of battery left at {:.0}%",
+                    durations::format(remaining),
+                    snapshot.percent
+                ),
+            );
+            if let Err(e) = hibernate::hibernate() {
+                eprintln!("Failed to hibernate: {}", e);
+            }
+        }
+        if let Some(notice) = state.charge_alert.update(&snapshot) {
+            self.store_event(conn, notice.event, Some(snapshot.percent))?;
+            alerts::notify_with(notice.urgency, notice.summary, &notice.body);
+        }
+        if !state.actions.update(&snapshot).is_empty() {
+            self.store_event(conn, "action", Some(snapshot.percent))?;
+        }
+        if let Some(ref mut alert) = state.temperature_alert
+            && let Some(message) = alert.update(&snapshot)
+        {
+            self.store_event(conn, "overheat", Some(snapshot.percent))?;
+            alerts::notify("Battery overheating", &message);
+        }
+        // A new status ends the session the previous samples belonged to.
+        if state
+            .last_status
+            .as_ref()
+            .is_some_and(|last| *last != snapshot.status)
+        {
+            sync_sessions(conn)?;
+        }
+        state.last_status = Some(snapshot.status);
+        Ok(())
+    }
 
+    pub fn start_daemon(&mut self) -> Result<()> {
+        let pid_dir = Self::data_dir();
+        let pid_file = Self::pid_file_path();
+        Self::prepare_pid_file()?;
+
+        let stem = Self::file_stem();
+        let stdout = fs::File::create(pid_dir.join(format!("{}.out", stem)))?;
+        let stderr = fs::File::create(pid_dir.join(format!("{}.err", stem)))?;
+
+        // Daemonize holds an exclusive flock on the PID file for the lifetime
+        // of the process, which is what `running_pid` checks for.
         let daemonize = Daemonize::new()
             .pid_file(pid_file)
             .working_directory(pid_dir)
@@ -123,64 +1127,605 @@ impl BatteryDaemon {
         self.monitor()
     }
 
-    pub fn is_running() -> bool {
-        let home = std::env::var("HOME").unwrap_or_else(|_| "/tmp".to_string());
-        let pid_file = PathBuf::from(home).join(".local/share/amptop/daemon.pid");
+    /// Monitors without detaching, for service managers such as systemd or
+    /// runit that supervise the process themselves. The PID file is still
+    /// written and locked, so `stop`, `reload` and `status` work as usual.
+    pub fn run_foreground(&mut self) -> Result<()> {
+        Self::prepare_pid_file()?;
+        // Held until monitoring ends; the lock is what `running_pid` checks.
+        let mut pid_file = fs::File::create(Self::pid_file_path())?;
+        if unsafe { libc::flock(pid_file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+            return Err(Error::DaemonAlreadyRunning);
+        }
+        writeln!(pid_file, "{}", std::process::id())?;
+
+        self.foreground = true;
+        self.monitor()
+    }
+
+    /// Fails when a daemon is already running, and otherwise clears away a
+    /// PID file left behind by one that crashed.
+    fn prepare_pid_file() -> Result<()> {
+        let pid_file = Self::pid_file_path();
+        fs::create_dir_all(Self::data_dir())?;
+
+        if Self::running_pid().is_some() {
+            return Err(Error::DaemonAlreadyRunning);
+        }
+        if pid_file.exists() {
+            eprintln!(
+                "Removing stale PID file {} left by a daemon that is no longer running",
+                pid_file.display()
+            );
+        }
+        Self::remove_stale_pid_file()
+    }
 
-        if !pid_file.exists() {
-            return false;
+    /// Returns the PID of the running daemon. A PID file that isn't locked by
+    /// a live process is left behind by a crash or power loss and is ignored.
+    pub fn running_pid() -> Option<i32> {
+        let file = fs::File::open(Self::pid_file_path()).ok()?;
+
+        let locked = unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_SH | libc::LOCK_NB) } != 0;
+        if !locked {
+            return None;
         }
 
-        if let Ok(pid_str) = fs::read_to_string(&pid_file) {
-            if let Ok(pid) = pid_str.trim().parse::<i32>() {
-                unsafe {
-                    return libc::kill(pid, 0) == 0;
-                }
-            }
+        let pid = fs::read_to_string(Self::pid_file_path())
+            .ok()?
+            .trim()
+            .parse::<i32>()
+            .ok()?;
+        let alive = unsafe { libc::kill(pid, 0) == 0 };
+        alive.then_some(pid)
+    }
+
+    fn remove_stale_pid_file() -> Result<()> {
+        let pid_file = Self::pid_file_path();
+        if pid_file.exists() && Self::running_pid().is_none() {
+            fs::remove_file(pid_file)?;
+        }
+        Ok(())
+    }
+
+    pub fn is_running() -> bool {
+        Self::running_pid().is_some()
+    }
+
+    /// Asks the running daemon to re-read its settings.
+    pub fn reload_daemon() -> Result<()> {
+        let pid = Self::running_pid().ok_or(Error::DaemonNotRunning)?;
+        if unsafe { libc::kill(pid, libc::SIGHUP) } != 0 {
+            return Err(std::io::Error::last_os_error().into());
         }
-        false
+        Ok(())
+    }
+
+    /// Collects the running daemon's details and what the database holds.
+    /// The start time and interval come from the open `daemon_runs` row.
+    pub fn status() -> Result<DaemonStatus> {
+        let pid = Self::running_pid();
+        let conn = Self::open_database()?;
+
+        let run = match pid {
+            Some(_) => conn
+                .query_row(
+                    "SELECT started_at, interval_secs FROM daemon_runs
+                    WHERE stopped_at IS NULL ORDER BY id DESC LIMIT 1",
+                    [],
+                    |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?)),
+                )
+                .optional()?,
+            None => None,
+        };
+        let snapshots = conn.query_row("SELECT COUNT(*) FROM history", [], |row| row.get(0))?;
+        let last_snapshot = conn.query_row("SELECT MAX(timestamp) FROM local_logs", [], |row| {
+            row.get(0)
+        })?;
+
+        let database = Self::init_or_get_path();
+        let mut wal = database.clone().into_os_string();
+        wal.push("-wal");
+        let database_bytes = [database.as_os_str(), wal.as_os_str()]
+            .iter()
+            .filter_map(|path| fs::metadata(path).ok())
+            .map(|metadata| metadata.len())
+            .sum();
+
+        Ok(DaemonStatus {
+            pid,
+            started_at: run.map(|(started_at, _)| started_at),
+            interval_secs: run.map(|(_, interval)| interval as u64),
+            database,
+            database_bytes,
+            snapshots,
+            last_snapshot,
+        })
     }
 
+    /// Stops the running daemon, failing if it is still running after
+    /// `STOP_TIMEOUT`, so nothing starts a second one next to it.
     pub fn stop_daemon() -> Result<()> {
-        let home = std::env::var("HOME").unwrap_or_else(|_| "/tmp".to_string());
-        let pid_file = PathBuf::from(home).join(".local/share/amptop/daemon.pid");
+        let Some(pid) = Self::running_pid() else {
+            Self::remove_stale_pid_file()?;
+            return Err(Error::DaemonNotRunning);
+        };
 
-        if pid_file.exists() {
-            let pid_str = fs::read_to_string(&pid_file)?;
-            let pid: i32 = pid_str.trim().parse()?;
+        unsafe {
+            libc::kill(pid, libc::SIGTERM);
+        }
+        if !Self::wait_for_exit(pid) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                format!(
+                    "daemon (PID {}) is still running {} after SIGTERM",
+                    pid,
+                    durations::format(STOP_TIMEOUT)
+                ),
+            )
+            .into());
+        }
+        // The daemon removes its own PID file once it has shut down
+        // cleanly; this clears one left by a crash on the way out.
+        Self::remove_stale_pid_file()
+    }
+
+    /// Waits up to `STOP_TIMEOUT` for the daemon with `pid` to exit and
+    /// tells whether it did.
+    fn wait_for_exit(pid: i32) -> bool {
+        let deadline = Instant::now() + STOP_TIMEOUT;
+        while Self::running_pid() == Some(pid) && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(100));
+        }
+        Self::running_pid() != Some(pid)
+    }
+
+    /// Stops the daemon holding the PID file, killing it if it doesn't exit
+    /// within `STOP_TIMEOUT`, and removes the PID file whatever state it was
+    /// left in. For `daemon start --force` and `daemon restart`.
+    pub fn force_stop() -> Result<()> {
+        if let Some(pid) = Self::running_pid() {
             unsafe {
                 libc::kill(pid, libc::SIGTERM);
             }
-            fs::remove_file(pid_file)?;
-            Ok(())
-        } else {
-            Err(Error::DaemonNotRunning)
+            if !Self::wait_for_exit(pid) {
+                eprintln!("Daemon (PID {}) ignored SIGTERM, killing it", pid);
+                unsafe {
+                    libc::kill(pid, libc::SIGKILL);
+                }
+                Self::wait_for_exit(pid);
+            }
+        }
+
+        match fs::remove_file(Self::pid_file_path()) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
         }
     }
 
-    pub fn get_logs(limit: Option<usize>) -> Result<Vec<BatterySnapshot>> {
-        let conn = Connection::open(Self::init_or_get_path())?;
-        let mut stmt = if let Some(limit) = limit {
-            conn.prepare(&format!(
-                "SELECT percent, timestamp, status FROM battery_logs ORDER BY timestamp DESC LIMIT {}",
-                limit
-            ))?
-        } else {
-            conn.prepare(
-                "SELECT percent, timestamp, status FROM battery_logs ORDER BY timestamp DESC",
-            )?
-        };
+    /// Computes how much of the last `days` days is covered by history and
+    /// attributes the gaps to the daemon being off or the machine sleeping.
+    pub fn history_coverage(days: i64) -> Result<Coverage> {
+        let conn = Self::open_database()?;
+        let now = Utc::now().timestamp();
+        let window = days * 24 * 60 * 60;
+        let since = now - window;
 
-        let logs = stmt
-            .query_map([], |row| {
-                Ok(BatterySnapshot {
-                    percent: row.get(0)?,
-                    timestamp: row.get(1)?,
-                    status: row.get(2)?,
+        let sampled: i64 = conn.query_row(
+            "SELECT COALESCE(SUM(duration), 0) FROM (
+                SELECT timestamp, LEAD(timestamp) OVER (ORDER BY timestamp) - timestamp AS duration
+                FROM local_logs
+            )
+            WHERE timestamp >= ?1 AND duration > 0 AND duration <= ?2",
+            (since, MAX_GAP.as_secs() as i64),
+            |row| row.get(0),
+        )?;
+
+        let daemon_running: i64 = conn.query_row(
+            "SELECT COALESCE(SUM(MAX(0, MIN(COALESCE(stopped_at, last_seen), ?1) - MAX(started_at, ?2))), 0)
+            FROM daemon_runs",
+            (now, since),
+            |row| row.get(0),
+        )?;
+
+        Ok(Coverage {
+            window,
+            sampled: sampled.min(window),
+            daemon_running: daemon_running.min(window),
+        })
+    }
+
+    /// Starts a tag named `name`, stopping the running one first. Returns
+    /// the tag that was stopped.
+    pub fn start_tag(name: &str) -> Result<Option<Tag>> {
+        let stopped = Self::stop_tag()?;
+        let conn = Self::open_database()?;
+        conn.execute(
+            "INSERT INTO tags (host, name, started_at) VALUES (?1, ?2, ?3)",
+            (local_hostname(), name, Utc::now().timestamp()),
+        )?;
+        Ok(stopped)
+    }
+
+    /// Stops the running tag, if any, and returns it.
+    pub fn stop_tag() -> Result<Option<Tag>> {
+        let conn = Self::open_database()?;
+        let now = Utc::now().timestamp();
+        let tag = conn
+            .query_row(
+                "UPDATE tags SET stopped_at = ?2
+                WHERE host = ?1 AND stopped_at IS NULL
+                RETURNING name, started_at, stopped_at",
+                (local_hostname(), now),
+                Self::tag_from_row,
+            )
+            .optional()?;
+        Ok(tag)
+    }
+
+    /// This machine's tags, oldest first, including a running one.
+    pub fn tags() -> Result<Vec<Tag>> {
+        let conn = Self::open_database()?;
+        let mut stmt = conn.prepare(
+            "SELECT name, started_at, stopped_at FROM tags
+            WHERE host = ?1 ORDER BY started_at",
+        )?;
+        let tags = stmt
+            .query_map([local_hostname()], Self::tag_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(tags)
+    }
+
+    fn tag_from_row(row: &rusqlite::Row) -> rusqlite::Result<Tag> {
+        Ok(Tag {
+            name: row.get(0)?,
+            started_at: row.get(1)?,
+            stopped_at: row.get(2)?,
+        })
+    }
+
+    /// Returns the `boot`, `shutdown` and `stopped` events recorded since
+    /// `since`, oldest first.
+    pub fn boundary_events(since: i64) -> Result<Vec<BoundaryEvent>> {
+        let conn = Self::open_database()?;
+        let mut stmt = conn.prepare(
+            "SELECT timestamp, event, percent FROM daemon_events
+            WHERE event IN ('boot', 'shutdown', 'stopped') AND timestamp >= ?1
+            ORDER BY timestamp",
+        )?;
+        let events = stmt
+            .query_map([since], |row| {
+                Ok(BoundaryEvent {
+                    timestamp: row.get(0)?,
+                    event: row.get(1)?,
+                    percent: row.get(2)?,
                 })
             })?
             .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(events)
+    }
+
+    /// This machine's most recent daily summaries with their dates, newest
+    /// first.
+    pub fn daily_summaries(limit: usize) -> Result<Vec<(String, DaySummary)>> {
+        let conn = Self::open_database()?;
+        let mut stmt = conn.prepare(
+            "SELECT date, battery_hours, avg_watts, cycles, health FROM daily_summaries
+            WHERE host = ?1 ORDER BY date DESC LIMIT ?2",
+        )?;
+        let summaries = stmt
+            .query_map((local_hostname(), limit as i64), |row| {
+                Ok((
+                    row.get(0)?,
+                    DaySummary {
+                        battery_hours: row.get(1)?,
+                        avg_watts: row.get(2)?,
+                        cycles: row.get(3)?,
+                        health: row.get(4)?,
+                    },
+                ))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(summaries)
+    }
+
+    /// This machine's sessions that ended at or after `since`, oldest
+    /// first, after storing any detected since the last call.
+    pub fn sessions(since: i64) -> Result<Vec<Session>> {
+        let conn = Self::open_database()?;
+        sync_sessions(&conn)?;
+        stored_sessions(&conn, since)
+    }
+
+    /// Percentage at which the current discharge started, if the history
+    /// is recent enough to tell. The latest rows are looked up with
+    /// `ORDER BY ... LIMIT 1` rather than `MAX`, which SQLite can't answer
+    /// from the index through the `history` view.
+    pub fn last_unplug_percent() -> Result<Option<f32>> {
+        let conn = Self::open_database()?;
+        let recent = Utc::now().timestamp() - MAX_GAP.as_secs() as i64;
+        let percent = conn
+            .query_row(
+                "SELECT percent FROM local_logs
+                WHERE status = 'discharging'
+                  AND timestamp > COALESCE((
+                      SELECT timestamp FROM local_logs
+                      WHERE status != 'discharging'
+                      ORDER BY timestamp DESC LIMIT 1
+                  ), 0)
+                  AND (SELECT timestamp FROM local_logs ORDER BY timestamp DESC LIMIT 1) >= ?1
+                ORDER BY timestamp ASC
+                LIMIT 1",
+                [recent],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(percent)
+    }
+
+    /// Returns all snapshots recorded since `since`, oldest first.
+    pub fn get_logs_since(since: i64) -> Result<Vec<BatterySnapshot>> {
+        Self::get_logs_between(since, i64::MAX)
+    }
+
+    /// Returns the snapshots recorded between `since` and `until`
+    /// (inclusive), oldest first.
+    pub fn get_logs_between(since: i64, until: i64) -> Result<Vec<BatterySnapshot>> {
+        History::open()?.logs_between(since, until)
+    }
+}
+
+/// Stores the sessions in this machine's history since the latest stored
+/// one started, replacing that one, which may have grown since.
+fn sync_sessions(conn: &Connection) -> Result<()> {
+    let host = local_hostname();
+    let since: Option<i64> = conn.query_row(
+        "SELECT MAX(started_at) FROM sessions WHERE host = ?1",
+        [&host],
+        |row| row.get(0),
+    )?;
+    let since = since.unwrap_or(0);
+    let logs = conn
+        .prepare_cached(&format!(
+            "SELECT {} FROM local_logs WHERE timestamp >= ?1 ORDER BY timestamp ASC",
+            SNAPSHOT_COLUMNS
+        ))?
+        .query_map([since], BatterySnapshot::from_row)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let tx = conn.unchecked_transaction()?;
+    tx.execute(
+        "DELETE FROM sessions WHERE host = ?1 AND started_at >= ?2",
+        (&host, since),
+    )?;
+    {
+        let mut insert = tx.prepare_cached(
+            "INSERT INTO sessions (host, kind, started_at, ended_at, start_percent, end_percent,
+                start_energy, end_energy, avg_watts, adapter_energy, adapter)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+        )?;
+        for session in sessions::detect(&logs) {
+            insert.execute((
+                &host,
+                session.kind.label(),
+                session.start,
+                session.end,
+                session.start_percent,
+                session.end_percent,
+                session.start_energy,
+                session.end_energy,
+                session.average_watts(),
+                session.adapter_energy,
+                &session.adapter,
+            ))?;
+        }
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+/// This machine's stored sessions that ended at or after `since`, oldest
+/// first.
+fn stored_sessions(conn: &Connection, since: i64) -> Result<Vec<Session>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT kind, started_at, ended_at, start_percent, end_percent, start_energy,
+            end_energy, adapter_energy, adapter
+        FROM sessions WHERE host = ?1 AND ended_at >= ?2 ORDER BY started_at ASC",
+    )?;
+    let sessions = stmt
+        .query_map((local_hostname(), since), |row| {
+            // Kinds written by a newer version are skipped.
+            let kind: String = row.get(0)?;
+            let Some(kind) = SessionKind::from_label(&kind) else {
+                return Ok(None);
+            };
+            Ok(Some(Session {
+                kind,
+                start: row.get(1)?,
+                end: row.get(2)?,
+                start_percent: row.get(3)?,
+                end_percent: row.get(4)?,
+                start_energy: row.get(5)?,
+                end_energy: row.get(6)?,
+                adapter_energy: row.get(7)?,
+                adapter: row.get(8)?,
+            }))
+        })?
+        .filter_map(|session| session.transpose())
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(sessions)
+}
+
+/// A history database connection kept open for repeated reads, as the TUI
+/// does on every refresh. Statements are prepared once per connection and
+/// reused from its cache.
+#[derive(Debug)]
+pub struct History {
+    conn: Connection,
+}
+
+impl History {
+    pub fn open() -> Result<Self> {
+        Ok(Self {
+            conn: BatteryDaemon::open_database()?,
+        })
+    }
+
+    /// Grows whenever samples, aggregates or annotations are added, so
+    /// readers can skip reloading history that hasn't changed.
+    pub fn version(&self) -> Result<i64> {
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT (SELECT COALESCE(MAX(id), 0) FROM battery_logs)
+                  + (SELECT COALESCE(MAX(id), 0) FROM battery_aggregates)
+                  + (SELECT COALESCE(MAX(id), 0) FROM annotations)",
+        )?;
+        Ok(stmt.query_row([], |row| row.get(0))?)
+    }
+
+    /// Snapshots recorded between `since` and `until` (inclusive), oldest
+    /// first.
+    pub fn logs_between(&self, since: i64, until: i64) -> Result<Vec<BatterySnapshot>> {
+        let mut stmt = self.conn.prepare_cached(&format!(
+            "SELECT {} FROM local_logs WHERE timestamp BETWEEN ?1 AND ?2 ORDER BY timestamp ASC",
+            SNAPSHOT_COLUMNS
+        ))?;
+
+        let logs = stmt
+            .query_map([since, until], BatterySnapshot::from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(logs)
+    }
+
+    /// The most recent snapshots recorded by `host`, newest first.
+    pub fn host_logs(&self, host: &str, limit: Option<usize>) -> Result<Vec<BatterySnapshot>> {
+        let mut stmt = self.conn.prepare_cached(&format!(
+            "SELECT {} FROM history
+            WHERE COALESCE(host, ?1) = ?2
+            ORDER BY timestamp DESC LIMIT ?3",
+            SNAPSHOT_COLUMNS
+        ))?;
+
+        let limit = limit.map_or(-1, |limit| limit as i64);
+        let logs = stmt
+            .query_map((local_hostname(), host, limit), BatterySnapshot::from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
 
         Ok(logs)
     }
+
+    /// Every host in the database with its latest snapshot.
+    pub fn fleet(&self) -> Result<Vec<HostSummary>> {
+        let mut stmt = self.conn.prepare_cached(&format!(
+            "SELECT {}, name FROM (
+                SELECT *, COALESCE(host, ?1) AS name,
+                       ROW_NUMBER() OVER (PARTITION BY COALESCE(host, ?1) ORDER BY timestamp DESC) AS rank
+                FROM history
+            )
+            WHERE rank = 1
+            ORDER BY name",
+            SNAPSHOT_COLUMNS
+        ))?;
+
+        let hosts = stmt
+            .query_map([local_hostname()], |row| {
+                Ok(HostSummary {
+                    latest: BatterySnapshot::from_row(row)?,
+                    host: row.get("name")?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(hosts)
+    }
+
+    /// This machine's sessions that ended at or after `since`, oldest
+    /// first, after storing any detected since the last call.
+    pub fn sessions(&self, since: i64) -> Result<Vec<Session>> {
+        sync_sessions(&self.conn)?;
+        stored_sessions(&self.conn, since)
+    }
+
+    /// Full cycles estimated from every charge session the daemon has
+    /// stored, for batteries that don't count them; `None` before the first
+    /// charge.
+    pub fn estimated_cycles(&self) -> Result<Option<f64>> {
+        let cycles = sessions::equivalent_cycles(&stored_sessions(&self.conn, 0)?);
+        Ok((cycles > 0.0).then_some(cycles))
+    }
+
+    /// Time spent in each charge band. Each sample accounts for the time
+    /// until the next one; gaps longer than `MAX_GAP` (daemon not running,
+    /// machine off) are left out.
+    pub fn charge_bands(&self) -> Result<ChargeBands> {
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT
+                CASE
+                    WHEN percent >= 99.5 THEN 0
+                    WHEN percent >= 80 THEN 1
+                    WHEN percent >= 20 THEN 2
+                    ELSE 3
+                END AS band,
+                SUM(duration)
+            FROM (
+                SELECT percent, LEAD(timestamp) OVER (ORDER BY timestamp) - timestamp AS duration
+                FROM local_logs
+            )
+            WHERE duration > 0 AND duration <= ?1
+            GROUP BY band",
+        )?;
+
+        let mut bands = ChargeBands::default();
+        let rows = stmt.query_map([MAX_GAP.as_secs() as i64], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?))
+        })?;
+        for row in rows {
+            let (band, seconds) = row?;
+            match band {
+                0 => bands.full = seconds,
+                1 => bands.high = seconds,
+                2 => bands.mid = seconds,
+                _ => bands.low = seconds,
+            }
+        }
+
+        Ok(bands)
+    }
+
+    /// This machine's capacity history, oldest first.
+    pub fn health(&self) -> Result<Vec<HealthSample>> {
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT timestamp, energy_full, energy_full_design, cycle_count FROM health_logs
+            WHERE host = ?1 ORDER BY timestamp",
+        )?;
+        let samples = stmt
+            .query_map([local_hostname()], |row| {
+                Ok(HealthSample {
+                    timestamp: row.get(0)?,
+                    energy_full: row.get(1)?,
+                    energy_full_design: row.get(2)?,
+                    cycle_count: row.get(3)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(samples)
+    }
+
+    /// This machine's annotations between `since` and `until`, oldest first.
+    pub fn annotations_between(&self, since: i64, until: i64) -> Result<Vec<Annotation>> {
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT timestamp, text FROM annotations
+            WHERE host = ?1 AND timestamp BETWEEN ?2 AND ?3
+            ORDER BY timestamp",
+        )?;
+        let annotations = stmt
+            .query_map((local_hostname(), since, until), |row| {
+                Ok(Annotation {
+                    timestamp: row.get(0)?,
+                    text: row.get(1)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(annotations)
+    }
 }
@@ -0,0 +1,254 @@
+use crate::errors::Result;
+use chrono::Utc;
+use rusqlite::Connection;
+
+/// Samples stamped further than this into the future are considered bogus.
+const MAX_CLOCK_SKEW: i64 = 24 * 60 * 60;
+
+/// Problems found in the history database by `check`.
+#[derive(Debug, Default)]
+pub struct IntegrityReport {
+    /// Messages from `PRAGMA integrity_check`, empty when SQLite reports "ok"
+    pub corruption: Vec<String>,
+    /// Rows with a percentage outside 0..=100 or an impossible timestamp
+    pub out_of_range: usize,
+    /// Rows recorded earlier than a sample that was inserted before them,
+    /// typically after the wall clock was set back
+    pub non_monotonic: usize,
+    /// Rows stamped with the same time as an earlier row from the same
+    /// host, but recording a different reading
+    pub repeated_timestamps: usize,
+    /// Rows identical to an earlier row from the same host
+    pub duplicates: usize,
+}
+
+impl IntegrityReport {
+    pub fn is_clean(&self) -> bool {
+        self.corruption.is_empty()
+            && self.out_of_range == 0
+            && self.non_monotonic == 0
+            && self.repeated_timestamps == 0
+            && self.duplicates == 0
+    }
+}
+
+const OUT_OF_RANGE: &str = "SELECT id FROM battery_logs
+    WHERE percent IS NULL OR percent < 0 OR percent > 100
+       OR timestamp <= 0 OR timestamp > ?1";
+
+const NON_MONOTONIC: &str = "SELECT id FROM (
+        SELECT id, timestamp,
+               MAX(timestamp) OVER (
+                   PARTITION BY host ORDER BY id
+                   ROWS BETWEEN UNBOUNDED PRECEDING AND 1 PRECEDING
+               ) AS latest
+        FROM battery_logs
+    )
+    WHERE timestamp < latest";
+
+const REPEATED_TIMESTAMPS: &str = "SELECT id FROM battery_logs
+    WHERE id NOT IN (
+        SELECT MIN(id) FROM battery_logs GROUP BY host, timestamp
+    )
+    AND id IN (
+        SELECT MIN(id) FROM battery_logs GROUP BY host, timestamp, percent, status
+    )";
+
+const DUPLICATES: &str = "SELECT id FROM battery_logs
+    WHERE id NOT IN (
+        SELECT MIN(id) FROM battery_logs GROUP BY host, timestamp, percent, status
+    )";
+
+pub fn check(conn: &Connection) -> Result<IntegrityReport> {
+    let corruption = conn
+        .prepare("PRAGMA integrity_check")?
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?
+        .into_iter()
+        .filter(|message| message != "ok")
+        .collect();
+
+    let max_timestamp = Utc::now().timestamp() + MAX_CLOCK_SKEW;
+    Ok(IntegrityReport {
+        corruption,
+        out_of_range: count(conn, OUT_OF_RANGE, [max_timestamp])?,
+        non_monotonic: count(conn, NON_MONOTONIC, [])?,
+        repeated_timestamps: count(conn, REPEATED_TIMESTAMPS, [])?,
+        duplicates: count(conn, DUPLICATES, [])?,
+    })
+}
+
+/// Deletes impossible and duplicated rows, shifts rows recorded after the
+/// clock was set back so intervals are positive again, and rebuilds the
+/// database file. Returns the number of rows removed and corrected.
+pub fn repair(conn: &Connection) -> Result<(usize, usize)> {
+    let max_timestamp = Utc::now().timestamp() + MAX_CLOCK_SKEW;
+    let mut removed = 0;
+
+    removed += conn.execute(
+        &format!("DELETE FROM battery_logs WHERE id IN ({})", OUT_OF_RANGE),
+        [max_timestamp],
+    )?;
+    removed += conn.execute(
+        &format!("DELETE FROM battery_logs WHERE id IN ({})", DUPLICATES),
+        [],
+    )?;
+    let corrected = correct_clock_jumps(conn)?;
+
+    conn.execute_batch("REINDEX; VACUUM;")?;
+    Ok((removed, corrected))
+}
+
+/// Rewrites timestamps that went backwards, in insertion order per host.
+/// The interval across a backward jump is unknown, so the host's typical
+/// sampling interval is assumed; later rows keep their original spacing
+/// until the wall clock catches up with the corrected timeline.
+fn correct_clock_jumps(conn: &Connection) -> Result<usize> {
+    let rows = conn
+        .prepare("SELECT id, timestamp, host FROM battery_logs ORDER BY host, id")?
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, Option<String>>(2)?,
+            ))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let mut updates = Vec::new();
+    for host_rows in rows.chunk_by(|a, b| a.2 == b.2) {
+        let interval = typical_interval(host_rows);
+        let mut previous: Option<(i64, i64)> = None;
+
+        for &(id, original, _) in host_rows {
+            let corrected = match previous {
+                Some((prev_original, prev_corrected)) if original <= prev_corrected => {
+                    let elapsed = original - prev_original;
+                    prev_corrected + if elapsed > 0 { elapsed } else { interval }
+                }
+                _ => original,
+            };
+            if corrected != original {
+                updates.push((id, corrected));
+            }
+            previous = Some((original, corrected));
+        }
+    }
+
+    let mut stmt = conn.prepare("UPDATE battery_logs SET timestamp = ?1 WHERE id = ?2")?;
+    for (id, timestamp) in &updates {
+        stmt.execute((timestamp, id))?;
+    }
+    Ok(updates.len())
+}
+
+/// Median positive interval between consecutive rows, at least one second.
+fn typical_interval(rows: &[(i64, i64, Option<String>)]) -> i64 {
+    let mut intervals: Vec<i64> = rows
+        .windows(2)
+        .map(|pair| pair[1].1 - pair[0].1)
+        .filter(|interval| *interval > 0)
+        .collect();
+    intervals.sort_unstable();
+    intervals.get(intervals.len() / 2).copied().unwrap_or(1)
+}
+
+fn count<P: rusqlite::Params>(conn: &Connection, query: &str, params: P) -> Result<usize> {
+    let total = conn.query_row(
+        &format!("SELECT COUNT(*) FROM ({})", query),
+        params,
+        |row| row.get::<_, i64>(0),
+    )?;
+    Ok(total as usize)
+}
+
+/// Rows older than the retention cutoff, per table.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PruneReport {
+    pub logs: usize,
+    pub events: usize,
+    pub runs: usize,
+}
+
+impl PruneReport {
+    pub fn total(&self) -> usize {
+        self.logs + self.events + self.runs
+    }
+}
+
+const PRUNE_TABLES: [(&str, &str); 4] = [
+    ("battery_logs", "timestamp < ?1"),
+    ("battery_aggregates", "timestamp < ?1"),
+    ("daemon_events", "timestamp < ?1"),
+    ("daemon_runs", "last_seen < ?1"),
+];
+
+/// Deletes history recorded before `cutoff` (a Unix timestamp). With
+/// `dry_run` nothing is deleted and the report counts what would be.
+pub fn prune(conn: &Connection, cutoff: i64, dry_run: bool) -> Result<PruneReport> {
+    let mut counts = [0; PRUNE_TABLES.len()];
+    for ((table, condition), count) in PRUNE_TABLES.iter().zip(&mut counts) {
+        let query = format!("SELECT id FROM {} WHERE {}", table, condition);
+        *count = if dry_run {
+            self::count(conn, &query, [cutoff])?
+        } else {
+            conn.execute(
+                &format!("DELETE FROM {} WHERE {}", table, condition),
+                [cutoff],
+            )?
+        };
+    }
+
+    let [logs, aggregates, events, runs] = counts;
+    Ok(PruneReport {
+        logs: logs + aggregates,
+        events,
+        runs,
+    })
+}
+
+/// Averages raw samples and finer aggregates into one row per host and
+/// bucket. The averaged percent and energy are weighted by sample count and
+/// the status is that of the latest sample in the bucket.
+const DOWNSAMPLE: &str = "INSERT INTO battery_aggregates
+        (bucket_secs, samples, percent, timestamp, status,
+         energy, adapter_watts, temperature, health, host, idle, power_watts, adapter, voltage)
+    SELECT ?2, SUM(samples), SUM(percent * samples) / SUM(samples), bucket, MAX(last_status),
+           SUM(energy * samples) / SUM(CASE WHEN energy IS NULL THEN 0 ELSE samples END),
+           AVG(adapter_watts), AVG(temperature), AVG(health), host, MIN(idle),
+           AVG(power_watts), MAX(adapter), AVG(voltage)
+    FROM (
+        SELECT *, timestamp / ?2 * ?2 AS bucket,
+               FIRST_VALUE(status) OVER (
+                   PARTITION BY host, timestamp / ?2 ORDER BY timestamp DESC
+               ) AS last_status
+        FROM (
+            SELECT 0 AS bucket_secs, 1 AS samples, percent, timestamp, status,
+                   energy, adapter_watts, temperature, health, host, idle, power_watts, adapter, voltage
+            FROM battery_logs
+            UNION ALL
+            SELECT bucket_secs, samples, percent, timestamp, status,
+                   energy, adapter_watts, temperature, health, host, idle, power_watts, adapter, voltage
+            FROM battery_aggregates
+        )
+        WHERE bucket_secs < ?2 AND timestamp < ?1
+    )
+    GROUP BY host, bucket";
+
+/// Replaces samples recorded before `cutoff` (a Unix timestamp) with one
+/// row per `bucket_secs`. Returns the number of rows replaced.
+pub fn downsample(conn: &Connection, cutoff: i64, bucket_secs: i64) -> Result<usize> {
+    // Only whole buckets, so a bucket is never split between raw and
+    // downsampled rows.
+    let cutoff = cutoff / bucket_secs * bucket_secs;
+
+    let tx = conn.unchecked_transaction()?;
+    tx.execute(DOWNSAMPLE, (cutoff, bucket_secs))?;
+    let mut replaced = tx.execute("DELETE FROM battery_logs WHERE timestamp < ?1", [cutoff])?;
+    replaced += tx.execute(
+        "DELETE FROM battery_aggregates WHERE bucket_secs < ?2 AND timestamp < ?1",
+        (cutoff, bucket_secs),
+    )?;
+    tx.commit()?;
+    Ok(replaced)
+}
@@ -0,0 +1,337 @@
+//! `--demo`: a simulated battery and a few weeks of made-up history, so the
+//! whole UI can be tried on machines without a battery or without history.
+//!
+//! The battery is a fake sysfs tree read through `sysfs::set_root`, kept
+//! moving by a background thread, and the history goes into a throwaway
+//! database. Both live in a temporary directory removed on exit.
+
+use crate::daemon::{BatteryDaemon, BatterySnapshot, HealthSample};
+use crate::errors::Result;
+use crate::sysfs;
+use chrono::{Datelike, Local, TimeZone, Timelike, Weekday};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+/// Days of history generated.
+const DAYS: i64 = 21;
+/// Seconds between generated samples.
+const SAMPLE_SECS: i64 = 120;
+/// How often the simulated battery moves on.
+const TICK: Duration = Duration::from_secs(2);
+const DESIGN_WH: f64 = 57.0;
+/// Full-charge capacity at the start and end of the generated history.
+const FULL_WH: (f64, f64) = (52.4, 51.9);
+/// Power the rest of the laptop draws from the adapter while plugged in.
+const SYSTEM_WATTS: f64 = 8.0;
+/// The big charger at home and the small one at the office, with the
+/// power each puts into the battery below 80%.
+const HOME_CHARGER: (&str, f64, f64) = ("amptop Demo 65W Adapter (65 W)", 24.0, 28.0);
+const OFFICE_CHARGER: (&str, f64, f64) = ("USB_PD 30 W", 14.0, 17.0);
+/// Charge at which the simulated user plugs in.
+const PLUG_IN_PERCENT: f64 = 8.0;
+
+/// The demo's temporary directory; dropping it removes everything.
+pub struct Demo {
+    dir: PathBuf,
+}
+
+impl Demo {
+    /// Creates the fake battery and history and points amptop at them.
+    pub fn start() -> Result<Demo> {
+        use std::os::unix::fs::DirBuilderExt;
+
+        // A fresh directory only we can read, never one that was already
+        // there: the name is predictable and the temporary directory shared.
+        let dir = std::env::temp_dir().join(format!("amptop-demo-{}", std::process::id()));
+        fs::DirBuilder::new().mode(0o700).create(&dir)?;
+        let demo = Demo { dir };
+
+        let mut sim = Simulation::new(Local::now().timestamp() - DAYS * 24 * 60 * 60);
+        let (logs, health) = sim.history();
+
+        BatteryDaemon::set_database_path(demo.dir.join("battery.db"));
+        BatteryDaemon::import_snapshots(&logs)?;
+        BatteryDaemon::import_health(&health)?;
+
+        let supply = demo.dir.join("sys/class/power_supply");
+        fs::create_dir_all(supply.join("BAT0"))?;
+        fs::create_dir_all(supply.join("AC"))?;
+        sim.write(&supply)?;
+        sysfs::set_root(demo.dir.join("sys"));
+
+        thread::spawn(move || {
+            loop {
+                thread::sleep(TICK);
+                sim.step(TICK.as_secs() as i64);
+                if sim.write(&supply).is_err() {
+                    // The directory is gone, amptop is exiting.
+                    break;
+                }
+            }
+        });
+        Ok(demo)
+    }
+}
+
+impl Drop for Demo {
+    fn drop(&mut self) {
+        fs::remove_dir_all(&self.dir).ok();
+    }
+}
+
+/// xorshift64*, enough to make the history look lived-in.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> f64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        (self.0.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn between(&mut self, low: f64, high: f64) -> f64 {
+        low + self.next() * (high - low)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Charging,
+    Discharging,
+    Full,
+}
+
+impl Status {
+    fn name(self) -> &'static str {
+        match self {
+            Status::Charging => "charging",
+            Status::Discharging => "discharging",
+            Status::Full => "full",
+        }
+    }
+
+    /// As written to sysfs.
+    fn sysfs(self) -> &'static str {
+        match self {
+            Status::Charging => "Charging",
+            Status::Discharging => "Discharging",
+            Status::Full => "Full",
+        }
+    }
+}
+
+/// A laptop used during the day, on the charger at night and over lunch
+/// on weekdays, suspended over some nights.
+struct Simulation {
+    rng: Rng,
+    timestamp: i64,
+    energy: f64,
+    energy_full: f64,
+    status: Status,
+    /// Battery power, in W
+    watts: f64,
+    idle: bool,
+    cycles: f64,
+}
+
+impl Simulation {
+    fn new(start: i64) -> Self {
+        Self {
+            rng: Rng(0x9e37_79b9_7f4a_7c15),
+            timestamp: start,
+            energy: FULL_WH.0 * 0.9,
+            energy_full: FULL_WH.0,
+            status: Status::Discharging,
+            watts: 0.0,
+            idle: false,
+            cycles: 287.0,
+        }
+    }
+
+    fn percent(&self) -> f64 {
+        (self.energy / self.energy_full * 100.0).clamp(0.0, 100.0)
+    }
+
+    /// Rises with the charge, and sags under load or rises while charging.
+    fn volts(&self) -> f64 {
+        let resting = 11.4 + self.percent() / 100.0 * 1.6;
+        match self.status {
+            Status::Discharging => resting - self.watts * 0.012,
+            Status::Charging => resting + self.watts * 0.004,
+            _ => resting,
+        }
+    }
+
+    /// Runs the simulation up to now, returning one sample per
+    /// `SAMPLE_SECS` and one capacity reading per day.
+    fn history(&mut self) -> (Vec<BatterySnapshot>, Vec<HealthSample>) {
+        let start = self.timestamp;
+        let end = Local::now().timestamp();
+        let mut logs = Vec::new();
+        let mut health = Vec::new();
+
+        while self.timestamp < end {
+            if self.suspended() {
+                // Suspended overnight: nothing recorded, a little drained.
+                self.energy -= 0.4 * SAMPLE_SECS as f64 / 3600.0;
+                self.timestamp += SAMPLE_SECS;
+                continue;
+            }
+            self.step(SAMPLE_SECS);
+            logs.push(self.snapshot());
+
+            if (self.timestamp - start) % (24 * 60 * 60) < SAMPLE_SECS {
+                let progress = (self.timestamp - start) as f64 / (end - start) as f64;
+                self.energy_full = FULL_WH.0 + (FULL_WH.1 - FULL_WH.0) * progress;
+                health.push(HealthSample {
+                    timestamp: self.timestamp,
+                    energy_full: self.energy_full as f32,
+                    energy_full_design: DESIGN_WH as f32,
+                    cycle_count: Some(self.cycles as u32),
+                });
+            }
+        }
+        (logs, health)
+    }
+
+    /// Friday and Saturday nights spent suspended instead of on the charger.
+    fn suspended(&self) -> bool {
+        let time = self.local_time();
+        let night = time.hour() >= 23 || time.hour() < 9;
+        let weekday = if time.hour() < 9 {
+            time.weekday().pred()
+        } else {
+            time.weekday()
+        };
+        night && matches!(weekday, Weekday::Fri | Weekday::Sat)
+    }
+
+    fn local_time(&self) -> chrono::DateTime<Local> {
+        Local
+            .timestamp_opt(self.timestamp, 0)
+            .single()
+            .unwrap_or_else(Local::now)
+    }
+
+    fn plugged_in(&self) -> bool {
+        let time = self.local_time();
+        let hour = time.hour();
+        let weekend = matches!(time.weekday(), Weekday::Sat | Weekday::Sun);
+        !(8..23).contains(&hour) || (!weekend && (13..15).contains(&hour))
+    }
+
+    /// Weekday lunch is spent on the office charger.
+    fn charger(&self) -> (&'static str, f64, f64) {
+        let time = self.local_time();
+        let weekend = matches!(time.weekday(), Weekday::Sat | Weekday::Sun);
+        if !weekend && (13..15).contains(&time.hour()) {
+            OFFICE_CHARGER
+        } else {
+            HOME_CHARGER
+        }
+    }
+
+    fn step(&mut self, secs: i64) {
+        self.timestamp += secs;
+        let hours = secs as f64 / 3600.0;
+
+        if self.plugged_in()
+            || (self.status != Status::Discharging && self.percent() < 95.0)
+            || self.percent() <= PLUG_IN_PERCENT
+        {
+            // Fast charge to 80%, then taper off.
+            let (_, low, high) = self.charger();
+            self.watts = if self.percent() < 80.0 {
+                self.rng.between(low, high)
+            } else {
+                self.rng.between(6.0, 10.0)
+            };
+            self.energy += self.watts * hours;
+            self.idle = false;
+            if self.energy >= self.energy_full {
+                self.energy = self.energy_full;
+                self.status = Status::Full;
+                self.watts = 0.0;
+            } else {
+                self.status = Status::Charging;
+            }
+            if !self.plugged_in() && self.status == Status::Full {
+                self.status = Status::Discharging;
+            }
+        } else {
+            if self.status != Status::Discharging || self.rng.next() < 0.02 {
+                self.idle = self.rng.next() < 0.25;
+            }
+            self.watts = if self.idle {
+                self.rng.between(2.5, 3.5)
+            } else {
+                self.rng.between(7.0, 13.0)
+            };
+            self.energy -= self.watts * hours;
+            self.cycles += self.watts * hours / self.energy_full;
+            self.status = Status::Discharging;
+        }
+        self.energy = self.energy.max(0.0);
+    }
+
+    fn snapshot(&self) -> BatterySnapshot {
+        BatterySnapshot {
+            percent: self.percent() as f32,
+            timestamp: self.timestamp,
+            status: self.status.name().to_string(),
+            energy: Some(self.energy as f32),
+            adapter_watts: (self.status != Status::Discharging)
+                .then_some((self.watts + SYSTEM_WATTS) as f32),
+            temperature: Some((30.0 + self.watts / 4.0) as f32),
+            health: Some((self.energy_full / DESIGN_WH * 100.0) as f32),
+            idle: (self.status == Status::Discharging).then_some(self.idle),
+            power_watts: Some(self.watts as f32),
+            adapter: (self.status != Status::Discharging).then(|| self.charger().0.to_string()),
+            voltage: Some(self.volts() as f32),
+        }
+    }
+
+    /// Writes the current state as a sysfs `power_supply` tree.
+    fn write(&self, supply: &Path) -> std::io::Result<()> {
+        let micro = |value: f64| ((value * 1e6) as i64).to_string();
+        let battery = supply.join("BAT0");
+        for (name, value) in [
+            ("type", "Battery".to_string()),
+            ("status", self.status.sysfs().to_string()),
+            ("capacity", format!("{:.0}", self.percent())),
+            ("energy_now", micro(self.energy)),
+            ("energy_full", micro(self.energy_full)),
+            ("energy_full_design", micro(DESIGN_WH)),
+            ("power_now", micro(self.watts)),
+            ("voltage_now", micro(self.volts())),
+            ("temp", format!("{:.0}", (30.0 + self.watts / 4.0) * 10.0)),
+            ("cycle_count", format!("{:.0}", self.cycles)),
+            ("technology", "Li-ion".to_string()),
+            ("manufacturer", "amptop".to_string()),
+            ("model_name", "Demo Battery".to_string()),
+            ("serial_number", "DEMO-0001".to_string()),
+            ("health", "Good".to_string()),
+            ("manufacture_year", "2023".to_string()),
+            ("manufacture_month", "3".to_string()),
+            ("manufacture_day", "14".to_string()),
+        ] {
+            fs::write(battery.join(name), value)?;
+        }
+
+        let adapter = supply.join("AC");
+        fs::write(adapter.join("type"), "Mains")?;
+        fs::write(
+            adapter.join("online"),
+            if self.status == Status::Discharging {
+                "0"
+            } else {
+                "1"
+            },
+        )?;
+        fs::write(adapter.join("power_now"), micro(self.watts + SYSTEM_WATTS))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,226 @@
+//! Power information for machines without a system battery: a UPS, the
+//! batteries of connected peripherals and the CPU package draw reported by
+//! Intel RAPL.
+
+use crate::sysfs::{self, read_number, read_string};
+use crate::upower;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+/// A battery-powered device such as a wireless mouse or keyboard.
+#[derive(Debug, Clone)]
+pub struct Peripheral {
+    /// Power supply directory or UPower native path, to spot the same
+    /// device reported twice
+    pub id: String,
+    pub name: String,
+    /// UPower's device kind, e.g. `mouse` or `headphones`
+    pub kind: Option<String>,
+    pub percent: Option<f64>,
+    pub status: Option<String>,
+}
+
+/// State of an uninterruptible power supply.
+#[derive(Debug, Clone)]
+pub struct Ups {
+    pub name: String,
+    pub percent: Option<f64>,
+    pub load_percent: Option<f64>,
+    pub status: Option<String>,
+    pub runtime: Option<Duration>,
+}
+
+impl Ups {
+    /// Queries a UPS managed by Network UPS Tools (`upsc <name>`).
+    fn from_nut(name: &str) -> Option<Ups> {
+        let output = Command::new("upsc").arg(name).output().ok()?;
+        if !output.status.success() {
+            return None;
+        }
+
+        let text = String::from_utf8_lossy(&output.stdout);
+        let value = |key: &str| {
+            text.lines()
+                .filter_map(|line| line.split_once(": "))
+                .find(|(k, _)| *k == key)
+                .map(|(_, v)| v.trim().to_string())
+        };
+        let number = |key: &str| value(key).and_then(|v| v.parse::<f64>().ok());
+
+        Some(Ups {
+            name: name.to_string(),
+            percent: number("battery.charge"),
+            load_percent: number("ups.load"),
+            status: value("ups.status").map(|status| nut_status(&status)),
+            runtime: number("battery.runtime").map(|secs| Duration::from_secs(secs as u64)),
+        })
+    }
+
+    /// Reads a UPS exposed by the kernel as a `UPS` power supply.
+    fn from_sysfs(path: &Path) -> Option<Ups> {
+        if read_string(&path.join("type")).as_deref() != Some("UPS") {
+            return None;
+        }
+
+        Some(Ups {
+            name: supply_name(path),
+            percent: read_number(&path.join("capacity")),
+            load_percent: None,
+            status: read_string(&path.join("status")),
+            runtime: read_number(&path.join("time_to_empty_now"))
+                .map(|secs| Duration::from_secs(secs as u64)),
+        })
+    }
+}
+
+fn nut_status(status: &str) -> String {
+    status
+        .split_whitespace()
+        .map(|flag| match flag {
+            "OL" => "online",
+            "OB" => "on battery",
+            "LB" => "low battery",
+            "CHRG" => "charging",
+            "DISCHRG" => "discharging",
+            "RB" => "replace battery",
+            other => other,
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Turns the cumulative RAPL energy counters into an average power draw.
+#[derive(Debug, Default)]
+struct RaplMeter {
+    last: Option<(Instant, f64)>,
+    watts: Option<f64>,
+}
+
+impl RaplMeter {
+    fn sample(&mut self) {
+        let Some(energy) = rapl_energy() else {
+            self.watts = None;
+            return;
+        };
+        let now = Instant::now();
+
+        if let Some((then, previous)) = self.last {
+            let seconds = now.duration_since(then).as_secs_f64();
+            // The counter wraps around; skip that interval rather than report
+            // a negative draw.
+            if seconds > 0.0 && energy >= previous {
+                self.watts = Some((energy - previous) / seconds);
+            }
+        }
+        self.last = Some((now, energy));
+    }
+}
+
+/// Total energy counted by the top-level RAPL domains (one per CPU
+/// package), in joules.
+fn rapl_energy() -> Option<f64> {
+    let domains: Vec<PathBuf> = fs::read_dir(sysfs::root().join("class/powercap"))
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+            name.starts_with("intel-rapl:") && name.matches(':').count() == 1
+        })
+        .collect();
+
+    let microjoules = domains
+        .iter()
+        .filter_map(|path| read_number(&path.join("energy_uj")))
+        .reduce(|total, energy| total + energy)?;
+    Some(microjoules / 1e6)
+}
+
+/// Every power supply directory, sorted by name.
+fn supplies() -> Vec<PathBuf> {
+    let mut supplies: Vec<PathBuf> = fs::read_dir(sysfs::power_supply_dir())
+        .map(|entries| entries.flatten().map(|entry| entry.path()).collect())
+        .unwrap_or_default();
+    supplies.sort();
+    supplies
+}
+
+fn supply_name(path: &Path) -> String {
+    read_string(&path.join("model_name")).unwrap_or_else(|| {
+        path.file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    })
+}
+
+/// Everything shown on the AC-power dashboard.
+#[derive(Debug, Default)]
+pub struct DesktopPower {
+    nut_ups: Option<String>,
+    /// Also list the peripherals UPower knows about
+    upower: bool,
+    rapl: RaplMeter,
+    pub ups: Option<Ups>,
+    pub peripherals: Vec<Peripheral>,
+}
+
+impl DesktopPower {
+    /// `nut_ups` names a UPS to query through Network UPS Tools, e.g.
+    /// `myups@localhost`.
+    pub fn new(nut_ups: Option<String>, upower: bool) -> Self {
+        Self {
+            nut_ups,
+            upower,
+            ..Self::default()
+        }
+    }
+
+    pub fn refresh(&mut self) {
+        self.rapl.sample();
+        self.refresh_peripherals();
+        self.ups = match self.nut_ups {
+            Some(ref name) => Ups::from_nut(name),
+            None => supplies().iter().find_map(|path| Ups::from_sysfs(path)),
+        };
+    }
+
+    /// Peripherals from sysfs, plus those only UPower sees when enabled.
+    pub fn refresh_peripherals(&mut self) {
+        self.peripherals = supplies()
+            .iter()
+            .filter(|path| {
+                read_string(&path.join("type")).as_deref() == Some("Battery")
+                    && read_string(&path.join("scope")).as_deref() == Some("Device")
+            })
+            .map(|path| Peripheral {
+                id: path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+                name: supply_name(path),
+                kind: None,
+                percent: read_number(&path.join("capacity")),
+                status: read_string(&path.join("status")),
+            })
+            .collect();
+
+        if self.upower {
+            for device in upower::peripherals() {
+                if !self.peripherals.iter().any(|known| known.id == device.id) {
+                    self.peripherals.push(device);
+                }
+            }
+        }
+    }
+
+    /// CPU package power draw, in watts, once two samples were taken.
+    pub fn cpu_watts(&self) -> Option<f64> {
+        self.rapl.watts
+    }
+
+    pub fn has_rapl(&self) -> bool {
+        self.rapl.last.is_some()
+    }
+}
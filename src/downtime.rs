@@ -0,0 +1,152 @@
+//! Stretches during which the machine was powered off or suspended, and how
+//! much charge they cost.
+
+use crate::daemon::{BatterySnapshot, BoundaryEvent, MAX_GAP};
+use crate::sysfs::{self, read_string};
+
+/// Suspend drain above this, in percent per hour, points at s2idle (or a
+/// device keeping the machine awake) rather than deep sleep.
+pub const SHALLOW_SLEEP_DRAIN: f64 = 1.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DowntimeKind {
+    PoweredOff,
+    Suspended,
+}
+
+impl DowntimeKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            DowntimeKind::PoweredOff => "powered off",
+            DowntimeKind::Suspended => "suspended",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Downtime {
+    pub kind: DowntimeKind,
+    pub start: i64,
+    pub end: i64,
+    pub start_percent: f32,
+    pub end_percent: f32,
+}
+
+impl Downtime {
+    pub fn duration(&self) -> i64 {
+        self.end - self.start
+    }
+
+    /// Charge lost over the downtime, negative if it went up.
+    pub fn lost(&self) -> f32 {
+        self.start_percent - self.end_percent
+    }
+
+    /// Charge lost per hour, in percent.
+    pub fn drain_rate(&self) -> f64 {
+        f64::from(self.lost()) / (self.duration() as f64 / 3600.0)
+    }
+}
+
+/// Average suspend drain over the suspends starting in one week.
+#[derive(Debug, Clone, Copy)]
+pub struct WeeklyDrain {
+    /// Start of the week, as a Unix timestamp
+    pub week: i64,
+    pub suspends: usize,
+    pub hours: f64,
+    /// Percent per hour asleep
+    pub rate: f64,
+}
+
+/// Groups suspends by week, weighting each by how long it lasted. Suspends
+/// during which the charge went up (on AC) are left out.
+pub fn weekly_suspend_drain(downtimes: &[Downtime]) -> Vec<WeeklyDrain> {
+    const WEEK: i64 = 7 * 24 * 60 * 60;
+    // The Unix epoch fell on a Thursday; shift so weeks start on Monday.
+    const MONDAY: i64 = 4 * 24 * 60 * 60;
+
+    let mut weeks: Vec<WeeklyDrain> = Vec::new();
+    for downtime in downtimes
+        .iter()
+        .filter(|d| d.kind == DowntimeKind::Suspended && d.lost() >= 0.0)
+    {
+        let week = (downtime.start - MONDAY).div_euclid(WEEK) * WEEK + MONDAY;
+        let hours = downtime.duration() as f64 / 3600.0;
+        let lost = f64::from(downtime.lost());
+        match weeks.last_mut() {
+            Some(last) if last.week == week => {
+                // Keep the running total in `rate` until every suspend is in.
+                last.suspends += 1;
+                last.hours += hours;
+                last.rate += lost;
+            }
+            _ => weeks.push(WeeklyDrain {
+                week,
+                suspends: 1,
+                hours,
+                rate: lost,
+            }),
+        }
+    }
+    for week in &mut weeks {
+        week.rate /= week.hours;
+    }
+    weeks
+}
+
+/// The sleep mode the kernel uses for suspend, e.g. `s2idle` or `deep`.
+pub fn mem_sleep_mode() -> Option<String> {
+    let modes = read_string(&sysfs::root().join("power/mem_sleep"))?;
+    modes
+        .split_whitespace()
+        .find_map(|mode| mode.strip_prefix('[')?.strip_suffix(']'))
+        .map(str::to_string)
+}
+
+/// Finds gaps in history (oldest first) longer than `MAX_GAP`. A gap with a
+/// `boot` event in it was spent powered off; one without a boot or a
+/// daemon stop was spent suspended. Gaps where only the daemon was stopped
+/// aren't downtime and are left out.
+pub fn detect(logs: &[BatterySnapshot], events: &[BoundaryEvent]) -> Vec<Downtime> {
+    logs.windows(2)
+        .filter(|pair| pair[1].timestamp - pair[0].timestamp > MAX_GAP.as_secs() as i64)
+        .filter_map(|pair| {
+            let (before, after) = (&pair[0], &pair[1]);
+            let within: Vec<&BoundaryEvent> = events
+                .iter()
+                .filter(|e| e.timestamp > before.timestamp && e.timestamp <= after.timestamp)
+                .collect();
+            let has = |name: &str| within.iter().any(|e| e.event == name);
+
+            let kind = if has("boot") {
+                DowntimeKind::PoweredOff
+            } else if has("stopped") {
+                return None;
+            } else {
+                DowntimeKind::Suspended
+            };
+
+            // A clean shutdown records the charge closer to power-off than
+            // the last regular sample.
+            let shutdown = within
+                .iter()
+                .rev()
+                .find(|e| e.event == "shutdown" && e.percent.is_some());
+            let (start, start_percent) = match shutdown {
+                Some(e) if kind == DowntimeKind::PoweredOff => {
+                    (e.timestamp, e.percent.unwrap_or(before.percent))
+                }
+                _ => (before.timestamp, before.percent),
+            };
+
+            Some(Downtime {
+                kind,
+                start,
+                end: after.timestamp,
+                start_percent,
+                end_percent: after.percent,
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,65 @@
+//! Formatting of durations shown to the user. Every duration goes through
+//! `format`, so unit names and the level of detail are decided in one place.
+
+use std::sync::OnceLock;
+use std::time::Duration;
+
+#[derive(Debug, Eq, PartialEq, Copy, Clone, Default)]
+pub enum DurationStyle {
+    /// Two largest units, abbreviated: `2h 13m`
+    #[default]
+    Compact,
+    /// Two largest units, spelled out: `2 hours 13 minutes`
+    Long,
+}
+
+impl DurationStyle {
+    pub fn name(self) -> &'static str {
+        match self {
+            DurationStyle::Compact => "compact",
+            DurationStyle::Long => "long",
+        }
+    }
+}
+
+static STYLE: OnceLock<DurationStyle> = OnceLock::new();
+
+/// Sets the style for the rest of the process. Only the first call has an
+/// effect.
+pub fn set_style(style: DurationStyle) {
+    STYLE.set(style).ok();
+}
+
+/// Unit names per style: abbreviation, singular and plural.
+const UNITS: [(u64, &str, &str, &str); 4] = [
+    (24 * 60 * 60, "d", "day", "days"),
+    (60 * 60, "h", "hour", "hours"),
+    (60, "m", "minute", "minutes"),
+    (1, "s", "second", "seconds"),
+];
+
+/// Formats `duration` with its two largest non-zero units.
+pub fn format(duration: Duration) -> String {
+    let style = STYLE.get().copied().unwrap_or_default();
+    let mut remaining = duration.as_secs();
+
+    let parts: Vec<String> = UNITS
+        .iter()
+        .filter_map(|&(secs, short, singular, plural)| {
+            let count = remaining / secs;
+            remaining %= secs;
+            (count > 0).then(|| match style {
+                DurationStyle::Compact => format!("{}{}", count, short),
+                DurationStyle::Long if count == 1 => format!("1 {}", singular),
+                DurationStyle::Long => format!("{} {}", count, plural),
+            })
+        })
+        .take(2)
+        .collect();
+
+    match (parts.is_empty(), style) {
+        (false, _) => parts.join(" "),
+        (true, DurationStyle::Compact) => "0s".to_string(),
+        (true, DurationStyle::Long) => "0 seconds".to_string(),
+    }
+}
@@ -8,11 +8,51 @@ pub enum Error {
     Io(io::Error),
     Channel(mpsc::RecvError),
     Crossterm(String),
+    Config(String),
     Database(rusqlite::Error),
     Daemonize(String),
     DaemonAlreadyRunning,
     DaemonNotRunning,
     InvalidPid(num::ParseIntError),
+    SchemaTooNew(usize),
+    ChargeLimit(String),
+    NoBattery,
+}
+
+impl Error {
+    /// Exit status of a command that failed with this error. Scripts and
+    /// service managers can tell "not running" (3, as in LSB init scripts)
+    /// and "already running" (4) apart from other failures, and mistakes in
+    /// the configuration (2, as for invalid arguments) from failures at
+    /// runtime (1).
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Config(_) => 2,
+            Error::DaemonNotRunning => 3,
+            Error::DaemonAlreadyRunning => 4,
+            Error::NoBattery => 5,
+            _ => 1,
+        }
+    }
+
+    /// Stable name of the error, for `--json` output.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::Battery(_) => "battery",
+            Error::Io(_) => "io",
+            Error::Channel(_) => "channel",
+            Error::Crossterm(_) => "terminal",
+            Error::Config(_) => "config",
+            Error::Database(_) => "database",
+            Error::Daemonize(_) => "daemonize",
+            Error::DaemonAlreadyRunning => "daemon_already_running",
+            Error::DaemonNotRunning => "daemon_not_running",
+            Error::InvalidPid(_) => "invalid_pid",
+            Error::SchemaTooNew(_) => "schema_too_new",
+            Error::ChargeLimit(_) => "charge_limit",
+            Error::NoBattery => "no_battery",
+        }
+    }
 }
 
 impl error::Error for Error {
@@ -32,14 +72,22 @@ impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Error::Crossterm(msg) => write!(f, "Crossterm error: {}", msg),
+            Error::Config(msg) => write!(f, "Config error: {}", msg),
             Error::Daemonize(msg) => write!(f, "Daemonize error: {}", msg),
+            Error::ChargeLimit(msg) => write!(f, "Charge limit error: {}", msg),
             Error::DaemonAlreadyRunning => f.write_str("Daemon is already running"),
             Error::DaemonNotRunning => f.write_str("Daemon is not running"),
+            Error::NoBattery => f.write_str("No battery detected"),
             Error::Battery(e) => fmt::Display::fmt(e, f),
             Error::Io(e) => fmt::Display::fmt(e, f),
             Error::Channel(e) => fmt::Display::fmt(e, f),
             Error::Database(e) => fmt::Display::fmt(e, f),
             Error::InvalidPid(e) => write!(f, "Invalid PID: {}", e),
+            Error::SchemaTooNew(version) => write!(
+                f,
+                "Database schema version {} is newer than this amptop supports; upgrade amptop",
+                version
+            ),
         }
     }
 }
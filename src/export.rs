@@ -0,0 +1,30 @@
+use crate::daemon::BatterySnapshot;
+use crate::errors::Result;
+use std::io::Write;
+
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum ExportFormat {
+    Json,
+    Csv,
+}
+
+/// Writes `logs` to `out` in the given format.
+pub fn write_logs(
+    logs: &[BatterySnapshot],
+    format: ExportFormat,
+    out: &mut impl Write,
+) -> Result<()> {
+    match format {
+        ExportFormat::Json => {
+            serde_json::to_writer_pretty(&mut *out, logs).map_err(std::io::Error::from)?;
+            writeln!(out)?;
+        }
+        ExportFormat::Csv => {
+            writeln!(out, "timestamp,percent,status")?;
+            for log in logs {
+                writeln!(out, "{},{},{}", log.timestamp, log.percent, log.status)?;
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,278 @@
+use crate::daemon::{BatteryDaemon, BatterySnapshot, HealthSample};
+use crate::durations;
+use crate::errors::Result;
+use crate::info::BatteryInfo;
+use crate::sessions::{self, SessionKind};
+use battery::units::{energy::watt_hour, power::watt};
+use std::time::Duration;
+
+/// How much of the personal history to learn the typical drain from.
+const MODEL_WINDOW_DAYS: i64 = 30;
+/// How quickly the smoothed drain follows a change in load: after this
+/// long, earlier readings make up only about a third of the average.
+const SMOOTHING_TIME: Duration = Duration::from_secs(5 * 60);
+/// How far back recorded samples seed the smoothed drain.
+const SMOOTHING_SEED: Duration = Duration::from_secs(30 * 60);
+
+/// How much capacity history the degradation trend needs, so a few days
+/// of noise in the reported capacity aren't extrapolated over years.
+const MIN_TREND_DAYS: i64 = 30;
+const YEAR_SECS: f64 = 365.25 * 24.0 * 60.0 * 60.0;
+/// Health lost per year below which the trend counts as flat, in percentage
+/// points; slower than this, the projection lands centuries out.
+const MIN_DECLINE: f64 = 0.1;
+/// Health below which a battery is commonly considered worn out, in
+/// percent of design capacity.
+pub const WORN_OUT_HEALTH: f64 = 80.0;
+
+/// Rough intensity of a planned task relative to everyday use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Load {
+    Light,
+    Normal,
+    Heavy,
+}
+
+impl Load {
+    pub const ALL: [Load; 3] = [Load::Light, Load::Normal, Load::Heavy];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Load::Light => "light",
+            Load::Normal => "normal",
+            Load::Heavy => "heavy",
+        }
+    }
+
+    fn factor(self) -> f64 {
+        match self {
+            Load::Light => 0.6,
+            Load::Normal => 1.0,
+            Load::Heavy => 1.6,
+        }
+    }
+
+    pub fn next(self) -> Load {
+        let index = Self::ALL.iter().position(|&load| load == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+}
+
+/// Average discharge power learned from past discharge sessions.
+#[derive(Debug, Clone, Copy)]
+pub struct UsageModel {
+    pub draw_watts: f64,
+    pub hours_observed: f64,
+}
+
+impl UsageModel {
+    /// Learns the model from history sorted oldest first.
+    pub fn from_history(logs: &[BatterySnapshot]) -> Option<Self> {
+        let (energy, seconds) = sessions::detect(logs)
+            .iter()
+            .filter(|session| session.kind == SessionKind::Discharge)
+            .filter_map(|session| Some((session.energy_delta()?, session.duration())))
+            .fold((0.0, 0), |(energy, seconds), (delta, duration)| {
+                (energy + f64::from(delta), seconds + duration)
+            });
+
+        let hours = seconds as f64 / 3600.0;
+        (hours > 0.0 && energy > 0.0).then(|| UsageModel {
+            draw_watts: energy / hours,
+            hours_observed: hours,
+        })
+    }
+
+    pub fn load() -> Result<Option<Self>> {
+        let since = chrono::Utc::now().timestamp() - MODEL_WINDOW_DAYS * 24 * 60 * 60;
+        Ok(Self::from_history(&BatteryDaemon::get_logs_since(since)?))
+    }
+}
+
+/// Discharge power averaged with exponentially decaying weights, so a
+/// short burst of load doesn't swing the time-to-empty estimate the way
+/// the battery's instantaneous rate does.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SmoothedDrain {
+    /// Average in W, and when the last reading was folded in
+    average: Option<(f64, i64)>,
+}
+
+impl SmoothedDrain {
+    /// Seeds the average from the ongoing discharge in the last half hour
+    /// of recorded history, if the daemon has been collecting.
+    pub fn load() -> Self {
+        let since = chrono::Utc::now().timestamp() - SMOOTHING_SEED.as_secs() as i64;
+        let logs = BatteryDaemon::get_logs_since(since).unwrap_or_default();
+        let discharge = logs
+            .iter()
+            .rposition(|log| log.status != "discharging")
+            .map_or(0, |index| index + 1);
+
+        let mut drain = Self::default();
+        for log in &logs[discharge..] {
+            if let Some(watts) = log.power_watts {
+                drain.update(log.timestamp, f64::from(watts));
+            }
+        }
+        drain
+    }
+
+    /// Folds in a reading of `watts` taken at `timestamp`. Each reading
+    /// weighs by the time since the previous one, so irregular refreshes
+    /// don't skew the average.
+    pub fn update(&mut self, timestamp: i64, watts: f64) {
+        self.average = Some(match self.average {
+            Some((average, at)) if timestamp > at => {
+                let elapsed = (timestamp - at) as f64 / SMOOTHING_TIME.as_secs_f64();
+                let weight = 1.0 - (-elapsed).exp();
+                (average + weight * (watts - average), timestamp)
+            }
+            Some(average) => average,
+            None => (watts, timestamp),
+        });
+    }
+
+    /// Forgets the average, e.g. when the charger is plugged in.
+    pub fn reset(&mut self) {
+        self.average = None;
+    }
+
+    /// How long `energy_wh` lasts at the smoothed drain.
+    pub fn time_to_empty(&self, energy_wh: f64) -> Option<Duration> {
+        let (watts, _) = self.average.filter(|(watts, _)| *watts > 0.0)?;
+        Some(Duration::from_secs((energy_wh / watts * 3600.0) as u64))
+    }
+}
+
+/// Answer to "will the current charge last this long?".
+#[derive(Debug, Clone, Copy)]
+pub struct Verdict {
+    pub available_wh: f64,
+    pub needed_wh: f64,
+    pub draw_watts: f64,
+}
+
+impl Verdict {
+    pub fn fits(&self) -> bool {
+        self.available_wh >= self.needed_wh
+    }
+
+    pub fn runtime(&self) -> Duration {
+        let minutes = (self.available_wh / self.draw_watts * 60.0) as u64;
+        Duration::from_secs(minutes * 60)
+    }
+
+    pub fn summary(&self) -> String {
+        let runtime = durations::format(self.runtime());
+        if self.fits() {
+            format!(
+                "Yes, with {:.1} Wh to spare (about {} at {:.1} W)",
+                self.available_wh - self.needed_wh,
+                runtime,
+                self.draw_watts
+            )
+        } else {
+            format!(
+                "No, short by {:.1} Wh (only about {} at {:.1} W)",
+                self.needed_wh - self.available_wh,
+                runtime,
+                self.draw_watts
+            )
+        }
+    }
+}
+
+/// Checks whether the energy left in the battery covers `task` at the
+/// personal average drain scaled by `load`. Falls back to the live
+/// discharge rate when there is no usable history.
+pub fn can_finish(
+    battery: &BatteryInfo,
+    model: Option<UsageModel>,
+    task: Duration,
+    load: Load,
+) -> Option<Verdict> {
+    let reading = battery.total()?;
+    let live_draw = (reading.state == battery::State::Discharging)
+        .then(|| f64::from(reading.energy_rate.get::<watt>()))
+        .filter(|watts| *watts > 0.0);
+
+    let draw_watts = match model {
+        Some(model) => model.draw_watts,
+        None => live_draw?,
+    } * load.factor();
+
+    Some(Verdict {
+        available_wh: f64::from(reading.energy.get::<watt_hour>()),
+        needed_wh: draw_watts * task.as_secs_f64() / 3600.0,
+        draw_watts,
+    })
+}
+
+/// Straight-line fit of the battery's health (full charge capacity relative
+/// to design) over time, for projecting when it wears out.
+#[derive(Debug, Clone, Copy)]
+pub struct CapacityTrend {
+    /// Health at `since`, in percent
+    health: f64,
+    since: i64,
+    /// Change in health, in percentage points per year
+    pub per_year: f64,
+    /// Design capacity, in Wh
+    pub design: f64,
+}
+
+impl CapacityTrend {
+    /// Least-squares fit over `samples` (oldest first), once they span
+    /// `MIN_TREND_DAYS`.
+    pub fn fit(samples: &[HealthSample]) -> Option<Self> {
+        let (first, last) = (samples.first()?, samples.last()?);
+        if last.timestamp - first.timestamp < MIN_TREND_DAYS * 24 * 60 * 60 {
+            return None;
+        }
+        let points: Vec<(f64, f64)> = samples
+            .iter()
+            .map(|sample| {
+                let years = (sample.timestamp - last.timestamp) as f64 / YEAR_SECS;
+                (years, 100.0 - f64::from(sample.wear()))
+            })
+            .collect();
+        let n = points.len() as f64;
+        let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / n;
+        let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / n;
+        let covariance: f64 = points
+            .iter()
+            .map(|(x, y)| (x - mean_x) * (y - mean_y))
+            .sum();
+        let variance: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+        let per_year = covariance / variance;
+        Some(Self {
+            health: mean_y - per_year * mean_x,
+            since: last.timestamp,
+            per_year,
+            design: f64::from(last.energy_full_design),
+        })
+    }
+
+    /// Projected health at `timestamp`, in percent.
+    pub fn health_at(&self, timestamp: i64) -> f64 {
+        self.health + self.per_year * (timestamp - self.since) as f64 / YEAR_SECS
+    }
+
+    /// Projected full charge capacity at `timestamp`, in Wh.
+    pub fn capacity_at(&self, timestamp: i64) -> f64 {
+        self.design * self.health_at(timestamp) / 100.0
+    }
+
+    /// `months` after `timestamp`.
+    pub fn months_after(timestamp: i64, months: u32) -> i64 {
+        timestamp + (f64::from(months) * YEAR_SECS / 12.0) as i64
+    }
+
+    /// When the health falls to `health` percent, if it's declining by at
+    /// least `MIN_DECLINE` a year. Already below it, that's in the past.
+    pub fn reaches(&self, health: f64) -> Option<i64> {
+        (self.per_year <= -MIN_DECLINE)
+            .then(|| self.since + ((health - self.health) / self.per_year * YEAR_SECS) as i64)
+    }
+}
@@ -0,0 +1,332 @@
+//! `amptop graph export`: the history graph drawn to an SVG or PNG file,
+//! for sharing battery behavior in a bug report without a terminal
+//! screenshot.
+//!
+//! SVG goes through the same renderer as the report's charts. PNG needs a
+//! rasterizer and fonts, so it is only built with the `png` feature.
+
+use crate::config::ChartAxis;
+use crate::daemon::{BatterySnapshot, MAX_GAP, local_hostname};
+use crate::svg;
+use std::path::Path;
+
+/// Image size in pixels.
+pub const DEFAULT_SIZE: (u32, u32) = (1200, 700);
+
+const CHARGING: (u8, u8, u8) = (0x2e, 0x7d, 0x32);
+const DISCHARGING: (u8, u8, u8) = (0xe6, 0x51, 0x00);
+const FULL: (u8, u8, u8) = (0x15, 0x65, 0xc0);
+const OTHER: (u8, u8, u8) = (0x75, 0x75, 0x75);
+
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum ImageFormat {
+    Svg,
+    Png,
+}
+
+impl ImageFormat {
+    /// The format named by `path`'s extension, if it's one we can write.
+    pub fn for_path(path: &Path) -> Option<Self> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("svg") => Some(ImageFormat::Svg),
+            Some(ext) if ext.eq_ignore_ascii_case("png") => Some(ImageFormat::Png),
+            _ => None,
+        }
+    }
+}
+
+/// Draws `logs` (oldest first, not empty) to `path`: the charge on `axis`
+/// colored by state, with the power draw below.
+pub fn export(
+    logs: &[BatterySnapshot],
+    axis: ChartAxis,
+    format: ImageFormat,
+    path: &Path,
+    size: (u32, u32),
+) -> Result<(), String> {
+    let graph = Graph::new(logs, axis);
+    match format {
+        ImageFormat::Svg => std::fs::write(path, graph.svg(size)).map_err(|e| e.to_string()),
+        #[cfg(feature = "png")]
+        ImageFormat::Png => png::draw(&graph, path, size),
+        #[cfg(not(feature = "png"))]
+        ImageFormat::Png => {
+            Err("this amptop was built without PNG support (cargo feature `png`); write an .svg instead".to_string())
+        }
+    }
+}
+
+/// What both image formats draw: the charge in the upper two thirds, the
+/// power below.
+struct Graph<'a> {
+    title: String,
+    /// Start and end of the history, as Unix timestamps; the SVG chart
+    /// finds them from the points
+    #[cfg(feature = "png")]
+    span: (i64, i64),
+    /// `%`, `Wh` or `kJ`
+    unit: &'static str,
+    top: f64,
+    charge: Vec<Run<'a>>,
+    power_top: f64,
+    power: Vec<Run<'a>>,
+}
+
+impl<'a> Graph<'a> {
+    fn new(logs: &'a [BatterySnapshot], axis: ChartAxis) -> Self {
+        let start = logs.first().map_or(0, |log| log.timestamp);
+        let end = logs.last().map_or(0, |log| log.timestamp).max(start + 60);
+        let date = |timestamp: i64| {
+            use chrono::{Local, TimeZone};
+            Local
+                .timestamp_opt(timestamp, 0)
+                .single()
+                .map(|dt| dt.format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_default()
+        };
+
+        // As in the TUI: energy axes plot the Wh recorded with each sample,
+        // in Wh or kJ; history without energy readings stays in percent
+        let energy = match axis {
+            ChartAxis::Percent => None,
+            ChartAxis::WattHours => Some((1.0, 10.0, "Wh")),
+            ChartAxis::Joules => Some((3.6, 50.0, "kJ")),
+        }
+        .filter(|_| logs.iter().any(|log| log.energy.is_some()));
+        let value = |log: &BatterySnapshot| match energy {
+            Some((scale, _, _)) => log.energy.map(|wh| f64::from(wh) * scale),
+            None => Some(f64::from(log.percent)),
+        };
+        // Round the axis up to the next 10 Wh or 50 kJ
+        let top = match energy {
+            Some((_, step, _)) => {
+                let max = logs.iter().filter_map(value).fold(0.0, f64::max);
+                ((max / step).floor() + 1.0) * step
+            }
+            None => 100.0,
+        };
+
+        let power: Vec<_> = runs(logs, |log| log.power_watts.map(f64::from))
+            .into_iter()
+            .filter(|(status, _)| *status == "charging" || *status == "discharging")
+            .collect();
+        // Round the axis up to the next 5 W
+        let max_watts = power
+            .iter()
+            .flat_map(|(_, points)| points.iter().map(|&(_, watts)| watts))
+            .fold(0.0, f64::max);
+
+        Graph {
+            title: format!(
+                "Battery on {}, {} to {}",
+                local_hostname(),
+                date(start),
+                date(end)
+            ),
+            #[cfg(feature = "png")]
+            span: (start, end),
+            unit: energy.map_or("%", |(_, _, unit)| unit),
+            top,
+            charge: runs(logs, value),
+            power_top: ((max_watts / 5.0).floor() + 1.0) * 5.0,
+            power,
+        }
+    }
+
+    fn svg(&self, (width, height): (u32, u32)) -> String {
+        let upper = height * 2 / 3;
+        let chart = |title: &str, unit: &str, top: f64, runs: &[Run]| {
+            let mut labelled: Vec<&str> = Vec::new();
+            svg::Chart {
+                title: title.to_string(),
+                unit: unit.to_string(),
+                y_range: Some((0.0, top)),
+                // The runs are already broken at gaps
+                max_gap: None,
+                series: runs
+                    .iter()
+                    .map(|(status, points)| {
+                        let (r, g, b) = color(status);
+                        let label = if labelled.contains(status) {
+                            String::new()
+                        } else {
+                            labelled.push(status);
+                            status.to_string()
+                        };
+                        svg::Series {
+                            label,
+                            color: format!("#{:02x}{:02x}{:02x}", r, g, b),
+                            points: points.clone(),
+                        }
+                    })
+                    .collect(),
+            }
+        };
+        let unit = if self.unit == "%" {
+            "%".to_string()
+        } else {
+            format!(" {}", self.unit)
+        };
+        format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">{}<g transform="translate(0,{})">{}</g></svg>"#,
+            chart(&self.title, &unit, self.top, &self.charge).render(width, upper),
+            upper,
+            chart("Power", " W", self.power_top, &self.power).render(width, height - upper),
+            w = width,
+            h = height
+        )
+    }
+}
+
+/// A stretch of history in one state: the state and its points as Unix
+/// timestamps and values.
+type Run<'a> = (&'a str, Vec<(i64, f64)>);
+
+/// Splits the history into stretches of one state, each joined to the start
+/// of the next so the line is continuous, and broken where the samples stop
+/// for longer than `MAX_GAP` or have no value.
+fn runs(logs: &[BatterySnapshot], value: impl Fn(&BatterySnapshot) -> Option<f64>) -> Vec<Run<'_>> {
+    let mut runs: Vec<Run> = Vec::new();
+    let mut previous: Option<i64> = None;
+    for log in logs {
+        let Some(y) = value(log) else {
+            previous = None;
+            continue;
+        };
+        let point = (log.timestamp, y);
+        let joined =
+            previous.is_some_and(|timestamp| log.timestamp - timestamp <= MAX_GAP.as_secs() as i64);
+        previous = Some(log.timestamp);
+        if let Some((status, points)) = runs.last_mut()
+            && joined
+        {
+            points.push(point);
+            if *status == log.status {
+                continue;
+            }
+        }
+        runs.push((&log.status, vec![point]));
+    }
+    runs
+}
+
+fn color(status: &str) -> (u8, u8, u8) {
+    match status {
+        "charging" => CHARGING,
+        "discharging" => DISCHARGING,
+        "full" => FULL,
+        _ => OTHER,
+    }
+}
+
+#[cfg(feature = "png")]
+mod png {
+    use super::{Graph, Run, color};
+    use chrono::{DateTime, Local, TimeZone};
+    use plotters::coord::types::{RangedCoordf64, RangedDateTime};
+    use plotters::prelude::*;
+    use std::path::Path;
+
+    pub fn draw(graph: &Graph, path: &Path, size: (u32, u32)) -> Result<(), String> {
+        let root = BitMapBackend::new(path, size).into_drawing_area();
+        draw_panels(&root, graph).map_err(|e| e.to_string())?;
+        root.present().map_err(|e| e.to_string())
+    }
+
+    fn draw_panels<DB: DrawingBackend>(
+        root: &DrawingArea<DB, plotters::coord::Shift>,
+        graph: &Graph,
+    ) -> Result<(), DrawingAreaErrorKind<DB::ErrorType>> {
+        root.fill(&WHITE)?;
+        let height = root.dim_in_pixel().1;
+        let (upper, lower) = root.split_vertically(height * 2 / 3);
+
+        let (start, end) = (local(graph.span.0), local(graph.span.1));
+        let time_format = if graph.span.1 - graph.span.0 > 24 * 60 * 60 {
+            "%m-%d %H:%M"
+        } else {
+            "%H:%M"
+        };
+        let format_time = |time: &DateTime<Local>| time.format(time_format).to_string();
+
+        let mut chart = ChartBuilder::on(&upper)
+            .caption(&graph.title, ("sans-serif", 20))
+            .margin(12)
+            .x_label_area_size(28)
+            .y_label_area_size(56)
+            .build_cartesian_2d(start..end, 0.0..graph.top)?;
+        chart
+            .configure_mesh()
+            .x_labels(8)
+            .x_label_formatter(&format_time)
+            .y_desc(graph.unit)
+            .draw()?;
+        draw_runs(&mut chart, &graph.charge, 2)?;
+
+        let mut chart = ChartBuilder::on(&lower)
+            .caption("Power", ("sans-serif", 16))
+            .margin(12)
+            .x_label_area_size(28)
+            .y_label_area_size(56)
+            .build_cartesian_2d(start..end, 0.0..graph.power_top)?;
+        chart
+            .configure_mesh()
+            .x_labels(8)
+            .x_label_formatter(&format_time)
+            .y_desc("W")
+            .draw()?;
+        if graph.power.is_empty() {
+            let (width, height) = lower.dim_in_pixel();
+            lower.draw(&Text::new(
+                "No power readings in this range",
+                (width as i32 / 2 - 100, height as i32 / 2),
+                ("sans-serif", 14).into_font().color(&rgb(color(""))),
+            ))?;
+        } else {
+            // Thin, since wide lines overshoot at the sharp turns of a noisy
+            // power reading.
+            draw_runs(&mut chart, &graph.power, 1)?;
+        }
+        Ok(())
+    }
+
+    fn draw_runs<'a, DB: DrawingBackend + 'a>(
+        chart: &mut ChartContext<
+            'a,
+            DB,
+            Cartesian2d<RangedDateTime<DateTime<Local>>, RangedCoordf64>,
+        >,
+        runs: &[Run],
+        width: u32,
+    ) -> Result<(), DrawingAreaErrorKind<DB::ErrorType>> {
+        let mut labelled: Vec<&str> = Vec::new();
+        for &(status, ref points) in runs {
+            let color = rgb(color(status));
+            let points = points.iter().map(|&(t, value)| (local(t), value));
+            let series = chart.draw_series(LineSeries::new(points, color.stroke_width(width)))?;
+            if !labelled.contains(&status) {
+                labelled.push(status);
+                series.label(status).legend(move |(x, y)| {
+                    PathElement::new(vec![(x, y), (x + 16, y)], color.stroke_width(width))
+                });
+            }
+        }
+        chart
+            .configure_series_labels()
+            .position(SeriesLabelPosition::UpperLeft)
+            .background_style(WHITE.mix(0.8))
+            .border_style(BLACK.mix(0.3))
+            .draw()
+    }
+
+    fn rgb((r, g, b): (u8, u8, u8)) -> RGBColor {
+        RGBColor(r, g, b)
+    }
+
+    fn local(timestamp: i64) -> DateTime<Local> {
+        Local
+            .timestamp_opt(timestamp, 0)
+            .single()
+            .unwrap_or_default()
+    }
+}
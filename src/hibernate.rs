@@ -0,0 +1,206 @@
+use crate::daemon::{BatterySnapshot, MAX_GAP};
+use crate::sysfs;
+use std::fs;
+use std::process::Command;
+use std::time::Duration;
+
+/// Weight of the newest drain reading in the moving average.
+const SMOOTHING: f32 = 0.2;
+/// Drain readings needed before the estimate is trusted.
+const MIN_READINGS: u32 = 3;
+
+/// Hibernates the machine when the estimated time left on battery drops
+/// below `threshold`. The estimate divides the energy left by an
+/// exponentially smoothed drain, so it works on worn batteries where a fixed
+/// percentage means very different runtimes.
+#[derive(Debug)]
+pub struct SmartHibernate {
+    threshold: Duration,
+    /// Timestamp and energy (Wh) of the previous discharging sample
+    previous: Option<(i64, f32)>,
+    /// Smoothed drain, in W
+    drain: Option<f32>,
+    readings: u32,
+    triggered: bool,
+}
+
+impl SmartHibernate {
+    pub fn new(threshold: Duration) -> Self {
+        Self {
+            threshold,
+            previous: None,
+            drain: None,
+            readings: 0,
+            triggered: false,
+        }
+    }
+
+    /// Returns the estimated time left the first time it falls below the
+    /// threshold. It doesn't fire again until the battery has charged.
+    pub fn update(&mut self, snapshot: &BatterySnapshot) -> Option<Duration> {
+        let energy = snapshot.energy?;
+        if snapshot.status != "discharging" {
+            if snapshot.status != "unknown" {
+                self.reset();
+                self.triggered = false;
+            }
+            return None;
+        }
+
+        let remaining = self.estimate(snapshot.timestamp, energy)?;
+        if self.triggered || remaining >= self.threshold {
+            return None;
+        }
+        self.triggered = true;
+        Some(remaining)
+    }
+
+    fn estimate(&mut self, timestamp: i64, energy: f32) -> Option<Duration> {
+        let previous = self.previous.replace((timestamp, energy));
+        let (last_timestamp, last_energy) = previous?;
+        let elapsed = timestamp - last_timestamp;
+        // A long gap means the machine was suspended; its drain says nothing
+        // about the current workload.
+        if elapsed <= 0 || elapsed > MAX_GAP.as_secs() as i64 {
+            self.reset();
+            self.previous = Some((timestamp, energy));
+            return None;
+        }
+
+        let watts = (last_energy - energy) * 3600.0 / elapsed as f32;
+        if watts < 0.0 {
+            return None;
+        }
+        let drain = match self.drain {
+            Some(drain) => drain + SMOOTHING * (watts - drain),
+            None => watts,
+        };
+        self.drain = Some(drain);
+        self.readings += 1;
+
+        if self.readings < MIN_READINGS || drain <= 0.0 {
+            return None;
+        }
+        Some(Duration::from_secs_f32(energy / drain * 3600.0))
+    }
+
+    fn reset(&mut self) {
+        self.previous = None;
+        self.drain = None;
+        self.readings = 0;
+    }
+}
+
+pub fn hibernate() -> std::io::Result<()> {
+    let status = Command::new("systemctl").arg("hibernate").status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(std::io::Error::other(format!(
+            "systemctl hibernate exited with {}",
+            status
+        )))
+    }
+}
+
+/// One of the conditions `amptop doctor` checks before hibernating can be
+/// relied on.
+#[derive(Debug)]
+pub struct Check {
+    pub name: &'static str,
+    /// What was found
+    pub detail: String,
+    /// Why hibernating may fail, `None` when the check passed
+    pub problem: Option<String>,
+}
+
+/// Checks the kernel, resume device and swap size needed to hibernate and
+/// come back.
+pub fn readiness() -> Vec<Check> {
+    let mut checks = Vec::new();
+
+    let states = sysfs::read_string(&sysfs::root().join("power/state"));
+    let states = states.as_deref().unwrap_or("missing");
+    checks.push(Check {
+        name: "Kernel support",
+        detail: format!("/sys/power/state: {}", states),
+        problem: (!states.split_whitespace().any(|state| state == "disk"))
+            .then(|| "the kernel doesn't offer hibernation (no \"disk\" state)".to_string()),
+    });
+
+    // e.g. "none [integrity] confidentiality"
+    if let Some(lockdown) = sysfs::read_string(&sysfs::root().join("kernel/security/lockdown")) {
+        let mode = lockdown
+            .split_whitespace()
+            .find(|mode| mode.starts_with('['))
+            .map(|mode| mode.trim_matches(['[', ']']).to_string())
+            .unwrap_or_else(|| "none".to_string());
+        checks.push(Check {
+            name: "Kernel lockdown",
+            problem: (mode != "none").then(|| {
+                "lockdown disables hibernation, usually because Secure Boot is on".to_string()
+            }),
+            detail: mode,
+        });
+    }
+
+    let cmdline = fs::read_to_string("/proc/cmdline").unwrap_or_default();
+    let resume_arg = cmdline
+        .split_whitespace()
+        .find_map(|arg| arg.strip_prefix("resume="))
+        .map(str::to_string);
+    let resume_device =
+        sysfs::read_string(&sysfs::root().join("power/resume")).filter(|device| device != "0:0");
+    checks.push(Check {
+        name: "Resume device",
+        problem: (resume_arg.is_none() && resume_device.is_none()).then(|| {
+            "no resume= on the kernel command line, so the saved image isn't restored on boot"
+                .to_string()
+        }),
+        detail: match (resume_arg, resume_device) {
+            (Some(arg), _) => format!("resume={}", arg),
+            (None, Some(device)) => format!("/sys/power/resume: {}", device),
+            (None, None) => "not set".to_string(),
+        },
+    });
+
+    let swap = swap_kib();
+    let memory = memory_kib();
+    let gib = |kib: u64| kib as f64 / (1024.0 * 1024.0);
+    checks.push(Check {
+        name: "Swap size",
+        detail: format!(
+            "{:.1} GiB of swap for {:.1} GiB of RAM",
+            gib(swap),
+            gib(memory)
+        ),
+        problem: match () {
+            _ if swap == 0 => Some("no swap is active to write the image to".to_string()),
+            _ if swap < memory => Some(
+                "swap is smaller than RAM; hibernating fails when memory is nearly full"
+                    .to_string(),
+            ),
+            _ => None,
+        },
+    });
+    checks
+}
+
+/// Total size of active swap, from `/proc/swaps`.
+fn swap_kib() -> u64 {
+    fs::read_to_string("/proc/swaps")
+        .unwrap_or_default()
+        .lines()
+        .skip(1)
+        .filter_map(|line| line.split_whitespace().nth(2)?.parse::<u64>().ok())
+        .sum()
+}
+
+fn memory_kib() -> u64 {
+    fs::read_to_string("/proc/meminfo")
+        .unwrap_or_default()
+        .lines()
+        .find_map(|line| line.strip_prefix("MemTotal:"))
+        .and_then(|rest| rest.split_whitespace().next()?.parse().ok())
+        .unwrap_or(0)
+}
@@ -0,0 +1,49 @@
+//! Detects whether the user is idle, so samples taken while the machine sat
+//! unattended can be told apart from active use.
+//!
+//! On X11 the XScreenSaver idle time is read through `xprintidle`. Elsewhere
+//! (Wayland and the console) the session's logind `IdleHint` is used, which
+//! compositors and idle daemons such as swayidle set from ext-idle-notify.
+
+use std::env;
+use std::process::Command;
+use std::time::Duration;
+
+/// Input-free time after which the user counts as idle.
+pub const IDLE_AFTER: Duration = Duration::from_secs(5 * 60);
+
+/// Returns `None` when idleness can't be determined.
+pub fn is_idle() -> Option<bool> {
+    if env::var_os("DISPLAY").is_some()
+        && env::var_os("WAYLAND_DISPLAY").is_none()
+        && let Some(idle) = x11_idle_time()
+    {
+        return Some(idle >= IDLE_AFTER);
+    }
+    logind_idle_hint()
+}
+
+fn x11_idle_time() -> Option<Duration> {
+    let millis = command_output(Command::new("xprintidle"))?.parse().ok()?;
+    Some(Duration::from_millis(millis))
+}
+
+fn logind_idle_hint() -> Option<bool> {
+    let session = env::var("XDG_SESSION_ID").unwrap_or_else(|_| "auto".to_string());
+    let mut command = Command::new("loginctl");
+    command.args(["show-session", &session, "--property=IdleHint", "--value"]);
+
+    match command_output(command)?.as_str() {
+        "yes" => Some(true),
+        "no" => Some(false),
+        _ => None,
+    }
+}
+
+fn command_output(mut command: Command) -> Option<String> {
+    let output = command.output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
@@ -1,170 +1,534 @@
-use crate::config::Unit;
+use crate::adapter::Adapter;
+use crate::backend::{Backend, BatteryBackend};
+use crate::charge_limit::{self, Thresholds};
+use crate::chemistry::Chemistry;
+use crate::config::{TemperatureUnit, Unit};
+use crate::daemon::BatteryDaemon;
+use crate::durations;
 use crate::errors::Result;
 use battery::units::{
-    Unit as _,
+    ElectricPotential, Energy, Power, Ratio, ThermodynamicTemperature, Time, Unit as _,
     electric_potential::volt,
     energy::{joule, watt_hour},
     power::watt,
     ratio::{percent, ratio},
-    thermodynamic_temperature::{degree_celsius, kelvin},
+    thermodynamic_temperature::{degree_celsius, degree_fahrenheit, kelvin},
     time::second,
 };
+use chrono::NaiveDate;
+use serde::Serialize;
 use std::time::Duration;
 
+/// A point-in-time view of a battery, independent of where it was read from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reading {
+    pub state_of_charge: Ratio,
+    pub energy: Energy,
+    pub energy_full: Energy,
+    pub energy_full_design: Energy,
+    pub energy_rate: Power,
+    pub voltage: ElectricPotential,
+    pub state_of_health: Ratio,
+    pub state: battery::State,
+    pub technology: battery::Technology,
+    pub temperature: Option<ThermodynamicTemperature>,
+    pub cycle_count: Option<u32>,
+    pub vendor: Option<String>,
+    pub model: Option<String>,
+    pub serial_number: Option<String>,
+    pub time_to_full: Option<Time>,
+    pub time_to_empty: Option<Time>,
+    /// Driver's health verdict, e.g. "Good" or "Overheat"
+    pub health_status: Option<String>,
+    pub manufactured: Option<NaiveDate>,
+    /// Charge counters, for drivers that report charge rather than energy
+    pub charge: Option<Charge>,
+}
+
+/// A battery's charge in mAh, as counted by the driver.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Charge {
+    pub now: f32,
+    pub full: f32,
+    pub full_design: f32,
+}
+
+impl From<&battery::Battery> for Reading {
+    fn from(b: &battery::Battery) -> Self {
+        Self {
+            state_of_charge: b.state_of_charge(),
+            energy: b.energy(),
+            energy_full: b.energy_full(),
+            energy_full_design: b.energy_full_design(),
+            energy_rate: b.energy_rate(),
+            voltage: b.voltage(),
+            state_of_health: b.state_of_health(),
+            state: b.state(),
+            technology: b.technology(),
+            temperature: b.temperature(),
+            cycle_count: b.cycle_count(),
+            vendor: b.vendor().map(str::to_string),
+            model: b.model().map(str::to_string),
+            serial_number: b.serial_number().map(str::to_string),
+            time_to_full: b.time_to_full(),
+            time_to_empty: b.time_to_empty(),
+            health_status: None,
+            manufactured: None,
+            charge: None,
+        }
+    }
+}
+
+impl Reading {
+    /// Merges several batteries into one pack: energies add up, and the
+    /// time estimates follow from the combined energy and power.
+    fn combine(readings: &[Reading]) -> Option<Reading> {
+        let (first, rest) = readings.split_first()?;
+        if rest.is_empty() {
+            return Some(first.clone());
+        }
+
+        let sum = |field: fn(&Reading) -> Energy| {
+            readings
+                .iter()
+                .map(field)
+                .fold(Energy::new::<watt_hour>(0.0), |total, energy| {
+                    total + energy
+                })
+        };
+        let energy = sum(|r| r.energy);
+        let energy_full = sum(|r| r.energy_full);
+        let energy_full_design = sum(|r| r.energy_full_design);
+        // Only meaningful when every battery counts charge
+        let charge = readings
+            .iter()
+            .map(|r| r.charge)
+            .collect::<Option<Vec<_>>>()
+            .map(|charges| Charge {
+                now: charges.iter().map(|c| c.now).sum(),
+                full: charges.iter().map(|c| c.full).sum(),
+                full_design: charges.iter().map(|c| c.full_design).sum(),
+            });
+
+        let any = |state| readings.iter().any(|r| r.state == state);
+        let state = if any(battery::State::Charging) {
+            battery::State::Charging
+        } else if any(battery::State::Discharging) {
+            battery::State::Discharging
+        } else {
+            first.state
+        };
+
+        // A battery that is idle while the other one works contributes no
+        // power, so only count the ones in the pack's state.
+        let energy_rate = readings
+            .iter()
+            .filter(|r| r.state == state)
+            .map(|r| r.energy_rate)
+            .fold(Power::new::<watt>(0.0), |total, power| total + power);
+        let watts = energy_rate.get::<watt>();
+        let hours = |wh: f32| Time::new::<second>(wh / watts * 3600.0);
+
+        Some(Reading {
+            state_of_charge: Ratio::new::<ratio>(ratio_of(energy, energy_full)),
+            energy,
+            energy_full,
+            energy_full_design,
+            energy_rate,
+            voltage: first.voltage,
+            state_of_health: Ratio::new::<ratio>(ratio_of(energy_full, energy_full_design)),
+            state,
+            technology: first.technology,
+            temperature: readings
+                .iter()
+                .filter_map(|r| r.temperature)
+                .reduce(|hottest, temp| if temp > hottest { temp } else { hottest }),
+            cycle_count: readings.iter().filter_map(|r| r.cycle_count).max(),
+            vendor: None,
+            model: None,
+            serial_number: None,
+            time_to_full: (state == battery::State::Charging && watts > 0.0)
+                .then(|| hours((energy_full - energy).get::<watt_hour>().max(0.0))),
+            time_to_empty: (state == battery::State::Discharging && watts > 0.0)
+                .then(|| hours(energy.get::<watt_hour>())),
+            // A problem with any battery is a problem with the pack.
+            health_status: readings
+                .iter()
+                .filter_map(|r| r.health_status.clone())
+                .reduce(|status, other| if status == "Good" { other } else { status }),
+            manufactured: None,
+            charge,
+        })
+    }
+}
+
+fn ratio_of(value: Energy, total: Energy) -> f32 {
+    let total = total.get::<watt_hour>();
+    if total > 0.0 {
+        value.get::<watt_hour>() / total
+    } else {
+        0.0
+    }
+}
+
+/// `energy` of `reading` in `unit`. In mAh, `counted` is the driver's
+/// charge counter for the same quantity; without one, the charge follows
+/// from the energy and the present voltage.
+fn format_energy(
+    reading: &Reading,
+    energy: Energy,
+    counted: Option<f32>,
+    unit: Unit,
+) -> Option<String> {
+    Some(match unit {
+        Unit::Human => format!(
+            "{:.2} {}",
+            energy.get::<watt_hour>(),
+            watt_hour::abbreviation()
+        ),
+        Unit::Si => format!("{:.2} {}", energy.get::<joule>(), joule::abbreviation()),
+        Unit::MilliampHours => {
+            let volts = reading.voltage.get::<volt>();
+            let mah = match counted {
+                Some(mah) => mah,
+                None if volts > 0.0 => energy.get::<watt_hour>() / volts * 1000.0,
+                None => return None,
+            };
+            format!("{:.0} mAh", mah)
+        }
+    })
+}
+
+/// Machine-readable snapshot of the battery for `amptop stat`.
+#[derive(Debug, Serialize)]
+pub struct Summary {
+    pub percent: f32,
+    pub state: String,
+    pub energy_rate_watts: f32,
+    pub energy_wh: f32,
+    pub energy_full_wh: f32,
+    pub health_percent: f32,
+    pub time_to_full_secs: Option<u64>,
+    pub time_to_empty_secs: Option<u64>,
+    pub temperature_celsius: Option<f32>,
+}
+
 #[derive(Debug)]
 pub struct BatteryInfo {
-    battery: Option<battery::Battery>,
-    manager: battery::Manager,
+    backend: Box<dyn BatteryBackend>,
+    batteries: Vec<Reading>,
+    /// Index into `batteries` being shown, or `None` for all of them combined
+    selected: Option<usize>,
+    /// The reading currently shown
+    battery: Option<Reading>,
+    adapter: Option<Adapter>,
+    /// Firmware charge thresholds of the first battery supporting them
+    charge_thresholds: Option<Thresholds>,
+    /// Energy stored in all batteries when the current discharge started
+    unplugged_energy: Option<Energy>,
 }
 
 impl BatteryInfo {
     pub fn new() -> Result<Self> {
-        let manager = battery::Manager::new()?;
-        let battery = manager.batteries()?.flatten().next();
-        Ok(Self { battery, manager })
+        let mut backend = Backend::open()?;
+        let batteries = backend.read()?;
+
+        let battery = Reading::combine(&batteries);
+        let unplugged_energy = battery
+            .as_ref()
+            .filter(|b| b.state == battery::State::Discharging)
+            .map(|b| b.energy);
+        Ok(Self {
+            backend,
+            batteries,
+            selected: None,
+            battery,
+            adapter: Adapter::detect(),
+            charge_thresholds: charge_limit::read().into_iter().next(),
+            unplugged_energy,
+        })
+    }
+
+    /// The reading currently shown: one battery or the combined pack.
+    pub fn reading(&self) -> Option<&Reading> {
+        self.battery.as_ref()
+    }
+
+    /// All batteries combined, regardless of which one is shown.
+    pub fn total(&self) -> Option<Reading> {
+        Reading::combine(&self.batteries)
+    }
+
+    /// Switches to the next battery, then back to the combined view.
+    pub fn cycle_battery(&mut self) {
+        if self.batteries.len() < 2 {
+            return;
+        }
+        self.selected = match self.selected {
+            None => Some(0),
+            Some(index) if index + 1 < self.batteries.len() => Some(index + 1),
+            Some(_) => None,
+        };
+        self.update_view();
+    }
+
+    /// Describes which battery is shown when there is more than one.
+    pub fn battery_label(&self) -> Option<String> {
+        let count = self.batteries.len();
+        if count < 2 {
+            return None;
+        }
+        Some(match self.selected {
+            Some(index) => format!("battery {}/{}", index + 1, count),
+            None => format!("all {} batteries", count),
+        })
+    }
+
+    fn update_view(&mut self) {
+        self.battery = match self.selected {
+            Some(index) => self.batteries.get(index).cloned(),
+            None => self.total(),
+        };
+    }
+
+    /// Looks up where the ongoing discharge started in the daemon history,
+    /// so "used since unplug" covers time before amptop was launched.
+    pub fn restore_unplugged_energy(&mut self) {
+        let Some(battery) = self.total() else {
+            return;
+        };
+        if battery.state != battery::State::Discharging {
+            return;
+        }
+        if let Ok(Some(start)) = BatteryDaemon::last_unplug_percent() {
+            let estimate = battery.energy_full * (start / 100.0);
+            self.unplugged_energy = Some(estimate.max(battery.energy));
+        }
     }
 
     pub fn refresh(&mut self) -> Result<()> {
-        if let Some(ref mut battery) = self.battery {
-            self.manager.refresh(battery)?;
+        let was_discharging = self
+            .total()
+            .is_some_and(|b| b.state == battery::State::Discharging);
+
+        self.batteries = self.backend.read()?;
+        if self
+            .selected
+            .is_some_and(|index| index >= self.batteries.len())
+        {
+            self.selected = None;
         }
+        self.update_view();
+
+        if let Some(battery) = self.total() {
+            match battery.state {
+                battery::State::Discharging if !was_discharging => {
+                    self.unplugged_energy = Some(battery.energy);
+                }
+                battery::State::Discharging => {}
+                _ => self.unplugged_energy = None,
+            }
+        }
+        self.adapter = Adapter::detect();
         Ok(())
     }
 
+    /// Re-reads details that rarely change, such as the charge thresholds.
+    pub fn refresh_details(&mut self) {
+        self.charge_thresholds = charge_limit::read().into_iter().next();
+    }
+
+    pub fn summary(&self) -> Option<Summary> {
+        let b = self.battery.as_ref()?;
+        Some(Summary {
+            percent: b.state_of_charge.get::<percent>(),
+            state: b.state.to_string(),
+            energy_rate_watts: b.energy_rate.get::<watt>(),
+            energy_wh: b.energy.get::<watt_hour>(),
+            energy_full_wh: b.energy_full.get::<watt_hour>(),
+            health_percent: b.state_of_health.get::<percent>(),
+            time_to_full_secs: b.time_to_full.map(|time| time.get::<second>() as u64),
+            time_to_empty_secs: b.time_to_empty.map(|time| time.get::<second>() as u64),
+            temperature_celsius: self.temperature_celsius(),
+        })
+    }
+
     pub fn has_battery(&self) -> bool {
         self.battery.is_some()
     }
 
     pub fn state_of_charge(&self) -> Option<(f64, f64)> {
         self.battery.as_ref().map(|b| {
-            let ratio_value = f64::from(b.state_of_charge().get::<ratio>());
-            let percent_value = f64::from(b.state_of_charge().get::<percent>());
+            let ratio_value = f64::from(b.state_of_charge.get::<ratio>());
+            let percent_value = f64::from(b.state_of_charge.get::<percent>());
             (ratio_value, percent_value)
         })
     }
 
+    /// Firmware charge limit, in percent, when set below 100%.
+    pub fn charge_limit(&self) -> Option<f64> {
+        self.charge_thresholds.as_ref().and_then(Thresholds::limit)
+    }
+
+    pub fn charge_thresholds(&self) -> Option<&Thresholds> {
+        self.charge_thresholds.as_ref()
+    }
+
+    pub fn health_status(&self) -> Option<&str> {
+        self.battery
+            .as_ref()
+            .and_then(|b| b.health_status.as_deref())
+    }
+
+    pub fn manufactured(&self) -> Option<NaiveDate> {
+        self.battery.as_ref().and_then(|b| b.manufactured)
+    }
+
     pub fn vendor(&self) -> Option<&str> {
-        self.battery.as_ref().and_then(|b| b.vendor())
+        self.battery.as_ref().and_then(|b| b.vendor.as_deref())
     }
 
     pub fn model(&self) -> Option<&str> {
-        self.battery.as_ref().and_then(|b| b.model())
+        self.battery.as_ref().and_then(|b| b.model.as_deref())
     }
 
     pub fn serial_number(&self) -> Option<&str> {
-        self.battery.as_ref().and_then(|b| b.serial_number())
+        self.battery
+            .as_ref()
+            .and_then(|b| b.serial_number.as_deref())
     }
 
     pub fn technology(&self) -> Option<String> {
-        self.battery.as_ref().map(|b| format!("{}", b.technology()))
+        self.battery.as_ref().map(|b| format!("{}", b.technology))
+    }
+
+    pub fn chemistry(&self) -> Option<Chemistry> {
+        self.battery
+            .as_ref()
+            .and_then(|b| Chemistry::from_technology(b.technology))
     }
 
     pub fn state(&self) -> Option<String> {
-        self.battery.as_ref().map(|b| format!("{}", b.state()))
+        self.battery.as_ref().map(|b| format!("{}", b.state))
     }
 
     pub fn battery_state(&self) -> Option<battery::State> {
-        self.battery.as_ref().map(|b| b.state())
+        self.battery.as_ref().map(|b| b.state)
     }
 
     pub fn cycle_count(&self) -> Option<u32> {
-        self.battery.as_ref().and_then(|b| b.cycle_count())
+        self.battery.as_ref().and_then(|b| b.cycle_count)
     }
 
     pub fn energy_rate(&self) -> Option<String> {
         self.battery.as_ref().map(|b| {
             format!(
                 "{:.2} {}",
-                b.energy_rate().get::<watt>(),
+                b.energy_rate.get::<watt>(),
                 watt::abbreviation()
             )
         })
     }
 
+    /// When plugged into a charger that can't keep up and the battery is still
+    /// draining, returns the watts the charger provides and the watts the
+    /// workload actually needs.
+    pub fn charger_shortfall(&self) -> Option<(f64, f64)> {
+        let adapter = self.adapter.as_ref().filter(|a| a.online)?;
+        let provided = adapter.watts?;
+        let battery = self.battery.as_ref()?;
+        if battery.state != battery::State::Discharging {
+            return None;
+        }
+        let drain = f64::from(battery.energy_rate.get::<watt>());
+        Some((provided, provided + drain))
+    }
+
     pub fn voltage(&self) -> Option<String> {
         self.battery
             .as_ref()
-            .map(|b| format!("{:.2} {}", b.voltage().get::<volt>(), volt::abbreviation()))
+            .map(|b| format!("{:.2} {}", b.voltage.get::<volt>(), volt::abbreviation()))
     }
 
     pub fn capacity(&self) -> Option<String> {
         self.battery.as_ref().map(|b| {
             format!(
                 "{:.2} {}",
-                b.state_of_health().get::<percent>(),
+                b.state_of_health.get::<percent>(),
                 percent::abbreviation()
             )
         })
     }
 
     pub fn current_energy(&self, unit: Unit) -> Option<String> {
-        self.battery.as_ref().map(|b| match unit {
-            Unit::Human => format!(
-                "{:.2} {}",
-                b.energy().get::<watt_hour>(),
-                watt_hour::abbreviation()
-            ),
-            Unit::Si => format!("{:.2} {}", b.energy().get::<joule>(), joule::abbreviation()),
-        })
+        let b = self.battery.as_ref()?;
+        format_energy(b, b.energy, b.charge.map(|c| c.now), unit)
     }
 
-    pub fn energy_full(&self, units: Unit) -> Option<String> {
-        self.battery.as_ref().map(|b| match units {
-            Unit::Human => format!(
-                "{:.2} {}",
-                b.energy_full().get::<watt_hour>(),
-                watt_hour::abbreviation()
-            ),
-            Unit::Si => format!(
-                "{:.2} {}",
-                b.energy_full().get::<joule>(),
-                joule::abbreviation()
-            ),
-        })
+    /// Energy consumed since the battery was last unplugged. Only tracked
+    /// for all batteries together.
+    pub fn energy_used(&self, unit: Unit) -> Option<String> {
+        if self.selected.is_some() {
+            return None;
+        }
+        let battery = self.battery.as_ref()?;
+        let used = self.unplugged_energy? - battery.energy;
+        let used = used.max(Energy::new::<watt_hour>(0.0));
+        format_energy(battery, used, None, unit)
     }
 
-    pub fn energy_full_design(&self, units: Unit) -> Option<String> {
-        self.battery.as_ref().map(|b| match units {
-            Unit::Human => format!(
-                "{:.2} {}",
-                b.energy_full_design().get::<watt_hour>(),
-                watt_hour::abbreviation()
-            ),
-            Unit::Si => format!(
-                "{:.2} {}",
-                b.energy_full_design().get::<joule>(),
-                joule::abbreviation()
-            ),
-        })
+    pub fn energy_full(&self, unit: Unit) -> Option<String> {
+        let b = self.battery.as_ref()?;
+        format_energy(b, b.energy_full, b.charge.map(|c| c.full), unit)
+    }
+
+    pub fn energy_full_design(&self, unit: Unit) -> Option<String> {
+        let b = self.battery.as_ref()?;
+        format_energy(
+            b,
+            b.energy_full_design,
+            b.charge.map(|c| c.full_design),
+            unit,
+        )
     }
 
     pub fn time_to_full(&self) -> Option<String> {
         self.battery.as_ref().and_then(|b| {
-            b.time_to_full().map(|time| {
-                humantime::format_duration(Duration::from_secs(time.get::<second>() as u64))
-                    .to_string()
-            })
+            b.time_to_full
+                .map(|time| durations::format(Duration::from_secs(time.get::<second>() as u64)))
         })
     }
 
     pub fn time_to_empty(&self) -> Option<String> {
         self.battery.as_ref().and_then(|b| {
-            b.time_to_empty().map(|time| {
-                humantime::format_duration(Duration::from_secs(time.get::<second>() as u64))
-                    .to_string()
-            })
+            b.time_to_empty
+                .map(|time| durations::format(Duration::from_secs(time.get::<second>() as u64)))
         })
     }
 
-    pub fn temperature(&self, units: Unit) -> Option<String> {
+    pub fn temperature_celsius(&self) -> Option<f32> {
+        self.battery
+            .as_ref()
+            .and_then(|b| b.temperature)
+            .map(|temp| temp.get::<degree_celsius>())
+    }
+
+    pub fn temperature(&self, unit: TemperatureUnit) -> Option<String> {
         self.battery.as_ref().and_then(|b| {
-            b.temperature().map(|temp| match units {
-                Unit::Human => format!(
+            b.temperature.map(|temp| match unit {
+                TemperatureUnit::Celsius => format!(
                     "{:.2} {}",
                     temp.get::<degree_celsius>(),
                     degree_celsius::abbreviation()
                 ),
-                Unit::Si => format!("{:.2} {}", temp.get::<kelvin>(), kelvin::abbreviation()),
+                TemperatureUnit::Fahrenheit => format!(
+                    "{:.2} {}",
+                    temp.get::<degree_fahrenheit>(),
+                    degree_fahrenheit::abbreviation()
+                ),
+                TemperatureUnit::Kelvin => {
+                    format!("{:.2} {}", temp.get::<kelvin>(), kelvin::abbreviation())
+                }
             })
         })
     }
@@ -0,0 +1,411 @@
+//! Keys bound to each TUI action. The `[keys]` section of the config file
+//! replaces the keys of single actions; the rest keep their defaults, less
+//! any key given to another action.
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::BTreeMap;
+
+/// Something a key does in the TUI, named in `[keys]` by `name`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Help,
+    Quit,
+    NextTab,
+    PreviousTab,
+    WidenRange,
+    NarrowRange,
+    PanLeft,
+    PanRight,
+    /// Also selects the previous host on the Fleet tab
+    ZoomIn,
+    /// Also selects the next host on the Fleet tab
+    ZoomOut,
+    Latest,
+    GoTo,
+    SwitchBattery,
+    ChartAxis,
+    ToggleUnit,
+    Peripherals,
+    Overhead,
+    Finish,
+}
+
+impl Action {
+    /// In the order the help overlay lists them.
+    pub const ALL: [Action; 18] = [
+        Action::Help,
+        Action::Quit,
+        Action::NextTab,
+        Action::PreviousTab,
+        Action::WidenRange,
+        Action::NarrowRange,
+        Action::PanLeft,
+        Action::PanRight,
+        Action::ZoomIn,
+        Action::ZoomOut,
+        Action::Latest,
+        Action::GoTo,
+        Action::SwitchBattery,
+        Action::ChartAxis,
+        Action::ToggleUnit,
+        Action::Peripherals,
+        Action::Overhead,
+        Action::Finish,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Help => "help",
+            Action::Quit => "quit",
+            Action::NextTab => "next_tab",
+            Action::PreviousTab => "previous_tab",
+            Action::WidenRange => "widen_range",
+            Action::NarrowRange => "narrow_range",
+            Action::PanLeft => "pan_left",
+            Action::PanRight => "pan_right",
+            Action::ZoomIn => "zoom_in",
+            Action::ZoomOut => "zoom_out",
+            Action::Latest => "latest",
+            Action::GoTo => "go_to",
+            Action::SwitchBattery => "switch_battery",
+            Action::ChartAxis => "chart_axis",
+            Action::ToggleUnit => "toggle_unit",
+            Action::Peripherals => "peripherals",
+            Action::Overhead => "overhead",
+            Action::Finish => "finish",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Action::Help => "Show or hide this help",
+            Action::Quit => "Quit",
+            Action::NextTab => "Next tab",
+            Action::PreviousTab => "Previous tab",
+            Action::WidenRange => "Widen the history range",
+            Action::NarrowRange => "Narrow the history range",
+            Action::PanLeft => "Pan the history graph back",
+            Action::PanRight => "Pan the history graph forward",
+            Action::ZoomIn => "Zoom in; previous host on Fleet",
+            Action::ZoomOut => "Zoom out; next host on Fleet",
+            Action::Latest => "Jump back to the latest history",
+            Action::GoTo => "Center the history graph on a date",
+            Action::SwitchBattery => "Cycle between batteries",
+            Action::ChartAxis => "Graph history in percent, Wh or kJ",
+            Action::ToggleUnit => "Switch units: human °C, human °F, SI, mAh",
+            Action::Peripherals => "Show peripheral batteries",
+            Action::Overhead => "Show amptop's own CPU, wakeups and disk use",
+            Action::Finish => "Ask whether the charge lasts for a task",
+        }
+    }
+
+    fn default_keys(self) -> &'static [&'static str] {
+        match self {
+            Action::Help => &["?"],
+            Action::Quit => &["q", "Esc"],
+            Action::NextTab => &["Tab"],
+            Action::PreviousTab => &["Shift+Tab"],
+            Action::WidenRange => &["+", "="],
+            Action::NarrowRange => &["-"],
+            Action::PanLeft => &["Left"],
+            Action::PanRight => &["Right"],
+            Action::ZoomIn => &["Up"],
+            Action::ZoomOut => &["Down"],
+            Action::Latest => &["End"],
+            Action::GoTo => &["g", ":"],
+            Action::SwitchBattery => &["b"],
+            Action::ChartAxis => &["a"],
+            Action::ToggleUnit => &["u"],
+            Action::Peripherals => &["p"],
+            Action::Overhead => &["o"],
+            Action::Finish => &["f"],
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|action| action.name() == name)
+    }
+}
+
+/// A key with the Ctrl and Alt modifiers held with it. Shift is part of
+/// the character typed, except in `Shift+Tab`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Key {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl Key {
+    /// Parses e.g. `q`, `Esc`, `Shift+Tab`, `Ctrl+n`, `PageDown` or `F5`.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let invalid = || format!("{} isn't a valid key", spec);
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = spec;
+        // A lone "+" is the key itself, not a modifier separator.
+        while rest.len() > 1
+            && let Some((modifier, key)) = rest.split_once('+')
+        {
+            match modifier.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => modifiers |= KeyModifiers::CONTROL,
+                "alt" => modifiers |= KeyModifiers::ALT,
+                "shift" => modifiers |= KeyModifiers::SHIFT,
+                _ => return Err(invalid()),
+            }
+            rest = key;
+        }
+
+        let mut chars = rest.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match rest.to_ascii_lowercase().as_str() {
+                "esc" | "escape" => KeyCode::Esc,
+                "enter" | "return" => KeyCode::Enter,
+                "tab" => KeyCode::Tab,
+                "backtab" => KeyCode::BackTab,
+                "backspace" => KeyCode::Backspace,
+                "space" => KeyCode::Char(' '),
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "pageup" => KeyCode::PageUp,
+                "pagedown" => KeyCode::PageDown,
+                "insert" => KeyCode::Insert,
+                "delete" => KeyCode::Delete,
+                other => match other.strip_prefix('f').and_then(|n| n.parse().ok()) {
+                    Some(n @ 1..=12) => KeyCode::F(n),
+                    _ => return Err(invalid()),
+                },
+            },
+        };
+
+        let shift = modifiers.contains(KeyModifiers::SHIFT);
+        let code = match code {
+            KeyCode::Tab if shift => KeyCode::BackTab,
+            KeyCode::Char(c) if shift => KeyCode::Char(c.to_ascii_uppercase()),
+            code => code,
+        };
+        Ok(Self::new(code, modifiers))
+    }
+
+    fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        Self {
+            code,
+            modifiers: modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT),
+        }
+    }
+
+    pub fn from_event(event: &KeyEvent) -> Self {
+        Self::new(event.code, event.modifiers)
+    }
+
+    /// As written in the config file, e.g. `Ctrl+n` or `Shift+Tab`.
+    pub fn spec(&self) -> String {
+        let mut spec = String::new();
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            spec.push_str("Ctrl+");
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            spec.push_str("Alt+");
+        }
+        let key = match self.code {
+            KeyCode::Char(' ') => "Space".to_string(),
+            KeyCode::Char(c) => c.to_string(),
+            KeyCode::BackTab => "Shift+Tab".to_string(),
+            KeyCode::F(n) => format!("F{}", n),
+            KeyCode::PageUp => "PageUp".to_string(),
+            KeyCode::PageDown => "PageDown".to_string(),
+            code => format!("{:?}", code),
+        };
+        spec.push_str(&key);
+        spec
+    }
+
+    /// As shown in the TUI, with arrows for the arrow keys.
+    pub fn label(&self) -> String {
+        if self.modifiers.is_empty() {
+            let arrow = match self.code {
+                KeyCode::Left => Some("←"),
+                KeyCode::Right => Some("→"),
+                KeyCode::Up => Some("↑"),
+                KeyCode::Down => Some("↓"),
+                _ => None,
+            };
+            if let Some(arrow) = arrow {
+                return arrow.to_string();
+            }
+        }
+        self.spec()
+    }
+
+    /// Keys that aren't remappable: the digits that go to a tab and Ctrl+C.
+    fn reserved(&self) -> bool {
+        let plain = self.modifiers.is_empty();
+        match self.code {
+            KeyCode::Char('1'..='9') => plain,
+            KeyCode::Char('c') => self.modifiers == KeyModifiers::CONTROL,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keymap {
+    /// Keys of each action, in the order of `Action::ALL`
+    bindings: Vec<(Action, Vec<Key>)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let bindings = Action::ALL
+            .into_iter()
+            .map(|action| {
+                let keys = action
+                    .default_keys()
+                    .iter()
+                    .map(|spec| Key::parse(spec).expect("default keys are valid"))
+                    .collect();
+                (action, keys)
+            })
+            .collect();
+        Self { bindings }
+    }
+}
+
+impl Keymap {
+    /// The default keys with those of the actions in `overrides` replaced,
+    /// and taken from the defaults of other actions. Fails on unknown
+    /// actions and keys, on reserved keys and on a key given to two actions
+    /// in `overrides`.
+    pub fn with_overrides(overrides: &BTreeMap<String, Vec<String>>) -> Result<Self, String> {
+        let mut keymap = Self::default();
+        let mut overridden = Vec::new();
+        for (name, specs) in overrides {
+            let action = Action::from_name(name).ok_or_else(|| {
+                let names: Vec<_> = Action::ALL.iter().map(|action| action.name()).collect();
+                format!(
+                    "{} isn't an action, expected one of {}",
+                    name,
+                    names.join(", ")
+                )
+            })?;
+            if specs.is_empty() {
+                return Err(format!("{} needs at least one key", name));
+            }
+            let keys = specs
+                .iter()
+                .map(|spec| Key::parse(spec))
+                .collect::<Result<Vec<_>, _>>()?;
+            if let Some(key) = keys.iter().find(|key| key.reserved()) {
+                return Err(format!("{} is reserved and can't be remapped", key.spec()));
+            }
+            keymap.bindings[Action::ALL.iter().position(|&a| a == action).unwrap()].1 = keys;
+            overridden.push(action);
+        }
+
+        // A key the config gives to one action no longer does its default one.
+        let taken: Vec<Key> = keymap
+            .bindings
+            .iter()
+            .filter(|(action, _)| overridden.contains(action))
+            .flat_map(|(_, keys)| keys.iter().copied())
+            .collect();
+        for (action, keys) in &mut keymap.bindings {
+            if !overridden.contains(action) {
+                keys.retain(|key| !taken.contains(key));
+            }
+        }
+
+        for (i, (action, keys)) in keymap.bindings.iter().enumerate() {
+            for key in keys {
+                if let Some((other, _)) = keymap.bindings[i + 1..]
+                    .iter()
+                    .find(|(_, other_keys)| other_keys.contains(key))
+                {
+                    return Err(format!(
+                        "{} is bound to both {} and {}",
+                        key.spec(),
+                        action.name(),
+                        other.name()
+                    ));
+                }
+            }
+        }
+        Ok(keymap)
+    }
+
+    /// The action bound to the pressed key, if any.
+    pub fn action(&self, event: &KeyEvent) -> Option<Action> {
+        let key = Key::from_event(event);
+        self.bindings
+            .iter()
+            .find(|(_, keys)| keys.contains(&key))
+            .map(|(action, _)| *action)
+    }
+
+    pub fn keys(&self, action: Action) -> &[Key] {
+        self.bindings
+            .iter()
+            .find(|(a, _)| *a == action)
+            .map(|(_, keys)| keys.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Every key of `action`, e.g. `q, Esc`.
+    pub fn label(&self, action: Action) -> String {
+        let labels: Vec<_> = self.keys(action).iter().map(Key::label).collect();
+        labels.join(", ")
+    }
+
+    /// The first key of `action`, for hints.
+    pub fn short_label(&self, action: Action) -> String {
+        self.keys(action)
+            .first()
+            .map(Key::label)
+            .unwrap_or_default()
+    }
+
+    /// The keys that move the history graph, for its bottom border.
+    pub fn graph_hint(&self) -> String {
+        let pair = |a, b| format!("{}/{}", self.short_label(a), self.short_label(b));
+        format!(
+            " {} range  {} pan  {} zoom  {} latest ",
+            pair(Action::WidenRange, Action::NarrowRange),
+            pair(Action::PanLeft, Action::PanRight),
+            pair(Action::ZoomIn, Action::ZoomOut),
+            self.short_label(Action::Latest)
+        )
+    }
+
+    /// `(action, keys)` for the actions whose keys differ from the
+    /// defaults, as written in the config file. Default keys that moved to
+    /// another action don't count as a difference.
+    pub fn overrides(&self) -> BTreeMap<String, Vec<String>> {
+        let defaults = Self::default();
+        self.bindings
+            .iter()
+            .zip(&defaults.bindings)
+            .filter(|((action, keys), (_, default))| {
+                let bound_elsewhere = |key: &Key| {
+                    self.bindings
+                        .iter()
+                        .any(|(other, keys)| other != action && keys.contains(key))
+                };
+                let kept: Vec<Key> = default
+                    .iter()
+                    .copied()
+                    .filter(|key| !bound_elsewhere(key))
+                    .collect();
+                *keys != kept
+            })
+            .map(|((action, keys), _)| {
+                (
+                    action.name().to_string(),
+                    keys.iter().map(Key::spec).collect(),
+                )
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,115 @@
+//! Live feed of new snapshots from the daemon to the TUI.
+//!
+//! The daemon listens on a Unix socket next to its PID file and writes each
+//! snapshot it records to every connected client, one line of JSON per
+//! snapshot. A TUI subscribed to it updates the history graph as samples
+//! arrive instead of querying the database for them.
+
+use crate::daemon::BatterySnapshot;
+use std::fs;
+use std::io::{self, BufRead, BufReader, ErrorKind, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+/// How long the daemon waits on a client that stopped reading before
+/// dropping it.
+const WRITE_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// The daemon's end: the socket and the clients connected to it.
+#[derive(Debug)]
+pub struct Publisher {
+    path: PathBuf,
+    listener: UnixListener,
+    subscribers: Vec<UnixStream>,
+}
+
+impl Publisher {
+    /// Listens on `path`, replacing a socket left behind by a daemon that
+    /// didn't exit cleanly.
+    pub fn bind(path: PathBuf) -> io::Result<Self> {
+        match fs::remove_file(&path) {
+            Err(e) if e.kind() != ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+        let listener = UnixListener::bind(&path)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            path,
+            listener,
+            subscribers: Vec::new(),
+        })
+    }
+
+    /// Sends `snapshot` to every client, accepting the ones that connected
+    /// since the last call. Clients that disconnected or stopped reading
+    /// are dropped.
+    pub fn publish(&mut self, snapshot: &BatterySnapshot) {
+        while let Ok((stream, _)) = self.listener.accept() {
+            if stream.set_nonblocking(false).is_ok()
+                && stream.set_write_timeout(Some(WRITE_TIMEOUT)).is_ok()
+            {
+                self.subscribers.push(stream);
+            }
+        }
+        if self.subscribers.is_empty() {
+            return;
+        }
+
+        let mut line = serde_json::to_string(snapshot).expect("snapshot is serializable");
+        line.push('\n');
+        self.subscribers
+            .retain_mut(|stream| stream.write_all(line.as_bytes()).is_ok());
+    }
+}
+
+impl Drop for Publisher {
+    fn drop(&mut self) {
+        fs::remove_file(&self.path).ok();
+    }
+}
+
+/// The TUI's end: snapshots read from the daemon on a background thread.
+#[derive(Debug)]
+pub struct Subscription {
+    receiver: Receiver<BatterySnapshot>,
+}
+
+impl Subscription {
+    /// Connects to the daemon listening on `path`. Fails when no daemon is
+    /// running.
+    pub fn connect(path: &Path) -> io::Result<Self> {
+        let stream = UnixStream::connect(path)?;
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stream).lines() {
+                let Ok(line) = line else {
+                    return;
+                };
+                // Skip what a different version of the daemon sends.
+                let Ok(snapshot) = serde_json::from_str(&line) else {
+                    continue;
+                };
+                if sender.send(snapshot).is_err() {
+                    return;
+                }
+            }
+        });
+        Ok(Self { receiver })
+    }
+
+    /// Snapshots received since the last call, and whether the daemon has
+    /// closed the connection.
+    pub fn poll(&self) -> (Vec<BatterySnapshot>, bool) {
+        let mut snapshots = Vec::new();
+        loop {
+            match self.receiver.try_recv() {
+                Ok(snapshot) => snapshots.push(snapshot),
+                Err(TryRecvError::Empty) => return (snapshots, false),
+                Err(TryRecvError::Disconnected) => return (snapshots, true),
+            }
+        }
+    }
+}
@@ -0,0 +1,103 @@
+//! Loads the history graph's samples on a background thread, so a wide
+//! window over a multi-year database doesn't stall the event loop.
+//!
+//! The window is queried in slices, newest first, and each slice is sent
+//! as soon as it is read so the graph fills in while the rest loads. The
+//! estimated cycle count, which reads every session, comes after.
+
+use crate::daemon::{BatterySnapshot, History};
+use crate::errors::Result;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+
+/// Number of slices a window is queried in.
+const SLICES: i64 = 8;
+
+/// Samples between `since` and `until` (inclusive), newest first.
+#[derive(Debug)]
+pub struct Slice {
+    pub since: i64,
+    pub until: i64,
+    pub logs: Result<Vec<BatterySnapshot>>,
+}
+
+#[derive(Debug)]
+pub struct HistoryLoader {
+    /// Bounds of the whole window being loaded
+    pub since: i64,
+    pub until: i64,
+    receiver: Receiver<Slice>,
+    cycles: Receiver<Option<f64>>,
+}
+
+impl HistoryLoader {
+    /// Starts loading `since..=until` on its own connection, then the
+    /// estimated cycle count if `cycles` is set. Dropping the loader stops
+    /// it after the slice in progress.
+    pub fn start(since: i64, until: i64, cycles: bool) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let (cycles_sender, cycles_receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let db = match History::open() {
+                Ok(db) => db,
+                Err(e) => {
+                    sender
+                        .send(Slice {
+                            since,
+                            until,
+                            logs: Err(e),
+                        })
+                        .ok();
+                    return;
+                }
+            };
+
+            let step = ((until - since) / SLICES).max(1);
+            let mut end = until;
+            while end >= since {
+                let start = (end - step + 1).max(since);
+                let logs = db.logs_between(start, end).map(|mut logs| {
+                    logs.reverse();
+                    logs
+                });
+                let failed = logs.is_err();
+                let slice = Slice {
+                    since: start,
+                    until: end,
+                    logs,
+                };
+                if sender.send(slice).is_err() || failed {
+                    return;
+                }
+                end = start - 1;
+            }
+            if cycles && let Ok(estimated) = db.estimated_cycles() {
+                cycles_sender.send(estimated).ok();
+            }
+        });
+
+        Self {
+            since,
+            until,
+            receiver,
+            cycles: cycles_receiver,
+        }
+    }
+
+    /// Slices read since the last call, and whether loading has finished.
+    pub fn poll(&self) -> (Vec<Slice>, bool) {
+        let mut slices = Vec::new();
+        loop {
+            match self.receiver.try_recv() {
+                Ok(slice) => slices.push(slice),
+                Err(TryRecvError::Empty) => return (slices, false),
+                Err(TryRecvError::Disconnected) => return (slices, true),
+            }
+        }
+    }
+
+    /// The estimated cycle count, once it has been read.
+    pub fn cycles(&self) -> Option<Option<f64>> {
+        self.cycles.try_recv().ok()
+    }
+}
@@ -1,26 +1,81 @@
+mod adapter;
+mod alerts;
+mod annotations;
 mod application;
+mod backend;
+mod bugreport;
+mod charge_limit;
+mod chemistry;
+mod clock;
+mod collector;
+mod compare;
 mod config;
 mod daemon;
+mod db;
+mod demo;
+mod desktop;
+mod downtime;
+mod durations;
 mod errors;
+mod export;
+mod forecast;
+mod graph;
+mod hibernate;
+mod idle;
 mod info;
+mod keymap;
+mod live;
+mod loader;
+mod migrations;
+mod mqtt;
+mod overhead;
+mod power_saver;
+mod report;
+mod service;
+mod sessions;
+mod stats;
+mod status_bar;
+mod svg;
+mod sysfs;
+mod tags;
+mod theme;
+mod uevent;
 mod ui;
+mod upower;
+mod wizard;
+mod workload;
+mod xdg;
 
 use crate::config::Config;
 use crate::errors::Result;
 use application::Application;
-use clap::Parser;
 use crossterm::{
     event::{DisableMouseCapture, EnableMouseCapture},
     execute,
     terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
 };
+use demo::Demo;
+use ratatui::DefaultTerminal;
 use ratatui::prelude::*;
+use wizard::Wizard;
 
 fn main() -> Result<()> {
-    let config = Config::parse();
-    if config.handle_command().unwrap_or(false) {
-        return Ok(());
+    let config = Config::load_or_exit();
+    if let Some(root) = config.sysfs_root() {
+        sysfs::set_root(root.to_path_buf());
     }
+    config.backend().select();
+    match config.handle_command() {
+        Some(0) => return Ok(()),
+        Some(code) => std::process::exit(code),
+        None => {}
+    }
+    // Kept until the TUI exits, then removed
+    let _demo = if config.demo() {
+        Some(Demo::start()?)
+    } else {
+        None
+    };
 
     let mut stdout = std::io::stdout();
 
@@ -28,7 +83,7 @@ fn main() -> Result<()> {
     execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
 
     let mut terminal = Terminal::new(CrosstermBackend::new(stdout))?;
-    let app_result = Application::init(config)?.run(&mut terminal);
+    let app_result = run_tui(&mut terminal, config);
 
     disable_raw_mode()?;
     execute!(
@@ -39,3 +94,20 @@ fn main() -> Result<()> {
 
     app_result
 }
+
+fn run_tui(terminal: &mut DefaultTerminal, mut config: Config) -> Result<()> {
+    let mut setup_done = false;
+    if Wizard::is_first_run(&config) {
+        setup_done = !Wizard::new().run(terminal, &config)?.is_empty();
+        if setup_done {
+            // Pick up the config file the wizard just wrote.
+            config = Config::load()?;
+        }
+    }
+
+    let mut app = Application::init(config)?;
+    if setup_done {
+        app.show_toast("Setup complete");
+    }
+    app.run(terminal)
+}
@@ -0,0 +1,259 @@
+use crate::errors::{Error, Result};
+use rusqlite::{Connection, OptionalExtension, Transaction, TransactionBehavior};
+
+/// Schema changes in the order they were made. A database at version `n`
+/// has had the first `n` applied. Append new steps; never edit or reorder
+/// released ones.
+///
+/// Databases from before versioning start at 0 but may already have any of
+/// the first four steps' tables and columns, so those steps only create
+/// what is missing.
+const MIGRATIONS: &[fn(&Connection) -> Result<()>] = &[
+    initial_schema,
+    battery_aggregates,
+    health_logs,
+    power_watts,
+    annotations,
+    tags,
+    adapter,
+    daily_summaries,
+    sessions,
+    voltage,
+];
+
+/// Brings the database up to the latest schema version. Each step runs in
+/// its own transaction together with the version bump, so an interrupted
+/// upgrade resumes where it stopped. The transactions take the write lock
+/// up front and check the version again, so when the TUI and the daemon
+/// start together only one of them applies each step.
+pub fn migrate(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS schema_version (version INTEGER NOT NULL)",
+        [],
+    )?;
+    let version = current_version(conn)?;
+    if version > MIGRATIONS.len() {
+        return Err(Error::SchemaTooNew(version));
+    }
+
+    for (index, step) in MIGRATIONS.iter().enumerate().skip(version) {
+        let tx = Transaction::new_unchecked(conn, TransactionBehavior::Immediate)?;
+        if current_version(&tx)? > index {
+            continue;
+        }
+        step(&tx)?;
+        tx.execute("DELETE FROM schema_version", [])?;
+        tx.execute(
+            "INSERT INTO schema_version (version) VALUES (?1)",
+            [index as i64 + 1],
+        )?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+pub fn current_version(conn: &Connection) -> Result<usize> {
+    let version: Option<i64> = conn
+        .query_row("SELECT MAX(version) FROM schema_version", [], |row| {
+            row.get(0)
+        })
+        .optional()?
+        .flatten();
+    Ok(version.unwrap_or(0) as usize)
+}
+
+fn initial_schema(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS battery_logs (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            percent REAL NOT NULL,
+            timestamp INTEGER NOT NULL,
+            status TEXT NOT NULL
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_timestamp ON battery_logs(timestamp)",
+        [],
+    )?;
+    add_column_if_missing(conn, "battery_logs", "energy", "REAL")?;
+    add_column_if_missing(conn, "battery_logs", "adapter_watts", "REAL")?;
+    add_column_if_missing(conn, "battery_logs", "temperature", "REAL")?;
+    add_column_if_missing(conn, "battery_logs", "health", "REAL")?;
+    add_column_if_missing(conn, "battery_logs", "host", "TEXT")?;
+    add_column_if_missing(conn, "battery_logs", "idle", "INTEGER")?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS daemon_events (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            timestamp INTEGER NOT NULL,
+            event TEXT NOT NULL
+        )",
+        [],
+    )?;
+    add_column_if_missing(conn, "daemon_events", "percent", "REAL")?;
+    add_column_if_missing(conn, "daemon_events", "boot_id", "TEXT")?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS daemon_runs (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            started_at INTEGER NOT NULL,
+            last_seen INTEGER NOT NULL,
+            stopped_at INTEGER,
+            interval_secs INTEGER NOT NULL
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Downsampled history: one row per host and bucket, averaged over the
+/// `samples` rows it replaced.
+fn battery_aggregates(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS battery_aggregates (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            bucket_secs INTEGER NOT NULL,
+            samples INTEGER NOT NULL,
+            percent REAL NOT NULL,
+            timestamp INTEGER NOT NULL,
+            status TEXT NOT NULL,
+            energy REAL,
+            adapter_watts REAL,
+            temperature REAL,
+            health REAL,
+            host TEXT,
+            idle INTEGER
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_aggregates_timestamp ON battery_aggregates(timestamp)",
+        [],
+    )?;
+    Ok(())
+}
+
+fn health_logs(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS health_logs (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            timestamp INTEGER NOT NULL,
+            host TEXT,
+            energy_full REAL NOT NULL,
+            energy_full_design REAL NOT NULL,
+            cycle_count INTEGER
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+fn power_watts(conn: &Connection) -> Result<()> {
+    add_column_if_missing(conn, "battery_logs", "power_watts", "REAL")?;
+    add_column_if_missing(conn, "battery_aggregates", "power_watts", "REAL")?;
+    Ok(())
+}
+
+fn annotations(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE annotations (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            timestamp INTEGER NOT NULL,
+            host TEXT,
+            text TEXT NOT NULL
+        );
+        CREATE INDEX idx_annotations_timestamp ON annotations(timestamp);",
+    )?;
+    Ok(())
+}
+
+/// Named activity intervals; `stopped_at` is NULL while one is running.
+fn tags(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE tags (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            host TEXT,
+            name TEXT NOT NULL,
+            started_at INTEGER NOT NULL,
+            stopped_at INTEGER
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Which charger was connected, to compare chargers.
+fn adapter(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "ALTER TABLE battery_logs ADD COLUMN adapter TEXT;
+        ALTER TABLE battery_aggregates ADD COLUMN adapter TEXT;",
+    )?;
+    Ok(())
+}
+
+/// The daemon's end-of-day summaries, one per host and local date.
+fn daily_summaries(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE daily_summaries (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            host TEXT NOT NULL,
+            date TEXT NOT NULL,
+            battery_hours REAL NOT NULL,
+            avg_watts REAL,
+            cycles REAL NOT NULL,
+            health REAL,
+            UNIQUE (host, date)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Charge and discharge sessions detected in the history, so they outlive
+/// the samples retention deletes.
+fn sessions(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE sessions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            host TEXT NOT NULL,
+            kind TEXT NOT NULL,
+            started_at INTEGER NOT NULL,
+            ended_at INTEGER NOT NULL,
+            start_percent REAL NOT NULL,
+            end_percent REAL NOT NULL,
+            start_energy REAL,
+            end_energy REAL,
+            avg_watts REAL,
+            adapter_energy REAL,
+            adapter TEXT,
+            UNIQUE (host, started_at)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Battery voltage, to see how far it sags under load.
+fn voltage(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "ALTER TABLE battery_logs ADD COLUMN voltage REAL;
+        ALTER TABLE battery_aggregates ADD COLUMN voltage REAL;",
+    )?;
+    Ok(())
+}
+
+/// Adds a nullable column to tables created before versioning.
+fn add_column_if_missing(conn: &Connection, table: &str, column: &str, decl: &str) -> Result<()> {
+    let exists = conn
+        .prepare(&format!("PRAGMA table_info({})", table))?
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<rusqlite::Result<Vec<_>>>()?
+        .iter()
+        .any(|name| name == column);
+    if !exists {
+        conn.execute(
+            &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, decl),
+            [],
+        )?;
+    }
+    Ok(())
+}
@@ -0,0 +1,322 @@
+//! Publishes the daemon's snapshots to an MQTT broker, with Home Assistant
+//! discovery so the battery shows up as a device with one sensor per
+//! reading.
+//!
+//! Speaks just enough MQTT 3.1.1 over plain TCP for that: connect with a
+//! last will marking the device unavailable, then publish at QoS 0. For a
+//! broker that only accepts TLS, bridge it through a local one.
+
+use crate::daemon::{BatterySnapshot, local_hostname};
+use serde_json::json;
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+const DEFAULT_PORT: u16 = 1883;
+/// How long to wait on the broker before giving up on a connect or publish.
+const TIMEOUT: Duration = Duration::from_secs(5);
+/// Wait before reconnecting after a failure, doubled after each further one
+/// up to `MAX_BACKOFF`, so a broker that is down doesn't hold up every
+/// snapshot.
+const MIN_BACKOFF: Duration = Duration::from_secs(30);
+const MAX_BACKOFF: Duration = Duration::from_secs(10 * 60);
+
+/// The `[mqtt]` section of the config file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MqttConfig {
+    /// `host:port`
+    pub broker: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// State and availability are published under `<prefix>/<host>/`
+    pub topic_prefix: String,
+    /// Where Home Assistant listens for discovery messages
+    pub discovery_prefix: String,
+}
+
+impl MqttConfig {
+    pub const DEFAULT_TOPIC_PREFIX: &'static str = "amptop";
+    pub const DEFAULT_DISCOVERY_PREFIX: &'static str = "homeassistant";
+
+    /// Reads the broker as `mqtt://host[:port]` or `host[:port]`.
+    pub fn parse_broker(url: &str) -> Result<String, String> {
+        if url.starts_with("mqtts://") || url.starts_with("ssl://") {
+            return Err(format!(
+                "{} needs TLS, which isn't supported; bridge it through a local broker",
+                url
+            ));
+        }
+        let address = url.strip_prefix("mqtt://").unwrap_or(url);
+        let address = address.strip_prefix("tcp://").unwrap_or(address);
+        let address = address.trim_end_matches('/');
+        match address.rsplit_once(':') {
+            _ if address.is_empty() || address.contains('/') => {
+                Err(format!("{} isn't a valid broker address", url))
+            }
+            Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => {
+                Ok(address.to_string())
+            }
+            Some(_) => Err(format!("{} isn't a valid broker address", url)),
+            None => Ok(format!("{}:{}", address, DEFAULT_PORT)),
+        }
+    }
+}
+
+/// Sensors announced to Home Assistant: key in the state message, name,
+/// unit, device class.
+const SENSORS: &[(&str, &str, Option<&str>, Option<&str>)] = &[
+    ("percent", "Battery", Some("%"), Some("battery")),
+    ("status", "Battery state", None, None),
+    ("power", "Battery power", Some("W"), Some("power")),
+    (
+        "energy",
+        "Battery energy",
+        Some("Wh"),
+        Some("energy_storage"),
+    ),
+    (
+        "temperature",
+        "Battery temperature",
+        Some("°C"),
+        Some("temperature"),
+    ),
+    ("health", "Battery health", Some("%"), None),
+];
+
+/// A connection to the broker, made on the first snapshot and remade after
+/// it drops.
+pub struct MqttPublisher {
+    config: MqttConfig,
+    /// Seconds the broker waits for a packet before publishing the last will
+    keep_alive: u16,
+    /// Host name in topics and Home Assistant ids
+    node: String,
+    stream: Option<TcpStream>,
+    /// Whether the last attempt failed, so a broker that stays down is
+    /// reported once rather than at every snapshot
+    failing: bool,
+    /// Current wait between reconnects, and when the next may be made
+    backoff: Duration,
+    retry_at: Option<Instant>,
+}
+
+impl MqttPublisher {
+    /// `interval_secs` is the time between snapshots; the broker marks the
+    /// battery unavailable after one and a half intervals without one.
+    pub fn new(config: MqttConfig, interval_secs: u64) -> Self {
+        let node = local_hostname()
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        Self {
+            config,
+            keep_alive: (interval_secs * 3 / 2).clamp(60, u64::from(u16::MAX)) as u16,
+            node,
+            stream: None,
+            failing: false,
+            backoff: Duration::ZERO,
+            retry_at: None,
+        }
+    }
+
+    /// Publishes `snapshot` as the battery's state, connecting and
+    /// announcing the sensors first if needed.
+    pub fn publish(&mut self, snapshot: &BatterySnapshot) {
+        if self.stream.is_none() && self.retry_at.is_some_and(|at| Instant::now() < at) {
+            return;
+        }
+        let result = self.try_publish(snapshot);
+        match result {
+            Ok(()) => {
+                if self.failing {
+                    eprintln!("Publishing to MQTT broker {} again", self.config.broker);
+                    self.failing = false;
+                }
+                self.backoff = Duration::ZERO;
+                self.retry_at = None;
+            }
+            Err(e) => {
+                self.stream = None;
+                self.backoff = (self.backoff * 2).clamp(MIN_BACKOFF, MAX_BACKOFF);
+                self.retry_at = Some(Instant::now() + self.backoff);
+                if !self.failing {
+                    eprintln!(
+                        "Failed to publish to MQTT broker {}: {}",
+                        self.config.broker, e
+                    );
+                    self.failing = true;
+                }
+            }
+        }
+    }
+
+    fn try_publish(&mut self, snapshot: &BatterySnapshot) -> io::Result<()> {
+        if self.stream.is_none() {
+            self.stream = Some(self.connect()?);
+            self.announce()?;
+        }
+        let state = json!({
+            "percent": snapshot.percent,
+            "status": snapshot.status,
+            "power": snapshot.power_watts,
+            "energy": snapshot.energy,
+            "temperature": snapshot.temperature,
+            "health": snapshot.health,
+            "timestamp": snapshot.timestamp,
+        });
+        self.send(&self.state_topic(), &state.to_string(), false)
+    }
+
+    fn state_topic(&self) -> String {
+        format!("{}/{}/state", self.config.topic_prefix, self.node)
+    }
+
+    fn availability_topic(&self) -> String {
+        format!("{}/{}/availability", self.config.topic_prefix, self.node)
+    }
+
+    /// Connects to the first address `broker` resolves to that answers
+    /// within `TIMEOUT`.
+    fn connect_any(broker: &str) -> io::Result<TcpStream> {
+        let mut last_error = None;
+        for addr in broker.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&addr, TIMEOUT) {
+                Ok(stream) => return Ok(stream),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, "no address found for the broker")
+        }))
+    }
+
+    fn connect(&self) -> io::Result<TcpStream> {
+        let mut stream = Self::connect_any(&self.config.broker)?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+
+        // Clean session, with a retained "offline" will
+        let mut flags = 0x02 | 0x04 | 0x20;
+        let mut body = Vec::new();
+        put_string(&mut body, "MQTT");
+        body.push(4);
+        let flags_at = body.len();
+        body.push(0);
+        body.extend_from_slice(&self.keep_alive.to_be_bytes());
+        put_string(&mut body, &format!("amptop-{}", self.node));
+        put_string(&mut body, &self.availability_topic());
+        put_string(&mut body, "offline");
+        if let Some(ref username) = self.config.username {
+            flags |= 0x80;
+            put_string(&mut body, username);
+        }
+        if let Some(ref password) = self.config.password {
+            flags |= 0x40;
+            put_string(&mut body, password);
+        }
+        body[flags_at] = flags;
+
+        stream.write_all(&packet(0x10, &body))?;
+        let mut connack = [0u8; 4];
+        stream.read_exact(&mut connack)?;
+        if connack[0] != 0x20 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "unexpected reply to connect",
+            ));
+        }
+        match connack[3] {
+            0 => Ok(stream),
+            code => Err(io::Error::new(
+                io::ErrorKind::ConnectionRefused,
+                match code {
+                    1 => "broker doesn't support MQTT 3.1.1",
+                    2 => "client id rejected",
+                    3 => "broker unavailable",
+                    4 => "bad username or password",
+                    5 => "not authorized",
+                    _ => "connection refused",
+                },
+            )),
+        }
+    }
+
+    /// Sends the retained discovery messages and marks the battery online.
+    fn announce(&mut self) -> io::Result<()> {
+        let device = json!({
+            "identifiers": [format!("amptop_{}", self.node)],
+            "name": format!("{} battery", local_hostname()),
+            "manufacturer": "amptop",
+        });
+        for &(key, name, unit, class) in SENSORS {
+            let mut config = json!({
+                "name": name,
+                "unique_id": format!("amptop_{}_{}", self.node, key),
+                "state_topic": self.state_topic(),
+                "value_template": format!("{{{{ value_json.{} }}}}", key),
+                "availability_topic": self.availability_topic(),
+                "device": device,
+            });
+            if let Some(unit) = unit {
+                config["unit_of_measurement"] = json!(unit);
+                config["state_class"] = json!("measurement");
+            }
+            if let Some(class) = class {
+                config["device_class"] = json!(class);
+            }
+            let topic = format!(
+                "{}/sensor/amptop_{}/{}/config",
+                self.config.discovery_prefix, self.node, key
+            );
+            self.send(&topic, &config.to_string(), true)?;
+        }
+        self.send(&self.availability_topic(), "online", true)
+    }
+
+    fn send(&self, topic: &str, payload: &str, retain: bool) -> io::Result<()> {
+        let mut body = Vec::new();
+        put_string(&mut body, topic);
+        body.extend_from_slice(payload.as_bytes());
+        let header = if retain { 0x31 } else { 0x30 };
+        let mut stream = self.stream.as_ref().expect("connected before sending");
+        stream.write_all(&packet(header, &body))
+    }
+}
+
+impl Drop for MqttPublisher {
+    /// Marks the battery unavailable right away rather than after the
+    /// keep-alive runs out.
+    fn drop(&mut self) {
+        if self.stream.is_some() {
+            self.send(&self.availability_topic(), "offline", true).ok();
+            if let Some(ref mut stream) = self.stream {
+                stream.write_all(&[0xe0, 0x00]).ok();
+            }
+        }
+    }
+}
+
+/// A control packet: its type and flags, the remaining length and `body`.
+fn packet(header: u8, body: &[u8]) -> Vec<u8> {
+    let mut packet = vec![header];
+    let mut length = body.len();
+    loop {
+        let mut byte = (length % 128) as u8;
+        length /= 128;
+        if length > 0 {
+            byte |= 0x80;
+        }
+        packet.push(byte);
+        if length == 0 {
+            break;
+        }
+    }
+    packet.extend_from_slice(body);
+    packet
+}
+
+/// A length-prefixed UTF-8 string.
+fn put_string(out: &mut Vec<u8>, s: &str) {
+    out.extend_from_slice(&(s.len() as u16).to_be_bytes());
+    out.extend_from_slice(s.as_bytes());
+}
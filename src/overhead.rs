@@ -0,0 +1,128 @@
+//! amptop's own resource use, read from `/proc`, so users can check that the
+//! monitor isn't a meaningful part of the drain it measures.
+
+use crate::durations;
+use std::fs;
+use std::time::Duration;
+
+/// Cumulative resource use of one process.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProcessUsage {
+    /// User plus system CPU time
+    pub cpu: Duration,
+    /// Voluntary and involuntary context switches, i.e. times the process
+    /// was woken or put back to sleep
+    pub wakeups: u64,
+    /// Bytes the process caused to be written to storage
+    pub write_bytes: Option<u64>,
+    /// How long the process has been running
+    pub uptime: Duration,
+}
+
+/// Average resource use over a stretch of time.
+#[derive(Debug, Clone, Copy)]
+pub struct Overhead {
+    /// Share of one CPU core, in percent
+    pub cpu_percent: f64,
+    pub wakeups_per_sec: f64,
+    pub write_bytes_per_hour: Option<f64>,
+}
+
+impl ProcessUsage {
+    /// Reads the usage of `pid`, or of this process when `None`.
+    pub fn read(pid: Option<i32>) -> Option<Self> {
+        let dir = match pid {
+            Some(pid) => format!("/proc/{}", pid),
+            None => "/proc/self".to_string(),
+        };
+
+        // Fields after the command name, which may itself contain spaces.
+        let stat = fs::read_to_string(format!("{}/stat", dir)).ok()?;
+        let fields: Vec<&str> = stat.rsplit_once(')')?.1.split_whitespace().collect();
+        let ticks = unsafe { libc::sysconf(libc::_SC_CLK_TCK) } as f64;
+        let field = |index: usize| fields.get(index)?.parse::<u64>().ok();
+        let cpu_ticks = field(11)? + field(12)?;
+        let started = field(19)? as f64 / ticks;
+        let boot_uptime: f64 = fs::read_to_string("/proc/uptime")
+            .ok()?
+            .split_whitespace()
+            .next()?
+            .parse()
+            .ok()?;
+
+        let status = fs::read_to_string(format!("{}/status", dir)).ok()?;
+        let wakeups = status
+            .lines()
+            .filter_map(|line| {
+                let value = line
+                    .strip_prefix("voluntary_ctxt_switches:")
+                    .or_else(|| line.strip_prefix("nonvoluntary_ctxt_switches:"))?;
+                value.trim().parse::<u64>().ok()
+            })
+            .sum();
+
+        // Only readable for our own processes, and not on every kernel.
+        let write_bytes = fs::read_to_string(format!("{}/io", dir))
+            .ok()
+            .and_then(|io| {
+                io.lines()
+                    .find_map(|line| line.strip_prefix("write_bytes:"))?
+                    .trim()
+                    .parse()
+                    .ok()
+            });
+
+        Some(Self {
+            cpu: Duration::from_secs_f64(cpu_ticks as f64 / ticks),
+            wakeups,
+            write_bytes,
+            uptime: Duration::from_secs_f64((boot_uptime - started).max(0.0)),
+        })
+    }
+
+    /// Average use since the process started.
+    pub fn lifetime(&self) -> Option<Overhead> {
+        self.since(&Self::default())
+    }
+
+    /// Average use between `earlier`, a reading of the same process, and
+    /// this one.
+    pub fn since(&self, earlier: &Self) -> Option<Overhead> {
+        let secs = self.uptime.checked_sub(earlier.uptime)?.as_secs_f64();
+        if secs <= 0.0 {
+            return None;
+        }
+        Some(Overhead {
+            cpu_percent: self.cpu.saturating_sub(earlier.cpu).as_secs_f64() / secs * 100.0,
+            wakeups_per_sec: self.wakeups.saturating_sub(earlier.wakeups) as f64 / secs,
+            write_bytes_per_hour: self
+                .write_bytes
+                .map(|bytes| bytes.saturating_sub(earlier.write_bytes.unwrap_or(0)) as f64)
+                .map(|bytes| bytes / secs * 3600.0),
+        })
+    }
+}
+
+impl Overhead {
+    /// E.g. "0.02% CPU, 0.1 wakeups/s, 14.2 kB/h written".
+    pub fn describe(&self) -> String {
+        let written = match self.write_bytes_per_hour {
+            Some(bytes) if bytes >= 1e6 => format!(", {:.1} MB/h written", bytes / 1e6),
+            Some(bytes) => format!(", {:.1} kB/h written", bytes / 1e3),
+            None => String::new(),
+        };
+        format!(
+            "{:.2}% CPU, {:.1} wakeups/s{}",
+            self.cpu_percent, self.wakeups_per_sec, written
+        )
+    }
+}
+
+/// CPU time, with hundredths of a second while it's below a minute.
+pub fn format_cpu_time(cpu: Duration) -> String {
+    if cpu < Duration::from_secs(60) {
+        format!("{:.2}s", cpu.as_secs_f64())
+    } else {
+        durations::format(cpu)
+    }
+}
@@ -0,0 +1,125 @@
+use crate::daemon::BatterySnapshot;
+use crate::sysfs;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+#[derive(Debug, Clone, Copy)]
+pub struct PowerSaverConfig {
+    /// Battery percentage at or below which power-saving actions kick in
+    pub threshold: f32,
+    /// Backlight level, in percent of the maximum, applied while saving power
+    pub backlight: Option<u8>,
+}
+
+#[derive(Debug)]
+struct SavedState {
+    profile: Option<String>,
+    brightness: Option<(PathBuf, u64)>,
+}
+
+/// Switches the system into a power-saving configuration when the battery
+/// runs low and restores the previous configuration once AC power returns.
+#[derive(Debug)]
+pub struct PowerSaver {
+    config: PowerSaverConfig,
+    saved: Option<SavedState>,
+}
+
+impl PowerSaver {
+    pub fn new(config: PowerSaverConfig) -> Self {
+        Self {
+            config,
+            saved: None,
+        }
+    }
+
+    pub fn update(&mut self, snapshot: &BatterySnapshot) {
+        let on_battery = snapshot.status == "discharging";
+
+        if self.saved.is_none() && on_battery && snapshot.percent <= self.config.threshold {
+            self.saved = Some(self.engage());
+        } else if !on_battery
+            && snapshot.status != "unknown"
+            && let Some(saved) = self.saved.take()
+        {
+            Self::revert(saved);
+        }
+    }
+
+    fn engage(&self) -> SavedState {
+        let profile = current_profile();
+        if let Err(e) = set_profile("power-saver") {
+            eprintln!("Failed to switch power profile: {}", e);
+        }
+
+        let brightness = self.config.backlight.and_then(|level| {
+            let device = backlight_device()?;
+            let current = read_u64(&device.join("brightness"))?;
+            let max = read_u64(&device.join("max_brightness"))?;
+            let target = max * u64::from(level.min(100)) / 100;
+            if target < current {
+                if let Err(e) = fs::write(device.join("brightness"), target.to_string()) {
+                    eprintln!("Failed to dim backlight: {}", e);
+                    return None;
+                }
+                Some((device, current))
+            } else {
+                None
+            }
+        });
+
+        SavedState {
+            profile,
+            brightness,
+        }
+    }
+
+    fn revert(saved: SavedState) {
+        if let Some(profile) = saved.profile
+            && let Err(e) = set_profile(&profile)
+        {
+            eprintln!("Failed to restore power profile: {}", e);
+        }
+        if let Some((device, brightness)) = saved.brightness
+            && let Err(e) = fs::write(device.join("brightness"), brightness.to_string())
+        {
+            eprintln!("Failed to restore backlight: {}", e);
+        }
+    }
+}
+
+fn current_profile() -> Option<String> {
+    let output = Command::new("powerprofilesctl").arg("get").output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let profile = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (!profile.is_empty()).then_some(profile)
+}
+
+fn set_profile(profile: &str) -> std::io::Result<()> {
+    let status = Command::new("powerprofilesctl")
+        .args(["set", profile])
+        .status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(std::io::Error::other(format!(
+            "powerprofilesctl exited with {}",
+            status
+        )))
+    }
+}
+
+fn backlight_device() -> Option<PathBuf> {
+    fs::read_dir(sysfs::root().join("class/backlight"))
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .next()
+}
+
+fn read_u64(path: &Path) -> Option<u64> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
@@ -0,0 +1,446 @@
+//! `amptop report`: a self-contained battery report in HTML or Markdown,
+//! with the installed battery, its capacity over time and forecast, recent
+//! use and charge and discharge sessions, along the lines of Windows'
+//! `powercfg /batteryreport`.
+
+use crate::daemon::{History, MAX_GAP, local_hostname};
+use crate::durations;
+use crate::errors::Result;
+use crate::forecast::{CapacityTrend, WORN_OUT_HEALTH};
+use crate::info::BatteryInfo;
+use crate::sessions::{Session, SessionKind};
+use crate::stats;
+use crate::svg::{self, Chart, Series};
+use battery::units::{energy::watt_hour, ratio::percent};
+use chrono::{Local, TimeZone, Utc};
+use std::path::Path;
+use std::time::Duration;
+
+/// History charted under "Recent usage", in seconds.
+const RECENT_SECS: i64 = 3 * 24 * 60 * 60;
+/// History summarized under "Sessions", in seconds.
+const SESSIONS_SECS: i64 = 30 * 24 * 60 * 60;
+/// Sessions listed individually, most recent first.
+const LISTED_SESSIONS: usize = 20;
+const CHART_WIDTH: u32 = 760;
+const CHART_HEIGHT: u32 = 240;
+
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum ReportFormat {
+    Html,
+    /// GitHub-flavored Markdown, with the charts as inline SVG
+    Markdown,
+}
+
+impl ReportFormat {
+    /// Markdown for `.md` files, HTML otherwise.
+    pub fn for_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("md") => ReportFormat::Markdown,
+            _ => ReportFormat::Html,
+        }
+    }
+}
+
+/// A piece of the report, rendered the same way in either format.
+enum Block {
+    Heading(String),
+    Text(String),
+    /// Label and value pairs
+    Facts(Vec<(String, String)>),
+    Table {
+        header: Vec<&'static str>,
+        rows: Vec<Vec<String>>,
+    },
+    Chart(String),
+}
+
+/// Builds the report from the battery as it is now and the recorded
+/// history.
+pub fn render(battery: &BatteryInfo, format: ReportFormat) -> Result<String> {
+    let history = History::open()?;
+    let now = Utc::now().timestamp();
+    let mut blocks = Vec::new();
+
+    blocks.push(Block::Facts(vec![
+        ("Computer".to_string(), local_hostname()),
+        ("Generated".to_string(), date_time(now)),
+        ("amptop".to_string(), env!("CARGO_PKG_VERSION").to_string()),
+    ]));
+
+    blocks.push(Block::Heading("Installed battery".to_string()));
+    match battery.reading() {
+        Some(reading) => {
+            let mut facts = Vec::new();
+            let mut fact = |label: &str, value: Option<String>| {
+                if let Some(value) = value {
+                    facts.push((label.to_string(), value));
+                }
+            };
+            fact("Manufacturer", battery.vendor().map(str::to_string));
+            fact("Model", battery.model().map(str::to_string));
+            fact("Serial number", battery.serial_number().map(str::to_string));
+            fact(
+                "Chemistry",
+                battery
+                    .chemistry()
+                    .map(|chemistry| chemistry.name().to_string())
+                    .or_else(|| battery.technology()),
+            );
+            fact(
+                "Manufactured",
+                battery.manufactured().map(|date| date.to_string()),
+            );
+            let design = reading.energy_full_design.get::<watt_hour>();
+            let full = reading.energy_full.get::<watt_hour>();
+            fact("Design capacity", Some(format!("{:.1} Wh", design)));
+            fact("Full charge capacity", Some(format!("{:.1} Wh", full)));
+            let health = reading.state_of_health.get::<percent>();
+            fact(
+                "Wear level",
+                Some(format!("{:.1}%", (100.0 - health).max(0.0))),
+            );
+            fact("Health", battery.health_status().map(str::to_string));
+            fact(
+                "Cycle count",
+                match battery.cycle_count() {
+                    Some(cycles) => Some(cycles.to_string()),
+                    None => history
+                        .estimated_cycles()?
+                        .map(|cycles| format!("{:.0} (estimated from history)", cycles)),
+                },
+            );
+            fact(
+                "Charge limit",
+                battery.charge_limit().map(|limit| format!("{:.0}%", limit)),
+            );
+            fact(
+                "Current charge",
+                battery
+                    .state_of_charge()
+                    .map(|(_, charge)| format!("{:.0}%", charge)),
+            );
+            blocks.push(Block::Facts(facts));
+        }
+        None => blocks.push(Block::Text("No battery found.".to_string())),
+    }
+
+    blocks.push(Block::Heading("Capacity history".to_string()));
+    let health = history.health()?;
+    match (health.first(), health.last()) {
+        (Some(first), Some(last)) => {
+            blocks.push(Block::Text(if health.len() == 1 {
+                format!(
+                    "Full charge capacity was {:.1} Wh on {}, against a design capacity of {:.1} Wh.",
+                    first.energy_full,
+                    date(first.timestamp),
+                    first.energy_full_design
+                )
+            } else {
+                format!(
+                "Full charge capacity went from {:.1} Wh on {} to {:.1} Wh on {}, against a design capacity of {:.1} Wh.",
+                first.energy_full,
+                date(first.timestamp),
+                last.energy_full,
+                date(last.timestamp),
+                last.energy_full_design
+                )
+            }));
+            let mut chart = Chart {
+                title: "Battery capacity".to_string(),
+                unit: " Wh".to_string(),
+                y_range: None,
+                max_gap: None,
+                series: vec![
+                    Series {
+                        label: "Full charge".to_string(),
+                        color: "#1565c0".to_string(),
+                        points: health
+                            .iter()
+                            .map(|sample| (sample.timestamp, f64::from(sample.energy_full)))
+                            .collect(),
+                    },
+                    Series {
+                        label: "Design".to_string(),
+                        color: "#9e9e9e".to_string(),
+                        points: health
+                            .iter()
+                            .map(|sample| (sample.timestamp, f64::from(sample.energy_full_design)))
+                            .collect(),
+                    },
+                ],
+            };
+            let trend = CapacityTrend::fit(&health);
+            if let Some(trend) = trend {
+                let until = CapacityTrend::months_after(now, 12);
+                chart.series.push(Series {
+                    label: "Forecast".to_string(),
+                    color: "#90caf9".to_string(),
+                    points: vec![
+                        (last.timestamp, trend.capacity_at(last.timestamp)),
+                        (until, trend.capacity_at(until)),
+                    ],
+                });
+            }
+            blocks.push(Block::Chart(chart.render(CHART_WIDTH, CHART_HEIGHT)));
+            blocks.push(match trend {
+                Some(trend) => {
+                    let projection = |months: u32| {
+                        let at = CapacityTrend::months_after(now, months);
+                        format!(
+                            "{:.1} Wh ({:.0}% of design)",
+                            trend.capacity_at(at),
+                            trend.health_at(at)
+                        )
+                    };
+                    let worn_out = match trend.reaches(WORN_OUT_HEALTH) {
+                        None => "not declining".to_string(),
+                        Some(at) if at <= now => format!("already below, since about {}", date(at)),
+                        Some(at) => format!("around {}", date(at)),
+                    };
+                    Block::Facts(vec![
+                        (
+                            "Trend".to_string(),
+                            format!("{:+.1}% of design a year", trend.per_year),
+                        ),
+                        ("In 6 months".to_string(), projection(6)),
+                        ("In 12 months".to_string(), projection(12)),
+                        (format!("{:.0}% of design", WORN_OUT_HEALTH), worn_out),
+                    ])
+                }
+                None => Block::Text(
+                    "A capacity forecast needs a month of capacity history.".to_string(),
+                ),
+            });
+        }
+        _ => blocks.push(Block::Text(
+            "No capacity history yet; the daemon records it while running.".to_string(),
+        )),
+    }
+
+    blocks.push(Block::Heading("Recent usage".to_string()));
+    let recent = history.logs_between(now - RECENT_SECS, now)?;
+    if recent.is_empty() {
+        blocks.push(Block::Text(
+            "No history in the last 3 days; start the daemon to record it.".to_string(),
+        ));
+    } else {
+        let chart = Chart {
+            title: "Charge, last 3 days".to_string(),
+            unit: "%".to_string(),
+            y_range: Some((0.0, 100.0)),
+            max_gap: Some(MAX_GAP.as_secs() as i64),
+            series: vec![Series {
+                label: "Charge".to_string(),
+                color: "#2e7d32".to_string(),
+                points: recent
+                    .iter()
+                    .map(|log| (log.timestamp, f64::from(log.percent)))
+                    .collect(),
+            }],
+        };
+        blocks.push(Block::Chart(chart.render(CHART_WIDTH, CHART_HEIGHT)));
+    }
+
+    let logs = history.logs_between(now - SESSIONS_SECS, now)?;
+    let sessions = history.sessions(now - SESSIONS_SECS)?;
+
+    blocks.push(Block::Heading("Battery life".to_string()));
+    let life = stats::battery_life(&logs, &sessions);
+    let life_facts: Vec<(String, String)> = [
+        (
+            "Full discharge",
+            life.full_discharge.map(|duration| {
+                format!(
+                    "{} from 100% to 0% (over {} discharge{})",
+                    durations::format(duration),
+                    life.discharges,
+                    if life.discharges == 1 { "" } else { "s" }
+                )
+            }),
+        ),
+        (
+            "Drain in use",
+            life.active_drain
+                .map(|drain| format!("{:.1} %/h with the screen on", drain)),
+        ),
+        (
+            "Charging 20% to 80%",
+            life.charge_20_80.map(|duration| {
+                format!(
+                    "{} (over {} charge{})",
+                    durations::format(duration),
+                    life.charges,
+                    if life.charges == 1 { "" } else { "s" }
+                )
+            }),
+        ),
+        (
+            "Cycles per week",
+            life.cycles_per_week.map(|cycles| format!("{:.1}", cycles)),
+        ),
+    ]
+    .into_iter()
+    .filter_map(|(label, value)| Some((label.to_string(), value?)))
+    .collect();
+    if life_facts.is_empty() {
+        blocks.push(Block::Text(
+            "Not enough history in the last 30 days to estimate.".to_string(),
+        ));
+    } else {
+        blocks.push(Block::Facts(life_facts));
+    }
+
+    blocks.push(Block::Heading("Sessions, last 30 days".to_string()));
+    if sessions.is_empty() {
+        blocks.push(Block::Text("No sessions recorded.".to_string()));
+    } else {
+        let mut facts = Vec::new();
+        for kind in SessionKind::ALL {
+            let of_kind: Vec<&Session> = sessions.iter().filter(|s| s.kind == kind).collect();
+            let seconds: i64 = of_kind.iter().map(|s| s.duration()).sum();
+            let watts: Vec<f64> = of_kind.iter().filter_map(|s| s.average_watts()).collect();
+            let mut value = format!(
+                "{}, {} in total",
+                of_kind.len(),
+                durations::format(Duration::from_secs(seconds.max(0) as u64))
+            );
+            if !watts.is_empty() {
+                value.push_str(&format!(
+                    ", {:.1} W on average",
+                    watts.iter().sum::<f64>() / watts.len() as f64
+                ));
+            }
+            let label = match kind {
+                SessionKind::Charge => "Charging",
+                SessionKind::Discharge => "On battery",
+            };
+            facts.push((label.to_string(), value));
+        }
+        blocks.push(Block::Facts(facts));
+        blocks.push(Block::Table {
+            header: vec!["Started", "Type", "Duration", "Change", "Average"],
+            rows: sessions
+                .iter()
+                .rev()
+                .take(LISTED_SESSIONS)
+                .map(|session| {
+                    vec![
+                        date_time(session.start),
+                        session.kind.label().to_string(),
+                        durations::format(Duration::from_secs(session.duration().max(0) as u64)),
+                        format!(
+                            "{:.0}% → {:.0}%",
+                            session.start_percent, session.end_percent
+                        ),
+                        session
+                            .average_watts()
+                            .map(|watts| format!("{:.1} W", watts))
+                            .unwrap_or_else(|| "N/A".to_string()),
+                    ]
+                })
+                .collect(),
+        });
+    }
+
+    Ok(match format {
+        ReportFormat::Html => html(&blocks),
+        ReportFormat::Markdown => markdown(&blocks),
+    })
+}
+
+fn html(blocks: &[Block]) -> String {
+    let mut out = String::from(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>Battery report</title>\n<style>\n\
+         body { font-family: sans-serif; max-width: 800px; margin: 2em auto; padding: 0 1em; color: #212121; }\n\
+         h2 { border-bottom: 1px solid #e0e0e0; padding-bottom: 0.2em; margin-top: 1.5em; }\n\
+         table { border-collapse: collapse; }\n\
+         th, td { text-align: left; padding: 0.2em 1em 0.2em 0; }\n\
+         table.sessions th { border-bottom: 1px solid #e0e0e0; }\n\
+         </style>\n</head>\n<body>\n<h1>Battery report</h1>\n",
+    );
+    for block in blocks {
+        match block {
+            Block::Heading(text) => out.push_str(&format!("<h2>{}</h2>\n", svg::escape(text))),
+            Block::Text(text) => out.push_str(&format!("<p>{}</p>\n", svg::escape(text))),
+            Block::Facts(facts) => {
+                out.push_str("<table>\n");
+                for (label, value) in facts {
+                    out.push_str(&format!(
+                        "<tr><th>{}</th><td>{}</td></tr>\n",
+                        svg::escape(label),
+                        svg::escape(value)
+                    ));
+                }
+                out.push_str("</table>\n");
+            }
+            Block::Table { header, rows } => {
+                out.push_str("<table class=\"sessions\">\n<tr>");
+                for cell in header {
+                    out.push_str(&format!("<th>{}</th>", cell));
+                }
+                out.push_str("</tr>\n");
+                for row in rows {
+                    out.push_str("<tr>");
+                    for cell in row {
+                        out.push_str(&format!("<td>{}</td>", svg::escape(cell)));
+                    }
+                    out.push_str("</tr>\n");
+                }
+                out.push_str("</table>\n");
+            }
+            Block::Chart(chart) => {
+                out.push_str("<p>");
+                out.push_str(chart);
+                out.push_str("</p>\n");
+            }
+        }
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+fn markdown(blocks: &[Block]) -> String {
+    // Pipes would end a table cell early.
+    let cell = |text: &str| text.replace('|', "\\|");
+    let mut out = String::from("# Battery report\n");
+    for block in blocks {
+        out.push('\n');
+        match block {
+            Block::Heading(text) => out.push_str(&format!("## {}\n", text)),
+            Block::Text(text) => out.push_str(&format!("{}\n", text)),
+            Block::Facts(facts) => {
+                out.push_str("| | |\n|---|---|\n");
+                for (label, value) in facts {
+                    out.push_str(&format!("| **{}** | {} |\n", cell(label), cell(value)));
+                }
+            }
+            Block::Table { header, rows } => {
+                out.push_str(&format!("| {} |\n", header.join(" | ")));
+                out.push_str(&format!("|{}\n", "---|".repeat(header.len())));
+                for row in rows {
+                    let row: Vec<String> = row.iter().map(|text| cell(text)).collect();
+                    out.push_str(&format!("| {} |\n", row.join(" | ")));
+                }
+            }
+            Block::Chart(chart) => out.push_str(&format!("{}\n", chart)),
+        }
+    }
+    out
+}
+
+fn date(timestamp: i64) -> String {
+    Local
+        .timestamp_opt(timestamp, 0)
+        .single()
+        .map(|dt| dt.format("%Y-%m-%d").to_string())
+        .unwrap_or_default()
+}
+
+fn date_time(timestamp: i64) -> String {
+    Local
+        .timestamp_opt(timestamp, 0)
+        .single()
+        .map(|dt| dt.format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_default()
+}
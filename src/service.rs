@@ -0,0 +1,101 @@
+//! The systemd user unit `daemon install` writes. It runs the daemon in the
+//! foreground, so systemd starts it on login and restarts it after a crash.
+
+use crate::xdg;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// `amptop.service`, or `amptop-<profile>.service` for a named profile.
+pub fn unit_name(profile: Option<&str>) -> String {
+    match profile {
+        Some(profile) => format!("amptop-{}.service", profile),
+        None => "amptop.service".to_string(),
+    }
+}
+
+/// `$XDG_CONFIG_HOME/systemd/user/<unit>`, falling back to `~/.config`.
+pub fn unit_path(unit: &str) -> PathBuf {
+    xdg::config_home().join("systemd/user").join(unit)
+}
+
+/// Writes the unit running `command`, then enables and (re)starts it.
+pub fn install(unit: &str, command: &[String]) -> io::Result<PathBuf> {
+    let path = unit_path(unit);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let exec = command.iter().map(|arg| quote(arg)).collect::<Vec<_>>();
+    fs::write(
+        &path,
+        format!(
+            "[Unit]
+Description=amptop battery history collector
+Documentation=https://github.com/harshvsri/amptop
+
+[Service]
+ExecStart={}
+ExecReload=/bin/kill -HUP $MAINPID
+Restart=on-failure
+RestartSec=10
+# Invalid configuration and an already running daemon won't fix themselves.
+RestartPreventExitStatus=2 4
+
+[Install]
+WantedBy=default.target
+",
+            exec.join(" ")
+        ),
+    )?;
+
+    systemctl(&["daemon-reload"])?;
+    systemctl(&["enable", unit])?;
+    systemctl(&["restart", unit])?;
+    Ok(path)
+}
+
+/// Stops and disables the unit, then removes its file.
+pub fn uninstall(unit: &str) -> io::Result<PathBuf> {
+    let path = unit_path(unit);
+    if !path.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} isn't installed", path.display()),
+        ));
+    }
+    systemctl(&["disable", "--now", unit])?;
+    fs::remove_file(&path)?;
+    systemctl(&["daemon-reload"])?;
+    Ok(path)
+}
+
+fn systemctl(args: &[&str]) -> io::Result<()> {
+    let status = Command::new("systemctl")
+        .arg("--user")
+        .args(args)
+        .status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "systemctl --user {} exited with {}",
+            args.join(" "),
+            status
+        )))
+    }
+}
+
+/// Quotes an `ExecStart=` argument. `%` and `$` are escaped so systemd
+/// doesn't expand them as specifiers or variables.
+fn quote(arg: &str) -> String {
+    let escaped = arg.replace('%', "%%").replace('$', "$$");
+    if escaped
+        .chars()
+        .any(|c| c.is_whitespace() || c == '"' || c == '\\' || c == '\'')
+    {
+        format!("\"{}\"", escaped.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        escaped
+    }
+}
@@ -0,0 +1,200 @@
+use crate::daemon::{BatterySnapshot, MAX_GAP};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionKind {
+    Charge,
+    Discharge,
+}
+
+impl SessionKind {
+    fn of(status: &str) -> Option<SessionKind> {
+        match status {
+            "charging" => Some(SessionKind::Charge),
+            "discharging" => Some(SessionKind::Discharge),
+            _ => None,
+        }
+    }
+
+    pub const ALL: [SessionKind; 2] = [SessionKind::Charge, SessionKind::Discharge];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SessionKind::Charge => "charge",
+            SessionKind::Discharge => "discharge",
+        }
+    }
+
+    /// The kind whose `label` is `label`.
+    pub fn from_label(label: &str) -> Option<SessionKind> {
+        Self::ALL.into_iter().find(|kind| kind.label() == label)
+    }
+}
+
+/// A contiguous stretch of history spent charging or discharging.
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub kind: SessionKind,
+    pub start: i64,
+    pub end: i64,
+    pub start_percent: f32,
+    pub end_percent: f32,
+    /// Energy stored at the start and end of the session, in Wh
+    pub start_energy: Option<f32>,
+    pub end_energy: Option<f32>,
+    /// Energy drawn from the wall over the session, in Wh, when the adapter
+    /// reported its output for every sample
+    pub adapter_energy: Option<f64>,
+    /// Charger used, when it could be identified
+    pub adapter: Option<String>,
+}
+
+impl Session {
+    pub fn duration(&self) -> i64 {
+        self.end - self.start
+    }
+
+    /// Absolute change in stored energy over the session, in Wh.
+    pub fn energy_delta(&self) -> Option<f32> {
+        Some((self.end_energy? - self.start_energy?).abs())
+    }
+
+    /// Average power going into or out of the battery, in W.
+    pub fn average_watts(&self) -> Option<f64> {
+        let energy = self.energy_delta()?;
+        let hours = self.duration() as f64 / 3600.0;
+        (hours > 0.0).then(|| f64::from(energy) / hours)
+    }
+
+    /// Share of the wall-side energy that ended up in the battery. The rest
+    /// went into conversion losses and powering the machine while charging.
+    pub fn charge_efficiency(&self) -> Option<f64> {
+        if self.kind != SessionKind::Charge {
+            return None;
+        }
+        let wall = self.adapter_energy.filter(|wh| *wh > 0.0)?;
+        Some(f64::from(self.energy_delta()?) / wall)
+    }
+}
+
+/// Splits history (oldest first) into charge and discharge sessions. A
+/// session ends when the status changes or when samples stop for longer
+/// than `MAX_GAP`.
+pub fn detect(logs: &[BatterySnapshot]) -> Vec<Session> {
+    let mut sessions = Vec::new();
+    let mut current: Option<Session> = None;
+    let mut previous: Option<&BatterySnapshot> = None;
+
+    for log in logs {
+        let kind = SessionKind::of(&log.status);
+        let continues = previous.filter(|prev| {
+            log.timestamp - prev.timestamp <= MAX_GAP.as_secs() as i64
+                && current.as_ref().map(|session| session.kind) == kind
+                && kind.is_some()
+        });
+
+        if let (Some(prev), Some(session)) = (continues, current.as_mut()) {
+            let hours = (log.timestamp - prev.timestamp) as f64 / 3600.0;
+            session.adapter_energy = match (prev.adapter_watts, session.adapter_energy) {
+                (Some(watts), Some(total)) => Some(total + f64::from(watts) * hours),
+                _ => None,
+            };
+            session.end = log.timestamp;
+            session.end_percent = log.percent;
+            session.end_energy = log.energy;
+            if session.adapter.is_none() {
+                session.adapter = log.adapter.clone();
+            }
+        } else {
+            sessions.extend(current.take());
+            current = kind.map(|kind| Session {
+                kind,
+                start: log.timestamp,
+                end: log.timestamp,
+                start_percent: log.percent,
+                end_percent: log.percent,
+                start_energy: log.energy,
+                end_energy: log.energy,
+                adapter_energy: Some(0.0),
+                adapter: log.adapter.clone(),
+            });
+        }
+        previous = Some(log);
+    }
+
+    sessions.extend(current);
+    sessions.retain(|session| session.duration() > 0);
+    sessions
+}
+
+/// Full cycles the battery went through by the recorded charge sessions:
+/// the charge they added up, in whole batteries, so two charges from 50% to
+/// 100% make one cycle.
+pub fn equivalent_cycles(sessions: &[Session]) -> f64 {
+    sessions
+        .iter()
+        .filter(|session| session.kind == SessionKind::Charge)
+        .map(|session| f64::from(session.end_percent - session.start_percent).max(0.0))
+        .sum::<f64>()
+        / 100.0
+}
+
+/// Charge sessions grouped by the charger used.
+#[derive(Debug, Clone, Default)]
+pub struct ChargerStats {
+    pub adapter: String,
+    pub sessions: usize,
+    pub hours: f64,
+    /// Percentage points gained
+    pub percent: f64,
+    /// Energy stored, in Wh, over the sessions that recorded it
+    pub energy: f64,
+    pub energy_hours: f64,
+}
+
+impl ChargerStats {
+    pub fn percent_per_hour(&self) -> Option<f64> {
+        (self.hours > 0.0).then(|| self.percent / self.hours)
+    }
+
+    /// Average power going into the battery, in W.
+    pub fn watts(&self) -> Option<f64> {
+        (self.energy_hours > 0.0).then(|| self.energy / self.energy_hours)
+    }
+}
+
+/// Average charge speed per identified charger, fastest first.
+pub fn charger_stats(sessions: &[Session]) -> Vec<ChargerStats> {
+    let mut chargers: Vec<ChargerStats> = Vec::new();
+    for session in sessions {
+        let Some(ref adapter) = session.adapter else {
+            continue;
+        };
+        if session.kind != SessionKind::Charge {
+            continue;
+        }
+        let entry = match chargers.iter().position(|stats| &stats.adapter == adapter) {
+            Some(index) => &mut chargers[index],
+            None => {
+                chargers.push(ChargerStats {
+                    adapter: adapter.clone(),
+                    ..ChargerStats::default()
+                });
+                chargers.last_mut().expect("just pushed")
+            }
+        };
+        let hours = session.duration() as f64 / 3600.0;
+        entry.sessions += 1;
+        entry.hours += hours;
+        entry.percent += f64::from(session.end_percent - session.start_percent).max(0.0);
+        if let Some(energy) = session.energy_delta() {
+            entry.energy += f64::from(energy);
+            entry.energy_hours += hours;
+        }
+    }
+    chargers.sort_by(|a, b| {
+        b.percent_per_hour()
+            .unwrap_or(0.0)
+            .total_cmp(&a.percent_per_hour().unwrap_or(0.0))
+    });
+    chargers
+}
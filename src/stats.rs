@@ -0,0 +1,496 @@
+use crate::daemon::{BatterySnapshot, MAX_GAP};
+use crate::durations;
+use crate::sessions::{Session, SessionKind};
+use std::time::Duration;
+
+/// Charge window timed for `BatteryLife::charge_20_80`, in percent.
+const CHARGE_FROM: f32 = 20.0;
+const CHARGE_TO: f32 = 80.0;
+/// Discharges shorter than this many percentage points are too noisy to
+/// extrapolate to a full discharge.
+const MIN_DISCHARGE_DROP: f32 = 10.0;
+/// History needed before cycles per week are worth reporting, in seconds.
+const MIN_CYCLE_SPAN: i64 = 24 * 60 * 60;
+
+/// How regularly samples were actually taken.
+#[derive(Debug, Clone, Copy)]
+pub struct IntervalStats {
+    pub samples: usize,
+    pub median: i64,
+    pub p95: i64,
+    pub largest_gap: i64,
+    /// Timestamp of the sample right before the largest gap
+    pub largest_gap_at: i64,
+}
+
+/// Computes achieved sampling intervals from history sorted oldest first.
+pub fn interval_stats(logs: &[BatterySnapshot]) -> Option<IntervalStats> {
+    let mut intervals: Vec<(i64, i64)> = logs
+        .windows(2)
+        .map(|pair| (pair[1].timestamp - pair[0].timestamp, pair[0].timestamp))
+        .collect();
+    if intervals.is_empty() {
+        return None;
+    }
+
+    let &(largest_gap, largest_gap_at) = intervals.iter().max_by_key(|(interval, _)| *interval)?;
+    intervals.sort_unstable();
+
+    Some(IntervalStats {
+        samples: logs.len(),
+        median: percentile(&intervals, 50),
+        p95: percentile(&intervals, 95),
+        largest_gap,
+        largest_gap_at,
+    })
+}
+
+fn percentile(sorted: &[(i64, i64)], percent: usize) -> i64 {
+    let index = (sorted.len() - 1) * percent / 100;
+    sorted[index].0
+}
+
+/// Discharge power split by whether the user was at the machine.
+#[derive(Debug, Clone, Copy)]
+pub struct UsageSplit {
+    /// Average drain while active and idle, in W
+    pub active_watts: Option<f64>,
+    pub idle_watts: Option<f64>,
+    pub active_hours: f64,
+    pub idle_hours: f64,
+    /// Energy a full battery holds, in Wh, estimated from the latest sample
+    pub full_energy: Option<f64>,
+}
+
+impl UsageSplit {
+    /// How long a full battery lasts under active use alone, leaving out
+    /// idle stretches such as overnight drain.
+    pub fn active_battery_life(&self) -> Option<Duration> {
+        let hours = self.full_energy? / self.active_watts.filter(|watts| *watts > 0.0)?;
+        Some(Duration::from_secs((hours * 60.0) as u64 * 60))
+    }
+}
+
+/// A stretch between two consecutive discharging samples.
+#[derive(Debug, Clone, Copy)]
+pub struct DischargeInterval<'a> {
+    pub start: &'a BatterySnapshot,
+    pub end: &'a BatterySnapshot,
+    pub hours: f64,
+    /// Energy drawn from the battery, in Wh
+    pub energy: f64,
+}
+
+impl DischargeInterval<'_> {
+    pub fn watts(&self) -> f64 {
+        self.energy / self.hours
+    }
+}
+
+/// Walks history (oldest first) and yields every interval spent
+/// discharging. Gaps longer than `MAX_GAP` and samples without energy are
+/// skipped.
+pub fn discharge_intervals(
+    logs: &[BatterySnapshot],
+) -> impl Iterator<Item = DischargeInterval<'_>> {
+    logs.windows(2).filter_map(|pair| {
+        let (from, to) = (&pair[0], &pair[1]);
+        let seconds = to.timestamp - from.timestamp;
+        if from.status != "discharging"
+            || to.status != "discharging"
+            || seconds <= 0
+            || seconds > MAX_GAP.as_secs() as i64
+        {
+            return None;
+        }
+
+        Some(DischargeInterval {
+            start: from,
+            end: to,
+            hours: seconds as f64 / 3600.0,
+            energy: f64::from(from.energy? - to.energy?).max(0.0),
+        })
+    })
+}
+
+/// Splits discharge time in history (oldest first) into active and idle
+/// use. Samples without idle information are skipped.
+pub fn usage_split(logs: &[BatterySnapshot]) -> Option<UsageSplit> {
+    let mut active = (0.0, 0.0);
+    let mut idle = (0.0, 0.0);
+
+    for interval in discharge_intervals(logs) {
+        let Some(was_idle) = interval.start.idle else {
+            continue;
+        };
+        let bucket = if was_idle { &mut idle } else { &mut active };
+        bucket.0 += interval.energy;
+        bucket.1 += interval.hours;
+    }
+
+    if active.1 == 0.0 && idle.1 == 0.0 {
+        return None;
+    }
+
+    let watts = |(energy, hours): (f64, f64)| (hours > 0.0).then(|| energy / hours);
+    let full_energy = logs
+        .iter()
+        .rev()
+        .find(|log| log.percent > 0.0)
+        .and_then(|log| Some(f64::from(log.energy?) / f64::from(log.percent) * 100.0));
+
+    Some(UsageSplit {
+        active_watts: watts(active),
+        idle_watts: watts(idle),
+        active_hours: active.1,
+        idle_hours: idle.1,
+        full_energy,
+    })
+}
+
+/// Long-run averages of how long the battery lasts and charges.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BatteryLife {
+    /// How long 100% to 0% lasts, extrapolated from discharge sessions
+    pub full_discharge: Option<Duration>,
+    pub discharges: usize,
+    /// Drain while the user was active, i.e. with the screen on, in
+    /// percentage points per hour
+    pub active_drain: Option<f64>,
+    /// Average time to charge from 20% to 80%
+    pub charge_20_80: Option<Duration>,
+    pub charges: usize,
+    /// Full cycles discharged per week
+    pub cycles_per_week: Option<f64>,
+}
+
+/// Computes the battery life averages from history (oldest first) and the
+/// sessions detected in it.
+pub fn battery_life(logs: &[BatterySnapshot], sessions: &[Session]) -> BatteryLife {
+    let hours = |seconds: i64| seconds as f64 / 3600.0;
+    let from_hours = |hours: f64| Duration::from_secs((hours * 60.0).round() as u64 * 60);
+
+    let discharges: Vec<&Session> = sessions
+        .iter()
+        .filter(|session| session.kind == SessionKind::Discharge)
+        .collect();
+    let long: Vec<&&Session> = discharges
+        .iter()
+        .filter(|session| session.start_percent - session.end_percent >= MIN_DISCHARGE_DROP)
+        .collect();
+    let long_hours: f64 = long.iter().map(|session| hours(session.duration())).sum();
+    let long_drop: f64 = long
+        .iter()
+        .map(|session| f64::from(session.start_percent - session.end_percent))
+        .sum();
+
+    let mut active = (0.0, 0.0);
+    for pair in logs.windows(2) {
+        let (from, to) = (&pair[0], &pair[1]);
+        let seconds = to.timestamp - from.timestamp;
+        if from.status == "discharging"
+            && to.status == "discharging"
+            && from.idle == Some(false)
+            && seconds > 0
+            && seconds <= MAX_GAP.as_secs() as i64
+        {
+            active.0 += f64::from(from.percent - to.percent).max(0.0);
+            active.1 += hours(seconds);
+        }
+    }
+
+    let charges = charge_times(logs);
+
+    let span = match (logs.first(), logs.last()) {
+        (Some(first), Some(last)) => last.timestamp - first.timestamp,
+        _ => 0,
+    };
+    let discharged: f64 = discharges
+        .iter()
+        .map(|session| f64::from(session.start_percent - session.end_percent).max(0.0))
+        .sum();
+
+    BatteryLife {
+        full_discharge: (long_drop > 0.0).then(|| from_hours(long_hours / long_drop * 100.0)),
+        discharges: long.len(),
+        active_drain: (active.1 > 0.0).then(|| active.0 / active.1),
+        charge_20_80: (!charges.is_empty())
+            .then(|| from_hours(charges.iter().sum::<f64>() / charges.len() as f64)),
+        charges: charges.len(),
+        cycles_per_week: (span >= MIN_CYCLE_SPAN)
+            .then(|| discharged / 100.0 / (hours(span) / (7.0 * 24.0))),
+    }
+}
+
+/// Hours each uninterrupted charge took from `CHARGE_FROM` to `CHARGE_TO`,
+/// interpolating between samples.
+fn charge_times(logs: &[BatterySnapshot]) -> Vec<f64> {
+    // When `percent` was reached between `from` and `to`.
+    let crossing = |from: &BatterySnapshot, to: &BatterySnapshot, percent: f32| {
+        let share = f64::from((percent - from.percent) / (to.percent - from.percent));
+        from.timestamp as f64 + share.clamp(0.0, 1.0) * (to.timestamp - from.timestamp) as f64
+    };
+
+    let mut times = Vec::new();
+    let mut started: Option<f64> = None;
+    for pair in logs.windows(2) {
+        let (from, to) = (&pair[0], &pair[1]);
+        let seconds = to.timestamp - from.timestamp;
+        if from.status != "charging" || seconds <= 0 || seconds > MAX_GAP.as_secs() as i64 {
+            started = None;
+            continue;
+        }
+        if started.is_none() && from.percent <= CHARGE_FROM && to.percent > CHARGE_FROM {
+            started = Some(crossing(from, to, CHARGE_FROM));
+        }
+        if let Some(start) = started
+            && to.percent >= CHARGE_TO
+        {
+            times.push((crossing(from, to, CHARGE_TO) - start) / 3600.0);
+            started = None;
+        }
+    }
+    times
+}
+
+/// One day's use, as sent in the daemon's daily summary.
+#[derive(Debug, Clone, Copy)]
+pub struct DaySummary {
+    pub battery_hours: f64,
+    /// Average drain while on battery, in W
+    pub avg_watts: Option<f64>,
+    /// Charge used on battery, in full cycles
+    pub cycles: f64,
+    /// Latest state of health, in percent
+    pub health: Option<f32>,
+}
+
+impl DaySummary {
+    /// E.g. "7h 10m on battery, avg 8.9W, 1.4 cycles, health 91%".
+    pub fn describe(&self) -> String {
+        let mut parts = vec![format!(
+            "{} on battery",
+            durations::format(Duration::from_secs(
+                (self.battery_hours * 60.0).round() as u64 * 60
+            ))
+        )];
+        if let Some(watts) = self.avg_watts {
+            parts.push(format!("avg {:.1}W", watts));
+        }
+        parts.push(format!("{:.1} cycles", self.cycles));
+        if let Some(health) = self.health {
+            parts.push(format!("health {:.0}%", health));
+        }
+        parts.join(", ")
+    }
+}
+
+/// Summarizes a day of history sorted oldest first.
+pub fn day_summary(logs: &[BatterySnapshot]) -> Option<DaySummary> {
+    if logs.is_empty() {
+        return None;
+    }
+    let mut hours = 0.0;
+    let mut energy = 0.0;
+    let mut percent = 0.0;
+    for interval in discharge_intervals(logs) {
+        hours += interval.hours;
+        energy += interval.energy;
+        percent += f64::from(interval.start.percent - interval.end.percent).max(0.0);
+    }
+
+    Some(DaySummary {
+        battery_hours: hours,
+        avg_watts: (hours > 0.0).then(|| energy / hours),
+        cycles: percent / 100.0,
+        health: logs.iter().rev().find_map(|log| log.health),
+    })
+}
+
+/// Figures shown under the history graph for the range it covers.
+#[derive(Debug, Clone, Copy)]
+pub struct RangeSummary {
+    pub min: f32,
+    pub max: f32,
+    pub avg: f32,
+    /// Last minus first percent
+    pub net_change: f32,
+    pub charging_hours: f64,
+    pub discharging_hours: f64,
+}
+
+/// Summarizes history sorted oldest first. Time between two samples counts
+/// towards the state of the earlier one; gaps longer than `MAX_GAP` are left
+/// out.
+pub fn range_summary(logs: &[&BatterySnapshot]) -> Option<RangeSummary> {
+    let (first, last) = (logs.first()?, logs.last()?);
+    let percents = logs.iter().map(|log| log.percent);
+
+    let mut charging_hours = 0.0;
+    let mut discharging_hours = 0.0;
+    for pair in logs.windows(2) {
+        let seconds = pair[1].timestamp - pair[0].timestamp;
+        if seconds <= 0 || seconds > MAX_GAP.as_secs() as i64 {
+            continue;
+        }
+        match pair[0].status.as_str() {
+            "charging" => charging_hours += seconds as f64 / 3600.0,
+            "discharging" => discharging_hours += seconds as f64 / 3600.0,
+            _ => {}
+        }
+    }
+
+    Some(RangeSummary {
+        min: percents.clone().fold(f32::INFINITY, f32::min),
+        max: percents.clone().fold(f32::NEG_INFINITY, f32::max),
+        avg: percents.sum::<f32>() / logs.len() as f32,
+        net_change: last.percent - first.percent,
+        charging_hours,
+        discharging_hours,
+    })
+}
+
+/// Width of the charge bands voltage sag is compared within, in percent,
+/// since the resting voltage itself falls with the charge.
+pub const VOLTAGE_BAND: f32 = 10.0;
+/// Smallest rise in current between light and heavy draw, in A, worth
+/// deriving a resistance from.
+const MIN_CURRENT_RISE: f64 = 0.1;
+
+/// One voltage reading and what the battery was doing at the time.
+#[derive(Debug, Clone, Copy)]
+pub struct VoltageReading {
+    pub volts: f64,
+    pub percent: f32,
+    pub watts: Option<f64>,
+}
+
+/// Voltage while discharging, and how far it sags at heavy draw.
+#[derive(Debug, Clone)]
+pub struct VoltageStats {
+    pub samples: usize,
+    pub min: VoltageReading,
+    pub max: VoltageReading,
+    pub avg: f64,
+    /// Draw from which a sample counts as heavy: the median, in W
+    pub heavy_from: Option<f64>,
+    /// Charge bands with both light and heavy samples, highest charge first
+    pub bands: Vec<VoltageSag>,
+}
+
+/// Average voltage and draw at light and heavy load within one charge
+/// band.
+#[derive(Debug, Clone, Copy)]
+pub struct VoltageSag {
+    /// Lower end of the band, in percent
+    pub from: f32,
+    pub light_volts: f64,
+    pub light_watts: f64,
+    pub heavy_volts: f64,
+    pub heavy_watts: f64,
+}
+
+impl VoltageSag {
+    /// How much lower the voltage is at heavy draw, in V.
+    pub fn sag(&self) -> f64 {
+        self.light_volts - self.heavy_volts
+    }
+
+    /// Internal resistance the sag implies, in mΩ: the drop in voltage over
+    /// the rise in current. It grows as cells age.
+    pub fn resistance(&self) -> Option<f64> {
+        let rise = self.heavy_watts / self.heavy_volts - self.light_watts / self.light_volts;
+        (rise >= MIN_CURRENT_RISE).then(|| self.sag() / rise * 1000.0)
+    }
+}
+
+impl VoltageStats {
+    /// Sag averaged over the charge bands, in V.
+    pub fn average_sag(&self) -> Option<f64> {
+        (!self.bands.is_empty())
+            .then(|| self.bands.iter().map(VoltageSag::sag).sum::<f64>() / self.bands.len() as f64)
+    }
+
+    /// Internal resistance averaged over the bands that give one, in mΩ.
+    pub fn resistance(&self) -> Option<f64> {
+        let values: Vec<f64> = self
+            .bands
+            .iter()
+            .filter_map(VoltageSag::resistance)
+            .collect();
+        (!values.is_empty()).then(|| values.iter().sum::<f64>() / values.len() as f64)
+    }
+}
+
+/// Summarizes the voltage of discharging samples in history. Samples at or
+/// above the median draw count as heavy load and are compared with the
+/// lighter ones at similar charge.
+pub fn voltage_stats(logs: &[BatterySnapshot]) -> Option<VoltageStats> {
+    let readings: Vec<VoltageReading> = logs
+        .iter()
+        .filter(|log| log.status == "discharging")
+        .filter_map(|log| {
+            Some(VoltageReading {
+                volts: f64::from(log.voltage?),
+                percent: log.percent,
+                watts: log.power_watts.map(f64::from).filter(|watts| *watts > 0.0),
+            })
+        })
+        .collect();
+    let min = *readings.iter().min_by(|a, b| a.volts.total_cmp(&b.volts))?;
+    let max = *readings.iter().max_by(|a, b| a.volts.total_cmp(&b.volts))?;
+    let avg = readings.iter().map(|reading| reading.volts).sum::<f64>() / readings.len() as f64;
+
+    let mut draws: Vec<f64> = readings
+        .iter()
+        .filter_map(|reading| reading.watts)
+        .collect();
+    draws.sort_by(f64::total_cmp);
+    let heavy_from = draws.get(draws.len() / 2).copied();
+
+    let mut bands = Vec::new();
+    if let Some(heavy_from) = heavy_from {
+        let band_count = (100.0 / VOLTAGE_BAND) as usize;
+        for band in (0..band_count).rev() {
+            let from = band as f32 * VOLTAGE_BAND;
+            let in_band = |reading: &&VoltageReading| {
+                // 100% belongs to the top band
+                (reading.percent >= from && reading.percent < from + VOLTAGE_BAND)
+                    || (band == band_count - 1 && reading.percent >= 100.0)
+            };
+            let (mut light, mut heavy) = ((0.0, 0.0, 0), (0.0, 0.0, 0));
+            for reading in readings.iter().filter(in_band) {
+                let Some(watts) = reading.watts else {
+                    continue;
+                };
+                let side = if watts >= heavy_from {
+                    &mut heavy
+                } else {
+                    &mut light
+                };
+                side.0 += reading.volts;
+                side.1 += watts;
+                side.2 += 1;
+            }
+            if light.2 == 0 || heavy.2 == 0 {
+                continue;
+            }
+            bands.push(VoltageSag {
+                from,
+                light_volts: light.0 / f64::from(light.2),
+                light_watts: light.1 / f64::from(light.2),
+                heavy_volts: heavy.0 / f64::from(heavy.2),
+                heavy_watts: heavy.1 / f64::from(heavy.2),
+            });
+        }
+    }
+
+    Some(VoltageStats {
+        samples: readings.len(),
+        min,
+        max,
+        avg,
+        heavy_from,
+        bands,
+    })
+}
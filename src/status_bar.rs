@@ -0,0 +1,137 @@
+//! One-line battery status for Waybar, Polybar and i3blocks, printed by
+//! `amptop status-bar`.
+
+use crate::config::GaugeThresholds;
+use crate::durations;
+use crate::info::Summary;
+use serde_json::json;
+use std::time::Duration;
+
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum BarFormat {
+    /// JSON for a `custom` module with `return-type` set to `json`
+    Waybar,
+    /// Text with `%{F}` color tags for a `custom/script` module
+    Polybar,
+    /// Full text, short text and color lines
+    I3blocks,
+}
+
+/// Icons and urgency colors, from the `[status_bar]` section of the config
+/// file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BarStyle {
+    /// Icons from empty to full, picked by the charge
+    pub icons: Vec<String>,
+    pub charging_icon: String,
+    pub warning_color: String,
+    pub critical_color: String,
+}
+
+impl Default for BarStyle {
+    fn default() -> Self {
+        Self {
+            icons: ["▁", "▂", "▄", "▆", "█"].map(str::to_string).to_vec(),
+            charging_icon: "⚡".to_string(),
+            warning_color: "#ffb52a".to_string(),
+            critical_color: "#ff5555".to_string(),
+        }
+    }
+}
+
+/// How pressing the charge is, by the gauge's thresholds.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum Urgency {
+    Normal,
+    Warning,
+    Critical,
+}
+
+impl Urgency {
+    fn of(summary: &Summary, thresholds: GaugeThresholds) -> Self {
+        // Plugged in, a low charge is only going up.
+        if summary.state != "discharging" {
+            Urgency::Normal
+        } else if summary.percent <= thresholds.critical {
+            Urgency::Critical
+        } else if summary.percent <= thresholds.low {
+            Urgency::Warning
+        } else {
+            Urgency::Normal
+        }
+    }
+
+    fn class(self) -> Option<&'static str> {
+        match self {
+            Urgency::Normal => None,
+            Urgency::Warning => Some("warning"),
+            Urgency::Critical => Some("critical"),
+        }
+    }
+}
+
+/// The status line in `format`, and how urgent it is.
+pub fn render(
+    summary: &Summary,
+    style: &BarStyle,
+    thresholds: GaugeThresholds,
+    format: BarFormat,
+) -> (String, Urgency) {
+    let urgency = Urgency::of(summary, thresholds);
+    let icon = if summary.state == "charging" {
+        style.charging_icon.as_str()
+    } else {
+        let last = style.icons.len().saturating_sub(1);
+        let level = ((summary.percent / 100.0) * last as f32).round() as usize;
+        style.icons.get(level.min(last)).map_or("", String::as_str)
+    };
+    let estimate = summary
+        .time_to_empty_secs
+        .or(summary.time_to_full_secs)
+        .map(|secs| durations::format(Duration::from_secs(secs)));
+    let percent = format!("{:.0}%", summary.percent);
+    let text = match estimate {
+        Some(ref estimate) => format!("{} {} ({})", icon, percent, estimate),
+        None => format!("{} {}", icon, percent),
+    };
+    let color = match urgency {
+        Urgency::Normal => None,
+        Urgency::Warning => Some(&style.warning_color),
+        Urgency::Critical => Some(&style.critical_color),
+    };
+
+    let line = match format {
+        BarFormat::Waybar => {
+            let mut classes = vec![summary.state.as_str()];
+            classes.extend(urgency.class());
+            let tooltip = match estimate {
+                Some(ref estimate) if summary.time_to_empty_secs.is_some() => {
+                    format!("{}, {} to empty", summary.state, estimate)
+                }
+                Some(ref estimate) => format!("{}, {} to full", summary.state, estimate),
+                None => summary.state.clone(),
+            };
+            json!({
+                "text": text,
+                "alt": summary.state,
+                "tooltip": tooltip,
+                "class": classes,
+                "percentage": summary.percent.round() as u32,
+            })
+            .to_string()
+        }
+        BarFormat::Polybar => match color {
+            Some(color) => format!("%{{F{}}}{}%{{F-}}", color, text),
+            None => text,
+        },
+        BarFormat::I3blocks => {
+            let mut lines = format!("{}\n{} {}", text, icon, percent);
+            if let Some(color) = color {
+                lines.push('\n');
+                lines.push_str(color);
+            }
+            lines
+        }
+    };
+    (line, urgency)
+}
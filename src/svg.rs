@@ -0,0 +1,182 @@
+//! Line charts over time as standalone SVG, for reports and exported
+//! graphs.
+
+use chrono::{Local, TimeZone};
+use std::fmt::Write;
+
+/// Space around the plot for the title and axis labels, in pixels.
+const MARGIN_LEFT: f64 = 56.0;
+const MARGIN_RIGHT: f64 = 16.0;
+const MARGIN_TOP: f64 = 32.0;
+const MARGIN_BOTTOM: f64 = 28.0;
+/// Grid lines, and labels, on each axis.
+const TICKS: usize = 5;
+
+/// One line of a chart: values at timestamps, oldest first.
+pub struct Series {
+    /// Shown in the legend, unless empty
+    pub label: String,
+    /// Any SVG color, e.g. `#2e7d32`
+    pub color: String,
+    pub points: Vec<(i64, f64)>,
+}
+
+pub struct Chart {
+    pub title: String,
+    /// Appended to the Y axis labels, e.g. `%` or ` Wh`
+    pub unit: String,
+    /// Fixed Y axis range; otherwise it fits the values
+    pub y_range: Option<(f64, f64)>,
+    /// Points further apart than this, in seconds, aren't joined
+    pub max_gap: Option<i64>,
+    pub series: Vec<Series>,
+}
+
+impl Chart {
+    /// Renders the chart as an `<svg>` element `width` by `height` pixels.
+    pub fn render(&self, width: u32, height: u32) -> String {
+        let (width, height) = (f64::from(width), f64::from(height));
+        let mut svg = String::new();
+        let _ = write!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" font-family="sans-serif" font-size="11">"#,
+            w = width,
+            h = height
+        );
+        let _ = write!(
+            svg,
+            r##"<rect width="100%" height="100%" fill="#ffffff"/><text x="{}" y="18" font-size="13" font-weight="bold">{}</text>"##,
+            MARGIN_LEFT,
+            escape(&self.title)
+        );
+
+        // Legend, right-aligned in the title row
+        let mut x = width - MARGIN_RIGHT;
+        for series in self
+            .series
+            .iter()
+            .rev()
+            .filter(|series| !series.label.is_empty())
+        {
+            let _ = write!(
+                svg,
+                r#"<text x="{}" y="18" text-anchor="end" fill="{}">{}</text>"#,
+                x,
+                escape(&series.color),
+                escape(&series.label)
+            );
+            x -= series.label.chars().count() as f64 * 7.0 + 16.0;
+        }
+
+        let points = || self.series.iter().flat_map(|series| series.points.iter());
+        let (Some(start), Some(end)) = (
+            points().map(|&(t, _)| t).min(),
+            points().map(|&(t, _)| t).max(),
+        ) else {
+            let _ = write!(
+                svg,
+                r##"<text x="{}" y="{}" text-anchor="middle" fill="#888888">No data</text></svg>"##,
+                width / 2.0,
+                height / 2.0
+            );
+            return svg;
+        };
+        let (low, high) = self.y_range.unwrap_or_else(|| {
+            let low = points().map(|&(_, v)| v).fold(f64::INFINITY, f64::min);
+            let high = points().map(|&(_, v)| v).fold(f64::NEG_INFINITY, f64::max);
+            let pad = ((high - low) * 0.1).max(0.5);
+            (low - pad, high + pad)
+        });
+
+        let plot_width = width - MARGIN_LEFT - MARGIN_RIGHT;
+        let plot_height = height - MARGIN_TOP - MARGIN_BOTTOM;
+        let span = (end - start).max(1) as f64;
+        let x_of = |t: i64| MARGIN_LEFT + (t - start) as f64 / span * plot_width;
+        let y_of = |v: f64| MARGIN_TOP + (1.0 - (v - low) / (high - low)) * plot_height;
+
+        // Grid and Y labels
+        for i in 0..TICKS {
+            let value = low + (high - low) * i as f64 / (TICKS - 1) as f64;
+            let y = y_of(value);
+            let _ = write!(
+                svg,
+                r##"<line x1="{}" y1="{y:.1}" x2="{}" y2="{y:.1}" stroke="#e0e0e0"/><text x="{}" y="{:.1}" text-anchor="end" fill="#555555">{:.0}{}</text>"##,
+                MARGIN_LEFT,
+                width - MARGIN_RIGHT,
+                MARGIN_LEFT - 6.0,
+                y + 4.0,
+                value,
+                escape(&self.unit)
+            );
+        }
+
+        // Time labels, with the date once the chart spans more than a day
+        let format = if end - start > 24 * 60 * 60 {
+            "%m-%d"
+        } else {
+            "%H:%M"
+        };
+        // A single moment gets a single label.
+        let ticks = if end > start { TICKS } else { 1 };
+        for i in 0..ticks {
+            let t = start + ((end - start) as f64 * i as f64 / (TICKS - 1) as f64) as i64;
+            let label = Local
+                .timestamp_opt(t, 0)
+                .single()
+                .map(|dt| dt.format(format).to_string())
+                .unwrap_or_default();
+            let anchor = match i {
+                0 => "start",
+                i if i == TICKS - 1 => "end",
+                _ => "middle",
+            };
+            let _ = write!(
+                svg,
+                r##"<text x="{:.1}" y="{}" text-anchor="{}" fill="#555555">{}</text>"##,
+                x_of(t),
+                height - 8.0,
+                anchor,
+                label
+            );
+        }
+
+        for series in &self.series {
+            for segment in series
+                .points
+                .chunk_by(|a, b| self.max_gap.is_none_or(|gap| b.0 - a.0 <= gap))
+            {
+                // A lone point has no line to draw, so mark it instead.
+                if let [(t, v)] = segment {
+                    let _ = write!(
+                        svg,
+                        r#"<circle cx="{:.1}" cy="{:.1}" r="2" fill="{}"/>"#,
+                        x_of(*t),
+                        y_of(v.clamp(low, high)),
+                        escape(&series.color)
+                    );
+                    continue;
+                }
+                let coordinates: Vec<String> = segment
+                    .iter()
+                    .map(|&(t, v)| format!("{:.1},{:.1}", x_of(t), y_of(v.clamp(low, high))))
+                    .collect();
+                let _ = write!(
+                    svg,
+                    r#"<polyline fill="none" stroke="{}" stroke-width="1.5" points="{}"/>"#,
+                    escape(&series.color),
+                    coordinates.join(" ")
+                );
+            }
+        }
+        svg.push_str("</svg>");
+        svg
+    }
+}
+
+/// `text` with the characters XML reserves replaced by entities.
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
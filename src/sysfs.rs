@@ -0,0 +1,188 @@
+//! Direct reader for `/sys/class/power_supply`.
+//!
+//! The `battery` crate always reads from `/sys`, which isn't the host's sysfs
+//! inside containers and Flatpak/Snap sandboxes. When a custom root is set
+//! (e.g. `/run/host/sys`), battery data is read from there instead.
+//!
+//! It also reads attributes the `battery` crate leaves out, such as the
+//! driver's health verdict and the manufacture date.
+
+use crate::info::{Charge, Reading};
+use battery::units::{
+    ElectricPotential, Energy, Power, Ratio, ThermodynamicTemperature, Time,
+    electric_potential::volt, energy::watt_hour, power::watt, ratio::percent,
+    thermodynamic_temperature::degree_celsius, time::second,
+};
+use chrono::NaiveDate;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+const DEFAULT_ROOT: &str = "/sys";
+
+static CUSTOM_ROOT: OnceLock<PathBuf> = OnceLock::new();
+
+/// Points every sysfs lookup at `root` instead of `/sys`. Only the first
+/// call has an effect.
+pub fn set_root(root: PathBuf) {
+    CUSTOM_ROOT.set(root).ok();
+}
+
+pub fn custom_root() -> Option<&'static Path> {
+    CUSTOM_ROOT.get().map(PathBuf::as_path)
+}
+
+pub fn root() -> &'static Path {
+    custom_root().unwrap_or(Path::new(DEFAULT_ROOT))
+}
+
+pub fn power_supply_dir() -> PathBuf {
+    root().join("class/power_supply")
+}
+
+/// Directories of every system battery (peripherals such as mice are
+/// skipped), sorted by name.
+pub fn battery_dirs() -> Vec<PathBuf> {
+    let mut entries: Vec<PathBuf> = fs::read_dir(power_supply_dir())
+        .map(|entries| entries.flatten().map(|entry| entry.path()).collect())
+        .unwrap_or_default();
+    entries.sort();
+
+    entries
+        .into_iter()
+        .filter(|path| {
+            let is_battery = read_string(&path.join("type")).as_deref() == Some("Battery");
+            let is_peripheral = read_string(&path.join("scope")).as_deref() == Some("Device");
+            is_battery && !is_peripheral
+        })
+        .collect()
+}
+
+/// Reads every system battery (peripherals such as mice are skipped).
+pub fn read_batteries() -> Vec<Reading> {
+    battery_dirs()
+        .iter()
+        .filter_map(|path| read_reading(path))
+        .collect()
+}
+
+/// Adds the attributes only sysfs has to readings from another source,
+/// pairing batteries by model and serial number.
+pub fn add_details(readings: &mut [Reading]) {
+    let dirs = battery_dirs();
+    for (index, reading) in readings.iter_mut().enumerate() {
+        let same_battery = |dir: &&PathBuf| {
+            read_string(&dir.join("model_name")) == reading.model
+                && read_string(&dir.join("serial_number")) == reading.serial_number
+        };
+        let dir = match dirs.iter().find(same_battery) {
+            Some(dir) => dir,
+            // Without a model or serial to go by, a lone battery is the
+            // same on both sides.
+            None if dirs.len() == 1 && index == 0 => &dirs[0],
+            None => continue,
+        };
+        reading.health_status = health_status(dir);
+        reading.manufactured = manufactured(dir);
+    }
+}
+
+/// `health`, when the driver reports one.
+fn health_status(path: &Path) -> Option<String> {
+    read_string(&path.join("health")).filter(|health| health != "Unknown")
+}
+
+/// `manufacture_year`/`_month`/`_day`, which only some drivers provide.
+fn manufactured(path: &Path) -> Option<NaiveDate> {
+    let part = |name: &str| read_number(&path.join(name)).map(|value| value as u32);
+    NaiveDate::from_ymd_opt(
+        part("manufacture_year")? as i32,
+        part("manufacture_month")?,
+        part("manufacture_day").unwrap_or(1),
+    )
+}
+
+fn read_reading(path: &Path) -> Option<Reading> {
+    // All values are exposed in micro-units (µWh, µAh, µW, µA, µV).
+    let micro = |name: &str| read_number(&path.join(name)).map(|v| v / 1e6);
+
+    let voltage = micro("voltage_now").unwrap_or(0.0);
+    let design_voltage = micro("voltage_min_design").unwrap_or(voltage);
+
+    let energy_of = |energy: &str, charge: &str| {
+        micro(energy).or_else(|| micro(charge).map(|ah| ah * design_voltage))
+    };
+    let energy = energy_of("energy_now", "charge_now")?;
+    let energy_full = energy_of("energy_full", "charge_full").unwrap_or(energy);
+    let energy_full_design =
+        energy_of("energy_full_design", "charge_full_design").unwrap_or(energy_full);
+    let power = micro("power_now")
+        .or_else(|| micro("current_now").map(|amps| amps * voltage))
+        .unwrap_or(0.0)
+        .abs();
+
+    let state = read_string(&path.join("status"))
+        .and_then(|status| status.parse().ok())
+        .unwrap_or(battery::State::Unknown);
+    let charge = read_number(&path.join("capacity"))
+        .map(|capacity| capacity / 100.0)
+        .unwrap_or_else(|| ratio_of(energy, energy_full));
+
+    let hours = match state {
+        battery::State::Discharging if power > 0.0 => Some(energy / power),
+        battery::State::Charging if power > 0.0 => Some((energy_full - energy).max(0.0) / power),
+        _ => None,
+    };
+    let time = hours.map(|hours| Time::new::<second>((hours * 3600.0) as f32));
+
+    Some(Reading {
+        state_of_charge: Ratio::new::<percent>((charge * 100.0).clamp(0.0, 100.0) as f32),
+        energy: Energy::new::<watt_hour>(energy as f32),
+        energy_full: Energy::new::<watt_hour>(energy_full as f32),
+        energy_full_design: Energy::new::<watt_hour>(energy_full_design as f32),
+        energy_rate: Power::new::<watt>(power as f32),
+        voltage: ElectricPotential::new::<volt>(voltage as f32),
+        state_of_health: Ratio::new::<percent>(
+            (ratio_of(energy_full, energy_full_design) * 100.0) as f32,
+        ),
+        state,
+        technology: read_string(&path.join("technology"))
+            .and_then(|tech| tech.parse().ok())
+            .unwrap_or(battery::Technology::Unknown),
+        temperature: read_number(&path.join("temp"))
+            .map(|tenths| ThermodynamicTemperature::new::<degree_celsius>((tenths / 10.0) as f32)),
+        cycle_count: read_number(&path.join("cycle_count"))
+            .filter(|count| *count > 0.0)
+            .map(|count| count as u32),
+        vendor: read_string(&path.join("manufacturer")),
+        model: read_string(&path.join("model_name")),
+        serial_number: read_string(&path.join("serial_number")),
+        time_to_full: time.filter(|_| state == battery::State::Charging),
+        time_to_empty: time.filter(|_| state == battery::State::Discharging),
+        health_status: health_status(path),
+        manufactured: manufactured(path),
+        charge: micro("charge_now").map(|now| {
+            let full = micro("charge_full").unwrap_or(now);
+            Charge {
+                now: (now * 1000.0) as f32,
+                full: (full * 1000.0) as f32,
+                full_design: (micro("charge_full_design").unwrap_or(full) * 1000.0) as f32,
+            }
+        }),
+    })
+}
+
+fn ratio_of(value: f64, total: f64) -> f64 {
+    if total > 0.0 { value / total } else { 0.0 }
+}
+
+pub fn read_string(path: &Path) -> Option<String> {
+    fs::read_to_string(path)
+        .ok()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+}
+
+pub fn read_number(path: &Path) -> Option<f64> {
+    read_string(path)?.parse().ok()
+}
@@ -0,0 +1,55 @@
+//! Named activity intervals ("video call", "compile") and what they cost.
+
+use crate::daemon::BatterySnapshot;
+use crate::stats;
+
+#[derive(Debug, Clone)]
+pub struct Tag {
+    pub name: String,
+    pub started_at: i64,
+    /// `None` while the tag is still running
+    pub stopped_at: Option<i64>,
+}
+
+impl Tag {
+    /// End of the interval, `now` for a running tag.
+    pub fn end(&self, now: i64) -> i64 {
+        self.stopped_at.unwrap_or(now)
+    }
+}
+
+/// Battery used during every interval tagged with one name.
+#[derive(Debug, Clone, Default)]
+pub struct TagUsage {
+    pub name: String,
+    pub intervals: usize,
+    pub seconds: i64,
+    /// Percentage points lost while discharging
+    pub percent: f64,
+    /// Energy drawn from the battery, in Wh
+    pub energy: f64,
+    /// Hours spent discharging, over which `energy` was drawn
+    pub discharge_hours: f64,
+}
+
+impl TagUsage {
+    pub fn average_watts(&self) -> Option<f64> {
+        (self.discharge_hours > 0.0).then(|| self.energy / self.discharge_hours)
+    }
+
+    /// Adds one tagged interval, given the history (oldest first) recorded
+    /// during it. Time on AC costs nothing.
+    pub fn add(&mut self, seconds: i64, logs: &[BatterySnapshot]) {
+        self.intervals += 1;
+        self.seconds += seconds;
+        self.percent += logs
+            .windows(2)
+            .filter(|pair| pair[0].status == "discharging" && pair[1].status == "discharging")
+            .map(|pair| f64::from(pair[0].percent - pair[1].percent).max(0.0))
+            .sum::<f64>();
+        for interval in stats::discharge_intervals(logs) {
+            self.energy += interval.energy;
+            self.discharge_hours += interval.hours;
+        }
+    }
+}
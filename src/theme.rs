@@ -0,0 +1,207 @@
+//! Colors the TUI draws with, by what they mean rather than by hue, so a
+//! preset or the `[colors]` section of the config file can change them all
+//! in one place.
+
+use ratatui::style::Color;
+
+/// Built-in color schemes, selected with `--theme` or `theme`.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub enum ThemeName {
+    Default,
+    Solarized,
+    Gruvbox,
+    /// No colors, for terminals and users that prefer plain text
+    Monochrome,
+    /// Bright colors on the terminal's background, for low-contrast
+    /// displays and low vision
+    HighContrast,
+}
+
+impl ThemeName {
+    pub const ALL: [ThemeName; 5] = [
+        ThemeName::Default,
+        ThemeName::Solarized,
+        ThemeName::Gruvbox,
+        ThemeName::Monochrome,
+        ThemeName::HighContrast,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ThemeName::Default => "default",
+            ThemeName::Solarized => "solarized",
+            ThemeName::Gruvbox => "gruvbox",
+            ThemeName::Monochrome => "mono",
+            ThemeName::HighContrast => "high-contrast",
+        }
+    }
+
+    /// Accepts `monochrome` as well as `mono`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        if name.eq_ignore_ascii_case("monochrome") {
+            return Some(ThemeName::Monochrome);
+        }
+        Self::ALL
+            .into_iter()
+            .find(|theme| theme.name().eq_ignore_ascii_case(name))
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+pub struct Theme {
+    /// Charging samples
+    pub charging: Color,
+    pub discharging: Color,
+    /// Samples taken while full
+    pub full: Color,
+    /// Samples in any other state, and highlights such as tips
+    pub accent: Color,
+    /// The wear trend
+    pub secondary: Color,
+    /// Empty states and annotations
+    pub warning: Color,
+    /// Errors and overheating
+    pub error: Color,
+    /// Hints, labels and stale readings
+    pub muted: Color,
+    /// Graph axes and inactive tabs
+    pub axis: Color,
+    /// Borders and text that stand out
+    pub foreground: Color,
+    /// The state-of-charge gauge above `gauge.low`, below it and below
+    /// `gauge.critical`
+    pub gauge_high: Color,
+    pub gauge_low: Color,
+    pub gauge_critical: Color,
+}
+
+impl Theme {
+    /// Names of the colors `set` accepts, in the order they are listed.
+    pub const ROLES: [&'static str; 13] = [
+        "charging",
+        "discharging",
+        "full",
+        "accent",
+        "secondary",
+        "warning",
+        "error",
+        "muted",
+        "axis",
+        "foreground",
+        "gauge_high",
+        "gauge_low",
+        "gauge_critical",
+    ];
+
+    pub fn new(name: ThemeName) -> Self {
+        match name {
+            ThemeName::Default => Self {
+                charging: Color::Green,
+                discharging: Color::Red,
+                full: Color::Blue,
+                accent: Color::Cyan,
+                secondary: Color::Magenta,
+                warning: Color::Yellow,
+                error: Color::Red,
+                muted: Color::DarkGray,
+                axis: Color::Gray,
+                foreground: Color::White,
+                gauge_high: Color::Green,
+                gauge_low: Color::Yellow,
+                gauge_critical: Color::Red,
+            },
+            ThemeName::Solarized => Self {
+                charging: Color::Rgb(0x85, 0x99, 0x00),
+                discharging: Color::Rgb(0xdc, 0x32, 0x2f),
+                full: Color::Rgb(0x26, 0x8b, 0xd2),
+                accent: Color::Rgb(0x2a, 0xa1, 0x98),
+                secondary: Color::Rgb(0x6c, 0x71, 0xc4),
+                warning: Color::Rgb(0xb5, 0x89, 0x00),
+                error: Color::Rgb(0xcb, 0x4b, 0x16),
+                muted: Color::Rgb(0x58, 0x6e, 0x75),
+                axis: Color::Rgb(0x83, 0x94, 0x96),
+                foreground: Color::Rgb(0x93, 0xa1, 0xa1),
+                gauge_high: Color::Rgb(0x85, 0x99, 0x00),
+                gauge_low: Color::Rgb(0xb5, 0x89, 0x00),
+                gauge_critical: Color::Rgb(0xcb, 0x4b, 0x16),
+            },
+            ThemeName::Gruvbox => Self {
+                charging: Color::Rgb(0xb8, 0xbb, 0x26),
+                discharging: Color::Rgb(0xfb, 0x49, 0x34),
+                full: Color::Rgb(0x83, 0xa5, 0x98),
+                accent: Color::Rgb(0x8e, 0xc0, 0x7c),
+                secondary: Color::Rgb(0xd3, 0x86, 0x9b),
+                warning: Color::Rgb(0xfa, 0xbd, 0x2f),
+                error: Color::Rgb(0xfe, 0x80, 0x19),
+                muted: Color::Rgb(0x92, 0x83, 0x74),
+                axis: Color::Rgb(0xa8, 0x99, 0x84),
+                foreground: Color::Rgb(0xeb, 0xdb, 0xb2),
+                gauge_high: Color::Rgb(0xb8, 0xbb, 0x26),
+                gauge_low: Color::Rgb(0xfa, 0xbd, 0x2f),
+                gauge_critical: Color::Rgb(0xfe, 0x80, 0x19),
+            },
+            ThemeName::Monochrome => Self {
+                charging: Color::Reset,
+                discharging: Color::Reset,
+                full: Color::Reset,
+                accent: Color::Reset,
+                secondary: Color::Reset,
+                warning: Color::Reset,
+                error: Color::Reset,
+                muted: Color::Reset,
+                axis: Color::Reset,
+                foreground: Color::Reset,
+                gauge_high: Color::Reset,
+                gauge_low: Color::Reset,
+                gauge_critical: Color::Reset,
+            },
+            ThemeName::HighContrast => Self {
+                charging: Color::LightGreen,
+                discharging: Color::LightRed,
+                full: Color::LightBlue,
+                accent: Color::LightCyan,
+                secondary: Color::LightMagenta,
+                warning: Color::LightYellow,
+                error: Color::LightRed,
+                muted: Color::Gray,
+                axis: Color::White,
+                foreground: Color::White,
+                gauge_high: Color::LightGreen,
+                gauge_low: Color::LightYellow,
+                gauge_critical: Color::LightRed,
+            },
+        }
+    }
+
+    /// Replaces one color, named as in `ROLES`, with a color name such as
+    /// `light-blue`, a 256-color index or `#rrggbb`.
+    pub fn set(&mut self, role: &str, value: &str) -> Result<(), String> {
+        let color = value
+            .parse()
+            .map_err(|_| format!("{} isn't a valid color for {}", value, role))?;
+        let slot = match role {
+            "charging" => &mut self.charging,
+            "discharging" => &mut self.discharging,
+            "full" => &mut self.full,
+            "accent" => &mut self.accent,
+            "secondary" => &mut self.secondary,
+            "warning" => &mut self.warning,
+            "error" => &mut self.error,
+            "muted" => &mut self.muted,
+            "axis" => &mut self.axis,
+            "foreground" => &mut self.foreground,
+            "gauge_high" => &mut self.gauge_high,
+            "gauge_low" => &mut self.gauge_low,
+            "gauge_critical" => &mut self.gauge_critical,
+            _ => {
+                return Err(format!(
+                    "{} isn't a themable color, expected one of {}",
+                    role,
+                    Self::ROLES.join(", ")
+                ));
+            }
+        };
+        *slot = color;
+        Ok(())
+    }
+}
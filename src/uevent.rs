@@ -0,0 +1,108 @@
+//! Kernel `power_supply` uevent subscription.
+//!
+//! Listens on a `NETLINK_KOBJECT_UEVENT` socket and forwards a notification
+//! whenever a power supply (battery or AC adapter) changes, so callers can
+//! react to plug/unplug immediately instead of waiting for the next poll.
+//! Without the socket, UPower's change signals are used instead; callers
+//! keep polling on their timer when neither is available.
+
+use crate::backend::Backend;
+use crate::errors::Result;
+use crate::upower;
+use std::sync::mpsc::{self, Receiver};
+
+#[derive(Debug, Clone)]
+pub struct PowerEvent {
+    /// Unix timestamp at which the event was received
+    pub timestamp: i64,
+}
+
+/// Spawns a background listener and returns the receiving end of its channel.
+///
+/// Kernel uevents are preferred. UPower's change signals, read from `upower
+/// --monitor`, are used where the netlink socket can't be opened (containers
+/// and sandboxes without one) and with `--backend upower`, whose
+/// readings only change once UPower has caught up with the kernel.
+pub fn subscribe() -> Result<Receiver<PowerEvent>> {
+    if Backend::selected() == Backend::UPower {
+        return upower::monitor().or_else(|_| netlink());
+    }
+    netlink().or_else(|e| upower::monitor().map_err(|_| e))
+}
+
+#[cfg(target_os = "linux")]
+fn netlink() -> Result<Receiver<PowerEvent>> {
+    use std::io;
+    use std::mem;
+
+    let fd = unsafe {
+        libc::socket(
+            libc::AF_NETLINK,
+            libc::SOCK_DGRAM | libc::SOCK_CLOEXEC,
+            libc::NETLINK_KOBJECT_UEVENT,
+        )
+    };
+    if fd < 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+
+    let mut addr: libc::sockaddr_nl = unsafe { mem::zeroed() };
+    addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+    addr.nl_groups = 1;
+
+    let bound = unsafe {
+        libc::bind(
+            fd,
+            &addr as *const libc::sockaddr_nl as *const libc::sockaddr,
+            mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+        )
+    };
+    if bound < 0 {
+        let err = io::Error::last_os_error();
+        unsafe { libc::close(fd) };
+        return Err(err.into());
+    }
+
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        let mut buf = [0u8; 8192];
+        loop {
+            let len = unsafe { libc::recv(fd, buf.as_mut_ptr().cast(), buf.len(), 0) };
+            if len < 0 {
+                if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                break;
+            }
+
+            if let Some(event) = parse_uevent(&buf[..len as usize])
+                && tx.send(event).is_err()
+            {
+                break;
+            }
+        }
+        unsafe { libc::close(fd) };
+    });
+
+    Ok(rx)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn netlink() -> Result<Receiver<PowerEvent>> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "power supply events are only available on Linux",
+    )
+    .into())
+}
+
+#[cfg(target_os = "linux")]
+fn parse_uevent(message: &[u8]) -> Option<PowerEvent> {
+    let is_power_supply = message
+        .split(|&b| b == 0)
+        .any(|field| field == b"SUBSYSTEM=power_supply");
+
+    is_power_supply.then(|| PowerEvent {
+        timestamp: chrono::Utc::now().timestamp(),
+    })
+}
@@ -1,38 +1,149 @@
-use crate::config::Unit;
-use crate::daemon::BatteryDaemon;
+use crate::annotations::Annotation;
+use crate::application::{FinishPrompt, GotoPrompt, HistoryWindow, OverheadPanel, Tab, TabData};
+use crate::config::{ChartAxis, Config, GaugeThresholds, SettingSource, TemperatureUnit, Unit};
+use crate::daemon::{BatterySnapshot, ChargeBands, HealthSample};
+use crate::desktop::{DesktopPower, Peripheral};
+use crate::durations;
+use crate::errors::Result;
+use crate::forecast::{CapacityTrend, WORN_OUT_HEALTH};
 use crate::info::BatteryInfo;
+use crate::keymap::{Action, Keymap};
+use crate::overhead;
+use crate::sessions::{Session, SessionKind};
+use crate::stats::{self, RangeSummary};
+use crate::theme::Theme;
+use crate::workload::{self, Preset};
 use ratatui::{
     Frame,
-    layout::{Alignment, Constraint, Rect},
+    layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
-    text::Span,
-    widgets::{Axis, Block, Borders, Chart, Dataset, Gauge, Paragraph, Row, Table},
+    text::{Line, Span},
+    widgets::{
+        Axis, Block, Borders, Chart, Clear, Dataset, Gauge, Paragraph, Row, Sparkline, Table, Tabs,
+    },
 };
+use std::collections::VecDeque;
+use std::time::Duration;
 
-pub fn draw_state_of_charge_bar(battery: &BatteryInfo, frame: &mut Frame, area: Rect) {
+/// Marks a graph whose history is still loading, on its top border.
+pub fn draw_loading(frame: &mut Frame, area: Rect, theme: &Theme) {
+    let label = " loading… ";
+    let width = label.chars().count() as u16;
+    if area.width < width + 4 {
+        return;
+    }
+    let area = Rect {
+        x: area.right() - width - 2,
+        y: area.y,
+        width,
+        height: 1,
+    };
+    frame.render_widget(
+        Paragraph::new(label).style(Style::default().fg(theme.warning)),
+        area,
+    );
+}
+
+/// Greys out readings that are no longer current.
+pub fn dim(frame: &mut Frame, area: Rect, theme: &Theme) {
+    let area = area.intersection(frame.area());
+    let buffer = frame.buffer_mut();
+    for y in area.top()..area.bottom() {
+        for x in area.left()..area.right() {
+            if let Some(cell) = buffer.cell_mut((x, y)) {
+                cell.set_fg(theme.muted);
+            }
+        }
+    }
+}
+
+/// Right-aligned on the tab row: why the readings below are stale.
+pub fn draw_error_badge(
+    message: &str,
+    age: Duration,
+    frame: &mut Frame,
+    area: Rect,
+    theme: &Theme,
+) {
+    let age = Duration::from_secs(age.as_secs());
+    let text = format!(" ⚠ {} ({} ago) ", message, durations::format(age));
+    let width = (text.chars().count() as u16).min(area.width);
+    let badge = Rect {
+        x: area.right().saturating_sub(width),
+        width,
+        ..area
+    };
+    let paragraph = Paragraph::new(text).style(
+        Style::default()
+            .fg(theme.foreground)
+            .bg(theme.error)
+            .add_modifier(Modifier::BOLD),
+    );
+    frame.render_widget(Clear, badge);
+    frame.render_widget(paragraph, badge);
+}
+
+pub fn draw_tabs(selected: Tab, frame: &mut Frame, area: Rect, theme: &Theme) {
+    let tabs = Tabs::new(Tab::ALL.iter().map(|tab| tab.title()))
+        .select(Tab::ALL.iter().position(|&tab| tab == selected))
+        .style(Style::default().fg(theme.axis))
+        .highlight_style(
+            Style::default()
+                .fg(theme.foreground)
+                .add_modifier(Modifier::BOLD | Modifier::REVERSED),
+        );
+
+    frame.render_widget(tabs, area);
+}
+
+/// With `scale_to_limit`, a detected firmware charge limit counts as full.
+pub fn draw_state_of_charge_bar(
+    battery: &BatteryInfo,
+    scale_to_limit: bool,
+    thresholds: GaugeThresholds,
+    frame: &mut Frame,
+    area: Rect,
+    theme: &Theme,
+) {
     if battery.has_battery() {
-        if let Some((ratio_value, percent_value)) = battery.state_of_charge() {
-            let label = format!("{:.1}%", percent_value);
+        if let Some((mut ratio_value, percent_value)) = battery.state_of_charge() {
+            let label = match battery.charge_limit() {
+                Some(limit) if scale_to_limit => {
+                    ratio_value = (percent_value / limit).min(1.0);
+                    format!(
+                        "{:.1}% of {:.0}% limit ({:.1}% raw)",
+                        ratio_value * 100.0,
+                        limit,
+                        percent_value
+                    )
+                }
+                Some(limit) => format!("{:.1}% (limit {:.0}%)", percent_value, limit),
+                None => format!("{:.1}%", percent_value),
+            };
 
             let gauge_color = match () {
-                _ if ratio_value > 0.3 => Color::Green,
-                _ if ratio_value > 0.15 => Color::Yellow,
-                _ => Color::Red,
+                _ if ratio_value * 100.0 > f64::from(thresholds.low) => theme.gauge_high,
+                _ if ratio_value * 100.0 > f64::from(thresholds.critical) => theme.gauge_low,
+                _ => theme.gauge_critical,
             };
 
+            let title = match battery.battery_label() {
+                Some(label) => format!(" State of charge ({}) ", label),
+                None => " State of charge ".to_string(),
+            };
             let gauge = Gauge::default()
                 .block(
                     Block::default()
-                        .title(" State of charge ")
+                        .title(title)
                         .borders(Borders::ALL)
-                        .border_style(Style::default().fg(Color::White)),
+                        .border_style(Style::default().fg(theme.foreground)),
                 )
                 .ratio(ratio_value)
                 .gauge_style(Style::default().fg(gauge_color))
                 .label(Span::styled(
                     label,
                     Style::default()
-                        .fg(Color::DarkGray)
+                        .fg(theme.muted)
                         .add_modifier(Modifier::BOLD),
                 ));
 
@@ -49,7 +160,47 @@ pub fn draw_state_of_charge_bar(battery: &BatteryInfo, frame: &mut Frame, area:
     }
 }
 
-pub fn draw_common_info(battery: &BatteryInfo, frame: &mut Frame, area: Rect) {
+/// Charge over the refreshes since amptop started, scaled to the range it
+/// covered so that a change of a percent or two still shows. Neighbouring
+/// samples are averaged once there are more than columns.
+pub fn draw_trend(trend: &VecDeque<f64>, frame: &mut Frame, area: Rect, theme: &Theme) {
+    let columns = usize::from(area.width.saturating_sub(2)).max(1);
+    let samples: Vec<f64> = trend.iter().copied().collect();
+    let shown: Vec<f64> = samples
+        .chunks(samples.len().div_ceil(columns).max(1))
+        .map(|chunk| chunk.iter().sum::<f64>() / chunk.len() as f64)
+        .collect();
+    let low = shown.iter().copied().fold(f64::INFINITY, f64::min);
+    let high = shown.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let title = if shown.is_empty() {
+        " This session ".to_string()
+    } else if high - low < 0.1 {
+        format!(" This session: {:.1}% ", high)
+    } else {
+        format!(" This session: {:.1}–{:.1}% ", low, high)
+    };
+    // Tenths of a percent above the lowest sample; one more keeps the lowest
+    // sample visible as a baseline.
+    let data: Vec<u64> = shown
+        .iter()
+        .map(|percent| ((percent - low) * 10.0).round() as u64 + 1)
+        .collect();
+    let sparkline = Sparkline::default()
+        .block(Block::default().title(title).borders(Borders::ALL))
+        .data(&data)
+        .max((((high - low) * 10.0).round() as u64 + 1).max(2))
+        .style(Style::default().fg(theme.accent));
+    frame.render_widget(sparkline, area);
+}
+
+/// `estimated_cycles` stands in for the cycle count when the battery
+/// doesn't report one.
+pub fn draw_common_info(
+    battery: &BatteryInfo,
+    estimated_cycles: Option<f64>,
+    frame: &mut Frame,
+    area: Rect,
+) {
     let block = Block::default()
         .title(" Device Information ")
         .borders(Borders::ALL);
@@ -57,18 +208,31 @@ pub fn draw_common_info(battery: &BatteryInfo, frame: &mut Frame, area: Rect) {
     if battery.has_battery() {
         let tech = battery.technology().unwrap_or_else(|| "N/A".to_string());
         let state = battery.state().unwrap_or_else(|| "N/A".to_string());
-        let cycles = battery
-            .cycle_count()
-            .map(|c| c.to_string())
+        let cycles = match (battery.cycle_count(), estimated_cycles) {
+            (Some(count), _) => count.to_string(),
+            (None, Some(estimate)) => format!("{:.0} (estimated)", estimate),
+            (None, None) => "N/A".to_string(),
+        };
+        let limit = battery
+            .charge_thresholds()
+            .map(|thresholds| thresholds.describe())
+            .unwrap_or_else(|| "N/A".to_string());
+
+        let manufactured = battery
+            .manufactured()
+            .map(|date| date.format("%Y-%m-%d").to_string())
             .unwrap_or_else(|| "N/A".to_string());
 
         let items = vec![
             ["Vendor", battery.vendor().unwrap_or("N/A")],
             ["Model", battery.model().unwrap_or("N/A")],
             ["S/N", battery.serial_number().unwrap_or("N/A")],
+            ["Manufactured", &manufactured],
             ["Technology", &tech],
             ["Charge state", &state],
+            ["Health", battery.health_status().unwrap_or("N/A")],
             ["Cycles count", &cycles],
+            ["Charge limit", &limit],
         ];
 
         draw_info_list(&items, block, frame, area);
@@ -96,6 +260,7 @@ pub fn draw_energy_info(battery: &BatteryInfo, frame: &mut Frame, area: Rect, un
         let full_design = battery
             .energy_full_design(unit)
             .unwrap_or_else(|| "N/A".to_string());
+        let used = battery.energy_used(unit);
 
         let consumption_label = match battery.battery_state() {
             Some(battery::State::Charging) => "Charging with",
@@ -103,14 +268,24 @@ pub fn draw_energy_info(battery: &BatteryInfo, frame: &mut Frame, area: Rect, un
             _ => "Consumption",
         };
 
-        let items = vec![
+        let shortfall = battery
+            .charger_shortfall()
+            .map(|(provided, needed)| format!("~{:.0}W, needs ~{:.0}W", provided, needed));
+
+        let mut items = vec![
             [consumption_label, &consumption],
             ["Voltage", &voltage],
             ["Capacity", &capacity],
-            ["Current", &current],
+            ["Remaining", &current],
             ["Last full", &last_full],
             ["Full design", &full_design],
         ];
+        if let Some(ref used) = used {
+            items.push(["Used since unplug", used]);
+        }
+        if let Some(ref shortfall) = shortfall {
+            items.push(["Charger", shortfall]);
+        }
 
         draw_info_list(&items, block, frame, area);
     } else {
@@ -121,16 +296,27 @@ pub fn draw_energy_info(battery: &BatteryInfo, frame: &mut Frame, area: Rect, un
     }
 }
 
-pub fn draw_timing_info(battery: &BatteryInfo, frame: &mut Frame, area: Rect) {
+/// Draws the time to full and to empty. `smoothed` is the time to empty at
+/// the smoothed drain, shown below the battery's own estimate.
+pub fn draw_timing_info(
+    battery: &BatteryInfo,
+    smoothed: Option<Duration>,
+    frame: &mut Frame,
+    area: Rect,
+) {
     let block = Block::default().title(" Timings ").borders(Borders::ALL);
 
     if battery.has_battery() {
         let time_to_full = battery.time_to_full().unwrap_or_else(|| "N/A".to_string());
         let time_to_empty = battery.time_to_empty().unwrap_or_else(|| "N/A".to_string());
+        let estimated = smoothed
+            .map(|time| format!("{} (smoothed)", durations::format(time)))
+            .unwrap_or_else(|| "N/A".to_string());
 
         let items = vec![
             ["Time to full", &time_to_full],
             ["Time to empty", &time_to_empty],
+            ["Estimated", &estimated],
         ];
 
         draw_info_list(&items, block, frame, area);
@@ -142,16 +328,41 @@ pub fn draw_timing_info(battery: &BatteryInfo, frame: &mut Frame, area: Rect) {
     }
 }
 
-pub fn draw_environment_info(battery: &BatteryInfo, frame: &mut Frame, area: Rect, unit: Unit) {
+pub fn draw_environment_info(
+    battery: &BatteryInfo,
+    frame: &mut Frame,
+    area: Rect,
+    temperature_unit: TemperatureUnit,
+    max_charge_temp: f32,
+    theme: &Theme,
+) {
     let block = Block::default()
         .title(" Environments ")
         .borders(Borders::ALL);
 
     if battery.has_battery() {
         let temperature = battery
-            .temperature(unit)
+            .temperature(temperature_unit)
             .unwrap_or_else(|| "N/A".to_string());
 
+        let overheating = battery.battery_state() == Some(battery::State::Charging)
+            && battery
+                .temperature_celsius()
+                .is_some_and(|celsius| celsius > max_charge_temp);
+
+        if overheating {
+            let row = Row::new(["Temperature".to_string(), format!("{} (hot!)", temperature)])
+                .style(
+                    Style::default()
+                        .fg(theme.error)
+                        .add_modifier(Modifier::BOLD),
+                );
+            let table = Table::new([row], [Constraint::Length(17), Constraint::Length(17)])
+                .block(block.border_style(Style::default().fg(theme.error)));
+            frame.render_widget(table, area);
+            return;
+        }
+
         let items = vec![["Temperature", &temperature]];
 
         draw_info_list(&items, block, frame, area);
@@ -168,24 +379,77 @@ fn draw_info_list(items: &[[&str; 2]], block: Block, frame: &mut Frame, area: Re
         .iter()
         .map(|item| Row::new(item.iter().map(|s| s.to_string())));
 
-    let table = Table::new(rows, [Constraint::Length(17), Constraint::Length(17)]).block(block);
+    let table = Table::new(rows, [Constraint::Length(17), Constraint::Min(17)]).block(block);
 
     frame.render_widget(table, area);
 }
 
-pub fn draw_drain_graph(frame: &mut Frame, area: Rect) {
-    use chrono::{Local, TimeZone, Timelike};
+/// A graph title followed by the states it draws, each in its color.
+fn legend(title: String, states: &[(&'static str, Color)]) -> Line<'static> {
+    let mut spans = vec![Span::raw(format!(" {} (", title))];
+    for (i, (state, color)) in states.iter().enumerate() {
+        if i > 0 {
+            spans.push(Span::raw(" | "));
+        }
+        spans.push(Span::styled(*state, Style::default().fg(*color)));
+    }
+    spans.push(Span::raw(") "));
+    Line::from(spans)
+}
 
-    let block = Block::default()
-        .title(" Battery History (Green: Charging | Red: Discharging | Blue: Full) ")
-        .borders(Borders::ALL);
+/// Draws the history graph. `window` is the time window the user can move
+/// and the keys that move it, if the graph has one.
+pub fn draw_drain_graph(
+    frame: &mut Frame,
+    area: Rect,
+    logs: &Result<Vec<BatterySnapshot>>,
+    annotations: &[Annotation],
+    window: Option<(&HistoryWindow, &str)>,
+    axis: ChartAxis,
+    theme: &Theme,
+) {
+    use chrono::{Local, TimeZone};
 
-    let logs_result = BatteryDaemon::get_logs(Some(500));
+    let states = [
+        ("Charging", theme.charging),
+        ("Discharging", theme.discharging),
+        ("Full", theme.full),
+    ];
+    let mut block = Block::default().borders(Borders::ALL);
+    block = match window {
+        Some((window, hint)) => {
+            let range = match window.end {
+                None => format!("last {}", window.span_label()),
+                Some(end) => format!(
+                    "{} to {}",
+                    window.span_label(),
+                    Local
+                        .timestamp_opt(end, 0)
+                        .single()
+                        .map(|dt| dt.format("%m-%d %H:%M").to_string())
+                        .unwrap_or_default()
+                ),
+            };
+            block
+                .title(legend(format!("Battery History, {}", range), &states))
+                .title_bottom(hint)
+        }
+        None => block.title(legend("Battery History".to_string(), &states)),
+    };
 
-    match logs_result {
-        Ok(mut logs) if !logs.is_empty() => {
+    match logs {
+        Ok(logs) if !logs.is_empty() => {
             // Reverse to show oldest to newest (left to right)
-            logs.reverse();
+            let logs: Vec<_> = logs.iter().rev().collect();
+
+            // Keep one line below the chart for the summary strip
+            let [area, strip_area] = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Min(0), Constraint::Length(1)])
+                .areas(area);
+            if let Some(summary) = stats::range_summary(&logs) {
+                draw_range_summary(&summary, frame, strip_area, theme);
+            }
 
             // Sample logs to fit available width (account for borders and Y-axis labels)
             let max_points = (area.width.saturating_sub(10)) as usize;
@@ -201,7 +465,7 @@ pub fn draw_drain_graph(frame: &mut Frame, area: Rect) {
                 let text = Paragraph::new("No data to display")
                     .block(block)
                     .alignment(Alignment::Center)
-                    .style(Style::default().fg(Color::Yellow));
+                    .style(Style::default().fg(theme.warning));
                 frame.render_widget(text, area);
                 return;
             }
@@ -213,86 +477,138 @@ pub fn draw_drain_graph(frame: &mut Frame, area: Rect) {
             let first_dt = Local.timestamp_opt(first_timestamp, 0).unwrap();
             let last_dt = Local.timestamp_opt(last_timestamp, 0).unwrap();
 
-            // Create X-axis labels - only show first and last time, evenly distributed
+            // Create X-axis labels - only show first and last time, evenly
+            // distributed, with the date once the graph spans more than a day
+            let time_format = if last_timestamp - first_timestamp > 24 * 60 * 60 {
+                "%m-%d %H:%M"
+            } else {
+                "%H:%M"
+            };
             let x_labels = vec![
-                Span::raw(format!("{:02}:{:02}", first_dt.hour(), first_dt.minute())),
+                Span::raw(first_dt.format(time_format).to_string()),
                 Span::raw(""),
                 Span::raw(""),
                 Span::raw(""),
-                Span::raw(format!("{:02}:{:02}", last_dt.hour(), last_dt.minute())),
+                Span::raw(last_dt.format(time_format).to_string()),
             ];
 
             // Create X-axis bounds (scaled to 0.0-4.0 for 5 label positions)
             let x_bounds = [0.0, 4.0];
 
-            // Prepare data points - scale x coordinates to 0.0-4.0 range
-            let scale_factor = 4.0 / (sampled_logs.len() - 1).max(1) as f64;
-            let data_points: Vec<(f64, f64)> = sampled_logs
-                .iter()
-                .enumerate()
-                .map(|(i, log)| (i as f64 * scale_factor, log.percent as f64))
-                .collect();
-
-            // Calculate dominant color based on most common status
-            let dominant_color = {
-                let mut charging_count = 0;
-                let mut discharging_count = 0;
-                let mut full_count = 0;
-
-                for log in &sampled_logs {
-                    match log.status.as_str() {
-                        "charging" => charging_count += 1,
-                        "discharging" => discharging_count += 1,
-                        "full" => full_count += 1,
-                        _ => {}
-                    }
+            // Energy axes plot the Wh recorded with each sample, in Wh or
+            // kJ; history without energy readings stays in percent
+            let energy = match axis {
+                ChartAxis::Percent => None,
+                ChartAxis::WattHours => Some((1.0, 10.0, "Wh")),
+                ChartAxis::Joules => Some((3.6, 50.0, "kJ")),
+            }
+            .filter(|_| sampled_logs.iter().any(|log| log.energy.is_some()));
+            let value = |log: &BatterySnapshot| match energy {
+                Some((scale, _, _)) => log.energy.map(|wh| f64::from(wh) * scale),
+                None => Some(log.percent as f64),
+            };
+            // Round the axis up to the next 10 Wh or 50 kJ
+            let top = match energy {
+                Some((_, step, _)) => {
+                    let max = sampled_logs
+                        .iter()
+                        .filter_map(|log| value(log))
+                        .fold(0.0, f64::max);
+                    ((max / step).floor() + 1.0) * step
                 }
+                None => 100.0,
+            };
 
-                if discharging_count > charging_count && discharging_count > full_count {
-                    Color::Red
-                } else if charging_count > full_count {
-                    Color::Green
-                } else if full_count > 0 {
-                    Color::Blue
-                } else {
-                    Color::Cyan
+            // Prepare data points - scale x coordinates to 0.0-4.0 range -
+            // split by charge state so each segment gets its own color
+            let scale_factor = 4.0 / (sampled_logs.len() - 1).max(1) as f64;
+            let mut segments: [(Color, Vec<(f64, f64)>); 4] = [
+                (theme.charging, Vec::new()),
+                (theme.discharging, Vec::new()),
+                (theme.full, Vec::new()),
+                (theme.accent, Vec::new()),
+            ];
+            for (i, log) in sampled_logs.iter().enumerate() {
+                let segment = match log.status.as_str() {
+                    "charging" => 0,
+                    "discharging" => 1,
+                    "full" => 2,
+                    _ => 3,
+                };
+                if let Some(y) = value(log) {
+                    segments[segment].1.push((i as f64 * scale_factor, y));
                 }
-            };
+            }
+
+            // Create datasets with Bar marker for solid vertical bars
+            let mut datasets: Vec<Dataset> = segments
+                .iter()
+                .filter(|(_, points)| !points.is_empty())
+                .map(|(color, points)| {
+                    Dataset::default()
+                        .marker(ratatui::symbols::Marker::Bar)
+                        .style(Style::default().fg(*color))
+                        .data(points)
+                })
+                .collect();
 
-            // Create dataset with Bar marker for solid vertical bars
-            let dataset = Dataset::default()
-                .marker(ratatui::symbols::Marker::Bar)
-                .style(Style::default().fg(dominant_color))
-                .data(&data_points);
+            // Annotations as vertical lines at the sample they precede,
+            // named in the legend
+            let markers: Vec<(String, [(f64, f64); 2])> = annotations
+                .iter()
+                .filter(|annotation| {
+                    (first_timestamp..=last_timestamp).contains(&annotation.timestamp)
+                })
+                .map(|annotation| {
+                    let index =
+                        sampled_logs.partition_point(|log| log.timestamp < annotation.timestamp);
+                    let x = index as f64 * scale_factor;
+                    let time = Local
+                        .timestamp_opt(annotation.timestamp, 0)
+                        .single()
+                        .map(|dt| dt.format(time_format).to_string())
+                        .unwrap_or_default();
+                    (
+                        format!("{} {}", time, annotation.text),
+                        [(x, 0.0), (x, top)],
+                    )
+                })
+                .collect();
+            for (name, line) in &markers {
+                datasets.push(
+                    Dataset::default()
+                        .name(name.as_str())
+                        .marker(ratatui::symbols::Marker::Braille)
+                        .graph_type(ratatui::widgets::GraphType::Line)
+                        .style(Style::default().fg(theme.warning))
+                        .data(line),
+                );
+            }
 
             // Create X-axis without title
             let x_axis = Axis::default()
-                .style(Style::default().fg(Color::Gray))
+                .style(Style::default().fg(theme.axis))
                 .bounds(x_bounds)
                 .labels(x_labels);
 
-            // Create Y-axis with percentage labels (0%, 10%, 20%, ..., 100%)
-            let y_labels = vec![
-                Span::raw("0%"),
-                Span::raw("10%"),
-                Span::raw("20%"),
-                Span::raw("30%"),
-                Span::raw("40%"),
-                Span::raw("50%"),
-                Span::raw("60%"),
-                Span::raw("70%"),
-                Span::raw("80%"),
-                Span::raw("90%"),
-                Span::raw("100%"),
-            ];
+            // Create Y-axis with percentage labels (0%, 10%, 20%, ..., 100%),
+            // or quarters of the energy range
+            let y_labels: Vec<Span> = match energy {
+                Some((_, _, unit)) => (0..=4)
+                    .map(|i| Span::raw(format!("{:.0} {}", top * i as f64 / 4.0, unit)))
+                    .collect(),
+                None => (0..=10)
+                    .map(|i| Span::raw(format!("{}%", i * 10)))
+                    .collect(),
+            };
 
             let y_axis = Axis::default()
-                .style(Style::default().fg(Color::Gray))
-                .bounds([0.0, 100.0])
+                .style(Style::default().fg(theme.axis))
+                .bounds([0.0, top])
                 .labels(y_labels);
 
             // Create the chart
-            let chart = Chart::new(vec![dataset])
+            let chart = Chart::new(datasets)
                 .block(block)
                 .x_axis(x_axis)
                 .y_axis(y_axis);
@@ -304,7 +620,7 @@ pub fn draw_drain_graph(frame: &mut Frame, area: Rect) {
             let text = Paragraph::new("No historical data available\n\nStart the daemon to collect data:\namptop daemon start --interval 60")
                 .block(block)
                 .alignment(Alignment::Center)
-                .style(Style::default().fg(Color::Yellow));
+                .style(Style::default().fg(theme.warning));
             frame.render_widget(text, area);
         }
         Err(e) => {
@@ -312,8 +628,1238 @@ pub fn draw_drain_graph(frame: &mut Frame, area: Rect) {
             let text = Paragraph::new(format!("Error loading data:\n{}", e))
                 .block(block)
                 .alignment(Alignment::Center)
-                .style(Style::default().fg(Color::Red));
+                .style(Style::default().fg(theme.error));
             frame.render_widget(text, area);
         }
     }
 }
+
+fn draw_range_summary(summary: &RangeSummary, frame: &mut Frame, area: Rect, theme: &Theme) {
+    let label = Style::default().fg(theme.muted);
+    let change_color = if summary.net_change < 0.0 {
+        theme.discharging
+    } else {
+        theme.charging
+    };
+
+    let line = Line::from(vec![
+        Span::styled(" Min ", label),
+        Span::raw(format!("{:.0}%", summary.min)),
+        Span::styled("  Max ", label),
+        Span::raw(format!("{:.0}%", summary.max)),
+        Span::styled("  Avg ", label),
+        Span::raw(format!("{:.0}%", summary.avg)),
+        Span::styled("  Net ", label),
+        Span::styled(
+            format!("{:+.0}%", summary.net_change),
+            Style::default().fg(change_color),
+        ),
+        Span::styled("  Charging ", label),
+        Span::styled(
+            format!("{:.1}h", summary.charging_hours),
+            Style::default().fg(theme.charging),
+        ),
+        Span::styled("  Discharging ", label),
+        Span::styled(
+            format!("{:.1}h", summary.discharging_hours),
+            Style::default().fg(theme.discharging),
+        ),
+    ]);
+    frame.render_widget(Paragraph::new(line), area);
+}
+
+pub fn draw_workloads(
+    battery: &BatteryInfo,
+    presets: &[Preset],
+    frame: &mut Frame,
+    area: Rect,
+    theme: &Theme,
+) {
+    use battery::units::{energy::watt_hour, power::watt};
+
+    let block = Block::default()
+        .title(" Runtime by workload ")
+        .borders(Borders::ALL);
+
+    let Some(total) = battery.total() else {
+        frame.render_widget(block, area);
+        return;
+    };
+    if presets.is_empty() {
+        let text = Paragraph::new("Not enough discharge history to learn workloads yet")
+            .block(block)
+            .alignment(Alignment::Center)
+            .style(Style::default().fg(theme.warning));
+        frame.render_widget(text, area);
+        return;
+    }
+
+    let energy = f64::from(total.energy.get::<watt_hour>());
+    let current = (total.state == battery::State::Discharging)
+        .then(|| workload::classify(presets, f64::from(total.energy_rate.get::<watt>())))
+        .flatten();
+
+    let rows = presets.iter().map(|preset| {
+        let runtime = Duration::from_secs((energy / preset.watts * 60.0) as u64 * 60);
+        let row = Row::new(vec![
+            preset.name.to_string(),
+            format!("~{}", durations::format(runtime)),
+            format!("at {:.1} W", preset.watts),
+        ]);
+        if current.is_some_and(|current| current.name == preset.name) {
+            row.style(Style::default().add_modifier(Modifier::BOLD | Modifier::REVERSED))
+        } else {
+            row
+        }
+    });
+
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(12),
+            Constraint::Length(12),
+            Constraint::Min(10),
+        ],
+    )
+    .block(block);
+    frame.render_widget(table, area);
+}
+
+pub fn draw_toast(message: &str, fading: bool, frame: &mut Frame, theme: &Theme) {
+    let width = (message.len() as u16 + 4).min(frame.area().width);
+    let height = 3.min(frame.area().height);
+    let area = Rect {
+        x: frame.area().right().saturating_sub(width + 1),
+        y: frame.area().y + 1,
+        width,
+        height,
+    };
+
+    let color = if fading {
+        theme.muted
+    } else {
+        theme.foreground
+    };
+    let text = Paragraph::new(message)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(color)),
+        )
+        .alignment(Alignment::Center)
+        .style(Style::default().fg(color).add_modifier(Modifier::BOLD));
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(text, area);
+}
+
+pub fn draw_desktop_power(desktop: &DesktopPower, frame: &mut Frame, area: Rect, theme: &Theme) {
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Length(40), Constraint::Min(20)])
+        .split(area);
+    let left_column = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(7)])
+        .split(columns[0]);
+
+    let not_available = || "N/A".to_string();
+
+    let cpu = match desktop.cpu_watts() {
+        Some(watts) => format!("{:.2} W", watts),
+        None if desktop.has_rapl() => "measuring...".to_string(),
+        None => not_available(),
+    };
+    draw_info_list(
+        &[["CPU package", &cpu]],
+        Block::default()
+            .title(" System power ")
+            .borders(Borders::ALL),
+        frame,
+        left_column[0],
+    );
+
+    let ups_block = Block::default().title(" UPS ").borders(Borders::ALL);
+    match desktop.ups {
+        Some(ref ups) => {
+            let percent = ups
+                .percent
+                .map(|p| format!("{:.0}%", p))
+                .unwrap_or_else(not_available);
+            let load = ups
+                .load_percent
+                .map(|p| format!("{:.0}%", p))
+                .unwrap_or_else(not_available);
+            let status = ups.status.clone().unwrap_or_else(not_available);
+            let runtime = ups
+                .runtime
+                .map(durations::format)
+                .unwrap_or_else(not_available);
+            draw_info_list(
+                &[
+                    ["Device", &ups.name],
+                    ["Status", &status],
+                    ["Charge", &percent],
+                    ["Load", &load],
+                    ["Runtime", &runtime],
+                ],
+                ups_block,
+                frame,
+                left_column[1],
+            );
+        }
+        None => {
+            let text = Paragraph::new("No UPS detected\n(see --ups)")
+                .block(ups_block)
+                .alignment(Alignment::Center)
+                .style(Style::default().fg(theme.muted));
+            frame.render_widget(text, left_column[1]);
+        }
+    }
+
+    let block = Block::default()
+        .title(" Peripheral batteries ")
+        .borders(Borders::ALL);
+    draw_peripheral_table(&desktop.peripherals, block, frame, columns[1], theme);
+}
+
+/// Pop-up listing peripheral batteries next to the laptop's own.
+pub fn draw_peripherals(desktop: &DesktopPower, keymap: &Keymap, frame: &mut Frame, theme: &Theme) {
+    let width = 70.min(frame.area().width);
+    let height = (desktop.peripherals.len().max(1) as u16 + 3).min(frame.area().height);
+    let area = Rect {
+        x: frame.area().x + (frame.area().width - width) / 2,
+        y: frame.area().y + (frame.area().height - height) / 2,
+        width,
+        height,
+    };
+    let block = Block::default()
+        .title(" Peripheral batteries ")
+        .title_bottom(close_hint(keymap, Action::Peripherals))
+        .borders(Borders::ALL);
+
+    frame.render_widget(Clear, area);
+    draw_peripheral_table(&desktop.peripherals, block, frame, area, theme);
+}
+
+fn draw_peripheral_table(
+    peripherals: &[Peripheral],
+    block: Block,
+    frame: &mut Frame,
+    area: Rect,
+    theme: &Theme,
+) {
+    let not_available = || "N/A".to_string();
+    if peripherals.is_empty() {
+        let text = Paragraph::new("No battery-powered devices connected")
+            .block(block)
+            .alignment(Alignment::Center)
+            .style(Style::default().fg(theme.muted));
+        frame.render_widget(text, area);
+        return;
+    }
+
+    let rows = peripherals.iter().map(|device| {
+        Row::new(vec![
+            match device.kind {
+                Some(ref kind) => format!("{} ({})", device.name, kind),
+                None => device.name.clone(),
+            },
+            device
+                .percent
+                .map(|p| format!("{:.0}%", p))
+                .unwrap_or_else(not_available),
+            device.status.clone().unwrap_or_else(not_available),
+        ])
+    });
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Min(20),
+            Constraint::Length(8),
+            Constraint::Length(14),
+        ],
+    )
+    .header(
+        Row::new(vec!["Device", "Charge", "Status"])
+            .style(Style::default().add_modifier(Modifier::BOLD)),
+    )
+    .block(block);
+    frame.render_widget(table, area);
+}
+
+pub fn draw_finish_prompt(prompt: &FinishPrompt, frame: &mut Frame, theme: &Theme) {
+    let width = 60.min(frame.area().width);
+    let height = 7.min(frame.area().height);
+    let area = Rect {
+        x: frame.area().x + (frame.area().width - width) / 2,
+        y: frame.area().y + (frame.area().height - height) / 2,
+        width,
+        height,
+    };
+
+    let answer = match prompt.answer {
+        Some(ref answer) => Line::from(answer.as_str()),
+        None => Line::styled(
+            "Enter to check, Tab to change load, Esc to close",
+            Style::default().fg(theme.muted),
+        ),
+    };
+    let lines = vec![
+        Line::from(vec![
+            Span::raw("Duration: "),
+            Span::styled(
+                format!("{}_", prompt.input),
+                Style::default().add_modifier(Modifier::BOLD),
+            ),
+        ]),
+        Line::from(format!("Load:     {}", prompt.load.label())),
+        Line::from(""),
+        answer,
+    ];
+
+    let text = Paragraph::new(lines).block(
+        Block::default()
+            .title(" Can I finish? ")
+            .borders(Borders::ALL),
+    );
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(text, area);
+}
+
+pub fn draw_goto_prompt(prompt: &GotoPrompt, frame: &mut Frame, theme: &Theme) {
+    let width = 60.min(frame.area().width);
+    let height = 5.min(frame.area().height);
+    let area = Rect {
+        x: frame.area().x + (frame.area().width - width) / 2,
+        y: frame.area().y + (frame.area().height - height) / 2,
+        width,
+        height,
+    };
+
+    let hint = match prompt.error {
+        Some(ref error) => Line::styled(error.as_str(), Style::default().fg(theme.error)),
+        None => Line::styled(
+            "e.g. 2024-11-03 14:00 or 3 days ago; Esc to close",
+            Style::default().fg(theme.muted),
+        ),
+    };
+    let lines = vec![
+        Line::from(vec![
+            Span::raw("Go to: "),
+            Span::styled(
+                format!("{}_", prompt.input),
+                Style::default().add_modifier(Modifier::BOLD),
+            ),
+        ]),
+        Line::from(""),
+        hint,
+    ];
+
+    let text =
+        Paragraph::new(lines).block(Block::default().title(" Go to date ").borders(Borders::ALL));
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(text, area);
+}
+
+/// Offered at startup when no daemon is recording history.
+pub fn draw_collector_prompt(frame: &mut Frame, theme: &Theme) {
+    let width = 66.min(frame.area().width);
+    let height = 8.min(frame.area().height);
+    let area = Rect {
+        x: frame.area().x + (frame.area().width - width) / 2,
+        y: frame.area().y + (frame.area().height - height) / 2,
+        width,
+        height,
+    };
+
+    let lines = vec![
+        Line::from("No daemon is recording battery history. Record it while"),
+        Line::from("amptop is open?"),
+        Line::from(""),
+        Line::styled("y to record, n to skip", Style::default().fg(theme.muted)),
+        Line::styled(
+            "Set collector to always or never in the config to stop asking.",
+            Style::default().fg(theme.muted),
+        ),
+    ];
+    let text = Paragraph::new(lines).block(
+        Block::default()
+            .title(" Record history ")
+            .borders(Borders::ALL),
+    );
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(text, area);
+}
+
+pub fn draw_overhead(panel: &OverheadPanel, keymap: &Keymap, frame: &mut Frame, theme: &Theme) {
+    let width = 70.min(frame.area().width);
+    let height = 6.min(frame.area().height);
+    let area = Rect {
+        x: frame.area().x + (frame.area().width - width) / 2,
+        y: frame.area().y + (frame.area().height - height) / 2,
+        width,
+        height,
+    };
+
+    let label = |text: String| Span::styled(text, Style::default().add_modifier(Modifier::BOLD));
+    let tui = match panel.tui {
+        Some(overhead) => overhead.describe(),
+        None => "Measuring...".to_string(),
+    };
+    let daemon = match panel.daemon {
+        Some((pid, usage)) => Line::from(vec![
+            label(format!("{:<18}", format!("Daemon ({})", pid))),
+            Span::raw(usage.lifetime().map(|o| o.describe()).unwrap_or_default()),
+        ]),
+        None => Line::from(vec![
+            label(format!("{:<18}", "Daemon")),
+            Span::styled("Not running", Style::default().fg(theme.muted)),
+        ]),
+    };
+    let daemon_cpu = match panel.daemon {
+        Some((_, usage)) => format!(
+            "{} of CPU time over {}",
+            overhead::format_cpu_time(usage.cpu),
+            durations::format(usage.uptime)
+        ),
+        None => String::new(),
+    };
+    let lines = vec![
+        Line::from(vec![label(format!("{:<18}", "This TUI")), Span::raw(tui)]),
+        daemon,
+        Line::from(vec![
+            Span::raw(" ".repeat(18)),
+            Span::styled(daemon_cpu, Style::default().fg(theme.muted)),
+        ]),
+    ];
+    let text = Paragraph::new(lines).block(
+        Block::default()
+            .title(" amptop overhead ")
+            .title_bottom(close_hint(keymap, Action::Overhead))
+            .borders(Borders::ALL)
+            .padding(ratatui::widgets::Padding::horizontal(1)),
+    );
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(text, area);
+}
+
+/// Bottom border of an overlay that `action` opens.
+fn close_hint(keymap: &Keymap, action: Action) -> String {
+    format!(" {} or Esc to close ", keymap.short_label(action))
+}
+
+/// Lists every action with its keys, as bound in `keymap`.
+pub fn draw_help(keymap: &Keymap, frame: &mut Frame) {
+    let mut bindings: Vec<(String, &str)> = Action::ALL
+        .into_iter()
+        .map(|action| (keymap.label(action), action.description()))
+        .collect();
+    // Keys the keymap can't change
+    bindings.insert(2, ("Ctrl+C".to_string(), "Force exit"));
+    bindings.insert(5, ("1-6".to_string(), "Go to a tab"));
+
+    let key_width = bindings
+        .iter()
+        .map(|(keys, _)| keys.chars().count())
+        .max()
+        .unwrap_or(0) as u16;
+    let width = (key_width + 48).min(frame.area().width);
+    let height = (bindings.len() as u16 + 2).min(frame.area().height);
+    let area = Rect {
+        x: frame.area().x + (frame.area().width - width) / 2,
+        y: frame.area().y + (frame.area().height - height) / 2,
+        width,
+        height,
+    };
+
+    let rows = bindings.into_iter().map(|(keys, description)| {
+        Row::new(vec![
+            Span::styled(keys, Style::default().add_modifier(Modifier::BOLD)),
+            Span::raw(description),
+        ])
+    });
+    let table = Table::new(
+        rows,
+        [Constraint::Length(key_width.max(6)), Constraint::Min(0)],
+    )
+    .block(
+        Block::default()
+            .title(" Keys ")
+            .title_bottom(close_hint(keymap, Action::Help))
+            .borders(Borders::ALL)
+            .padding(ratatui::widgets::Padding::horizontal(1)),
+    );
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(table, area);
+}
+
+/// Full-screen history graph with the power and the temperature drawn below
+/// it. `units` are the charge axis, the temperature unit and the charging
+/// temperature limit in °C.
+pub fn draw_history(
+    logs: &Result<Vec<BatterySnapshot>>,
+    annotations: &[Annotation],
+    window: (&HistoryWindow, &str),
+    (axis, temperature_unit, max_charge_temp): (ChartAxis, TemperatureUnit, f32),
+    frame: &mut Frame,
+    area: Rect,
+    theme: &Theme,
+) {
+    let [drain_area, lower_area] = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+        .areas(area);
+    let [power_area, temperature_area] = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .areas(lower_area);
+    draw_drain_graph(
+        frame,
+        drain_area,
+        logs,
+        annotations,
+        Some(window),
+        axis,
+        theme,
+    );
+    draw_power_graph(frame, power_area, logs, theme);
+    draw_temperature_graph(
+        frame,
+        temperature_area,
+        logs,
+        (temperature_unit, max_charge_temp),
+        theme,
+    );
+}
+
+/// Time axis of the graphs below the history graph, spanning `logs`
+/// (newest first) like the history graph does, with the span it covers.
+fn time_axis(logs: &[BatterySnapshot], theme: &Theme) -> ((i64, i64), Axis<'static>) {
+    use chrono::{Local, TimeZone};
+
+    let since = logs.last().map_or(0, |log| log.timestamp);
+    let until = logs.first().map_or(0, |log| log.timestamp).max(since + 1);
+    let time_format = if until - since > 24 * 60 * 60 {
+        "%m-%d %H:%M"
+    } else {
+        "%H:%M"
+    };
+    let time = |timestamp: i64| {
+        Local
+            .timestamp_opt(timestamp, 0)
+            .single()
+            .map(|dt| dt.format(time_format).to_string())
+            .unwrap_or_default()
+    };
+    let axis = Axis::default()
+        .style(Style::default().fg(theme.axis))
+        .bounds([since as f64, until as f64])
+        .labels(vec![Span::raw(time(since)), Span::raw(time(until))]);
+    ((since, until), axis)
+}
+
+/// Watts flowing into (green) and out of (red) the battery, from history
+/// ordered newest first.
+pub fn draw_power_graph(
+    frame: &mut Frame,
+    area: Rect,
+    logs: &Result<Vec<BatterySnapshot>>,
+    theme: &Theme,
+) {
+    let block = Block::default()
+        .title(legend(
+            "Power".to_string(),
+            &[
+                ("Charging", theme.charging),
+                ("Discharging", theme.discharging),
+            ],
+        ))
+        .borders(Borders::ALL);
+
+    let logs = match logs {
+        Ok(logs) => logs,
+        Err(e) => {
+            let text = Paragraph::new(format!("Error loading data:\n{}", e))
+                .block(block)
+                .alignment(Alignment::Center)
+                .style(Style::default().fg(theme.error));
+            frame.render_widget(text, area);
+            return;
+        }
+    };
+
+    let mut charging = Vec::new();
+    let mut discharging = Vec::new();
+    for log in logs.iter().rev() {
+        let Some(watts) = log.power_watts else {
+            continue;
+        };
+        let point = (log.timestamp as f64, f64::from(watts));
+        match log.status.as_str() {
+            "charging" => charging.push(point),
+            "discharging" => discharging.push(point),
+            _ => {}
+        }
+    }
+    if charging.is_empty() && discharging.is_empty() {
+        let text = Paragraph::new(
+            "No power readings in this range\n\nThe daemon records them from now on",
+        )
+        .block(block)
+        .alignment(Alignment::Center)
+        .style(Style::default().fg(theme.warning));
+        frame.render_widget(text, area);
+        return;
+    }
+
+    let max_watts = charging
+        .iter()
+        .chain(&discharging)
+        .map(|(_, watts)| *watts)
+        .fold(0.0, f64::max);
+    // Round the axis up to the next 5 W
+    let top = ((max_watts / 5.0).floor() + 1.0) * 5.0;
+    let average = |points: &[(f64, f64)]| {
+        (!points.is_empty())
+            .then(|| points.iter().map(|(_, watts)| watts).sum::<f64>() / points.len() as f64)
+    };
+    let mut averages = Vec::new();
+    if let Some(watts) = average(&charging) {
+        averages.push(format!("charging {:.1} W", watts));
+    }
+    if let Some(watts) = average(&discharging) {
+        averages.push(format!("discharging {:.1} W", watts));
+    }
+
+    let (_, x_axis) = time_axis(logs, theme);
+    let y_axis = Axis::default()
+        .style(Style::default().fg(theme.axis))
+        .bounds([0.0, top])
+        .labels(vec![
+            Span::raw("0 W"),
+            Span::raw(format!("{:.0} W", top / 2.0)),
+            Span::raw(format!("{:.0} W", top)),
+        ]);
+
+    let datasets = [
+        (theme.charging, &charging),
+        (theme.discharging, &discharging),
+    ]
+    .into_iter()
+    .filter(|(_, points)| !points.is_empty())
+    .map(|(color, points)| {
+        Dataset::default()
+            .marker(ratatui::symbols::Marker::Braille)
+            .graph_type(ratatui::widgets::GraphType::Scatter)
+            .style(Style::default().fg(color))
+            .data(points)
+    })
+    .collect();
+    let chart = Chart::new(datasets)
+        .block(block.title_bottom(format!(" average {} ", averages.join(", "))))
+        .x_axis(x_axis)
+        .y_axis(y_axis);
+    frame.render_widget(chart, area);
+}
+
+/// Battery temperature from history ordered newest first, with the limit
+/// above which the daemon warns while charging.
+pub fn draw_temperature_graph(
+    frame: &mut Frame,
+    area: Rect,
+    logs: &Result<Vec<BatterySnapshot>>,
+    (unit, max_charge_temp): (TemperatureUnit, f32),
+    theme: &Theme,
+) {
+    let limit = unit.convert(f64::from(max_charge_temp));
+    let block = Block::default()
+        .title(legend(
+            "Temperature".to_string(),
+            &[
+                ("Charging", theme.charging),
+                ("Other", theme.accent),
+                ("Charging limit", theme.error),
+            ],
+        ))
+        .borders(Borders::ALL);
+
+    let logs = match logs {
+        Ok(logs) => logs,
+        Err(e) => {
+            let text = Paragraph::new(format!("Error loading data:\n{}", e))
+                .block(block)
+                .alignment(Alignment::Center)
+                .style(Style::default().fg(theme.error));
+            frame.render_widget(text, area);
+            return;
+        }
+    };
+
+    let mut charging = Vec::new();
+    let mut other = Vec::new();
+    for log in logs.iter().rev() {
+        let Some(celsius) = log.temperature else {
+            continue;
+        };
+        let point = (log.timestamp as f64, unit.convert(f64::from(celsius)));
+        if log.status == "charging" {
+            charging.push(point);
+        } else {
+            other.push(point);
+        }
+    }
+    if charging.is_empty() && other.is_empty() {
+        let text = Paragraph::new(
+            "No temperature readings in this range\n\nNot every battery reports one",
+        )
+        .block(block)
+        .alignment(Alignment::Center)
+        .style(Style::default().fg(theme.warning));
+        frame.render_widget(text, area);
+        return;
+    }
+
+    // Fit the readings and the limit, rounded out to the nearest 5 degrees
+    let (low, high) = charging
+        .iter()
+        .chain(&other)
+        .map(|(_, value)| *value)
+        .fold((limit, limit), |(low, high), value| {
+            (low.min(value), high.max(value))
+        });
+    let bottom = (low / 5.0).floor() * 5.0 - 5.0;
+    let top = (high / 5.0).floor() * 5.0 + 5.0;
+    let peak = charging
+        .iter()
+        .chain(&other)
+        .map(|(_, value)| *value)
+        .fold(f64::NEG_INFINITY, f64::max);
+
+    let ((since, until), x_axis) = time_axis(logs, theme);
+    let y_axis = Axis::default()
+        .style(Style::default().fg(theme.axis))
+        .bounds([bottom, top])
+        .labels(vec![
+            Span::raw(format!("{:.0} {}", bottom, unit.symbol())),
+            Span::raw(format!("{:.0} {}", (bottom + top) / 2.0, unit.symbol())),
+            Span::raw(format!("{:.0} {}", top, unit.symbol())),
+        ]);
+
+    let limit_line = [(since as f64, limit), (until as f64, limit)];
+    let mut datasets = vec![
+        Dataset::default()
+            .marker(ratatui::symbols::Marker::Braille)
+            .graph_type(ratatui::widgets::GraphType::Line)
+            .style(Style::default().fg(theme.error))
+            .data(&limit_line),
+    ];
+    datasets.extend(
+        [(theme.charging, &charging), (theme.accent, &other)]
+            .into_iter()
+            .filter(|(_, points)| !points.is_empty())
+            .map(|(color, points)| {
+                Dataset::default()
+                    .marker(ratatui::symbols::Marker::Braille)
+                    .graph_type(ratatui::widgets::GraphType::Scatter)
+                    .style(Style::default().fg(color))
+                    .data(points)
+            }),
+    );
+    let chart = Chart::new(datasets)
+        .block(block.title_bottom(format!(
+            " peak {:.1} {}, limit {:.0} {} ",
+            peak,
+            unit.symbol(),
+            limit,
+            unit.symbol()
+        )))
+        .x_axis(x_axis)
+        .y_axis(y_axis);
+    frame.render_widget(chart, area);
+}
+
+pub fn draw_settings(config: &Config, frame: &mut Frame, area: Rect, theme: &Theme) {
+    let path = config.config_path();
+    let block = Block::default()
+        .title(format!(" Settings ({}) ", path.display()))
+        .title_bottom(" Edit the config file or use `amptop config import`, then restart ")
+        .borders(Borders::ALL);
+
+    let header = Row::new(vec!["Setting", "Value", "Source"])
+        .style(Style::default().add_modifier(Modifier::BOLD));
+    let rows = config.settings().into_iter().map(|(name, value, source)| {
+        let color = match source {
+            SettingSource::Default => theme.muted,
+            SettingSource::File => theme.accent,
+            SettingSource::CommandLine => theme.warning,
+        };
+        Row::new(vec![
+            Span::raw(name),
+            Span::raw(value),
+            Span::styled(source.label(), Style::default().fg(color)),
+        ])
+    });
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(23),
+            Constraint::Min(20),
+            Constraint::Length(13),
+        ],
+    )
+    .header(header)
+    .block(block);
+
+    frame.render_widget(table, area);
+}
+
+/// Health tab: a care tip for the battery's chemistry, then time in each
+/// charge band above the wear trend.
+pub fn draw_health(
+    battery: &BatteryInfo,
+    data: &TabData,
+    frame: &mut Frame,
+    area: Rect,
+    theme: &Theme,
+) {
+    let tip = battery.chemistry().map(|chemistry| {
+        Line::from(vec![
+            Span::styled(
+                format!(" Tip ({}): ", chemistry.name()),
+                Style::default()
+                    .fg(theme.accent)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::raw(chemistry.tip()),
+        ])
+    });
+    let [tip_area, bands_area, forecast_area, wear_area] = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(if tip.is_some() { 1 } else { 0 }),
+            Constraint::Length(8),
+            Constraint::Length(3),
+            Constraint::Min(8),
+        ])
+        .areas(area);
+    if let Some(tip) = tip {
+        frame.render_widget(Paragraph::new(tip), tip_area);
+    }
+    draw_charge_bands(&data.charge_bands, frame, bands_area, theme);
+    if let Ok(ref samples) = data.wear {
+        draw_capacity_forecast(samples, frame, forecast_area, theme);
+    }
+    draw_wear_chart(&data.wear, frame, wear_area, theme);
+}
+
+/// Projected capacity in 6 and 12 months and when the battery wears out,
+/// from the trend of the capacity history.
+fn draw_capacity_forecast(samples: &[HealthSample], frame: &mut Frame, area: Rect, theme: &Theme) {
+    use chrono::{Local, TimeZone};
+
+    let block = Block::default()
+        .title(" Capacity forecast ")
+        .borders(Borders::ALL);
+    let now = chrono::Utc::now().timestamp();
+    let line = match CapacityTrend::fit(samples) {
+        None => Line::styled(
+            "Needs a month of capacity history",
+            Style::default().fg(theme.warning),
+        ),
+        Some(trend) => {
+            let projection = |months: u32| {
+                let at = CapacityTrend::months_after(now, months);
+                format!(
+                    "{:.1} Wh ({:.0}%) in {} months",
+                    trend.capacity_at(at),
+                    trend.health_at(at),
+                    months
+                )
+            };
+            let month = |timestamp: i64| {
+                Local
+                    .timestamp_opt(timestamp, 0)
+                    .single()
+                    .map(|dt| dt.format("%Y-%m").to_string())
+                    .unwrap_or_default()
+            };
+            let worn_out = match trend.reaches(WORN_OUT_HEALTH) {
+                None => "not declining".to_string(),
+                Some(at) if at <= now => {
+                    format!("below {:.0}% since {}", WORN_OUT_HEALTH, month(at))
+                }
+                Some(at) => format!("{:.0}% of design around {}", WORN_OUT_HEALTH, month(at)),
+            };
+            Line::from(vec![
+                Span::raw(format!("{}, {}; ", projection(6), projection(12))),
+                Span::styled(worn_out, Style::default().fg(theme.accent)),
+                Span::raw(format!(" ({:+.1}% a year)", trend.per_year)),
+            ])
+        }
+    };
+    frame.render_widget(Paragraph::new(line).block(block), area);
+}
+
+fn draw_wear_chart(
+    samples: &Result<Vec<HealthSample>>,
+    frame: &mut Frame,
+    area: Rect,
+    theme: &Theme,
+) {
+    use chrono::{Local, TimeZone};
+
+    let block = Block::default()
+        .title(" Wear over time (capacity lost vs. design) ")
+        .borders(Borders::ALL);
+
+    let samples = match samples {
+        Ok(samples) if !samples.is_empty() => samples,
+        Ok(_) => {
+            let text = Paragraph::new(
+                "No capacity history yet\n\nThe daemon records it once a day:\namptop daemon start",
+            )
+            .block(block)
+            .alignment(Alignment::Center)
+            .style(Style::default().fg(theme.warning));
+            frame.render_widget(text, area);
+            return;
+        }
+        Err(e) => {
+            let text = Paragraph::new(format!("Error loading data:\n{}", e))
+                .block(block)
+                .alignment(Alignment::Center)
+                .style(Style::default().fg(theme.error));
+            frame.render_widget(text, area);
+            return;
+        }
+    };
+
+    let (first, last) = (samples[0], samples[samples.len() - 1]);
+    let points: Vec<(f64, f64)> = samples
+        .iter()
+        .map(|sample| (sample.timestamp as f64, f64::from(sample.wear())))
+        .collect();
+    let max_wear = points.iter().map(|(_, wear)| *wear).fold(0.0, f64::max);
+    // Round the axis up to the next 10% so the trend isn't squashed
+    let top = ((max_wear / 10.0).floor() + 1.0) * 10.0;
+
+    let date = |timestamp: i64| {
+        Local
+            .timestamp_opt(timestamp, 0)
+            .single()
+            .map(|dt| dt.format("%Y-%m-%d").to_string())
+            .unwrap_or_default()
+    };
+    let x_axis = Axis::default()
+        .style(Style::default().fg(theme.axis))
+        .bounds([
+            first.timestamp as f64,
+            (last.timestamp as f64).max(first.timestamp as f64 + 1.0),
+        ])
+        .labels(vec![
+            Span::raw(date(first.timestamp)),
+            Span::raw(date(last.timestamp)),
+        ]);
+    let y_axis = Axis::default()
+        .style(Style::default().fg(theme.axis))
+        .bounds([0.0, top])
+        .labels(vec![
+            Span::raw("0%"),
+            Span::raw(format!("{:.0}%", top / 2.0)),
+            Span::raw(format!("{:.0}%", top)),
+        ]);
+
+    let dataset = Dataset::default()
+        .marker(ratatui::symbols::Marker::Braille)
+        .graph_type(ratatui::widgets::GraphType::Line)
+        .style(Style::default().fg(theme.secondary))
+        .data(&points);
+    let title = format!(
+        " now {:.1}% worn, {:.1} of {:.1} Wh{} ",
+        last.wear(),
+        last.energy_full,
+        last.energy_full_design,
+        last.cycle_count
+            .map(|cycles| format!(", {} cycles", cycles))
+            .unwrap_or_default()
+    );
+    let chart = Chart::new(vec![dataset])
+        .block(block.title_bottom(title))
+        .x_axis(x_axis)
+        .y_axis(y_axis);
+    frame.render_widget(chart, area);
+}
+
+pub fn draw_charge_bands(
+    bands: &Result<ChargeBands>,
+    frame: &mut Frame,
+    area: Rect,
+    theme: &Theme,
+) {
+    let block = Block::default()
+        .title(" Time in charge band ")
+        .borders(Borders::ALL);
+
+    let bands = match bands {
+        Ok(bands) if bands.total() > 0 => bands,
+        Ok(_) => {
+            let text = Paragraph::new("No historical data available")
+                .block(block)
+                .alignment(Alignment::Center)
+                .style(Style::default().fg(theme.warning));
+            frame.render_widget(text, area);
+            return;
+        }
+        Err(e) => {
+            let text = Paragraph::new(format!("Error loading data:\n{}", e))
+                .block(block)
+                .alignment(Alignment::Center)
+                .style(Style::default().fg(theme.error));
+            frame.render_widget(text, area);
+            return;
+        }
+    };
+
+    let segments = charge_band_segments(bands, theme);
+    let total = bands.total() as f64;
+    let width = area.width.saturating_sub(2) as usize;
+
+    // Stacked bar: each band gets a share of the width proportional to its time
+    let mut bar = Vec::new();
+    let mut used = 0;
+    for (i, (_, seconds, color)) in segments.iter().enumerate() {
+        let cells = if i == segments.len() - 1 {
+            width - used
+        } else {
+            ((*seconds as f64 / total) * width as f64).round() as usize
+        }
+        .min(width - used);
+        used += cells;
+        bar.push(Span::styled("█".repeat(cells), Style::default().fg(*color)));
+    }
+
+    let mut lines = vec![Line::from(bar), Line::from("")];
+    for (label, seconds, color) in &segments {
+        lines.push(Line::from(vec![
+            Span::styled("■ ", Style::default().fg(*color)),
+            Span::raw(format!(
+                "{:<10} {:>5.1}%  {}",
+                label,
+                *seconds as f64 / total * 100.0,
+                durations::format(Duration::from_secs(*seconds as u64))
+            )),
+        ]));
+    }
+
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+fn charge_band_segments(bands: &ChargeBands, theme: &Theme) -> [(&'static str, i64, Color); 4] {
+    [
+        ("100%", bands.full, theme.full),
+        ("80-100%", bands.high, theme.charging),
+        ("20-80%", bands.mid, theme.warning),
+        ("<20%", bands.low, theme.error),
+    ]
+}
+
+pub fn draw_sessions(
+    sessions: &Result<Vec<Session>>,
+    frame: &mut Frame,
+    area: Rect,
+    theme: &Theme,
+) {
+    use chrono::{Local, TimeZone};
+
+    let block = Block::default()
+        .title(" Sessions (last 30 days) ")
+        .borders(Borders::ALL);
+
+    let sessions = match sessions {
+        Ok(sessions) => sessions,
+        Err(e) => {
+            let text = Paragraph::new(format!("Error loading data:\n{}", e))
+                .block(block)
+                .alignment(Alignment::Center)
+                .style(Style::default().fg(theme.error));
+            frame.render_widget(text, area);
+            return;
+        }
+    };
+
+    if sessions.is_empty() {
+        let text = Paragraph::new("No sessions recorded yet")
+            .block(block)
+            .alignment(Alignment::Center)
+            .style(Style::default().fg(theme.warning));
+        frame.render_widget(text, area);
+        return;
+    }
+
+    let not_available = || "N/A".to_string();
+    let rows = sessions.iter().rev().map(|session| {
+        let started = Local
+            .timestamp_opt(session.start, 0)
+            .single()
+            .map(|dt| dt.format("%m-%d %H:%M").to_string())
+            .unwrap_or_else(not_available);
+        let duration = durations::format(Duration::from_secs(session.duration() as u64));
+        let color = match session.kind {
+            SessionKind::Charge => theme.charging,
+            SessionKind::Discharge => theme.discharging,
+        };
+
+        Row::new(vec![
+            started,
+            session.kind.label().to_string(),
+            duration,
+            format!(
+                "{:.0}% → {:.0}%",
+                session.start_percent, session.end_percent
+            ),
+            session
+                .energy_delta()
+                .map(|wh| format!("{:.1} Wh", wh))
+                .unwrap_or_else(not_available),
+            session
+                .adapter_energy
+                .filter(|_| session.kind == SessionKind::Charge)
+                .map(|wh| format!("{:.1} Wh", wh))
+                .unwrap_or_else(not_available),
+            session
+                .charge_efficiency()
+                .map(|ratio| format!("{:.0}%", ratio * 100.0))
+                .unwrap_or_else(not_available),
+            session.adapter.clone().unwrap_or_default(),
+        ])
+        .style(Style::default().fg(color))
+    });
+
+    let header = Row::new(vec![
+        "Started",
+        "Type",
+        "Duration",
+        "Change",
+        "Energy",
+        "From wall",
+        "Efficiency",
+        "Charger",
+    ])
+    .style(Style::default().add_modifier(Modifier::BOLD));
+
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(12),
+            Constraint::Length(10),
+            Constraint::Length(16),
+            Constraint::Length(12),
+            Constraint::Length(10),
+            Constraint::Length(10),
+            Constraint::Length(10),
+            Constraint::Min(10),
+        ],
+    )
+    .header(header)
+    .block(block);
+
+    frame.render_widget(table, area);
+}
+
+pub fn draw_fleet(
+    data: &TabData,
+    keymap: &Keymap,
+    axis: ChartAxis,
+    frame: &mut Frame,
+    area: Rect,
+    selected: usize,
+    theme: &Theme,
+) {
+    use chrono::{Local, TimeZone};
+
+    let block = Block::default().title(" Fleet ").borders(Borders::ALL);
+
+    let hosts = match &data.hosts {
+        Ok(hosts) if !hosts.is_empty() => hosts,
+        Ok(_) => {
+            let text = Paragraph::new("No hosts have recorded any history yet")
+                .block(block)
+                .alignment(Alignment::Center)
+                .style(Style::default().fg(theme.warning));
+            frame.render_widget(text, area);
+            return;
+        }
+        Err(e) => {
+            let text = Paragraph::new(format!("Error loading data:\n{}", e))
+                .block(block)
+                .alignment(Alignment::Center)
+                .style(Style::default().fg(theme.error));
+            frame.render_widget(text, area);
+            return;
+        }
+    };
+    let selected = selected.min(hosts.len() - 1);
+
+    let sections = ratatui::layout::Layout::default()
+        .direction(ratatui::layout::Direction::Vertical)
+        .constraints([
+            Constraint::Length((hosts.len() as u16 + 3).min(area.height / 2)),
+            Constraint::Min(0),
+        ])
+        .split(area);
+
+    let rows = hosts.iter().enumerate().map(|(i, summary)| {
+        let seen = Local
+            .timestamp_opt(summary.latest.timestamp, 0)
+            .single()
+            .map(|dt| dt.format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_else(|| "N/A".to_string());
+        let grade = summary
+            .health_grade()
+            .map(|grade| grade.to_string())
+            .unwrap_or_else(|| "N/A".to_string());
+
+        let style = if i == selected {
+            Style::default().add_modifier(Modifier::REVERSED)
+        } else {
+            Style::default()
+        };
+        Row::new(vec![
+            summary.host.clone(),
+            format!("{:.1}%", summary.latest.percent),
+            summary.latest.status.clone(),
+            grade,
+            seen,
+        ])
+        .style(style)
+    });
+
+    let header = Row::new(vec!["Host", "Charge", "State", "Health", "Last seen"])
+        .style(Style::default().add_modifier(Modifier::BOLD));
+
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(24),
+            Constraint::Length(8),
+            Constraint::Length(12),
+            Constraint::Length(7),
+            Constraint::Length(17),
+        ],
+    )
+    .header(header)
+    .block(block.title_bottom(format!(
+        " {}/{} select host ",
+        keymap.short_label(Action::ZoomIn),
+        keymap.short_label(Action::ZoomOut)
+    )));
+
+    frame.render_widget(table, sections[0]);
+    draw_drain_graph(frame, sections[1], &data.host_logs, &[], None, axis, theme);
+}
@@ -0,0 +1,352 @@
+//! Reads the charge history UPower keeps in `/var/lib/upower`, so a new
+//! install doesn't start with an empty graph, and lists the devices UPower
+//! knows about: Bluetooth peripherals the kernel doesn't list as power
+//! supplies, and the system batteries for `--backend upower`. `upower
+//! --monitor` also stands in for kernel uevents where their netlink socket
+//! can't be opened.
+//!
+//! This doesn't speak D-Bus. It scrapes the human-readable output of the
+//! `upower` command-line tool, which isn't a stable interface, so it is a
+//! best-effort fallback: a field UPower renames or reformats reads as
+//! missing rather than as an error.
+
+use crate::daemon::BatterySnapshot;
+use crate::desktop::Peripheral;
+use crate::errors::Result;
+use crate::info::Reading;
+use crate::uevent::PowerEvent;
+use battery::units::{
+    ElectricPotential, Energy, Power, Ratio, ThermodynamicTemperature, Time,
+    electric_potential::volt, energy::watt_hour, power::watt, ratio,
+    thermodynamic_temperature::degree_celsius, time::second,
+};
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const HISTORY_DIR: &str = "/var/lib/upower";
+/// How long the output of `upower --dump` is reused. Running it costs a
+/// process and a round of D-Bus calls, and UPower refreshes its batteries
+/// far less often than the TUI redraws.
+const DUMP_MAX_AGE: Duration = Duration::from_secs(30);
+
+/// The last `upower --dump` and when it ran; failures are kept too, so a
+/// missing UPower isn't retried at every refresh.
+static LAST_DUMP: Mutex<Option<(Instant, std::result::Result<String, String>)>> = Mutex::new(None);
+
+/// Charge history files, one per battery UPower has seen.
+pub fn history_files() -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = fs::read_dir(HISTORY_DIR)
+        .map(|entries| entries.flatten().map(|entry| entry.path()).collect())
+        .unwrap_or_default();
+    files.retain(|path| {
+        path.file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with("history-charge-") && name.ends_with(".dat"))
+    });
+    files.sort();
+    files
+}
+
+/// Parses every charge history file. Each line holds a Unix timestamp, the
+/// charge in percent and UPower's state name, separated by tabs.
+pub fn read_history() -> Vec<BatterySnapshot> {
+    let mut snapshots: Vec<BatterySnapshot> = history_files()
+        .iter()
+        .filter_map(|path| fs::read_to_string(path).ok())
+        .flat_map(|text| text.lines().filter_map(parse_line).collect::<Vec<_>>())
+        .collect();
+    snapshots.sort_by_key(|snapshot| snapshot.timestamp);
+    snapshots
+}
+
+fn parse_line(line: &str) -> Option<BatterySnapshot> {
+    let mut fields = line.split('\t');
+    let timestamp = fields.next()?.trim().parse().ok()?;
+    let percent: f32 = fields.next()?.trim().parse().ok()?;
+    let status = match fields.next()?.trim() {
+        "charging" | "pending-charge" => "charging",
+        "discharging" | "pending-discharge" => "discharging",
+        "fully-charged" => "full",
+        "empty" => "empty",
+        _ => "unknown",
+    };
+
+    Some(BatterySnapshot {
+        percent: percent.clamp(0.0, 100.0),
+        timestamp,
+        status: status.to_string(),
+        energy: None,
+        adapter_watts: None,
+        temperature: None,
+        health: None,
+        idle: None,
+        power_watts: None,
+        adapter: None,
+        voltage: None,
+    })
+}
+
+/// Device kinds that are the machine's own power rather than a peripheral.
+const SYSTEM_KINDS: [&str; 3] = ["battery", "line-power", "ups"];
+
+/// One device of `upower --dump`: a few `key: value` lines, the device kind
+/// alone on a line, then the kind's `key: value` properties.
+struct Device<'a> {
+    path: &'a str,
+    kind: &'a str,
+    properties: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> Device<'a> {
+    fn parse(block: &'a str) -> Option<Device<'a>> {
+        let mut lines = block.lines();
+        let path = lines.next()?.trim();
+        let mut kind = None;
+        let mut properties = Vec::new();
+        for line in lines {
+            match line.split_once(':') {
+                Some((key, value)) => properties.push((key.trim(), value.trim())),
+                None if kind.is_none() && !line.trim().is_empty() => kind = Some(line.trim()),
+                None => {}
+            }
+        }
+        Some(Device {
+            path,
+            kind: kind?,
+            properties,
+        })
+    }
+
+    fn value(&self, name: &str) -> Option<&'a str> {
+        self.properties
+            .iter()
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| *value)
+            .filter(|value| !value.is_empty() && *value != "N/A")
+    }
+
+    /// Leading number of a value such as `38.02 Wh` or `67%`.
+    fn number(&self, name: &str) -> Option<f64> {
+        let value = self.value(name)?;
+        let end = value
+            .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-'))
+            .unwrap_or(value.len());
+        value[..end].parse().ok()
+    }
+
+    /// The aggregate every desktop shows, not a device of its own.
+    fn is_display_device(&self) -> bool {
+        self.path.ends_with("DisplayDevice")
+    }
+}
+
+/// The output of `upower --dump`, run again only once the last one is
+/// `DUMP_MAX_AGE` old.
+fn cached_dump() -> std::io::Result<String> {
+    let mut last = LAST_DUMP.lock().unwrap_or_else(|e| e.into_inner());
+    let result = match *last {
+        Some((at, ref result)) if at.elapsed() < DUMP_MAX_AGE => result.clone(),
+        _ => {
+            let result = dump().map_err(|e| e.to_string());
+            *last = Some((Instant::now(), result.clone()));
+            result
+        }
+    };
+    result.map_err(std::io::Error::other)
+}
+
+/// Runs `upower --dump`, returning its devices' text blocks.
+pub fn dump() -> std::io::Result<String> {
+    let output = Command::new("upower")
+        .arg("--dump")
+        .output()
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => {
+                std::io::Error::other("upower isn't installed; pick another --backend")
+            }
+            _ => e,
+        })?;
+    if !output.status.success() {
+        return Err(std::io::Error::other(format!(
+            "upower --dump exited with {}",
+            output.status
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Runs `upower --monitor` and sends an event whenever it reports a device
+/// or daemon change. The channel closes when UPower isn't running.
+pub fn monitor() -> Result<Receiver<PowerEvent>> {
+    let mut child = Command::new("upower")
+        .arg("--monitor")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    let stdout = child.stdout.take().expect("stdout is piped");
+
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        // Changes are printed as e.g. "[10:21:33.152]\tdevice changed:
+        // /org/freedesktop/UPower/devices/battery_BAT0", after a banner.
+        for line in BufReader::new(stdout).lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            if line.starts_with('[')
+                && tx
+                    .send(PowerEvent {
+                        timestamp: chrono::Utc::now().timestamp(),
+                    })
+                    .is_err()
+            {
+                break;
+            }
+        }
+        child.kill().ok();
+        child.wait().ok();
+    });
+    Ok(rx)
+}
+
+/// Battery-powered devices such as wireless mice, keyboards and earbuds.
+/// Empty when UPower isn't installed or running.
+pub fn peripherals() -> Vec<Peripheral> {
+    let Ok(text) = cached_dump() else {
+        return Vec::new();
+    };
+    text.split("Device: ")
+        .skip(1)
+        .filter_map(Device::parse)
+        .filter_map(|device| peripheral(&device))
+        .collect()
+}
+
+fn peripheral(device: &Device) -> Option<Peripheral> {
+    if device.is_display_device()
+        || SYSTEM_KINDS.contains(&device.kind)
+        || device.value("power supply") == Some("yes")
+    {
+        return None;
+    }
+    let percent = device
+        .value("percentage")
+        .filter(|value| !value.contains("ignored"))
+        .and_then(|_| device.number("percentage"));
+    let status = device
+        .value("state")
+        .filter(|state| *state != "unknown")
+        .map(|state| match state {
+            "fully-charged" => "Full".to_string(),
+            state => state.to_string(),
+        });
+
+    Some(Peripheral {
+        id: device
+            .value("native-path")
+            .unwrap_or(device.path)
+            .to_string(),
+        name: device.value("model").unwrap_or(device.kind).to_string(),
+        kind: Some(device.kind.to_string()),
+        percent,
+        status,
+    })
+}
+
+/// The system batteries UPower reports, for `--backend upower`.
+pub fn batteries() -> Result<Vec<Reading>> {
+    let text = cached_dump()?;
+    Ok(text
+        .split("Device: ")
+        .skip(1)
+        .filter_map(Device::parse)
+        .filter(|device| {
+            !device.is_display_device()
+                && device.kind == "battery"
+                && device.value("power supply") == Some("yes")
+        })
+        .map(|device| reading(&device))
+        .collect())
+}
+
+fn reading(device: &Device) -> Reading {
+    let wh = |name: &str| Energy::new::<watt_hour>(device.number(name).unwrap_or(0.0) as f32);
+    let energy_full = device.number("energy-full").unwrap_or(0.0);
+    let energy_full_design = device.number("energy-full-design").unwrap_or(energy_full);
+    let state = match device.value("state") {
+        Some("charging" | "pending-charge") => battery::State::Charging,
+        Some("discharging" | "pending-discharge") => battery::State::Discharging,
+        Some("fully-charged") => battery::State::Full,
+        Some("empty") => battery::State::Empty,
+        _ => battery::State::Unknown,
+    };
+    let technology = match device.value("technology") {
+        Some("lithium-ion") => battery::Technology::LithiumIon,
+        Some("lithium-polymer") => battery::Technology::LithiumPolymer,
+        Some("lithium-iron-phosphate") => battery::Technology::LithiumIronPhosphate,
+        Some("lead-acid") => battery::Technology::LeadAcid,
+        Some("nickel-cadmium") => battery::Technology::NickelCadmium,
+        Some("nickel-metal-hydride") => battery::Technology::NickelMetalHydride,
+        _ => battery::Technology::Unknown,
+    };
+
+    Reading {
+        state_of_charge: Ratio::new::<ratio::percent>(
+            device.number("percentage").unwrap_or(0.0) as f32
+        ),
+        energy: wh("energy"),
+        energy_full: Energy::new::<watt_hour>(energy_full as f32),
+        energy_full_design: Energy::new::<watt_hour>(energy_full_design as f32),
+        energy_rate: Power::new::<watt>(device.number("energy-rate").unwrap_or(0.0).abs() as f32),
+        voltage: ElectricPotential::new::<volt>(device.number("voltage").unwrap_or(0.0) as f32),
+        state_of_health: Ratio::new::<ratio::percent>(match device.number("capacity") {
+            Some(capacity) => capacity as f32,
+            None if energy_full_design > 0.0 => (energy_full / energy_full_design * 100.0) as f32,
+            None => 0.0,
+        }),
+        state,
+        technology,
+        temperature: device
+            .number("temperature")
+            .map(|celsius| ThermodynamicTemperature::new::<degree_celsius>(celsius as f32)),
+        cycle_count: device
+            .number("charge-cycles")
+            .filter(|count| *count > 0.0)
+            .map(|count| count as u32),
+        vendor: device.value("vendor").map(str::to_string),
+        model: device.value("model").map(str::to_string),
+        serial_number: device.value("serial").map(str::to_string),
+        time_to_full: device
+            .value("time to full")
+            .and_then(parse_time)
+            .filter(|_| state == battery::State::Charging),
+        time_to_empty: device
+            .value("time to empty")
+            .and_then(parse_time)
+            .filter(|_| state == battery::State::Discharging),
+        health_status: None,
+        manufactured: None,
+        charge: None,
+    }
+}
+
+/// Parses UPower's durations, e.g. `4.2 hours` or `35.0 minutes`.
+fn parse_time(value: &str) -> Option<Time> {
+    let (amount, unit) = value.split_once(' ')?;
+    let amount: f32 = amount.parse().ok()?;
+    let seconds = match unit.trim() {
+        "seconds" | "second" => amount,
+        "minutes" | "minute" => amount * 60.0,
+        "hours" | "hour" => amount * 3600.0,
+        "days" | "day" => amount * 86400.0,
+        _ => return None,
+    };
+    Some(Time::new::<second>(seconds))
+}
@@ -0,0 +1,210 @@
+//! Setup wizard shown the first time amptop runs, when there is neither a
+//! config file nor a history database yet.
+
+use crate::config::Config;
+use crate::daemon::BatteryDaemon;
+use crate::errors::{Error, Result};
+use crate::upower;
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::{
+    DefaultTerminal, Frame,
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::Line,
+    widgets::{Block, Borders, Clear, Paragraph},
+};
+use std::fs;
+use std::process::Command;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Question {
+    Unit,
+    Theme,
+    Daemon,
+    Import,
+}
+
+impl Question {
+    fn prompt(self) -> &'static str {
+        match self {
+            Question::Unit => "Which units should energy be shown in?",
+            Question::Theme => "Which color theme do you prefer?",
+            Question::Daemon => "Start the daemon to record battery history in the background?",
+            Question::Import => "Import the charge history UPower has already recorded?",
+        }
+    }
+
+    fn options(self) -> &'static [&'static str] {
+        match self {
+            Question::Unit => &["human (Wh)", "si (J)", "mah (mAh)"],
+            Question::Theme => &["default", "solarized", "gruvbox", "mono", "high-contrast"],
+            Question::Daemon | Question::Import => &["yes", "no"],
+        }
+    }
+}
+
+/// What the user picked, one option index per question.
+#[derive(Debug)]
+pub struct Wizard {
+    questions: Vec<Question>,
+    answers: Vec<usize>,
+    selected: usize,
+    skipped: bool,
+}
+
+impl Wizard {
+    /// Whether this looks like the very first run.
+    pub fn is_first_run(config: &Config) -> bool {
+        !config.config_path().exists() && !BatteryDaemon::database_exists()
+    }
+
+    pub fn new() -> Self {
+        let mut questions = vec![Question::Unit, Question::Theme, Question::Daemon];
+        if !upower::history_files().is_empty() {
+            questions.push(Question::Import);
+        }
+        Self {
+            questions,
+            answers: Vec::new(),
+            selected: 0,
+            skipped: false,
+        }
+    }
+
+    /// Asks every question, then applies the answers. Returns a summary of
+    /// what was done, or nothing if the wizard was skipped.
+    pub fn run(mut self, terminal: &mut DefaultTerminal, config: &Config) -> Result<Vec<String>> {
+        while self.answers.len() < self.questions.len() && !self.skipped {
+            terminal.draw(|frame| self.draw(frame))?;
+            self.handle_events()?;
+        }
+        if self.skipped {
+            return Ok(Vec::new());
+        }
+        self.apply(config)
+    }
+
+    fn handle_events(&mut self) -> Result<()> {
+        let Event::Key(key) =
+            event::read().map_err(|e| Error::Crossterm(format!("Event read error: {}", e)))?
+        else {
+            return Ok(());
+        };
+        if key.kind != KeyEventKind::Press {
+            return Ok(());
+        }
+
+        let options = self.questions[self.answers.len()].options().len();
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => self.skipped = true,
+            KeyCode::Up | KeyCode::Char('k') => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.selected = (self.selected + 1).min(options - 1)
+            }
+            KeyCode::Enter => {
+                self.answers.push(self.selected);
+                self.selected = 0;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn answer(&self, question: Question) -> Option<&'static str> {
+        let index = self.questions.iter().position(|&q| q == question)?;
+        let option = question.options()[*self.answers.get(index)?];
+        Some(option.split_whitespace().next().unwrap_or(option))
+    }
+
+    fn apply(&self, config: &Config) -> Result<Vec<String>> {
+        let mut done = Vec::new();
+
+        let path = config.config_path();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(
+            &path,
+            format!(
+                "# Created by the amptop setup wizard\nunit = \"{}\"\ntheme = \"{}\"\n",
+                self.answer(Question::Unit).unwrap_or("human"),
+                self.answer(Question::Theme).unwrap_or("default"),
+            ),
+        )?;
+        done.push(format!("Wrote {}", path.display()));
+
+        if self.answer(Question::Import) == Some("yes") {
+            let imported = BatteryDaemon::import_snapshots(&upower::read_history())?;
+            done.push(format!("Imported {} samples from UPower", imported));
+        }
+
+        if self.answer(Question::Daemon) == Some("yes") {
+            // Daemonizing forks and ends the calling process, so the daemon
+            // is started from a separate amptop process.
+            let mut command = Command::new(std::env::current_exe()?);
+            command.arg("--config").arg(&path);
+            if let Some(db_path) = config.db_path() {
+                command.arg("--db-path").arg(db_path);
+            }
+            if let Some(root) = config.sysfs_root() {
+                command.arg("--sysfs-root").arg(root);
+            }
+            let status = command.args(["daemon", "start"]).output()?.status;
+            done.push(if status.success() {
+                "Started the daemon".to_string()
+            } else {
+                "Couldn't start the daemon, try `amptop daemon start`".to_string()
+            });
+        }
+
+        Ok(done)
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let question = self.questions[self.answers.len()];
+        let width = 70.min(frame.area().width);
+        let height = (question.options().len() as u16 + 7).min(frame.area().height);
+        let area = Rect {
+            x: frame.area().x + (frame.area().width - width) / 2,
+            y: frame.area().y + (frame.area().height - height) / 2,
+            width,
+            height,
+        };
+
+        let mut lines = vec![
+            Line::styled(
+                format!(
+                    "Step {} of {}",
+                    self.answers.len() + 1,
+                    self.questions.len()
+                ),
+                Style::default().fg(Color::DarkGray),
+            ),
+            Line::from(question.prompt()),
+            Line::from(""),
+        ];
+        lines.extend(question.options().iter().enumerate().map(|(i, option)| {
+            if i == self.selected {
+                Line::styled(
+                    format!("> {}", option),
+                    Style::default().add_modifier(Modifier::BOLD | Modifier::REVERSED),
+                )
+            } else {
+                Line::from(format!("  {}", option))
+            }
+        }));
+        lines.push(Line::from(""));
+        lines.push(Line::styled(
+            "Up/Down select, Enter confirm, Esc skip setup",
+            Style::default().fg(Color::DarkGray),
+        ));
+
+        let text = Paragraph::new(lines).block(
+            Block::default()
+                .title(" Welcome to amptop ")
+                .borders(Borders::ALL),
+        );
+        frame.render_widget(Clear, area);
+        frame.render_widget(text, area);
+    }
+}
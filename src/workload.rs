@@ -0,0 +1,61 @@
+//! Workload presets learned from the discharge history.
+//!
+//! Each preset sits at a fixed percentile of the power drawn while on
+//! battery: light idling at the bottom, games at the top. The current draw
+//! is classified as the nearest preset.
+
+use crate::daemon::{BatteryDaemon, BatterySnapshot};
+use crate::errors::Result;
+use crate::stats;
+
+/// Preset names and the percentile of discharge power each stands for.
+const PRESETS: [(&str, usize); 4] = [
+    ("Idle", 10),
+    ("Browsing", 40),
+    ("Compiling", 80),
+    ("Gaming", 97),
+];
+/// How much history to learn from.
+const LEARN_WINDOW_DAYS: i64 = 30;
+/// Minimum number of discharge intervals before presets are offered.
+const MIN_INTERVALS: usize = 20;
+
+#[derive(Debug, Clone)]
+pub struct Preset {
+    pub name: &'static str,
+    /// Typical draw, in W
+    pub watts: f64,
+}
+
+/// Learns the presets from history sorted oldest first. Returns nothing
+/// when there isn't enough discharge history yet.
+pub fn learn(logs: &[BatterySnapshot]) -> Vec<Preset> {
+    let mut watts: Vec<f64> = stats::discharge_intervals(logs)
+        .map(|interval| interval.watts())
+        .filter(|watts| *watts > 0.0)
+        .collect();
+    if watts.len() < MIN_INTERVALS {
+        return Vec::new();
+    }
+    watts.sort_by(f64::total_cmp);
+
+    PRESETS
+        .iter()
+        .map(|&(name, percentile)| Preset {
+            name,
+            watts: watts[(watts.len() - 1) * percentile / 100],
+        })
+        .collect()
+}
+
+pub fn load() -> Result<Vec<Preset>> {
+    let since = chrono::Utc::now().timestamp() - LEARN_WINDOW_DAYS * 24 * 60 * 60;
+    Ok(learn(&BatteryDaemon::get_logs_since(since)?))
+}
+
+/// The preset closest to `watts`.
+pub fn classify(presets: &[Preset], watts: f64) -> Option<&Preset> {
+    presets
+        .iter()
+        .min_by(|a, b| (a.watts - watts).abs().total_cmp(&(b.watts - watts).abs()))
+}
@@ -0,0 +1,24 @@
+//! XDG base directories. A variable that is unset, empty or relative is
+//! ignored, as the spec asks, in favor of the default under `$HOME`.
+
+use std::path::PathBuf;
+
+/// `$XDG_CONFIG_HOME`, falling back to `~/.config`.
+pub fn config_home() -> PathBuf {
+    base_dir("XDG_CONFIG_HOME", ".config")
+}
+
+/// `$XDG_DATA_HOME`, falling back to `~/.local/share`.
+pub fn data_home() -> PathBuf {
+    base_dir("XDG_DATA_HOME", ".local/share")
+}
+
+fn base_dir(var: &str, default: &str) -> PathBuf {
+    std::env::var_os(var)
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .unwrap_or_else(|| {
+            let home = std::env::var("HOME").unwrap_or_else(|_| "/tmp".to_string());
+            PathBuf::from(home).join(default)
+        })
+}